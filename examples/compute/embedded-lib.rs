@@ -8,14 +8,22 @@
 use metal::*;
 use objc::rc::autoreleasepool;
 
-fn main() {
-    let library_data = include_bytes!("shaders.metallib");
+// Fails to compile if the file is not a valid metallib or has no `sum` function.
+include_metallib! {
+    mod shaders = "shaders.metallib" {
+        SUM = "sum",
+    }
+}
 
+fn main() {
     autoreleasepool(|| {
         let device = Device::system_default().expect("no device found");
 
-        let library = device.new_library_with_data(&library_data[..]).unwrap();
-        let kernel = library.get_function("sum", None).unwrap();
+        let library = device
+            .new_library_with_data(shaders::LIBRARY.as_bytes())
+            .unwrap();
+        println!("Functions: {:?}", shaders::FUNCTION_NAMES);
+        let kernel = library.get_function(shaders::SUM, None).unwrap();
 
         println!("Function name: {}", kernel.name());
         println!("Function type: {:?}", kernel.function_type());
@@ -201,7 +201,7 @@ fn get_window_layer(window: &Window, device: &Device) -> MetalLayer {
 }
 
 fn shader_metallib() -> PathBuf {
-    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("shaders.metallib")
+    PathBuf::from(env!("OUT_DIR")).join("shaders.metallib")
 }
 
 fn prepare_pipeline_state(device: &Device, library: &Library) -> RenderPipelineState {
@@ -0,0 +1,694 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Compile `.metal` shaders into a `.metallib` from a build script.
+//!
+//! ```no_run
+//! // In build.rs:
+//! metal_build::Build::new()
+//!     .dir("shaders")
+//!     .language_version(3, 0)
+//!     .fast_math(true)
+//!     .prebuilt("shaders/prebuilt.metallib")
+//!     .compile("shaders");
+//! ```
+//!
+//! ```ignore
+//! // main.rs
+//! let library = device.new_library_with_file(concat!(env!("OUT_DIR"), "/shaders.metallib"))?;
+//! ```
+//!
+//! Every source and every file it `#include`s is reported with
+//! `cargo:rerun-if-changed`. The SDK is chosen from the `TARGET` triple.
+//!
+//! The compiler is run through `xcrun`, or through the program named by the
+//! `METAL_BUILD_XCRUN` environment variable. If the target has no Metal SDK,
+//! or `xcrun` can't be started or can't find the SDK, as on Linux, the
+//! [`prebuilt`](Build::prebuilt) library is copied to `OUT_DIR` instead.
+//! Errors in the shaders themselves are always reported.
+
+use std::collections::BTreeSet;
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Environment variable overriding the `xcrun` program.
+pub const XCRUN_ENV: &str = "METAL_BUILD_XCRUN";
+
+/// The SDK passed to `xcrun -sdk`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum Sdk {
+    MacOsx,
+    IphoneOs,
+    IphoneSimulator,
+    AppleTvOs,
+    AppleTvSimulator,
+    XrOs,
+    XrSimulator,
+}
+
+impl Sdk {
+    /// The SDK for a target triple such as `aarch64-apple-ios-sim`.
+    pub fn from_target(target: &str) -> Option<Self> {
+        let mut parts = target.split('-').skip(2);
+        let os = parts.next()?;
+        let simulator = target.ends_with("-sim") || target.starts_with("x86_64-");
+        Some(match os {
+            "darwin" | "macos" => Sdk::MacOsx,
+            // Mac Catalyst builds against the macOS SDK.
+            "ios" if target.ends_with("-macabi") => Sdk::MacOsx,
+            "ios" if simulator => Sdk::IphoneSimulator,
+            "ios" => Sdk::IphoneOs,
+            "tvos" if simulator => Sdk::AppleTvSimulator,
+            "tvos" => Sdk::AppleTvOs,
+            "visionos" if simulator => Sdk::XrSimulator,
+            "visionos" => Sdk::XrOs,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Sdk::MacOsx => "macosx",
+            Sdk::IphoneOs => "iphoneos",
+            Sdk::IphoneSimulator => "iphonesimulator",
+            Sdk::AppleTvOs => "appletvos",
+            Sdk::AppleTvSimulator => "appletvsimulator",
+            Sdk::XrOs => "xros",
+            Sdk::XrSimulator => "xrsimulator",
+        }
+    }
+
+    fn is_macos(&self) -> bool {
+        *self == Sdk::MacOsx
+    }
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// A required environment variable, normally set by Cargo, is missing.
+    MissingEnv(&'static str),
+    UnsupportedTarget(String),
+    NoSources,
+    /// `xcrun` couldn't be found and there is no prebuilt library.
+    XcrunUnavailable(io::Error),
+    /// `xcrun` was found but couldn't be started.
+    Spawn(PathBuf, io::Error),
+    /// The compiler or linker exited with an error.
+    ToolFailed {
+        command: String,
+        stdout: String,
+        stderr: String,
+    },
+    Io(PathBuf, io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::MissingEnv(name) => write!(f, "environment variable {} is not set", name),
+            Error::UnsupportedTarget(target) => {
+                write!(f, "target {} has no Metal SDK", target)
+            }
+            Error::NoSources => f.write_str("no .metal sources to compile"),
+            Error::XcrunUnavailable(error) => write!(
+                f,
+                "failed to run xcrun ({}); set {} or provide a prebuilt metallib",
+                error, XCRUN_ENV
+            ),
+            Error::Spawn(program, error) => {
+                write!(f, "failed to start {}: {}", program.display(), error)
+            }
+            Error::ToolFailed {
+                command,
+                stdout,
+                stderr,
+            } => write!(
+                f,
+                "`{}` failed\nstdout: {}\nstderr: {}",
+                command, stdout, stderr
+            ),
+            Error::Io(path, error) => write!(f, "{}: {}", path.display(), error),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+    /// Whether the error comes from the toolchain or the target rather than
+    /// the shaders, so that a prebuilt library can stand in.
+    fn is_toolchain_failure(&self) -> bool {
+        match self {
+            Error::MissingEnv(name) => *name == "TARGET",
+            Error::UnsupportedTarget(_) | Error::XcrunUnavailable(_) | Error::Spawn(..) => true,
+            // Reported by `xcrun` itself, e.g. for a missing SDK or tool.
+            Error::ToolFailed { stderr, .. } => stderr.trim_start().starts_with("xcrun: error"),
+            Error::NoSources | Error::Io(..) => false,
+        }
+    }
+}
+
+/// Configuration for compiling a set of `.metal` files into one `.metallib`.
+#[derive(Clone, Debug, Default)]
+pub struct Build {
+    files: Vec<PathBuf>,
+    dirs: Vec<PathBuf>,
+    include_dirs: Vec<PathBuf>,
+    defines: Vec<(String, Option<String>)>,
+    flags: Vec<String>,
+    language_version: Option<(u32, u32)>,
+    fast_math: Option<bool>,
+    debug_info: bool,
+    sdk: Option<Sdk>,
+    target: Option<String>,
+    out_dir: Option<PathBuf>,
+    xcrun: Option<PathBuf>,
+    prebuilt: Option<PathBuf>,
+    cargo_metadata: Option<bool>,
+}
+
+impl Build {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn file<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        self.files.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Compile every `.metal` file under `dir`, recursively.
+    pub fn dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.dirs.push(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Add an `-I` search path, also used to find included files.
+    pub fn include<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.include_dirs.push(dir.as_ref().to_path_buf());
+        self
+    }
+
+    pub fn define(&mut self, name: &str, value: Option<&str>) -> &mut Self {
+        self.defines
+            .push((name.to_string(), value.map(str::to_string)));
+        self
+    }
+
+    /// Pass an extra flag to `metal`.
+    pub fn flag(&mut self, flag: &str) -> &mut Self {
+        self.flags.push(flag.to_string());
+        self
+    }
+
+    /// The Metal Shading Language version, e.g. `(2, 4)` or `(3, 1)`.
+    pub fn language_version(&mut self, major: u32, minor: u32) -> &mut Self {
+        self.language_version = Some((major, minor));
+        self
+    }
+
+    pub fn fast_math(&mut self, enabled: bool) -> &mut Self {
+        self.fast_math = Some(enabled);
+        self
+    }
+
+    /// Embed debug information and sources for the GPU debugger.
+    pub fn debug_info(&mut self, enabled: bool) -> &mut Self {
+        self.debug_info = enabled;
+        self
+    }
+
+    /// Override the SDK derived from the target triple.
+    pub fn sdk(&mut self, sdk: Sdk) -> &mut Self {
+        self.sdk = Some(sdk);
+        self
+    }
+
+    /// Defaults to the `TARGET` environment variable.
+    pub fn target(&mut self, target: &str) -> &mut Self {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    /// Defaults to the `OUT_DIR` environment variable.
+    pub fn out_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Self {
+        self.out_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Defaults to [`XCRUN_ENV`], then `xcrun`.
+    pub fn xcrun<P: AsRef<Path>>(&mut self, program: P) -> &mut Self {
+        self.xcrun = Some(program.as_ref().to_path_buf());
+        self
+    }
+
+    /// A checked-in library used when the target has no SDK or `xcrun`
+    /// can't be run.
+    pub fn prebuilt<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        self.prebuilt = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Whether to print `cargo:` directives. Defaults to true when run from
+    /// a build script.
+    pub fn cargo_metadata(&mut self, enabled: bool) -> &mut Self {
+        self.cargo_metadata = Some(enabled);
+        self
+    }
+
+    /// Like [`try_compile`](Self::try_compile), but panics with the error
+    /// message, which Cargo prints as the build script failure.
+    pub fn compile(&self, name: &str) -> PathBuf {
+        match self.try_compile(name) {
+            Ok(path) => path,
+            Err(error) => panic!("metal-build: {}", error),
+        }
+    }
+
+    /// Compile the sources into `<OUT_DIR>/<name>.metallib` and return its
+    /// path.
+    pub fn try_compile(&self, name: &str) -> Result<PathBuf, Error> {
+        let out_dir = match &self.out_dir {
+            Some(dir) => dir.clone(),
+            None => PathBuf::from(env::var_os("OUT_DIR").ok_or(Error::MissingEnv("OUT_DIR"))?),
+        };
+        let output = out_dir.join(format!("{}.metallib", name));
+
+        let sources = self.sources()?;
+        if sources.is_empty() {
+            return Err(Error::NoSources);
+        }
+        self.print(&format!("cargo:rerun-if-env-changed={}", XCRUN_ENV));
+        for dir in &self.dirs {
+            self.print(&format!("cargo:rerun-if-changed={}", dir.display()));
+        }
+        for dependency in self.dependencies(&sources) {
+            self.print(&format!("cargo:rerun-if-changed={}", dependency.display()));
+        }
+        if let Some(prebuilt) = &self.prebuilt {
+            self.print(&format!("cargo:rerun-if-changed={}", prebuilt.display()));
+        }
+
+        // Check for a prebuilt library before giving up on a target without
+        // an SDK or a host without a working toolchain.
+        let result = self
+            .resolve_sdk()
+            .and_then(|sdk| self.run_compiler(sdk, name, &out_dir, &sources, &output));
+        match result {
+            Err(error) if error.is_toolchain_failure() => self.use_prebuilt(&output, error),
+            result => result,
+        }
+    }
+
+    fn run_compiler(
+        &self,
+        sdk: Sdk,
+        name: &str,
+        out_dir: &Path,
+        sources: &[PathBuf],
+        output: &Path,
+    ) -> Result<PathBuf, Error> {
+        let xcrun = self.xcrun_program();
+        let mut airs = Vec::with_capacity(sources.len());
+        for (index, source) in sources.iter().enumerate() {
+            let stem = source.file_stem().unwrap_or_default().to_string_lossy();
+            // Prefix with the index so same-named files in different
+            // directories don't collide.
+            let air = out_dir.join(format!("{}-{}-{}.air", name, index, stem));
+            let mut command = Command::new(&xcrun);
+            command
+                .args(["-sdk", sdk.as_str(), "metal", "-c"])
+                .args(self.compile_flags(sdk))
+                .arg(source)
+                .arg("-o")
+                .arg(&air);
+            run(command)?;
+            airs.push(air);
+        }
+
+        let mut command = Command::new(&xcrun);
+        command
+            .args(["-sdk", sdk.as_str(), "metallib"])
+            .args(&airs)
+            .arg("-o")
+            .arg(output);
+        run(command)?;
+        Ok(output.to_path_buf())
+    }
+
+    /// The flags passed to `metal` for `sdk`, excluding the input and output.
+    pub fn compile_flags(&self, sdk: Sdk) -> Vec<OsString> {
+        let mut flags: Vec<OsString> = Vec::new();
+        if let Some((major, minor)) = self.language_version {
+            // 1.x and 2.x versions are per platform; 3.0 and later are not.
+            let std = match (major, sdk.is_macos()) {
+                (0..=2, true) => format!("-std=macos-metal{}.{}", major, minor),
+                (0..=2, false) => format!("-std=ios-metal{}.{}", major, minor),
+                _ => format!("-std=metal{}.{}", major, minor),
+            };
+            flags.push(std.into());
+        }
+        match self.fast_math {
+            Some(true) => flags.push("-ffast-math".into()),
+            Some(false) => flags.push("-fno-fast-math".into()),
+            None => {}
+        }
+        if self.debug_info {
+            flags.push("-gline-tables-only".into());
+            flags.push("-frecord-sources".into());
+        }
+        for dir in &self.include_dirs {
+            let mut flag = OsString::from("-I");
+            flag.push(dir);
+            flags.push(flag);
+        }
+        for (name, value) in &self.defines {
+            flags.push(match value {
+                Some(value) => format!("-D{}={}", name, value).into(),
+                None => format!("-D{}", name).into(),
+            });
+        }
+        flags.extend(self.flags.iter().map(OsString::from));
+        flags
+    }
+
+    /// All sources, sorted so the output doesn't depend on directory order.
+    fn sources(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut sources = BTreeSet::new();
+        sources.extend(self.files.iter().cloned());
+        for dir in &self.dirs {
+            find_metal_files(dir, &mut sources)?;
+        }
+        Ok(sources.into_iter().collect())
+    }
+
+    /// Every source and the files it transitively includes.
+    fn dependencies(&self, sources: &[PathBuf]) -> BTreeSet<PathBuf> {
+        let mut seen = BTreeSet::new();
+        let mut pending: Vec<PathBuf> = sources.to_vec();
+        while let Some(path) = pending.pop() {
+            if !seen.insert(path.clone()) {
+                continue;
+            }
+            let contents = match fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                Err(_) => continue,
+            };
+            let local_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            for include in quoted_includes(&contents) {
+                let found = std::iter::once(&local_dir)
+                    .chain(&self.include_dirs)
+                    .map(|dir| dir.join(include))
+                    .find(|candidate| candidate.is_file());
+                if let Some(found) = found {
+                    pending.push(found);
+                }
+            }
+        }
+        seen
+    }
+
+    fn resolve_sdk(&self) -> Result<Sdk, Error> {
+        if let Some(sdk) = self.sdk {
+            return Ok(sdk);
+        }
+        let target = match &self.target {
+            Some(target) => target.clone(),
+            None => env::var("TARGET").map_err(|_| Error::MissingEnv("TARGET"))?,
+        };
+        Sdk::from_target(&target).ok_or(Error::UnsupportedTarget(target))
+    }
+
+    fn xcrun_program(&self) -> PathBuf {
+        self.xcrun
+            .clone()
+            .or_else(|| env::var_os(XCRUN_ENV).map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from("xcrun"))
+    }
+
+    fn use_prebuilt(&self, output: &Path, error: Error) -> Result<PathBuf, Error> {
+        let prebuilt = match &self.prebuilt {
+            Some(prebuilt) => prebuilt,
+            None => return Err(error),
+        };
+        fs::copy(prebuilt, output).map_err(|e| Error::Io(prebuilt.clone(), e))?;
+        let reason = error.to_string();
+        self.print(&format!(
+            "cargo:warning=can't compile shaders ({}), using prebuilt {}",
+            reason.lines().next().unwrap_or_default(),
+            prebuilt.display()
+        ));
+        Ok(output.to_path_buf())
+    }
+
+    fn print(&self, line: &str) {
+        let enabled = self
+            .cargo_metadata
+            .unwrap_or_else(|| env::var_os("CARGO_MANIFEST_DIR").is_some());
+        if enabled {
+            println!("{}", line);
+        }
+    }
+}
+
+fn run(mut command: Command) -> Result<(), Error> {
+    let output = match command.output() {
+        Ok(output) => output,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Err(Error::XcrunUnavailable(error))
+        }
+        Err(error) => return Err(Error::Spawn(PathBuf::from(command.get_program()), error)),
+    };
+    if output.status.success() {
+        return Ok(());
+    }
+    let program = command.get_program().to_string_lossy().into_owned();
+    let args: Vec<_> = command
+        .get_args()
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    Err(Error::ToolFailed {
+        command: format!("{} {}", program, args.join(" ")),
+        stdout: String::from_utf8_lossy(&output.stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+    })
+}
+
+fn find_metal_files(dir: &Path, files: &mut BTreeSet<PathBuf>) -> Result<(), Error> {
+    let entries = fs::read_dir(dir).map_err(|e| Error::Io(dir.to_path_buf(), e))?;
+    for entry in entries {
+        let path = entry.map_err(|e| Error::Io(dir.to_path_buf(), e))?.path();
+        if path.is_dir() {
+            find_metal_files(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "metal") {
+            files.insert(path);
+        }
+    }
+    Ok(())
+}
+
+/// The targets of `#include "..."` and `#import "..."` directives in `source`.
+fn quoted_includes(source: &str) -> impl Iterator<Item = &str> {
+    source.lines().filter_map(|line| {
+        let rest = line.trim_start().strip_prefix('#')?.trim_start();
+        let rest = rest
+            .strip_prefix("include")
+            .or_else(|| rest.strip_prefix("import"))?
+            .trim_start();
+        let rest = rest.strip_prefix('"')?;
+        rest.split_once('"').map(|(target, _)| target)
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// A scratch directory holding `shader.metal` and `prebuilt.metallib`.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("metal-build-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(dir.join("include")).unwrap();
+        fs::write(
+            dir.join("shader.metal"),
+            "#include \"common.h\"\nkernel void main0() {}\n",
+        )
+        .unwrap();
+        fs::write(dir.join("include/common.h"), "// common\n").unwrap();
+        fs::write(dir.join("prebuilt.metallib"), "prebuilt").unwrap();
+        dir
+    }
+
+    /// A stand-in for `xcrun` that logs its arguments to `xcrun.log` and runs
+    /// `body` with `$out` set to the `-o` argument.
+    fn stub_xcrun(dir: &Path, body: &str) -> PathBuf {
+        let path = dir.join("xcrun");
+        let script = format!(
+            "#!/bin/sh\n\
+             echo \"$@\" >> \"{}\"\n\
+             out=\n\
+             while [ $# -gt 0 ]; do\n\
+             \x20 if [ \"$1\" = -o ]; then out=\"$2\"; fi\n\
+             \x20 shift\n\
+             done\n\
+             {}\n",
+            dir.join("xcrun.log").display(),
+            body
+        );
+        fs::write(&path, script).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    fn build(dir: &Path) -> Build {
+        let mut build = Build::new();
+        build
+            .file(dir.join("shader.metal"))
+            .include(dir.join("include"))
+            .out_dir(dir)
+            .target("aarch64-apple-darwin")
+            .cargo_metadata(false);
+        build
+    }
+
+    #[test]
+    fn compile_with_stub_xcrun() {
+        let dir = scratch("compile");
+        let xcrun = stub_xcrun(&dir, "echo compiled > \"$out\"");
+        let output = build(&dir)
+            .xcrun(&xcrun)
+            .language_version(3, 0)
+            .define("FOO", Some("1"))
+            .try_compile("shaders")
+            .unwrap();
+        assert_eq!(output, dir.join("shaders.metallib"));
+        assert_eq!(fs::read_to_string(&output).unwrap(), "compiled\n");
+
+        let log = fs::read_to_string(dir.join("xcrun.log")).unwrap();
+        let lines = log.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("-sdk macosx metal -c -std=metal3.0 -I"));
+        assert!(lines[0].contains("-DFOO=1"));
+        assert!(lines[0].ends_with("shaders-0-shader.air"));
+        assert!(lines[1].starts_with("-sdk macosx metallib"));
+        assert!(lines[1].ends_with("shaders.metallib"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prebuilt_for_unsupported_target() {
+        let dir = scratch("unsupported");
+        let xcrun = stub_xcrun(&dir, "exit 1");
+        let output = build(&dir)
+            .xcrun(&xcrun)
+            .target("x86_64-unknown-linux-gnu")
+            .prebuilt(dir.join("prebuilt.metallib"))
+            .try_compile("shaders")
+            .unwrap();
+        assert_eq!(fs::read_to_string(output).unwrap(), "prebuilt");
+        // The SDK is resolved before anything is run.
+        assert!(!dir.join("xcrun.log").exists());
+
+        let error = build(&dir)
+            .xcrun(&xcrun)
+            .target("x86_64-unknown-linux-gnu")
+            .try_compile("shaders")
+            .unwrap_err();
+        assert!(matches!(error, Error::UnsupportedTarget(_)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prebuilt_when_xcrun_is_missing() {
+        let dir = scratch("missing");
+        let output = build(&dir)
+            .xcrun(dir.join("no-such-xcrun"))
+            .prebuilt(dir.join("prebuilt.metallib"))
+            .try_compile("shaders")
+            .unwrap();
+        assert_eq!(fs::read_to_string(output).unwrap(), "prebuilt");
+
+        let error = build(&dir)
+            .xcrun(dir.join("no-such-xcrun"))
+            .try_compile("shaders")
+            .unwrap_err();
+        assert!(matches!(error, Error::XcrunUnavailable(_)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prebuilt_when_sdk_is_missing() {
+        let dir = scratch("sdk");
+        let xcrun = stub_xcrun(
+            &dir,
+            "echo 'xcrun: error: SDK \"macosx\" cannot be located' >&2\nexit 1",
+        );
+        let output = build(&dir)
+            .xcrun(&xcrun)
+            .prebuilt(dir.join("prebuilt.metallib"))
+            .try_compile("shaders")
+            .unwrap();
+        assert_eq!(fs::read_to_string(output).unwrap(), "prebuilt");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shader_errors_are_reported() {
+        let dir = scratch("shader-error");
+        let xcrun = stub_xcrun(
+            &dir,
+            "echo 'shader.metal:2:1: error: unknown type name' >&2\nexit 1",
+        );
+        let error = build(&dir)
+            .xcrun(&xcrun)
+            .prebuilt(dir.join("prebuilt.metallib"))
+            .try_compile("shaders")
+            .unwrap_err();
+        match error {
+            Error::ToolFailed { stderr, .. } => assert!(stderr.contains("unknown type name")),
+            error => panic!("unexpected error: {}", error),
+        }
+        assert!(!dir.join("shaders.metallib").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn dependencies_follow_includes() {
+        let dir = scratch("dependencies");
+        let build = build(&dir);
+        let sources = build.sources().unwrap();
+        let dependencies = build.dependencies(&sources);
+        assert!(dependencies.contains(&dir.join("shader.metal")));
+        assert!(dependencies.contains(&dir.join("include/common.h")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sdk_from_target() {
+        assert_eq!(Sdk::from_target("aarch64-apple-darwin"), Some(Sdk::MacOsx));
+        assert_eq!(Sdk::from_target("aarch64-apple-ios"), Some(Sdk::IphoneOs));
+        assert_eq!(
+            Sdk::from_target("aarch64-apple-ios-sim"),
+            Some(Sdk::IphoneSimulator)
+        );
+        assert_eq!(
+            Sdk::from_target("aarch64-apple-ios-macabi"),
+            Some(Sdk::MacOsx)
+        );
+        assert_eq!(
+            Sdk::from_target("x86_64-apple-tvos"),
+            Some(Sdk::AppleTvSimulator)
+        );
+        assert_eq!(Sdk::from_target("x86_64-unknown-linux-gnu"), None);
+    }
+}
@@ -0,0 +1,192 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Derive macros for the `metal` crate. Use them through its `derive`
+//! feature rather than depending on this crate directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Error, Fields, LitStr, Path};
+
+/// Implement `metal::Pod` for a `#[repr(C)]` or `#[repr(transparent)]`
+/// struct whose fields are all `Pod`.
+///
+/// Fails to compile if a field is not `Pod` or the layout has padding.
+///
+/// The generated impl names the trait as `::metal::Pod`. If `metal` is
+/// renamed in `Cargo.toml` or re-exported from another crate, point the
+/// derive at it with `#[pod(crate = "path::to::metal")]`.
+///
+/// ```ignore
+/// #[derive(Clone, Copy, metal::Pod)]
+/// #[repr(C)]
+/// struct Uniforms {
+///     model: [[f32; 4]; 4],
+///     tint: [f32; 4],
+/// }
+/// ```
+#[proc_macro_derive(Pod, attributes(pod))]
+pub fn derive_pod(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match pod_impl(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
+
+fn pod_impl(input: &DeriveInput) -> Result<TokenStream2, Error> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "#[derive(Pod)] does not support generic types",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields.named.iter().collect::<Vec<_>>(),
+            Fields::Unnamed(fields) => fields.unnamed.iter().collect(),
+            Fields::Unit => Vec::new(),
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                name,
+                "#[derive(Pod)] is only supported on structs",
+            ))
+        }
+    };
+    if !has_stable_layout(input)? {
+        return Err(Error::new_spanned(
+            name,
+            "#[derive(Pod)] requires #[repr(C)] or #[repr(transparent)]",
+        ));
+    }
+
+    let krate = crate_path(input)?;
+    let types = fields.iter().map(|field| &field.ty).collect::<Vec<_>>();
+    let message = format!("`{}` has padding bytes and cannot be Pod", name);
+    Ok(quote! {
+        unsafe impl #krate::Pod for #name {}
+
+        const _: () = {
+            fn assert_pod<T: #krate::Pod>() {}
+            #[allow(dead_code)]
+            fn assert_fields() {
+                #(assert_pod::<#types>();)*
+            }
+            assert!(
+                ::std::mem::size_of::<#name>() == 0 #(+ ::std::mem::size_of::<#types>())*,
+                #message
+            );
+        };
+    })
+}
+
+/// The path given by `#[pod(crate = "...")]`, or `::metal`.
+fn crate_path(input: &DeriveInput) -> Result<Path, Error> {
+    let mut path = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("pod"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("crate") {
+                let value: LitStr = meta.value()?.parse()?;
+                path = Some(value.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown #[pod] attribute, expected `crate`"))
+            }
+        })?;
+    }
+    Ok(path.unwrap_or_else(|| parse_quote!(::metal)))
+}
+
+fn has_stable_layout(input: &DeriveInput) -> Result<bool, Error> {
+    let mut stable = false;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("repr"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("C") || meta.path.is_ident("transparent") {
+                stable = true;
+            } else if meta.input.peek(syn::token::Paren) {
+                // `align(N)` or `packed(N)`.
+                let content;
+                syn::parenthesized!(content in meta.input);
+                content.parse::<TokenStream2>()?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(stable)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand(input: &str) -> Result<String, String> {
+        let input = syn::parse_str::<DeriveInput>(input).unwrap();
+        pod_impl(&input)
+            .map(|tokens| tokens.to_string())
+            .map_err(|err| err.to_string())
+    }
+
+    #[test]
+    fn custom_crate_path() {
+        let tokens = expand("#[repr(C)] struct Uniforms { tint: [f32; 4] }").unwrap();
+        assert!(tokens.starts_with("unsafe impl :: metal :: Pod for Uniforms"));
+
+        let tokens = expand(
+            r#"#[repr(C)] #[pod(crate = "engine::gpu::metal")] struct Uniforms { tint: [f32; 4] }"#,
+        )
+        .unwrap();
+        assert!(tokens.starts_with("unsafe impl engine :: gpu :: metal :: Pod for Uniforms"));
+        assert!(tokens.contains("fn assert_pod < T : engine :: gpu :: metal :: Pod > ()"));
+        assert_eq!(tokens.matches(":: metal :: Pod").count(), 2);
+    }
+
+    #[test]
+    fn rejected_inputs() {
+        assert_eq!(
+            expand("struct Uniforms { tint: [f32; 4] }").unwrap_err(),
+            "#[derive(Pod)] requires #[repr(C)] or #[repr(transparent)]"
+        );
+        assert_eq!(
+            expand("#[repr(packed(2))] struct Uniforms { tint: [f32; 4] }").unwrap_err(),
+            "#[derive(Pod)] requires #[repr(C)] or #[repr(transparent)]"
+        );
+        assert_eq!(
+            expand("#[repr(C)] struct Wrapper<T> { value: T }").unwrap_err(),
+            "#[derive(Pod)] does not support generic types"
+        );
+        assert_eq!(
+            expand("#[repr(C)] enum Mode { A, B }").unwrap_err(),
+            "#[derive(Pod)] is only supported on structs"
+        );
+        assert_eq!(
+            expand(r#"#[repr(C)] #[pod(krate = "metal")] struct Unit;"#).unwrap_err(),
+            "unknown #[pod] attribute, expected `crate`"
+        );
+        assert!(expand(r#"#[repr(C)] #[pod(crate = "not a path")] struct Unit;"#).is_err());
+    }
+
+    #[test]
+    fn padding_assertion() {
+        let tokens = expand("#[repr(C, align(16))] struct Tint(u8, u32);").unwrap();
+        assert!(tokens.contains(
+            ":: std :: mem :: size_of :: < Tint > () == 0 + :: std :: mem :: size_of :: < u8 > () \
+             + :: std :: mem :: size_of :: < u32 > ()"
+        ));
+        assert!(tokens.contains("\"`Tint` has padding bytes and cannot be Pod\""));
+    }
+}
@@ -0,0 +1,385 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::*;
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+/// A resource that only lives for some passes of a frame.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TransientResource {
+    pub size: NSUInteger,
+    pub align: NSUInteger,
+    /// Index of the first pass that uses the resource.
+    pub first_pass: usize,
+    /// Index of the last pass that uses the resource.
+    pub last_pass: usize,
+}
+
+impl TransientResource {
+    /// `size_and_align` comes from `DeviceRef::heap_texture_size_and_align`
+    /// or `heap_buffer_size_and_align`.
+    pub fn new(size_and_align: MTLSizeAndAlign, passes: RangeInclusive<usize>) -> Self {
+        TransientResource {
+            size: size_and_align.size,
+            align: size_and_align.align,
+            first_pass: *passes.start(),
+            last_pass: *passes.end(),
+        }
+    }
+
+    fn lifetime_overlaps(&self, other: &TransientResource) -> bool {
+        self.first_pass <= other.last_pass && other.first_pass <= self.last_pass
+    }
+}
+
+/// Where a resource goes in the heap.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct AliasPlacement {
+    pub offset: NSUInteger,
+    pub size: NSUInteger,
+}
+
+impl AliasPlacement {
+    fn overlaps(&self, other: &AliasPlacement) -> bool {
+        self.offset < other.offset + other.size && other.offset < self.offset + self.size
+    }
+}
+
+/// Memory handed from one resource to another: the pass that first uses
+/// `next` must wait for a fence updated after the last pass using
+/// `previous`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AliasFence {
+    /// Update the fence at the end of this pass.
+    pub after_pass: usize,
+    /// Wait for the fence at the start of this pass.
+    pub before_pass: usize,
+    pub previous: usize,
+    pub next: usize,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AliasingPlanError {
+    /// The resource's last pass comes before its first.
+    InvalidLifetime { resource: usize },
+    /// Placing the resource would put its end past `NSUInteger::MAX`.
+    SizeOverflow { resource: usize },
+}
+
+impl fmt::Display for AliasingPlanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AliasingPlanError::InvalidLifetime { resource } => {
+                write!(f, "transient resource {} ends before it starts", resource)
+            }
+            AliasingPlanError::SizeOverflow { resource } => write!(
+                f,
+                "transient resource {} doesn't fit in a heap's address range",
+                resource
+            ),
+        }
+    }
+}
+
+impl std::error::Error for AliasingPlanError {}
+
+/// Heap offsets for a set of transient resources, such that resources whose
+/// lifetimes overlap never share memory.
+///
+/// Create a placement heap of [`heap_size`](Self::heap_size), place each
+/// resource at its offset and encode the [`fences`](Self::fences). Placement
+/// heaps alias whatever overlaps, so `make_aliasable`, which only applies to
+/// automatic heaps, isn't needed; the fences are what keep a resource from
+/// being overwritten while a pass still uses it.
+///
+/// ```ignore
+/// let resources = [
+///     TransientResource::new(device.heap_texture_size_and_align(&gbuffer), 0..=1),
+///     TransientResource::new(device.heap_texture_size_and_align(&bloom), 2..=3),
+/// ];
+/// let plan = AliasingPlan::new(&resources)?;
+/// descriptor.set_size(plan.heap_size());
+/// let heap = device.new_heap(&descriptor);
+/// let gbuffer = heap.new_texture_with_offset(&gbuffer, plan.placement(0).offset);
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AliasingPlan {
+    resources: Vec<TransientResource>,
+    placements: Vec<AliasPlacement>,
+    heap_size: NSUInteger,
+    fences: Vec<AliasFence>,
+}
+
+impl AliasingPlan {
+    /// Place the largest resources first, each at the lowest offset that
+    /// doesn't collide with an already placed resource it is alive with.
+    pub fn new(resources: &[TransientResource]) -> Result<Self, AliasingPlanError> {
+        if let Some(resource) = resources.iter().position(|r| r.first_pass > r.last_pass) {
+            return Err(AliasingPlanError::InvalidLifetime { resource });
+        }
+
+        let mut order = (0..resources.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&index| {
+            let resource = &resources[index];
+            (std::cmp::Reverse(resource.size), resource.first_pass, index)
+        });
+
+        let mut placements = vec![AliasPlacement { offset: 0, size: 0 }; resources.len()];
+        let mut placed = Vec::<usize>::with_capacity(resources.len());
+        for index in order {
+            let resource = &resources[index];
+            let mut taken = placed
+                .iter()
+                .filter(|&&other| resource.lifetime_overlaps(&resources[other]))
+                .map(|&other| placements[other])
+                .collect::<Vec<_>>();
+            taken.sort_by_key(|placement| placement.offset);
+
+            let overflow = AliasingPlanError::SizeOverflow { resource: index };
+            let align = resource.align.max(1);
+            let mut offset: NSUInteger = 0;
+            for placement in taken {
+                if offset.checked_add(resource.size).ok_or(overflow)? <= placement.offset {
+                    break;
+                }
+                // Placed resources already passed this check, so their end
+                // can't overflow.
+                let after = (placement.offset + placement.size)
+                    .checked_next_multiple_of(align)
+                    .ok_or(overflow)?;
+                offset = offset.max(after);
+            }
+            offset.checked_add(resource.size).ok_or(overflow)?;
+            placements[index] = AliasPlacement {
+                offset,
+                size: resource.size,
+            };
+            placed.push(index);
+        }
+
+        let heap_size = placements
+            .iter()
+            .map(|placement| placement.offset + placement.size)
+            .max()
+            .unwrap_or(0);
+
+        let mut fences = Vec::new();
+        for (next, resource) in resources.iter().enumerate() {
+            for (previous, other) in resources.iter().enumerate() {
+                if other.last_pass < resource.first_pass
+                    && placements[previous].overlaps(&placements[next])
+                {
+                    fences.push(AliasFence {
+                        after_pass: other.last_pass,
+                        before_pass: resource.first_pass,
+                        previous,
+                        next,
+                    });
+                }
+            }
+        }
+        fences.sort_unstable();
+
+        Ok(AliasingPlan {
+            resources: resources.to_vec(),
+            placements,
+            heap_size,
+            fences,
+        })
+    }
+
+    /// The smallest heap that holds every placement.
+    pub fn heap_size(&self) -> NSUInteger {
+        self.heap_size
+    }
+
+    /// The largest alignment any resource needs; the heap itself must be at
+    /// least this aligned.
+    pub fn heap_align(&self) -> NSUInteger {
+        self.resources
+            .iter()
+            .map(|resource| resource.align)
+            .max()
+            .unwrap_or(1)
+    }
+
+    pub fn placement(&self, resource: usize) -> AliasPlacement {
+        self.placements[resource]
+    }
+
+    pub fn placements(&self) -> &[AliasPlacement] {
+        &self.placements
+    }
+
+    /// Every hand-over of memory between resources, by pass.
+    pub fn fences(&self) -> &[AliasFence] {
+        &self.fences
+    }
+
+    /// The distinct `(after_pass, before_pass)` pairs that need a fence.
+    pub fn fence_passes(&self) -> Vec<(usize, usize)> {
+        let mut passes = self
+            .fences
+            .iter()
+            .map(|fence| (fence.after_pass, fence.before_pass))
+            .collect::<Vec<_>>();
+        passes.dedup();
+        passes
+    }
+
+    /// Resources whose last use is `pass`. Their memory goes to the
+    /// resources placed over them, behind the fences after `pass`.
+    pub fn released_after(&self, pass: usize) -> impl Iterator<Item = usize> + '_ {
+        self.resources
+            .iter()
+            .enumerate()
+            .filter(move |(_, resource)| resource.last_pass == pass)
+            .map(|(index, _)| index)
+    }
+
+    /// Bytes saved over giving every resource its own memory.
+    pub fn bytes_saved(&self) -> NSUInteger {
+        let total = self
+            .resources
+            .iter()
+            .fold(0, |total: NSUInteger, resource| {
+                total.saturating_add(resource.size)
+            });
+        total.saturating_sub(self.heap_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resource(
+        size: NSUInteger,
+        align: NSUInteger,
+        passes: RangeInclusive<usize>,
+    ) -> TransientResource {
+        TransientResource::new(MTLSizeAndAlign { size, align }, passes)
+    }
+
+    #[test]
+    fn reuses_memory_between_passes() {
+        let resources = [
+            resource(1000, 256, 0..=1),
+            resource(1000, 256, 2..=3),
+            resource(500, 256, 1..=2),
+        ];
+        let plan = AliasingPlan::new(&resources).unwrap();
+        assert_eq!(plan.placement(0).offset, plan.placement(1).offset);
+        assert_eq!(
+            plan.placement(2),
+            AliasPlacement {
+                offset: 1024,
+                size: 500
+            }
+        );
+        assert_eq!((plan.heap_size(), plan.heap_align()), (1524, 256));
+        assert_eq!(plan.bytes_saved(), 2500 - 1524);
+        assert_eq!(
+            plan.fences(),
+            [AliasFence {
+                after_pass: 1,
+                before_pass: 2,
+                previous: 0,
+                next: 1
+            }]
+        );
+        assert_eq!(plan.fence_passes(), [(1, 2)]);
+        assert_eq!(plan.released_after(1).collect::<Vec<_>>(), [0]);
+        assert_eq!(plan.released_after(3).collect::<Vec<_>>(), [1]);
+    }
+
+    #[test]
+    fn empty_and_invalid() {
+        let plan = AliasingPlan::new(&[]).unwrap();
+        assert_eq!((plan.heap_size(), plan.heap_align()), (0, 1));
+        assert!(plan.fences().is_empty());
+        let backwards = TransientResource {
+            first_pass: 3,
+            last_pass: 2,
+            ..resource(1, 1, 0..=0)
+        };
+        let err = AliasingPlan::new(&[resource(16, 16, 0..=0), backwards]);
+        assert_eq!(err, Err(AliasingPlanError::InvalidLifetime { resource: 1 }));
+    }
+
+    #[test]
+    fn offsets_that_overflow() {
+        // One resource may span the whole address range.
+        let plan = AliasingPlan::new(&[resource(NSUInteger::MAX, 1, 0..=0)]).unwrap();
+        assert_eq!(plan.heap_size(), NSUInteger::MAX);
+
+        let resources = [resource(NSUInteger::MAX, 1, 0..=1), resource(1, 1, 1..=2)];
+        assert_eq!(
+            AliasingPlan::new(&resources),
+            Err(AliasingPlanError::SizeOverflow { resource: 1 })
+        );
+        // Aligning past the first resource overflows, too.
+        let resources = [
+            resource(NSUInteger::MAX - 10, 1, 0..=0),
+            resource(1, 256, 0..=0),
+        ];
+        let err = AliasingPlan::new(&resources).unwrap_err();
+        assert_eq!(err, AliasingPlanError::SizeOverflow { resource: 1 });
+        assert_eq!(
+            err.to_string(),
+            "transient resource 1 doesn't fit in a heap's address range"
+        );
+        // Resources that don't overlap in time can still share it.
+        let resources = [
+            resource(NSUInteger::MAX, 1, 0..=0),
+            resource(NSUInteger::MAX, 1, 1..=1),
+        ];
+        let plan = AliasingPlan::new(&resources).unwrap();
+        assert_eq!(plan.fence_passes(), [(0, 1)]);
+    }
+
+    #[test]
+    fn random_plans_are_valid() {
+        let mut state = 12345u64;
+        let mut random = |n: u64| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state % n
+        };
+        for _ in 0..500 {
+            let count = 1 + random(20) as usize;
+            let resources = (0..count)
+                .map(|_| {
+                    let first = random(10) as usize;
+                    let last = first + random(5) as usize;
+                    resource(1 + random(5000), 1 << random(10), first..=last)
+                })
+                .collect::<Vec<_>>();
+            let plan = AliasingPlan::new(&resources).unwrap();
+            for (i, a) in resources.iter().enumerate() {
+                let placement = plan.placement(i);
+                assert_eq!(placement.offset % a.align, 0);
+                assert!(placement.offset + placement.size <= plan.heap_size());
+                for (j, b) in resources.iter().enumerate() {
+                    if i == j || !placement.overlaps(&plan.placement(j)) {
+                        continue;
+                    }
+                    assert!(!a.lifetime_overlaps(b), "{} and {} collide", i, j);
+                    if b.last_pass < a.first_pass {
+                        assert!(plan
+                            .fences()
+                            .iter()
+                            .any(|fence| fence.previous == j && fence.next == i));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -293,6 +293,12 @@ impl ArgumentRef {
         unsafe { msg_send_bool![self, isActive] }
     }
 
+    /// The number of elements of an array argument, which take up
+    /// consecutive indices starting at [`index`](Self::index); 1 otherwise.
+    pub fn array_length(&self) -> NSUInteger {
+        unsafe { msg_send![self, arrayLength] }
+    }
+
     pub fn buffer_alignment(&self) -> NSUInteger {
         unsafe { msg_send![self, bufferAlignment] }
     }
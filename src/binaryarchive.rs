@@ -0,0 +1,719 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Persistent [`BinaryArchive`]s, one per device, app version and OS build.
+//!
+//! Next to each archive a manifest lists the pipelines that were added to it,
+//! so an app can tell whether the archive still covers the pipelines it is
+//! about to create, and rebuild it in the background when it doesn't.
+
+use super::*;
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+
+const MANIFEST_HEADER: &str = "metal-binary-archive-manifest 1";
+const ARCHIVE_EXTENSION: &str = "binarchive";
+const MANIFEST_EXTENSION: &str = "manifest";
+
+/// What an archive was compiled for. Archives are only valid for the device,
+/// app build and OS build that produced them.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BinaryArchiveIdentity {
+    pub registry_id: u64,
+    pub app_version: String,
+    pub os_build: String,
+}
+
+impl BinaryArchiveIdentity {
+    pub fn new(registry_id: u64, app_version: &str, os_build: &str) -> Self {
+        BinaryArchiveIdentity {
+            registry_id,
+            app_version: app_version.to_string(),
+            os_build: os_build.to_string(),
+        }
+    }
+
+    /// The identity of archives compiled for `device` on the running OS.
+    pub fn current(device: &DeviceRef, app_version: &str) -> Self {
+        Self::new(device.registry_id(), app_version, &os_build_version())
+    }
+
+    /// The file name, without extension, archives with this identity use.
+    pub fn file_stem(&self) -> String {
+        let sanitize = |s: &str| -> String {
+            s.chars()
+                .map(|c| {
+                    if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                        c
+                    } else {
+                        '_'
+                    }
+                })
+                .collect()
+        };
+        format!(
+            "{:016x}_{}_{}",
+            self.registry_id,
+            sanitize(&self.app_version),
+            sanitize(&self.os_build)
+        )
+    }
+}
+
+/// The build number of the running OS, e.g. `23C64`, from
+/// `NSProcessInfo.operatingSystemVersionString`.
+pub fn os_build_version() -> String {
+    let version = unsafe {
+        let info: *mut Object = msg_send![class!(NSProcessInfo), processInfo];
+        let version: *mut Object = msg_send![info, operatingSystemVersionString];
+        crate::nsstring_as_str(&*version).to_string()
+    };
+    // "Version 14.2 (Build 23C64)"
+    version
+        .split("(Build ")
+        .nth(1)
+        .and_then(|rest| rest.split(')').next())
+        .map(str::to_string)
+        .unwrap_or(version)
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum BinaryArchivePipelineKind {
+    Render,
+    Compute,
+    Tile,
+}
+
+impl BinaryArchivePipelineKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Render => "render",
+            Self::Compute => "compute",
+            Self::Tile => "tile",
+        }
+    }
+
+    fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "render" => Self::Render,
+            "compute" => Self::Compute,
+            "tile" => Self::Tile,
+            _ => return None,
+        })
+    }
+}
+
+/// A pipeline recorded in a manifest.
+///
+/// `fingerprint` identifies the descriptor's contents, e.g. the
+/// [`pipeline_key`] of its descriptor value; a pipeline whose fingerprint
+/// changed needs to be added again.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BinaryArchiveManifestEntry {
+    pub kind: BinaryArchivePipelineKind,
+    pub name: String,
+    pub fingerprint: u64,
+}
+
+impl BinaryArchiveManifestEntry {
+    pub fn new(kind: BinaryArchivePipelineKind, name: &str, fingerprint: u64) -> Self {
+        BinaryArchiveManifestEntry {
+            kind,
+            name: name.to_string(),
+            fingerprint,
+        }
+    }
+}
+
+/// The pipelines an archive contains, and what it was compiled for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BinaryArchiveManifest {
+    pub identity: BinaryArchiveIdentity,
+    pub entries: Vec<BinaryArchiveManifestEntry>,
+}
+
+impl BinaryArchiveManifest {
+    pub fn new(identity: BinaryArchiveIdentity) -> Self {
+        BinaryArchiveManifest {
+            identity,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Record an entry, replacing an earlier one of the same kind and name.
+    pub fn add(&mut self, entry: BinaryArchiveManifestEntry) {
+        match self
+            .entries
+            .iter_mut()
+            .find(|e| e.kind == entry.kind && e.name == entry.name)
+        {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    pub fn contains(&self, entry: &BinaryArchiveManifestEntry) -> bool {
+        self.entries.contains(entry)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines();
+        if lines.next() != Some(MANIFEST_HEADER) {
+            return Err("not a binary archive manifest".to_string());
+        }
+        let mut field = |name: &str| -> Result<String, String> {
+            lines
+                .next()
+                .and_then(|line| line.strip_prefix(name))
+                .and_then(|rest| rest.strip_prefix(' '))
+                .map(str::to_string)
+                .ok_or_else(|| format!("missing {}", name))
+        };
+        let registry_id = field("registry_id")?;
+        let registry_id = u64::from_str_radix(registry_id.trim_start_matches("0x"), 16)
+            .map_err(|e| format!("invalid registry_id: {}", e))?;
+        let app_version = field("app_version")?;
+        let os_build = field("os_build")?;
+
+        let mut manifest = Self::new(BinaryArchiveIdentity::new(
+            registry_id,
+            &app_version,
+            &os_build,
+        ));
+        for (index, line) in lines.enumerate().filter(|(_, l)| !l.is_empty()) {
+            let invalid = || format!("invalid entry {}: '{}'", index + 1, line);
+            let mut parts = line.splitn(3, ' ');
+            let kind = parts
+                .next()
+                .and_then(BinaryArchivePipelineKind::parse)
+                .ok_or_else(invalid)?;
+            let fingerprint = parts
+                .next()
+                .and_then(|f| u64::from_str_radix(f, 16).ok())
+                .ok_or_else(invalid)?;
+            let name = parts.next().ok_or_else(invalid)?;
+            manifest.add(BinaryArchiveManifestEntry::new(kind, name, fingerprint));
+        }
+        Ok(manifest)
+    }
+}
+
+impl std::fmt::Display for BinaryArchiveManifest {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // Values are one line each; fold any newlines so the file stays parseable.
+        let line = |s: &str| s.replace(['\n', '\r'], " ");
+        writeln!(f, "{}", MANIFEST_HEADER)?;
+        writeln!(f, "registry_id {:#x}", self.identity.registry_id)?;
+        writeln!(f, "app_version {}", line(&self.identity.app_version))?;
+        writeln!(f, "os_build {}", line(&self.identity.os_build))?;
+        for entry in &self.entries {
+            writeln!(
+                f,
+                "{} {:016x} {}",
+                entry.kind.as_str(),
+                entry.fingerprint,
+                line(&entry.name)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether the stored archive can be used as is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BinaryArchiveStatus {
+    Fresh,
+    /// There is no archive, or no manifest for it.
+    Missing,
+    /// The manifest could not be read or parsed.
+    Corrupt(String),
+    /// The archive was compiled for a different device, app or OS build.
+    IdentityChanged(BinaryArchiveIdentity),
+    /// The archive lacks these pipelines, or has outdated versions of them.
+    MissingPipelines(Vec<BinaryArchiveManifestEntry>),
+}
+
+impl BinaryArchiveStatus {
+    pub fn is_fresh(&self) -> bool {
+        *self == BinaryArchiveStatus::Fresh
+    }
+}
+
+/// A pipeline to add to an archive during a rebuild.
+pub enum BinaryArchivePipeline {
+    Render {
+        name: String,
+        fingerprint: u64,
+        descriptor: RenderPipelineDescriptor,
+    },
+    Compute {
+        name: String,
+        fingerprint: u64,
+        descriptor: ComputePipelineDescriptor,
+    },
+    Tile {
+        name: String,
+        fingerprint: u64,
+        descriptor: TileRenderPipelineDescriptor,
+    },
+}
+
+impl BinaryArchivePipeline {
+    pub fn entry(&self) -> BinaryArchiveManifestEntry {
+        let (kind, name, fingerprint) = match self {
+            Self::Render {
+                name, fingerprint, ..
+            } => (BinaryArchivePipelineKind::Render, name, *fingerprint),
+            Self::Compute {
+                name, fingerprint, ..
+            } => (BinaryArchivePipelineKind::Compute, name, *fingerprint),
+            Self::Tile {
+                name, fingerprint, ..
+            } => (BinaryArchivePipelineKind::Tile, name, *fingerprint),
+        };
+        BinaryArchiveManifestEntry::new(kind, name, fingerprint)
+    }
+
+    /// Compile the pipeline's functions into `archive`.
+    pub fn add_to(&self, archive: &BinaryArchiveRef) -> Result<(), MetalError> {
+        match self {
+            Self::Render { descriptor, .. } => {
+                archive.add_render_pipeline_functions_with_descriptor(descriptor)?
+            }
+            Self::Compute { descriptor, .. } => {
+                archive.add_compute_pipeline_functions_with_descriptor(descriptor)?
+            }
+            Self::Tile { descriptor, .. } => {
+                archive.add_tile_render_pipeline_functions_with_descriptor(descriptor)?
+            }
+        };
+        Ok(())
+    }
+}
+
+/// A rebuild running on a background thread.
+pub struct BinaryArchiveRebuild {
+    handle: JoinHandle<Result<BinaryArchiveManifest, String>>,
+}
+
+impl BinaryArchiveRebuild {
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Wait for the rebuild and return the manifest of the new archive.
+    pub fn wait(self) -> Result<BinaryArchiveManifest, String> {
+        self.handle
+            .join()
+            .unwrap_or_else(|_| Err("binary archive rebuild panicked".to_string()))
+    }
+}
+
+/// Stores archives in a directory, named after their
+/// [`BinaryArchiveIdentity`].
+///
+/// ```ignore
+/// let identity = BinaryArchiveIdentity::current(&device, env!("CARGO_PKG_VERSION"));
+/// let manager = BinaryArchiveManager::new(cache_dir, identity);
+/// manager.remove_stale_archives()?;
+///
+/// let pipelines: Vec<BinaryArchivePipeline> = /* every pipeline the app uses */;
+/// let required: Vec<_> = pipelines.iter().map(|p| p.entry()).collect();
+/// let archive = manager.open_archive(&device)?;
+/// if !manager.status(&required).is_fresh() {
+///     // Use the archive as far as it goes, and replace it for the next launch.
+///     manager.rebuild_in_background(&device, pipelines);
+/// }
+/// ```
+pub struct BinaryArchiveManager {
+    directory: PathBuf,
+    identity: BinaryArchiveIdentity,
+}
+
+impl BinaryArchiveManager {
+    pub fn new<P: AsRef<Path>>(directory: P, identity: BinaryArchiveIdentity) -> Self {
+        BinaryArchiveManager {
+            directory: directory.as_ref().to_path_buf(),
+            identity,
+        }
+    }
+
+    pub fn identity(&self) -> &BinaryArchiveIdentity {
+        &self.identity
+    }
+
+    pub fn archive_path(&self) -> PathBuf {
+        self.path_with_extension(ARCHIVE_EXTENSION)
+    }
+
+    pub fn manifest_path(&self) -> PathBuf {
+        self.path_with_extension(MANIFEST_EXTENSION)
+    }
+
+    fn path_with_extension(&self, extension: &str) -> PathBuf {
+        self.directory
+            .join(format!("{}.{}", self.identity.file_stem(), extension))
+    }
+
+    /// The manifest of the stored archive, or `None` if there isn't one.
+    pub fn load_manifest(&self) -> Result<Option<BinaryArchiveManifest>, String> {
+        match fs::read_to_string(self.manifest_path()) {
+            Ok(text) => BinaryArchiveManifest::parse(&text).map(Some),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    /// Check the stored archive against the pipelines the app needs.
+    pub fn status(&self, required: &[BinaryArchiveManifestEntry]) -> BinaryArchiveStatus {
+        let manifest = match self.load_manifest() {
+            Ok(Some(manifest)) => manifest,
+            Ok(None) => return BinaryArchiveStatus::Missing,
+            Err(error) => return BinaryArchiveStatus::Corrupt(error),
+        };
+        if !self.archive_path().is_file() {
+            return BinaryArchiveStatus::Missing;
+        }
+        if manifest.identity != self.identity {
+            return BinaryArchiveStatus::IdentityChanged(manifest.identity);
+        }
+        let missing: Vec<_> = required
+            .iter()
+            .filter(|entry| !manifest.contains(entry))
+            .cloned()
+            .collect();
+        if missing.is_empty() {
+            BinaryArchiveStatus::Fresh
+        } else {
+            BinaryArchiveStatus::MissingPipelines(missing)
+        }
+    }
+
+    /// Archives in the directory built for another app version or OS build,
+    /// which can never be used again, as paths to the archive files. Archives
+    /// of other devices are kept; archives without a readable manifest are
+    /// stale, whatever they were built for.
+    ///
+    /// Don't call this while a rebuild is running: the archive it is about to
+    /// replace briefly has no manifest.
+    pub fn stale_archives(&self) -> Vec<PathBuf> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        let mut archives = BTreeSet::new();
+        for path in entries.filter_map(|entry| entry.ok().map(|e| e.path())) {
+            match path.extension() {
+                Some(ext) if ext == ARCHIVE_EXTENSION => archives.insert(path),
+                Some(ext) if ext == MANIFEST_EXTENSION => {
+                    archives.insert(path.with_extension(ARCHIVE_EXTENSION))
+                }
+                _ => false,
+            };
+        }
+        archives
+            .into_iter()
+            .filter(|archive| {
+                let manifest = fs::read_to_string(archive.with_extension(MANIFEST_EXTENSION))
+                    .ok()
+                    .and_then(|text| BinaryArchiveManifest::parse(&text).ok());
+                match manifest {
+                    Some(manifest) => {
+                        manifest.identity.app_version != self.identity.app_version
+                            || manifest.identity.os_build != self.identity.os_build
+                    }
+                    None => true,
+                }
+            })
+            .collect()
+    }
+
+    /// Delete the stale archives and their manifests, returning how many
+    /// archives were removed.
+    pub fn remove_stale_archives(&self) -> io::Result<usize> {
+        let stale = self.stale_archives();
+        for archive in &stale {
+            for path in [archive.clone(), archive.with_extension(MANIFEST_EXTENSION)] {
+                match fs::remove_file(path) {
+                    Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                    _ => {}
+                }
+            }
+        }
+        Ok(stale.len())
+    }
+
+    /// Open the stored archive, or create an empty one if there is none.
+    ///
+    /// An archive that doesn't contain a pipeline is still usable: Metal
+    /// compiles the missing functions as if there were no archive.
+    pub fn open_archive(&self, device: &DeviceRef) -> Result<BinaryArchive, MetalError> {
+        let descriptor = BinaryArchiveDescriptor::new();
+        let path = self.archive_path();
+        if path.is_file() {
+            descriptor.set_url(&file_url(&path));
+        }
+        device.new_binary_archive_with_descriptor(&descriptor)
+    }
+
+    /// Write a new archive on a background thread with `build`, which
+    /// receives the path to serialize to. Once it succeeds the archive and a
+    /// manifest listing `entries` replace the stored ones.
+    pub fn rebuild_with<F>(
+        &self,
+        entries: Vec<BinaryArchiveManifestEntry>,
+        build: F,
+    ) -> BinaryArchiveRebuild
+    where
+        F: FnOnce(&Path) -> Result<(), String> + Send + 'static,
+    {
+        let archive_path = self.archive_path();
+        let manifest_path = self.manifest_path();
+        let mut manifest = BinaryArchiveManifest::new(self.identity.clone());
+        for entry in entries {
+            manifest.add(entry);
+        }
+        let directory = self.directory.clone();
+
+        let handle = thread::spawn(move || {
+            fs::create_dir_all(&directory).map_err(|e| e.to_string())?;
+            // Write next to the final paths and rename, so a crash mid-write
+            // never leaves a truncated archive behind.
+            let archive_tmp = archive_path.with_extension("binarchive.tmp");
+            let manifest_tmp = manifest_path.with_extension("manifest.tmp");
+            let _ = fs::remove_file(&archive_tmp);
+            build(&archive_tmp)?;
+            fs::write(&manifest_tmp, manifest.to_string()).map_err(|e| e.to_string())?;
+            // Drop the old manifest first: an archive without one is
+            // treated as missing, never as fresh.
+            let _ = fs::remove_file(&manifest_path);
+            fs::rename(&archive_tmp, &archive_path).map_err(|e| e.to_string())?;
+            fs::rename(&manifest_tmp, &manifest_path).map_err(|e| e.to_string())?;
+            Ok(manifest)
+        });
+        BinaryArchiveRebuild { handle }
+    }
+
+    /// Compile `pipelines` into a new archive on a background thread.
+    pub fn rebuild_in_background(
+        &self,
+        device: &DeviceRef,
+        pipelines: Vec<BinaryArchivePipeline>,
+    ) -> BinaryArchiveRebuild {
+        let device = device.to_owned();
+        let entries = pipelines.iter().map(|p| p.entry()).collect();
+        self.rebuild_with(entries, move |path| {
+            let archive =
+                device.new_binary_archive_with_descriptor(&BinaryArchiveDescriptor::new())?;
+            for pipeline in &pipelines {
+                pipeline.add_to(&archive)?;
+            }
+            archive.serialize_to_url(&file_url(path))?;
+            Ok(())
+        })
+    }
+}
+
+fn file_url(path: &Path) -> URL {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    URL::new_with_file_path(&path.to_string_lossy())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use BinaryArchivePipelineKind::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("metal-archives-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn identity(app_version: &str) -> BinaryArchiveIdentity {
+        BinaryArchiveIdentity::new(0xabc, app_version, "23C64")
+    }
+
+    /// Store an archive and its manifest for `identity` in `dir`.
+    fn store(dir: &Path, identity: BinaryArchiveIdentity, entries: &[BinaryArchiveManifestEntry]) {
+        BinaryArchiveManager::new(dir, identity)
+            .rebuild_with(entries.to_vec(), |path| {
+                fs::write(path, b"archive").map_err(|e| e.to_string())
+            })
+            .wait()
+            .unwrap();
+    }
+
+    fn file_names(dir: &Path) -> Vec<String> {
+        let mut names = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn manifest_round_trip() {
+        let mut manifest = BinaryArchiveManifest::new(identity("1.2 beta"));
+        manifest.add(BinaryArchiveManifestEntry::new(
+            Render,
+            "gbuffer opaque",
+            42,
+        ));
+        manifest.add(BinaryArchiveManifestEntry::new(Compute, "cull", 7));
+        manifest.add(BinaryArchiveManifestEntry::new(Tile, "resolve", u64::MAX));
+        // Replaces the first entry.
+        manifest.add(BinaryArchiveManifestEntry::new(
+            Render,
+            "gbuffer opaque",
+            43,
+        ));
+        assert_eq!(manifest.entries.len(), 3);
+
+        let text = manifest.to_string();
+        assert_eq!(
+            text,
+            "metal-binary-archive-manifest 1\n\
+             registry_id 0xabc\n\
+             app_version 1.2 beta\n\
+             os_build 23C64\n\
+             render 000000000000002b gbuffer opaque\n\
+             compute 0000000000000007 cull\n\
+             tile ffffffffffffffff resolve\n"
+        );
+        assert_eq!(BinaryArchiveManifest::parse(&text).unwrap(), manifest);
+    }
+
+    #[test]
+    fn manifest_folds_newlines() {
+        let mut manifest = BinaryArchiveManifest::new(identity("1.2\nbeta"));
+        manifest.add(BinaryArchiveManifestEntry::new(Render, "two\r\nlines", 1));
+        let parsed = BinaryArchiveManifest::parse(&manifest.to_string()).unwrap();
+        assert_eq!(parsed.identity.app_version, "1.2 beta");
+        assert_eq!(parsed.entries[0].name, "two  lines");
+    }
+
+    #[test]
+    fn manifest_errors() {
+        assert!(BinaryArchiveManifest::parse("").is_err());
+        assert!(BinaryArchiveManifest::parse("something else\n").is_err());
+        let header = "metal-binary-archive-manifest 1\nregistry_id 0x1\n";
+        assert_eq!(
+            BinaryArchiveManifest::parse(header).unwrap_err(),
+            "missing app_version"
+        );
+        let identity = format!("{}app_version 1\nos_build 2\n", header);
+        assert!(BinaryArchiveManifest::parse(&identity).is_ok());
+        for entry in ["mesh 0 name", "render xyz name", "render 0"] {
+            let text = format!("{}{}\n", identity, entry);
+            let error = BinaryArchiveManifest::parse(&text).unwrap_err();
+            assert!(error.starts_with("invalid entry 1"), "{}", error);
+        }
+    }
+
+    #[test]
+    fn file_stem() {
+        let identity = BinaryArchiveIdentity::new(0xabc, "1.2 beta/3", "23C64");
+        assert_eq!(identity.file_stem(), "0000000000000abc_1.2_beta_3_23C64");
+    }
+
+    #[test]
+    fn status() {
+        let dir = scratch("status");
+        let manager = BinaryArchiveManager::new(&dir, identity("1.2"));
+        let gbuffer = BinaryArchiveManifestEntry::new(Render, "gbuffer", 42);
+        let cull = BinaryArchiveManifestEntry::new(Compute, "cull", 7);
+        assert_eq!(manager.status(&[]), BinaryArchiveStatus::Missing);
+
+        store(&dir, identity("1.2"), std::slice::from_ref(&gbuffer));
+        assert!(manager.status(std::slice::from_ref(&gbuffer)).is_fresh());
+        assert_eq!(
+            manager.status(&[gbuffer.clone(), cull.clone()]),
+            BinaryArchiveStatus::MissingPipelines(vec![cull.clone()])
+        );
+        let changed = BinaryArchiveManifestEntry::new(Render, "gbuffer", 43);
+        assert_eq!(
+            manager.status(std::slice::from_ref(&changed)),
+            BinaryArchiveStatus::MissingPipelines(vec![changed])
+        );
+
+        // A failed rebuild keeps the stored archive.
+        let rebuild = manager.rebuild_with(vec![cull.clone()], |_| Err("boom".to_string()));
+        assert_eq!(rebuild.wait().unwrap_err(), "boom");
+        assert!(manager.status(std::slice::from_ref(&gbuffer)).is_fresh());
+
+        // A manifest written under the wrong name.
+        let mut other = BinaryArchiveManifest::new(identity("1.1"));
+        other.add(gbuffer.clone());
+        fs::write(manager.manifest_path(), other.to_string()).unwrap();
+        assert_eq!(
+            manager.status(std::slice::from_ref(&gbuffer)),
+            BinaryArchiveStatus::IdentityChanged(identity("1.1"))
+        );
+
+        fs::write(manager.manifest_path(), "garbage").unwrap();
+        assert!(matches!(
+            manager.status(&[]),
+            BinaryArchiveStatus::Corrupt(_)
+        ));
+
+        fs::remove_file(manager.manifest_path()).unwrap();
+        assert_eq!(manager.status(&[]), BinaryArchiveStatus::Missing);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn staleness() {
+        let dir = scratch("stale");
+        let current = BinaryArchiveManager::new(&dir, identity("1.3"));
+        assert!(current.stale_archives().is_empty());
+
+        let old_app = BinaryArchiveManager::new(&dir, identity("1.2"));
+        let old_os =
+            BinaryArchiveManager::new(&dir, BinaryArchiveIdentity::new(0xabc, "1.3", "22A1"));
+        let other_device =
+            BinaryArchiveManager::new(&dir, BinaryArchiveIdentity::new(0xdef, "1.3", "23C64"));
+        let no_manifest =
+            BinaryArchiveManager::new(&dir, BinaryArchiveIdentity::new(0x123, "1.3", "23C64"));
+        let bad_manifest =
+            BinaryArchiveManager::new(&dir, BinaryArchiveIdentity::new(0x456, "1.3", "23C64"));
+        for manager in [&current, &old_app, &old_os, &other_device, &bad_manifest] {
+            store(&dir, manager.identity().clone(), &[]);
+        }
+        fs::write(no_manifest.archive_path(), b"archive").unwrap();
+        fs::write(bad_manifest.manifest_path(), "garbage").unwrap();
+        fs::write(dir.join("unrelated.txt"), "keep").unwrap();
+
+        let mut expected = vec![
+            old_app.archive_path(),
+            old_os.archive_path(),
+            no_manifest.archive_path(),
+            bad_manifest.archive_path(),
+        ];
+        expected.sort();
+        assert_eq!(current.stale_archives(), expected);
+
+        assert_eq!(current.remove_stale_archives().unwrap(), 4);
+        let mut kept = vec![
+            "unrelated.txt".to_string(),
+            format!("{}.binarchive", current.identity().file_stem()),
+            format!("{}.manifest", current.identity().file_stem()),
+            format!("{}.binarchive", other_device.identity().file_stem()),
+            format!("{}.manifest", other_device.identity().file_stem()),
+        ];
+        kept.sort();
+        assert_eq!(file_names(&dir), kept);
+        assert!(current.stale_archives().is_empty());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
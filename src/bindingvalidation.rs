@@ -0,0 +1,926 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Opt-in checks of bound resources against a pipeline's reflection, run at
+//! every draw or dispatch.
+//!
+//! The validator only sees plain descriptions of what is bound, so it can
+//! shadow a real encoder, a recorded [`RenderCommandList`] or
+//! [`ComputeCommandList`], or a test. Encoders aren't wrapped: to validate
+//! direct encoding, mirror each bind and draw on a [`BindingValidator`].
+
+use super::*;
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
+
+/// The function of a pipeline an argument belongs to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ArgumentStage {
+    Vertex,
+    Fragment,
+    Tile,
+    Compute,
+}
+
+impl ArgumentStage {
+    fn as_str(self) -> &'static str {
+        match self {
+            ArgumentStage::Vertex => "vertex",
+            ArgumentStage::Fragment => "fragment",
+            ArgumentStage::Tile => "tile",
+            ArgumentStage::Compute => "compute",
+        }
+    }
+}
+
+/// The parts of an [`ArgumentRef`] the validator checks.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ArgumentInfo {
+    pub name: String,
+    pub index: NSUInteger,
+    /// Array arguments take up `array_length` consecutive indices starting
+    /// at `index`.
+    pub array_length: NSUInteger,
+    pub type_: MTLArgumentType,
+    pub access: MTLArgumentAccess,
+    pub active: bool,
+    /// Only meaningful for buffers.
+    pub buffer_data_size: NSUInteger,
+    /// Only set for textures.
+    pub texture_type: Option<MTLTextureType>,
+}
+
+impl ArgumentInfo {
+    pub fn from_argument(argument: &ArgumentRef) -> Self {
+        let type_ = argument.type_();
+        ArgumentInfo {
+            name: argument.name().to_string(),
+            index: argument.index(),
+            array_length: argument.array_length(),
+            type_,
+            access: argument.access(),
+            active: argument.is_active(),
+            buffer_data_size: match type_ {
+                MTLArgumentType::Buffer => argument.buffer_data_size(),
+                _ => 0,
+            },
+            texture_type: match type_ {
+                MTLArgumentType::Texture => Some(argument.texture_type()),
+                _ => None,
+            },
+        }
+    }
+}
+
+/// The arguments of every function in a pipeline.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PipelineArguments {
+    arguments: Vec<(ArgumentStage, ArgumentInfo)>,
+}
+
+impl PipelineArguments {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_render_reflection(reflection: &RenderPipelineReflectionRef) -> Self {
+        let mut arguments = Self::new();
+        arguments.add_array(ArgumentStage::Vertex, reflection.vertex_arguments());
+        arguments.add_array(ArgumentStage::Fragment, reflection.fragment_arguments());
+        arguments.add_array(ArgumentStage::Tile, reflection.tile_arguments());
+        arguments
+    }
+
+    pub fn from_compute_reflection(reflection: &ComputePipelineReflectionRef) -> Self {
+        let mut arguments = Self::new();
+        arguments.add_array(ArgumentStage::Compute, reflection.arguments());
+        arguments
+    }
+
+    fn add_array(&mut self, stage: ArgumentStage, array: &ArgumentArrayRef) {
+        for i in 0..array.count() {
+            if let Some(argument) = array.object_at(i) {
+                self.add(stage, ArgumentInfo::from_argument(argument));
+            }
+        }
+    }
+
+    pub fn add(&mut self, stage: ArgumentStage, argument: ArgumentInfo) -> &mut Self {
+        self.arguments.push((stage, argument));
+        self
+    }
+
+    pub fn arguments(&self, stage: ArgumentStage) -> impl Iterator<Item = &ArgumentInfo> {
+        self.arguments
+            .iter()
+            .filter(move |(s, _)| *s == stage)
+            .map(|(_, argument)| argument)
+    }
+}
+
+/// A buffer, or inline bytes, as bound to a slot.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BoundBuffer {
+    pub length: NSUInteger,
+    pub offset: NSUInteger,
+    pub read_only: bool,
+}
+
+impl BoundBuffer {
+    pub fn new(buffer: &BufferRef, offset: NSUInteger) -> Self {
+        BoundBuffer {
+            length: buffer.length() as NSUInteger,
+            offset,
+            read_only: false,
+        }
+    }
+
+    /// Bytes set with `set_*_bytes`, which the shader cannot write to.
+    pub fn bytes(length: NSUInteger) -> Self {
+        BoundBuffer {
+            length,
+            offset: 0,
+            read_only: true,
+        }
+    }
+
+    fn available(&self) -> NSUInteger {
+        self.length.saturating_sub(self.offset)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BoundTexture {
+    pub texture_type: MTLTextureType,
+    pub usage: MTLTextureUsage,
+}
+
+impl BoundTexture {
+    pub fn new(texture: &TextureRef) -> Self {
+        BoundTexture {
+            texture_type: texture.texture_type(),
+            usage: texture.usage(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BindingProblem {
+    /// Nothing is bound at the argument's index.
+    Missing,
+    TextureTypeMismatch {
+        expected: MTLTextureType,
+        found: MTLTextureType,
+    },
+    BufferTooSmall {
+        required: NSUInteger,
+        available: NSUInteger,
+    },
+    /// The shader may write to the argument but the binding is read-only:
+    /// inline bytes, or a texture without `MTLTextureUsage::ShaderWrite`.
+    ReadOnlyBinding,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BindingWarning {
+    pub encoder_label: Option<String>,
+    /// Debug groups open at the draw or dispatch, outermost first.
+    pub debug_groups: Vec<String>,
+    /// Which draw or dispatch of the encoder, counting from 0.
+    pub command_index: usize,
+    pub stage: ArgumentStage,
+    pub argument: String,
+    /// The slot with the problem, past the argument's index for an element
+    /// of an array.
+    pub index: NSUInteger,
+    pub type_: MTLArgumentType,
+    pub problem: BindingProblem,
+}
+
+impl fmt::Display for BindingWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.encoder_label.as_deref().unwrap_or("<unlabeled>")
+        )?;
+        for group in &self.debug_groups {
+            write!(f, " > {}", group)?;
+        }
+        write!(
+            f,
+            ": command {}: {} {:?} '{}' at index {}: ",
+            self.command_index,
+            self.stage.as_str(),
+            self.type_,
+            self.argument,
+            self.index
+        )?;
+        match self.problem {
+            BindingProblem::Missing => write!(f, "nothing bound"),
+            BindingProblem::TextureTypeMismatch { expected, found } => {
+                write!(
+                    f,
+                    "bound {:?} texture, shader expects {:?}",
+                    found, expected
+                )
+            }
+            BindingProblem::BufferTooSmall {
+                required,
+                available,
+            } => write!(
+                f,
+                "{} bytes available past the offset, shader reads {}",
+                available, required
+            ),
+            BindingProblem::ReadOnlyBinding => write!(f, "shader writes to a read-only binding"),
+        }
+    }
+}
+
+type Slot = (ArgumentStage, NSUInteger);
+
+/// Shadows the bindings of one encoder and checks them at each draw or
+/// dispatch against the current pipeline's arguments.
+///
+/// ```ignore
+/// let mut validator = BindingValidator::new();
+/// validator.set_pipeline(Arc::new(PipelineArguments::from_render_reflection(&reflection)));
+/// validator.set_buffer(ArgumentStage::Vertex, 0, Some(BoundBuffer::new(&vertices, 0)));
+/// validator.validate_draw();
+/// for warning in validator.take_warnings() {
+///     log::warn!("{}", warning);
+/// }
+/// ```
+#[derive(Default)]
+pub struct BindingValidator {
+    label: Option<String>,
+    debug_groups: Vec<String>,
+    pipeline: Option<Arc<PipelineArguments>>,
+    buffers: HashMap<Slot, BoundBuffer>,
+    textures: HashMap<Slot, BoundTexture>,
+    samplers: HashSet<Slot>,
+    commands: usize,
+    warnings: Vec<BindingWarning>,
+}
+
+impl BindingValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        self.label = Some(label.to_string());
+    }
+
+    pub fn push_debug_group(&mut self, name: &str) {
+        self.debug_groups.push(name.to_string());
+    }
+
+    pub fn pop_debug_group(&mut self) {
+        self.debug_groups.pop();
+    }
+
+    pub fn set_pipeline(&mut self, arguments: Arc<PipelineArguments>) {
+        self.pipeline = Some(arguments);
+    }
+
+    pub fn set_buffer(
+        &mut self,
+        stage: ArgumentStage,
+        index: NSUInteger,
+        buffer: Option<BoundBuffer>,
+    ) {
+        match buffer {
+            Some(buffer) => self.buffers.insert((stage, index), buffer),
+            None => self.buffers.remove(&(stage, index)),
+        };
+    }
+
+    /// Move the offset of the buffer already bound at `index`.
+    pub fn set_buffer_offset(
+        &mut self,
+        stage: ArgumentStage,
+        index: NSUInteger,
+        offset: NSUInteger,
+    ) {
+        if let Some(buffer) = self.buffers.get_mut(&(stage, index)) {
+            buffer.offset = offset;
+        }
+    }
+
+    pub fn set_texture(
+        &mut self,
+        stage: ArgumentStage,
+        index: NSUInteger,
+        texture: Option<BoundTexture>,
+    ) {
+        match texture {
+            Some(texture) => self.textures.insert((stage, index), texture),
+            None => self.textures.remove(&(stage, index)),
+        };
+    }
+
+    pub fn set_sampler_state(&mut self, stage: ArgumentStage, index: NSUInteger, bound: bool) {
+        if bound {
+            self.samplers.insert((stage, index));
+        } else {
+            self.samplers.remove(&(stage, index));
+        }
+    }
+
+    /// Check the vertex and fragment arguments. Returns how many warnings
+    /// were added.
+    pub fn validate_draw(&mut self) -> usize {
+        self.validate(&[ArgumentStage::Vertex, ArgumentStage::Fragment])
+    }
+
+    pub fn validate_tile_dispatch(&mut self) -> usize {
+        self.validate(&[ArgumentStage::Tile])
+    }
+
+    pub fn validate_dispatch(&mut self) -> usize {
+        self.validate(&[ArgumentStage::Compute])
+    }
+
+    /// Replay the binds of a recorded render pass and check every draw,
+    /// returning how many warnings were added.
+    ///
+    /// `pipelines` holds the arguments of the pipeline states the list sets;
+    /// draws with a pipeline missing from it aren't checked. Handles that
+    /// don't resolve in `resources` count as unbound. Object and mesh stage
+    /// binds aren't tracked, so mesh draws only check the fragment stage.
+    /// Each list is encoded on its own encoder, so use one validator per
+    /// list.
+    pub fn validate_render_commands(
+        &mut self,
+        commands: &RenderCommandList,
+        resources: &CommandResources,
+        pipelines: &HashMap<RenderPipelineHandle, Arc<PipelineArguments>>,
+    ) -> usize {
+        use RenderCommand::*;
+        let before = self.warnings.len();
+        if let Some(label) = commands.label() {
+            self.set_label(label);
+        }
+        let argument_stage = |stage| match stage {
+            RenderStage::Vertex => Some(ArgumentStage::Vertex),
+            RenderStage::Fragment => Some(ArgumentStage::Fragment),
+            RenderStage::Object | RenderStage::Mesh => None,
+        };
+        for command in commands {
+            match *command {
+                SetRenderPipelineState { pipeline } => {
+                    self.pipeline = pipelines.get(&pipeline).cloned();
+                }
+                SetBuffer {
+                    stage,
+                    index,
+                    buffer,
+                    offset,
+                } => {
+                    if let Some(stage) = argument_stage(stage) {
+                        let buffer = buffer.and_then(|b| resources.buffer(b));
+                        self.set_buffer(stage, index, buffer.map(|b| BoundBuffer::new(b, offset)));
+                    }
+                }
+                SetBufferOffset {
+                    stage,
+                    index,
+                    offset,
+                } => {
+                    if let Some(stage) = argument_stage(stage) {
+                        self.set_buffer_offset(stage, index, offset);
+                    }
+                }
+                SetBytes {
+                    stage,
+                    index,
+                    ref bytes,
+                } => {
+                    if let Some(stage) = argument_stage(stage) {
+                        let bytes = BoundBuffer::bytes(bytes.len() as NSUInteger);
+                        self.set_buffer(stage, index, Some(bytes));
+                    }
+                }
+                SetTexture {
+                    stage,
+                    index,
+                    texture,
+                } => {
+                    if let Some(stage) = argument_stage(stage) {
+                        let texture = texture.and_then(|t| resources.texture(t));
+                        self.set_texture(stage, index, texture.map(BoundTexture::new));
+                    }
+                }
+                SetSamplerState {
+                    stage,
+                    index,
+                    sampler,
+                    ..
+                } => {
+                    if let Some(stage) = argument_stage(stage) {
+                        let bound = sampler.and_then(|s| resources.sampler(s)).is_some();
+                        self.set_sampler_state(stage, index, bound);
+                    }
+                }
+                Draw { .. }
+                | DrawIndexed { .. }
+                | DrawIndirect { .. }
+                | DrawIndexedIndirect { .. } => {
+                    self.validate_draw();
+                }
+                DrawMeshThreadgroups { .. } | DrawMeshThreads { .. } => {
+                    self.validate(&[ArgumentStage::Fragment]);
+                }
+                PushDebugGroup { ref name } => self.push_debug_group(name),
+                PopDebugGroup => self.pop_debug_group(),
+                _ => {}
+            }
+        }
+        self.warnings.len() - before
+    }
+
+    /// The compute counterpart of
+    /// [`validate_render_commands`](Self::validate_render_commands).
+    pub fn validate_compute_commands(
+        &mut self,
+        commands: &ComputeCommandList,
+        resources: &CommandResources,
+        pipelines: &HashMap<ComputePipelineHandle, Arc<PipelineArguments>>,
+    ) -> usize {
+        use ComputeCommand::*;
+        const STAGE: ArgumentStage = ArgumentStage::Compute;
+        let before = self.warnings.len();
+        if let Some(label) = commands.label() {
+            self.set_label(label);
+        }
+        for command in commands {
+            match *command {
+                SetComputePipelineState { pipeline } => {
+                    self.pipeline = pipelines.get(&pipeline).cloned();
+                }
+                SetBuffer {
+                    index,
+                    buffer,
+                    offset,
+                } => {
+                    let buffer = buffer.and_then(|b| resources.buffer(b));
+                    self.set_buffer(STAGE, index, buffer.map(|b| BoundBuffer::new(b, offset)));
+                }
+                SetBytes { index, ref bytes } => {
+                    let bytes = BoundBuffer::bytes(bytes.len() as NSUInteger);
+                    self.set_buffer(STAGE, index, Some(bytes));
+                }
+                SetTexture { index, texture } => {
+                    let texture = texture.and_then(|t| resources.texture(t));
+                    self.set_texture(STAGE, index, texture.map(BoundTexture::new));
+                }
+                SetSamplerState { index, sampler, .. } => {
+                    let bound = sampler.and_then(|s| resources.sampler(s)).is_some();
+                    self.set_sampler_state(STAGE, index, bound);
+                }
+                DispatchThreadgroups { .. }
+                | DispatchThreads { .. }
+                | DispatchThreadgroupsIndirect { .. } => {
+                    self.validate_dispatch();
+                }
+                PushDebugGroup { ref name } => self.push_debug_group(name),
+                PopDebugGroup => self.pop_debug_group(),
+                _ => {}
+            }
+        }
+        self.warnings.len() - before
+    }
+
+    pub fn warnings(&self) -> &[BindingWarning] {
+        &self.warnings
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<BindingWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    fn validate(&mut self, stages: &[ArgumentStage]) -> usize {
+        let command_index = self.commands;
+        self.commands += 1;
+        let pipeline = match &self.pipeline {
+            Some(pipeline) => pipeline.clone(),
+            None => return 0,
+        };
+        let before = self.warnings.len();
+        for &stage in stages {
+            for argument in pipeline.arguments(stage).filter(|a| a.active) {
+                let slots = argument.index..argument.index + argument.array_length.max(1);
+                for index in slots {
+                    if let Some(problem) = self.check(stage, index, argument) {
+                        self.warnings.push(BindingWarning {
+                            encoder_label: self.label.clone(),
+                            debug_groups: self.debug_groups.clone(),
+                            command_index,
+                            stage,
+                            argument: argument.name.clone(),
+                            index,
+                            type_: argument.type_,
+                            problem,
+                        });
+                    }
+                }
+            }
+        }
+        self.warnings.len() - before
+    }
+
+    fn check(
+        &self,
+        stage: ArgumentStage,
+        index: NSUInteger,
+        argument: &ArgumentInfo,
+    ) -> Option<BindingProblem> {
+        let slot = (stage, index);
+        let writes = argument.access != MTLArgumentAccess::ReadOnly;
+        match argument.type_ {
+            MTLArgumentType::Buffer => {
+                let buffer = match self.buffers.get(&slot) {
+                    Some(buffer) => buffer,
+                    None => return Some(BindingProblem::Missing),
+                };
+                if buffer.available() < argument.buffer_data_size {
+                    Some(BindingProblem::BufferTooSmall {
+                        required: argument.buffer_data_size,
+                        available: buffer.available(),
+                    })
+                } else if writes && buffer.read_only {
+                    Some(BindingProblem::ReadOnlyBinding)
+                } else {
+                    None
+                }
+            }
+            MTLArgumentType::Texture => {
+                let texture = match self.textures.get(&slot) {
+                    Some(texture) => texture,
+                    None => return Some(BindingProblem::Missing),
+                };
+                match argument.texture_type {
+                    Some(expected) if expected != texture.texture_type => {
+                        Some(BindingProblem::TextureTypeMismatch {
+                            expected,
+                            found: texture.texture_type,
+                        })
+                    }
+                    _ if writes && !texture.usage.contains(MTLTextureUsage::ShaderWrite) => {
+                        Some(BindingProblem::ReadOnlyBinding)
+                    }
+                    _ => None,
+                }
+            }
+            MTLArgumentType::Sampler if !self.samplers.contains(&slot) => {
+                Some(BindingProblem::Missing)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn argument(name: &str, index: NSUInteger, type_: MTLArgumentType) -> ArgumentInfo {
+        ArgumentInfo {
+            name: name.to_string(),
+            index,
+            array_length: 1,
+            type_,
+            access: MTLArgumentAccess::ReadOnly,
+            active: true,
+            buffer_data_size: 0,
+            texture_type: None,
+        }
+    }
+
+    fn buffer(name: &str, index: NSUInteger, size: NSUInteger) -> ArgumentInfo {
+        ArgumentInfo {
+            buffer_data_size: size,
+            ..argument(name, index, MTLArgumentType::Buffer)
+        }
+    }
+
+    fn texture(name: &str, index: NSUInteger, texture_type: MTLTextureType) -> ArgumentInfo {
+        ArgumentInfo {
+            texture_type: Some(texture_type),
+            ..argument(name, index, MTLArgumentType::Texture)
+        }
+    }
+
+    fn bound_texture(texture_type: MTLTextureType, usage: MTLTextureUsage) -> Option<BoundTexture> {
+        Some(BoundTexture {
+            texture_type,
+            usage,
+        })
+    }
+
+    fn render_pipeline() -> Arc<PipelineArguments> {
+        let mut arguments = PipelineArguments::new();
+        arguments
+            .add(ArgumentStage::Vertex, buffer("uniforms", 1, 64))
+            .add(
+                ArgumentStage::Fragment,
+                texture("albedo", 0, MTLTextureType::D2),
+            )
+            .add(
+                ArgumentStage::Fragment,
+                argument("linear", 0, MTLArgumentType::Sampler),
+            )
+            .add(
+                ArgumentStage::Fragment,
+                ArgumentInfo {
+                    active: false,
+                    ..buffer("unused", 3, 16)
+                },
+            );
+        Arc::new(arguments)
+    }
+
+    fn problems(validator: &BindingValidator) -> Vec<(NSUInteger, BindingProblem)> {
+        validator
+            .warnings()
+            .iter()
+            .map(|warning| (warning.index, warning.problem))
+            .collect()
+    }
+
+    #[test]
+    fn draw_checks() {
+        let mut validator = BindingValidator::new();
+        validator.set_label("gbuffer");
+        validator.push_debug_group("opaque");
+        validator.set_pipeline(render_pipeline());
+        let buffer = BoundBuffer {
+            length: 100,
+            offset: 64,
+            read_only: false,
+        };
+        validator.set_buffer(ArgumentStage::Vertex, 1, Some(buffer));
+        validator.set_texture(
+            ArgumentStage::Fragment,
+            0,
+            bound_texture(MTLTextureType::D2Array, MTLTextureUsage::ShaderRead),
+        );
+        assert_eq!(validator.validate_draw(), 3);
+        assert_eq!(
+            problems(&validator),
+            [
+                (
+                    1,
+                    BindingProblem::BufferTooSmall {
+                        required: 64,
+                        available: 36
+                    }
+                ),
+                (
+                    0,
+                    BindingProblem::TextureTypeMismatch {
+                        expected: MTLTextureType::D2,
+                        found: MTLTextureType::D2Array,
+                    }
+                ),
+                (0, BindingProblem::Missing),
+            ]
+        );
+        assert_eq!(
+            validator.warnings()[0].to_string(),
+            "gbuffer > opaque: command 0: vertex Buffer 'uniforms' at index 1: \
+             36 bytes available past the offset, shader reads 64"
+        );
+
+        validator.take_warnings();
+        validator.set_buffer_offset(ArgumentStage::Vertex, 1, 0);
+        validator.set_texture(
+            ArgumentStage::Fragment,
+            0,
+            bound_texture(MTLTextureType::D2, MTLTextureUsage::ShaderRead),
+        );
+        validator.set_sampler_state(ArgumentStage::Fragment, 0, true);
+        assert_eq!(validator.validate_draw(), 0);
+
+        // Unbinding brings the warning back.
+        validator.set_buffer(ArgumentStage::Vertex, 1, None);
+        validator.pop_debug_group();
+        assert_eq!(validator.validate_draw(), 1);
+        let warning = &validator.warnings()[0];
+        assert_eq!(warning.command_index, 2);
+        assert!(warning.debug_groups.is_empty());
+        assert_eq!(warning.problem, BindingProblem::Missing);
+    }
+
+    #[test]
+    fn writes_to_read_only_bindings() {
+        let mut arguments = PipelineArguments::new();
+        let writable = |argument: ArgumentInfo| ArgumentInfo {
+            access: MTLArgumentAccess::ReadWrite,
+            ..argument
+        };
+        arguments
+            .add(ArgumentStage::Compute, writable(buffer("out", 0, 4)))
+            .add(
+                ArgumentStage::Compute,
+                writable(texture("image", 1, MTLTextureType::D2)),
+            );
+        let mut validator = BindingValidator::new();
+        validator.set_pipeline(Arc::new(arguments));
+        validator.set_buffer(ArgumentStage::Compute, 0, Some(BoundBuffer::bytes(16)));
+        validator.set_texture(
+            ArgumentStage::Compute,
+            1,
+            bound_texture(MTLTextureType::D2, MTLTextureUsage::ShaderRead),
+        );
+        assert_eq!(validator.validate_dispatch(), 2);
+        assert_eq!(
+            problems(&validator),
+            [
+                (0, BindingProblem::ReadOnlyBinding),
+                (1, BindingProblem::ReadOnlyBinding)
+            ]
+        );
+
+        validator.take_warnings();
+        let buffer = BoundBuffer {
+            length: 16,
+            offset: 0,
+            read_only: false,
+        };
+        validator.set_buffer(ArgumentStage::Compute, 0, Some(buffer));
+        validator.set_texture(
+            ArgumentStage::Compute,
+            1,
+            bound_texture(
+                MTLTextureType::D2,
+                MTLTextureUsage::ShaderRead | MTLTextureUsage::ShaderWrite,
+            ),
+        );
+        assert_eq!(validator.validate_dispatch(), 0);
+        // Draws don't look at compute arguments.
+        assert_eq!(validator.validate_draw(), 0);
+    }
+
+    #[test]
+    fn arrays() {
+        let mut arguments = PipelineArguments::new();
+        arguments
+            .add(
+                ArgumentStage::Fragment,
+                ArgumentInfo {
+                    array_length: 3,
+                    ..texture("layers", 1, MTLTextureType::D2)
+                },
+            )
+            .add(
+                ArgumentStage::Fragment,
+                ArgumentInfo {
+                    array_length: 2,
+                    ..argument("samplers", 4, MTLArgumentType::Sampler)
+                },
+            );
+        let mut validator = BindingValidator::new();
+        validator.set_pipeline(Arc::new(arguments));
+        let d2 = bound_texture(MTLTextureType::D2, MTLTextureUsage::ShaderRead);
+        validator.set_texture(ArgumentStage::Fragment, 1, d2);
+        validator.set_texture(
+            ArgumentStage::Fragment,
+            3,
+            bound_texture(MTLTextureType::D3, MTLTextureUsage::ShaderRead),
+        );
+        validator.set_sampler_state(ArgumentStage::Fragment, 4, true);
+        assert_eq!(validator.validate_draw(), 3);
+        assert_eq!(
+            problems(&validator),
+            [
+                (2, BindingProblem::Missing),
+                (
+                    3,
+                    BindingProblem::TextureTypeMismatch {
+                        expected: MTLTextureType::D2,
+                        found: MTLTextureType::D3,
+                    }
+                ),
+                (5, BindingProblem::Missing),
+            ]
+        );
+        assert!(validator
+            .warnings()
+            .iter()
+            .all(|w| w.argument == "layers" || w.argument == "samplers"));
+    }
+
+    #[test]
+    fn without_pipeline() {
+        let mut validator = BindingValidator::new();
+        assert_eq!(validator.validate_draw(), 0);
+        validator.set_pipeline(render_pipeline());
+        assert_eq!(validator.validate_draw(), 3);
+        // Commands are counted even when they can't be checked.
+        assert!(validator.warnings().iter().all(|w| w.command_index == 1));
+        assert_eq!(
+            validator.warnings()[0].to_string(),
+            "<unlabeled>: command 1: vertex Buffer 'uniforms' at index 1: nothing bound"
+        );
+    }
+
+    #[test]
+    fn render_command_list() {
+        let pipeline = RenderPipelineHandle::new(0);
+        let other = RenderPipelineHandle::new(1);
+        let pipelines = HashMap::from([(pipeline, render_pipeline())]);
+        let resources = CommandResources::new();
+
+        let mut list = RenderCommandList::new();
+        list.set_label("main")
+            .push_debug_group("sky")
+            .set_render_pipeline_state(pipeline)
+            .set_bytes(RenderStage::Vertex, 1, &[0; 16])
+            // Doesn't resolve in `resources`.
+            .set_texture(RenderStage::Fragment, 0, Some(TextureHandle::new(7)))
+            .draw_primitives(MTLPrimitiveType::Triangle, 0, 3)
+            .pop_debug_group()
+            .set_bytes(RenderStage::Vertex, 1, &[0; 64])
+            .set_bytes(RenderStage::Mesh, 1, &[0; 4])
+            .draw_primitives(MTLPrimitiveType::Triangle, 0, 3)
+            // Not in `pipelines`, so not checked.
+            .set_render_pipeline_state(other)
+            .draw_primitives(MTLPrimitiveType::Triangle, 0, 3);
+
+        let mut validator = BindingValidator::new();
+        let added = validator.validate_render_commands(&list, &resources, &pipelines);
+        assert_eq!(added, 5);
+        let warnings = validator.warnings();
+        assert_eq!(
+            warnings[0].to_string(),
+            "main > sky: command 0: vertex Buffer 'uniforms' at index 1: \
+             16 bytes available past the offset, shader reads 64"
+        );
+        assert_eq!(
+            warnings
+                .iter()
+                .map(|w| (w.command_index, w.argument.as_str(), w.problem))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    0,
+                    "uniforms",
+                    BindingProblem::BufferTooSmall {
+                        required: 64,
+                        available: 16
+                    }
+                ),
+                (0, "albedo", BindingProblem::Missing),
+                (0, "linear", BindingProblem::Missing),
+                (1, "albedo", BindingProblem::Missing),
+                (1, "linear", BindingProblem::Missing),
+            ]
+        );
+        assert!(warnings[3].debug_groups.is_empty());
+    }
+
+    #[test]
+    fn compute_command_list() {
+        let pipeline = ComputePipelineHandle::new(0);
+        let mut arguments = PipelineArguments::new();
+        arguments.add(
+            ArgumentStage::Compute,
+            ArgumentInfo {
+                access: MTLArgumentAccess::WriteOnly,
+                ..buffer("out", 0, 4)
+            },
+        );
+        let pipelines = HashMap::from([(pipeline, Arc::new(arguments))]);
+
+        let mut list = ComputeCommandList::new();
+        list.set_compute_pipeline_state(pipeline)
+            .dispatch_threads(MTLSize::new(64, 1, 1), MTLSize::new(64, 1, 1))
+            .set_bytes(0, &[0; 4])
+            .dispatch_threads(MTLSize::new(64, 1, 1), MTLSize::new(64, 1, 1));
+
+        let mut validator = BindingValidator::new();
+        let added =
+            validator.validate_compute_commands(&list, &CommandResources::new(), &pipelines);
+        assert_eq!(added, 2);
+        assert_eq!(
+            validator
+                .warnings()
+                .iter()
+                .map(|w| (w.command_index, w.problem))
+                .collect::<Vec<_>>(),
+            [
+                (0, BindingProblem::Missing),
+                (1, BindingProblem::ReadOnlyBinding)
+            ]
+        );
+    }
+}
@@ -0,0 +1,1010 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::*;
+
+use std::fmt;
+
+/// The largest row pitch one buffer/texture copy accepts, in pixels or
+/// blocks.
+pub const MAX_BLIT_ROW_BLOCKS: NSUInteger = 32767;
+
+/// Per-call restrictions on buffer/texture copies.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BlitLimits {
+    /// Every copy's buffer offset must be a multiple of this, as well as of
+    /// the format's block size.
+    pub buffer_offset_alignment: NSUInteger,
+    /// The largest `bytes_per_row` a copy may use, in blocks.
+    pub max_row_blocks: NSUInteger,
+    /// Copies larger than this are split into bands of rows or images,
+    /// where the alignment rules allow it.
+    pub max_copy_bytes: NSUInteger,
+}
+
+impl BlitLimits {
+    pub fn new(buffer_offset_alignment: NSUInteger) -> Self {
+        BlitLimits {
+            buffer_offset_alignment,
+            max_row_blocks: MAX_BLIT_ROW_BLOCKS,
+            max_copy_bytes: NSUInteger::MAX,
+        }
+    }
+
+    /// Limits using [`MTLFeatureSet::copy_texture_buffer_alignment`].
+    pub fn from_feature_set(feature_set: MTLFeatureSet) -> Self {
+        Self::new(feature_set.copy_texture_buffer_alignment() as NSUInteger)
+    }
+}
+
+/// The texture a copy reads or writes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BlitTextureDesc {
+    pub texture_type: MTLTextureType,
+    pub pixel_format: MTLPixelFormat,
+    pub width: NSUInteger,
+    pub height: NSUInteger,
+    pub depth: NSUInteger,
+    pub mipmap_level_count: NSUInteger,
+    pub array_length: NSUInteger,
+}
+
+impl BlitTextureDesc {
+    pub fn from_texture(texture: &TextureRef) -> Self {
+        BlitTextureDesc {
+            texture_type: texture.texture_type(),
+            pixel_format: texture.pixel_format(),
+            width: texture.width(),
+            height: texture.height(),
+            depth: texture.depth(),
+            mipmap_level_count: texture.mipmap_level_count(),
+            array_length: texture.array_length(),
+        }
+    }
+
+    /// The number of slices, counting each cube face.
+    pub fn slice_count(&self) -> NSUInteger {
+        match self.texture_type {
+            MTLTextureType::Cube | MTLTextureType::CubeArray => self.array_length * 6,
+            _ => self.array_length,
+        }
+    }
+
+    /// The size of mipmap `level`.
+    pub fn level_size(&self, level: NSUInteger) -> MTLSize {
+        let shrink = |size: NSUInteger| size.checked_shr(level as u32).unwrap_or(0).max(1);
+        MTLSize::new(
+            shrink(self.width),
+            shrink(self.height),
+            if self.texture_type == MTLTextureType::D3 {
+                shrink(self.depth)
+            } else {
+                1
+            },
+        )
+    }
+}
+
+/// How texel data is laid out on the buffer side of a copy.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BlitBufferLayout {
+    pub offset: NSUInteger,
+    /// Distance between rows of pixels, or of blocks for compressed
+    /// formats. Zero means tightly packed.
+    pub bytes_per_row: NSUInteger,
+    /// Distance between images: depth slices of a 3D texture or array
+    /// slices. Zero means tightly packed.
+    pub bytes_per_image: NSUInteger,
+}
+
+impl BlitBufferLayout {
+    /// Tightly packed data starting at `offset`.
+    pub fn packed(offset: NSUInteger) -> Self {
+        BlitBufferLayout {
+            offset,
+            ..Default::default()
+        }
+    }
+}
+
+/// The part of a texture a copy covers. Each array slice is one image in
+/// the buffer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BlitTextureRegion {
+    pub level: NSUInteger,
+    pub slice: NSUInteger,
+    pub slice_count: NSUInteger,
+    pub origin: MTLOrigin,
+    pub size: MTLSize,
+}
+
+impl BlitTextureRegion {
+    pub fn new(level: NSUInteger, slice: NSUInteger, origin: MTLOrigin, size: MTLSize) -> Self {
+        BlitTextureRegion {
+            level,
+            slice,
+            slice_count: 1,
+            origin,
+            size,
+        }
+    }
+}
+
+/// One legal `copy_from_buffer_to_texture` or `copy_from_texture_to_buffer`
+/// call.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct BlitCopy {
+    pub buffer_offset: NSUInteger,
+    pub bytes_per_row: NSUInteger,
+    pub bytes_per_image: NSUInteger,
+    pub slice: NSUInteger,
+    pub level: NSUInteger,
+    pub origin: MTLOrigin,
+    pub size: MTLSize,
+}
+
+impl BlitCopy {
+    pub fn encode_buffer_to_texture(
+        &self,
+        encoder: &BlitCommandEncoderRef,
+        buffer: &BufferRef,
+        texture: &TextureRef,
+        options: MTLBlitOption,
+    ) {
+        encoder.copy_from_buffer_to_texture(
+            buffer,
+            self.buffer_offset,
+            self.bytes_per_row,
+            self.bytes_per_image,
+            self.size,
+            texture,
+            self.slice,
+            self.level,
+            self.origin,
+            options,
+        );
+    }
+
+    pub fn encode_texture_to_buffer(
+        &self,
+        encoder: &BlitCommandEncoderRef,
+        texture: &TextureRef,
+        buffer: &BufferRef,
+        options: MTLBlitOption,
+    ) {
+        encoder.copy_from_texture_to_buffer(
+            texture,
+            self.slice,
+            self.level,
+            self.origin,
+            self.size,
+            buffer,
+            self.buffer_offset,
+            self.bytes_per_row,
+            self.bytes_per_image,
+            options,
+        );
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlitPlanError {
+    /// The format has no single block layout, or is PVRTC, which can only
+    /// be copied whole.
+    UnsupportedFormat(MTLPixelFormat),
+    /// Multisample textures can't be copied to or from buffers.
+    UnsupportedTextureType(MTLTextureType),
+    LevelOutOfRange {
+        level: NSUInteger,
+        level_count: NSUInteger,
+    },
+    SliceOutOfRange {
+        slice: NSUInteger,
+        slice_count: NSUInteger,
+        texture_slices: NSUInteger,
+    },
+    RegionOutOfBounds {
+        origin: MTLOrigin,
+        size: MTLSize,
+        level_size: MTLSize,
+    },
+    /// The region doesn't start on a block boundary, or doesn't end on one
+    /// short of the level's edge.
+    UnalignedRegion {
+        origin: MTLOrigin,
+        size: MTLSize,
+        block: PixelFormatBlock,
+    },
+    /// `bytes_per_row` is shorter than a row or not a multiple of the block
+    /// size.
+    InvalidBytesPerRow {
+        bytes_per_row: NSUInteger,
+        row_bytes: NSUInteger,
+    },
+    InvalidBytesPerImage {
+        bytes_per_image: NSUInteger,
+        image_bytes: NSUInteger,
+    },
+    /// A copy would start at an offset the device can't copy from. See
+    /// [`BlitCopyPlanner::plan`].
+    UnalignedOffset {
+        offset: NSUInteger,
+        alignment: NSUInteger,
+    },
+    BufferTooSmall {
+        required: NSUInteger,
+        buffer_length: NSUInteger,
+    },
+}
+
+impl fmt::Display for BlitPlanError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            BlitPlanError::UnsupportedFormat(format) => {
+                write!(f, "{:?} can't be copied in parts", format)
+            }
+            BlitPlanError::UnsupportedTextureType(texture_type) => {
+                write!(
+                    f,
+                    "{:?} textures can't be copied to or from buffers",
+                    texture_type
+                )
+            }
+            BlitPlanError::LevelOutOfRange { level, level_count } => write!(
+                f,
+                "mipmap level {} is out of range for a texture with {} levels",
+                level, level_count
+            ),
+            BlitPlanError::SliceOutOfRange {
+                slice,
+                slice_count,
+                texture_slices,
+            } => write!(
+                f,
+                "slices {}..{} are out of range for a texture with {} slices",
+                slice,
+                slice + slice_count,
+                texture_slices
+            ),
+            BlitPlanError::RegionOutOfBounds {
+                origin,
+                size,
+                level_size,
+            } => write!(
+                f,
+                "region {:?} {:?} is outside the level's {:?}",
+                origin, size, level_size
+            ),
+            BlitPlanError::UnalignedRegion {
+                origin,
+                size,
+                block,
+            } => write!(
+                f,
+                "region {:?} {:?} is not aligned to {}x{} blocks",
+                origin, size, block.width, block.height
+            ),
+            BlitPlanError::InvalidBytesPerRow {
+                bytes_per_row,
+                row_bytes,
+            } => write!(
+                f,
+                "bytes_per_row {} is invalid for {} byte rows",
+                bytes_per_row, row_bytes
+            ),
+            BlitPlanError::InvalidBytesPerImage {
+                bytes_per_image,
+                image_bytes,
+            } => write!(
+                f,
+                "bytes_per_image {} is smaller than a {} byte image",
+                bytes_per_image, image_bytes
+            ),
+            BlitPlanError::UnalignedOffset { offset, alignment } => write!(
+                f,
+                "buffer offset {} is not a multiple of {}",
+                offset, alignment
+            ),
+            BlitPlanError::BufferTooSmall {
+                required,
+                buffer_length,
+            } => write!(
+                f,
+                "copy needs {} bytes of a {} byte buffer",
+                required, buffer_length
+            ),
+        }
+    }
+}
+
+impl std::error::Error for BlitPlanError {}
+
+/// Splits buffer/texture copies into calls that follow Metal's rules:
+/// block-aligned regions, block-multiple row pitches within
+/// [`BlitLimits::max_row_blocks`], aligned buffer offsets, and one call per
+/// array slice and mipmap level.
+///
+/// The same plan works in both directions.
+///
+/// ```ignore
+/// let planner = BlitCopyPlanner::new(
+///     BlitTextureDesc::from_texture(&atlas),
+///     BlitLimits::from_feature_set(MTLFeatureSet::macOS_GPUFamily2_v1),
+/// )?;
+/// let region = BlitTextureRegion::new(0, 0, MTLOrigin { x: 0, y: 0, z: 0 }, size);
+/// for copy in planner.plan(BlitBufferLayout::packed(offset), staging.length(), region)? {
+///     copy.encode_buffer_to_texture(blit, &staging, &atlas, MTLBlitOption::empty());
+/// }
+/// ```
+#[derive(Copy, Clone, Debug)]
+pub struct BlitCopyPlanner {
+    texture: BlitTextureDesc,
+    block: PixelFormatBlock,
+    limits: BlitLimits,
+}
+
+impl BlitCopyPlanner {
+    pub fn new(texture: BlitTextureDesc, limits: BlitLimits) -> Result<Self, BlitPlanError> {
+        match texture.texture_type {
+            MTLTextureType::D2Multisample | MTLTextureType::D2MultisampleArray => {
+                return Err(BlitPlanError::UnsupportedTextureType(texture.texture_type))
+            }
+            _ => {}
+        }
+        let block = match texture.pixel_format.block() {
+            Some(block) if !texture.pixel_format.is_pvrtc() => block,
+            _ => return Err(BlitPlanError::UnsupportedFormat(texture.pixel_format)),
+        };
+        Ok(BlitCopyPlanner {
+            texture,
+            block,
+            limits,
+        })
+    }
+
+    pub fn texture(&self) -> &BlitTextureDesc {
+        &self.texture
+    }
+
+    pub fn limits(&self) -> &BlitLimits {
+        &self.limits
+    }
+
+    /// Plan the copies between `region` and a buffer of `buffer_length`
+    /// bytes laid out as `layout`. An empty region needs no copies.
+    ///
+    /// Fails with [`BlitPlanError::UnalignedOffset`] if `layout.offset` is
+    /// not a multiple of both the block size and
+    /// [`BlitLimits::buffer_offset_alignment`], or if a split copy would
+    /// start at such an offset because `bytes_per_row` or `bytes_per_image`
+    /// isn't a multiple of them. The planner only splits copies, it doesn't
+    /// move data: stage the data at an aligned offset with aligned pitches,
+    /// or `copy_from_buffer` it to one first.
+    pub fn plan(
+        &self,
+        layout: BlitBufferLayout,
+        buffer_length: NSUInteger,
+        region: BlitTextureRegion,
+    ) -> Result<Vec<BlitCopy>, BlitPlanError> {
+        let block_width = self.block.width as NSUInteger;
+        let block_height = self.block.height as NSUInteger;
+        let block_bytes = self.block.bytes as NSUInteger;
+        let BlitTextureRegion {
+            level,
+            slice,
+            slice_count,
+            origin,
+            size,
+        } = region;
+
+        if level >= self.texture.mipmap_level_count {
+            return Err(BlitPlanError::LevelOutOfRange {
+                level,
+                level_count: self.texture.mipmap_level_count,
+            });
+        }
+        let texture_slices = self.texture.slice_count();
+        if slice
+            .checked_add(slice_count)
+            .is_none_or(|end| end > texture_slices)
+        {
+            return Err(BlitPlanError::SliceOutOfRange {
+                slice,
+                slice_count,
+                texture_slices,
+            });
+        }
+        let level_size = self.texture.level_size(level);
+        let fits = |start: NSUInteger, length: NSUInteger, limit: NSUInteger| {
+            start.checked_add(length).is_some_and(|end| end <= limit)
+        };
+        if !fits(origin.x, size.width, level_size.width)
+            || !fits(origin.y, size.height, level_size.height)
+            || !fits(origin.z, size.depth, level_size.depth)
+        {
+            return Err(BlitPlanError::RegionOutOfBounds {
+                origin,
+                size,
+                level_size,
+            });
+        }
+        if size.width == 0 || size.height == 0 || size.depth == 0 || slice_count == 0 {
+            return Ok(Vec::new());
+        }
+        let block_aligned = |start: NSUInteger, length: NSUInteger, edge, block| {
+            start % block == 0 && (length % block == 0 || start + length == edge)
+        };
+        if !block_aligned(origin.x, size.width, level_size.width, block_width)
+            || !block_aligned(origin.y, size.height, level_size.height, block_height)
+        {
+            return Err(BlitPlanError::UnalignedRegion {
+                origin,
+                size,
+                block: self.block,
+            });
+        }
+
+        let row_blocks = size.width.div_ceil(block_width);
+        let block_rows = size.height.div_ceil(block_height);
+        let row_bytes = row_blocks * block_bytes;
+        let bytes_per_row = match layout.bytes_per_row {
+            0 => row_bytes,
+            bytes_per_row if bytes_per_row >= row_bytes && bytes_per_row % block_bytes == 0 => {
+                bytes_per_row
+            }
+            bytes_per_row => {
+                return Err(BlitPlanError::InvalidBytesPerRow {
+                    bytes_per_row,
+                    row_bytes,
+                })
+            }
+        };
+        let image_bytes = (block_rows - 1) * bytes_per_row + row_bytes;
+        let bytes_per_image = match layout.bytes_per_image {
+            0 => bytes_per_row * block_rows,
+            bytes_per_image if bytes_per_image >= image_bytes => bytes_per_image,
+            bytes_per_image => {
+                return Err(BlitPlanError::InvalidBytesPerImage {
+                    bytes_per_image,
+                    image_bytes,
+                })
+            }
+        };
+
+        // Either the depth of a 3D region or the array slices, never both.
+        let images = size.depth * slice_count;
+        let required = (images - 1)
+            .checked_mul(bytes_per_image)
+            .and_then(|images| images.checked_add(image_bytes))
+            .and_then(|bytes| bytes.checked_add(layout.offset));
+        if required.is_none_or(|required| required > buffer_length) {
+            return Err(BlitPlanError::BufferTooSmall {
+                required: required.unwrap_or(NSUInteger::MAX),
+                buffer_length,
+            });
+        }
+
+        let alignment = lcm(block_bytes, self.limits.buffer_offset_alignment.max(1));
+        if layout.offset % alignment != 0 {
+            return Err(BlitPlanError::UnalignedOffset {
+                offset: layout.offset,
+                alignment,
+            });
+        }
+        let max_row_bytes = self.limits.max_row_blocks.max(1) * block_bytes;
+
+        // Columns: only split when a row is wider than a copy allows, in
+        // chunks whose byte offsets stay aligned.
+        let column_step = alignment / block_bytes;
+        let mut chunk_blocks = row_blocks.min(self.limits.max_row_blocks.max(1));
+        if chunk_blocks < row_blocks {
+            chunk_blocks = (chunk_blocks - chunk_blocks % column_step).max(column_step);
+        }
+
+        // Rows: one block row per copy when the pitch itself is too long,
+        // otherwise bands that fit `max_copy_bytes`.
+        let max_copy_bytes = self.limits.max_copy_bytes.max(1);
+        let band_rows = if bytes_per_row > max_row_bytes {
+            1
+        } else {
+            let step = alignment / gcd(bytes_per_row, alignment);
+            let rows = max_copy_bytes / bytes_per_row;
+            (rows - rows % step).max(step).min(block_rows)
+        };
+
+        // Images: several depth slices per copy only for 3D regions whose
+        // images are copied whole, with a pitch a copy accepts. A single
+        // block row wider than that is copied one column chunk at a time.
+        let band_images =
+            if band_rows == block_rows && bytes_per_row <= max_row_bytes && slice_count == 1 {
+                let step = alignment / gcd(bytes_per_image, alignment);
+                let count = max_copy_bytes / bytes_per_image;
+                (count - count % step).max(step).min(size.depth)
+            } else {
+                1
+            };
+
+        let mut copies = Vec::new();
+        for slice_index in 0..slice_count {
+            for z in (0..size.depth).step_by(band_images as usize) {
+                let depth = band_images.min(size.depth - z);
+                let image = slice_index + z;
+                for row in (0..block_rows).step_by(band_rows as usize) {
+                    let rows = band_rows.min(block_rows - row);
+                    for column in (0..row_blocks).step_by(chunk_blocks as usize) {
+                        let columns = chunk_blocks.min(row_blocks - column);
+                        let buffer_offset = layout.offset
+                            + image * bytes_per_image
+                            + row * bytes_per_row
+                            + column * block_bytes;
+                        if buffer_offset % alignment != 0 {
+                            return Err(BlitPlanError::UnalignedOffset {
+                                offset: buffer_offset,
+                                alignment,
+                            });
+                        }
+                        let x = column * block_width;
+                        let y = row * block_height;
+                        let copy_bytes_per_row = if rows > 1 || depth > 1 {
+                            bytes_per_row
+                        } else {
+                            columns * block_bytes
+                        };
+                        copies.push(BlitCopy {
+                            buffer_offset,
+                            bytes_per_row: copy_bytes_per_row,
+                            bytes_per_image: if depth > 1 {
+                                bytes_per_image
+                            } else {
+                                copy_bytes_per_row * rows
+                            },
+                            slice: slice + slice_index,
+                            level,
+                            origin: MTLOrigin {
+                                x: origin.x + x,
+                                y: origin.y + y,
+                                z: origin.z + z,
+                            },
+                            size: MTLSize::new(
+                                (columns * block_width).min(size.width - x),
+                                (rows * block_height).min(size.height - y),
+                                depth,
+                            ),
+                        });
+                    }
+                }
+            }
+        }
+        Ok(copies)
+    }
+}
+
+fn gcd(mut a: NSUInteger, mut b: NSUInteger) -> NSUInteger {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+fn lcm(a: NSUInteger, b: NSUInteger) -> NSUInteger {
+    a / gcd(a, b) * b
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn desc(
+        pixel_format: MTLPixelFormat,
+        texture_type: MTLTextureType,
+        size: (NSUInteger, NSUInteger, NSUInteger),
+        mipmap_level_count: NSUInteger,
+        array_length: NSUInteger,
+    ) -> BlitTextureDesc {
+        BlitTextureDesc {
+            texture_type,
+            pixel_format,
+            width: size.0,
+            height: size.1,
+            depth: size.2,
+            mipmap_level_count,
+            array_length,
+        }
+    }
+
+    fn origin(x: NSUInteger, y: NSUInteger, z: NSUInteger) -> MTLOrigin {
+        MTLOrigin { x, y, z }
+    }
+
+    fn level_region(level: NSUInteger, size: MTLSize) -> BlitTextureRegion {
+        BlitTextureRegion::new(level, 0, origin(0, 0, 0), size)
+    }
+
+    fn limits(
+        buffer_offset_alignment: NSUInteger,
+        max_row_blocks: NSUInteger,
+        max_copy_bytes: NSUInteger,
+    ) -> BlitLimits {
+        BlitLimits {
+            buffer_offset_alignment,
+            max_row_blocks,
+            max_copy_bytes,
+        }
+    }
+
+    /// Checks `copies` follow `planner`'s limits and cover `region` once.
+    fn check_copies(planner: &BlitCopyPlanner, region: BlitTextureRegion, copies: &[BlitCopy]) {
+        let block = planner.block;
+        let block_bytes = block.bytes as NSUInteger;
+        let alignment = lcm(block_bytes, planner.limits.buffer_offset_alignment);
+        let mut texels = 0;
+        for copy in copies {
+            assert_eq!(copy.buffer_offset % alignment, 0, "{:?}", copy);
+            assert!(
+                copy.bytes_per_row <= planner.limits.max_row_blocks * block_bytes,
+                "{:?}",
+                copy
+            );
+            assert_eq!(copy.bytes_per_row % block_bytes, 0, "{:?}", copy);
+            assert!(copy.slice >= region.slice && copy.slice < region.slice + region.slice_count);
+            assert!(copy.origin.x + copy.size.width <= region.origin.x + region.size.width);
+            assert!(copy.origin.y + copy.size.height <= region.origin.y + region.size.height);
+            assert!(copy.origin.z + copy.size.depth <= region.origin.z + region.size.depth);
+            texels += copy.size.width * copy.size.height * copy.size.depth;
+        }
+        assert_eq!(
+            texels,
+            region.size.width * region.size.height * region.size.depth * region.slice_count
+        );
+    }
+
+    #[test]
+    fn whole_texture() {
+        let planner = BlitCopyPlanner::new(
+            desc(
+                MTLPixelFormat::RGBA8Unorm,
+                MTLTextureType::D2,
+                (1024, 1024, 1),
+                11,
+                1,
+            ),
+            BlitLimits::new(256),
+        )
+        .unwrap();
+        let region = level_region(0, MTLSize::new(1024, 1024, 1));
+        let copies = planner
+            .plan(BlitBufferLayout::packed(512), 1 << 30, region)
+            .unwrap();
+        assert_eq!(
+            copies,
+            [BlitCopy {
+                buffer_offset: 512,
+                bytes_per_row: 4096,
+                bytes_per_image: 4096 * 1024,
+                slice: 0,
+                level: 0,
+                origin: origin(0, 0, 0),
+                size: MTLSize::new(1024, 1024, 1),
+            }]
+        );
+        // Small mipmap levels clamp to one texel.
+        let copies = planner
+            .plan(
+                BlitBufferLayout::packed(0),
+                1 << 30,
+                level_region(10, MTLSize::new(1, 1, 1)),
+            )
+            .unwrap();
+        assert_eq!((copies.len(), copies[0].bytes_per_row), (1, 4));
+        assert!(planner
+            .plan(
+                BlitBufferLayout::packed(0),
+                0,
+                level_region(0, MTLSize::new(0, 4, 1))
+            )
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn bands_of_rows() {
+        let planner = BlitCopyPlanner::new(
+            desc(
+                MTLPixelFormat::RGBA8Unorm,
+                MTLTextureType::D2,
+                (1024, 1024, 1),
+                1,
+                1,
+            ),
+            limits(256, MAX_BLIT_ROW_BLOCKS, 4096 * 100),
+        )
+        .unwrap();
+        let region = level_region(0, MTLSize::new(1024, 1024, 1));
+        let copies = planner
+            .plan(BlitBufferLayout::packed(0), 1 << 30, region)
+            .unwrap();
+        assert_eq!(copies.len(), 11);
+        assert_eq!((copies[1].origin.y, copies[1].buffer_offset), (100, 409600));
+        assert_eq!(copies[10].size.height, 24);
+        check_copies(&planner, region, &copies);
+    }
+
+    #[test]
+    fn wide_rows_are_split_into_columns() {
+        let planner = BlitCopyPlanner::new(
+            desc(
+                MTLPixelFormat::R8Unorm,
+                MTLTextureType::D2,
+                (2500, 3, 1),
+                1,
+                1,
+            ),
+            limits(16, 1000, NSUInteger::MAX),
+        )
+        .unwrap();
+        let region = level_region(0, MTLSize::new(2500, 3, 1));
+        // Packed rows are 2500 bytes, so the second row starts unaligned.
+        assert_eq!(
+            planner.plan(BlitBufferLayout::packed(0), 1 << 30, region),
+            Err(BlitPlanError::UnalignedOffset {
+                offset: 2500,
+                alignment: 16
+            })
+        );
+        let layout = BlitBufferLayout {
+            offset: 0,
+            bytes_per_row: 2512,
+            bytes_per_image: 0,
+        };
+        let copies = planner.plan(layout, 1 << 30, region).unwrap();
+        assert_eq!(copies.len(), 9);
+        assert_eq!(
+            copies[..3]
+                .iter()
+                .map(|copy| (copy.origin.x, copy.size.width, copy.bytes_per_row))
+                .collect::<Vec<_>>(),
+            [(0, 992, 992), (992, 992, 992), (1984, 516, 516)]
+        );
+        check_copies(&planner, region, &copies);
+    }
+
+    #[test]
+    fn wide_single_row_volumes() {
+        // One block row wider than a copy allows: depth slices must not be
+        // batched, or the batched copy would use the full pitch.
+        let planner = BlitCopyPlanner::new(
+            desc(
+                MTLPixelFormat::R8Unorm,
+                MTLTextureType::D3,
+                (2500, 1, 4),
+                1,
+                1,
+            ),
+            limits(16, 1000, NSUInteger::MAX),
+        )
+        .unwrap();
+        let region = level_region(0, MTLSize::new(2500, 1, 4));
+        let layout = BlitBufferLayout {
+            offset: 0,
+            bytes_per_row: 2512,
+            bytes_per_image: 2512,
+        };
+        let copies = planner.plan(layout, 1 << 30, region).unwrap();
+        assert_eq!(copies.len(), 12);
+        assert!(copies.iter().all(|copy| copy.size.depth == 1));
+        assert_eq!(copies[3].buffer_offset, 2512);
+        check_copies(&planner, region, &copies);
+    }
+
+    #[test]
+    fn volumes_are_batched() {
+        let planner = BlitCopyPlanner::new(
+            desc(
+                MTLPixelFormat::RGBA8Unorm,
+                MTLTextureType::D3,
+                (64, 64, 10),
+                1,
+                1,
+            ),
+            limits(256, MAX_BLIT_ROW_BLOCKS, 64 * 64 * 4 * 4),
+        )
+        .unwrap();
+        let region = level_region(0, MTLSize::new(64, 64, 10));
+        let copies = planner
+            .plan(BlitBufferLayout::packed(0), 1 << 30, region)
+            .unwrap();
+        assert_eq!(
+            copies
+                .iter()
+                .map(|copy| copy.size.depth)
+                .collect::<Vec<_>>(),
+            [4, 4, 2]
+        );
+        assert_eq!(copies[1].bytes_per_image, 16384);
+        assert_eq!(copies[1].buffer_offset, 4 * 16384);
+        check_copies(&planner, region, &copies);
+    }
+
+    #[test]
+    fn compressed_array_slices() {
+        let planner = BlitCopyPlanner::new(
+            desc(
+                MTLPixelFormat::BC1_RGBA,
+                MTLTextureType::D2Array,
+                (10, 10, 1),
+                2,
+                3,
+            ),
+            BlitLimits::new(16),
+        )
+        .unwrap();
+        // Level 1 is 5x5: the region ends on the edge, not a block boundary.
+        let region = BlitTextureRegion {
+            slice_count: 2,
+            ..BlitTextureRegion::new(1, 1, origin(0, 0, 0), MTLSize::new(5, 5, 1))
+        };
+        let copies = planner
+            .plan(BlitBufferLayout::packed(0), 1 << 20, region)
+            .unwrap();
+        assert_eq!(copies.len(), 2);
+        assert_eq!(
+            (
+                copies[0].bytes_per_row,
+                copies[1].buffer_offset,
+                copies[1].slice
+            ),
+            (16, 32, 2)
+        );
+        check_copies(&planner, region, &copies);
+    }
+
+    #[test]
+    fn errors() {
+        let planner = BlitCopyPlanner::new(
+            desc(
+                MTLPixelFormat::BC1_RGBA,
+                MTLTextureType::D2Array,
+                (10, 10, 1),
+                2,
+                3,
+            ),
+            BlitLimits::new(16),
+        )
+        .unwrap();
+        let packed = BlitBufferLayout::packed(0);
+        let plan = |layout, length, region| planner.plan(layout, length, region);
+        assert!(matches!(
+            plan(
+                packed,
+                1 << 20,
+                BlitTextureRegion::new(0, 0, origin(2, 0, 0), MTLSize::new(4, 4, 1))
+            ),
+            Err(BlitPlanError::UnalignedRegion { .. })
+        ));
+        assert_eq!(
+            plan(packed, 1 << 20, level_region(2, MTLSize::new(1, 1, 1))),
+            Err(BlitPlanError::LevelOutOfRange {
+                level: 2,
+                level_count: 2
+            })
+        );
+        assert_eq!(
+            plan(packed, 10, level_region(0, MTLSize::new(8, 8, 1))),
+            Err(BlitPlanError::BufferTooSmall {
+                required: 32,
+                buffer_length: 10
+            })
+        );
+        assert!(matches!(
+            plan(packed, 1 << 20, level_region(0, MTLSize::new(12, 4, 1))),
+            Err(BlitPlanError::RegionOutOfBounds { .. })
+        ));
+        assert_eq!(
+            plan(
+                packed,
+                1 << 20,
+                BlitTextureRegion {
+                    slice_count: 2,
+                    ..BlitTextureRegion::new(0, 2, origin(0, 0, 0), MTLSize::new(4, 4, 1))
+                }
+            ),
+            Err(BlitPlanError::SliceOutOfRange {
+                slice: 2,
+                slice_count: 2,
+                texture_slices: 3
+            })
+        );
+        let short_rows = BlitBufferLayout {
+            bytes_per_row: 12,
+            ..packed
+        };
+        assert_eq!(
+            plan(short_rows, 1 << 20, level_region(0, MTLSize::new(8, 8, 1))),
+            Err(BlitPlanError::InvalidBytesPerRow {
+                bytes_per_row: 12,
+                row_bytes: 16
+            })
+        );
+        let short_images = BlitBufferLayout {
+            bytes_per_image: 16,
+            ..packed
+        };
+        assert_eq!(
+            plan(
+                short_images,
+                1 << 20,
+                level_region(0, MTLSize::new(8, 8, 1))
+            ),
+            Err(BlitPlanError::InvalidBytesPerImage {
+                bytes_per_image: 16,
+                image_bytes: 32
+            })
+        );
+        // Unaligned staging offsets are rejected before planning anything.
+        let err = plan(
+            BlitBufferLayout::packed(8),
+            1 << 20,
+            level_region(0, MTLSize::new(4, 4, 1)),
+        )
+        .unwrap_err();
+        assert_eq!(
+            err,
+            BlitPlanError::UnalignedOffset {
+                offset: 8,
+                alignment: 16
+            }
+        );
+        assert_eq!(err.to_string(), "buffer offset 8 is not a multiple of 16");
+    }
+
+    #[test]
+    fn unsupported_textures() {
+        let limits = BlitLimits::new(256);
+        assert_eq!(
+            BlitCopyPlanner::new(
+                desc(
+                    MTLPixelFormat::PVRTC_RGB_4BPP,
+                    MTLTextureType::D2,
+                    (8, 8, 1),
+                    1,
+                    1
+                ),
+                limits
+            )
+            .err(),
+            Some(BlitPlanError::UnsupportedFormat(
+                MTLPixelFormat::PVRTC_RGB_4BPP
+            ))
+        );
+        assert_eq!(
+            BlitCopyPlanner::new(
+                desc(
+                    MTLPixelFormat::RGBA8Unorm,
+                    MTLTextureType::D2Multisample,
+                    (8, 8, 1),
+                    1,
+                    1
+                ),
+                limits
+            )
+            .err(),
+            Some(BlitPlanError::UnsupportedTextureType(
+                MTLTextureType::D2Multisample
+            ))
+        );
+        let cube = desc(
+            MTLPixelFormat::RGBA8Unorm,
+            MTLTextureType::CubeArray,
+            (8, 8, 1),
+            4,
+            2,
+        );
+        assert_eq!(cube.slice_count(), 12);
+        assert_eq!(cube.level_size(2), MTLSize::new(2, 2, 1));
+        assert_eq!(cube.level_size(40), MTLSize::new(1, 1, 1));
+    }
+}
@@ -6,7 +6,15 @@
 // copied, modified, or distributed except according to those terms.
 
 use super::*;
-use std::ffi::CStr;
+
+/// See <https://developer.apple.com/documentation/metal/mtlcaptureerror>
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum MTLCaptureError {
+    NotSupported = 1,
+    AlreadyCapturing = 2,
+    InvalidDescriptor = 3,
+}
 
 /// See <https://developer.apple.com/documentation/metal/mtlcapturescope>
 pub enum MTLCaptureScope {}
@@ -77,7 +85,7 @@ impl CaptureManagerRef {
     ///   1. Running from Xcode
     ///   2. Setting the environment variable `METAL_CAPTURE_ENABLED=1`
     ///   3. Adding an info.plist file containing the `MetalCaptureEnabled` key set to `YES`
-    pub fn start_capture(&self, descriptor: &CaptureDescriptorRef) -> Result<(), String> {
+    pub fn start_capture(&self, descriptor: &CaptureDescriptorRef) -> Result<(), MetalError> {
         #[allow(clippy::unit_arg)]
         unsafe {
             Ok(try_objc! { err =>
@@ -8,6 +8,7 @@
 use super::*;
 
 use block::Block;
+use std::ops::Deref;
 
 /// See <https://developer.apple.com/documentation/metal/mtlcommandbufferstatus>
 #[repr(u32)]
@@ -50,6 +51,98 @@ pub enum MTLDispatchType {
 
 type CommandBufferHandler<'a> = Block<(&'a CommandBufferRef,), ()>;
 
+/// An encoder that calls `end_encoding` when dropped.
+///
+/// Returned by the `CommandBufferRef::scoped_*` methods. It mutably borrows
+/// the command buffer, so encoders used one after another compile:
+///
+/// ```no_run
+/// # use metal::*;
+/// # fn encode(command_buffer: &mut CommandBufferRef) {
+/// let blit = command_buffer.scoped_blit_command_encoder();
+/// blit.end();
+/// let compute = command_buffer.scoped_compute_command_encoder();
+/// compute.dispatch_threads(MTLSize::new(64, 1, 1), MTLSize::new(64, 1, 1));
+/// compute.end();
+/// command_buffer.commit();
+/// # }
+/// ```
+///
+/// but a second encoder can't be opened while the first is alive:
+///
+/// ```compile_fail
+/// # use metal::*;
+/// # fn encode(command_buffer: &mut CommandBufferRef) {
+/// let blit = command_buffer.scoped_blit_command_encoder();
+/// let compute = command_buffer.scoped_compute_command_encoder();
+/// blit.end();
+/// # }
+/// ```
+///
+/// nor can the command buffer be committed before encoding ends:
+///
+/// ```compile_fail
+/// # use metal::*;
+/// # fn encode(command_buffer: &mut CommandBufferRef) {
+/// let compute = command_buffer.scoped_compute_command_encoder();
+/// command_buffer.commit();
+/// compute.end();
+/// # }
+/// ```
+///
+/// nor can the encoder be used after it ends:
+///
+/// ```compile_fail
+/// # use metal::*;
+/// # fn encode(command_buffer: &mut CommandBufferRef) {
+/// let compute = command_buffer.scoped_compute_command_encoder();
+/// let encoder: &ComputeCommandEncoderRef = &compute;
+/// compute.end();
+/// encoder.dispatch_threads(MTLSize::new(64, 1, 1), MTLSize::new(64, 1, 1));
+/// # }
+/// ```
+///
+/// The `&mut CommandBufferRef` comes from an owned [`CommandBuffer`], e.g.
+/// `queue.new_command_buffer().to_owned()`; the `&CommandBufferRef` the
+/// queue hands out can't open scoped encoders. The check is per handle:
+/// `CommandBuffer` is reference counted, and encoding through a clone of
+/// it, or through the plain `new_*_command_encoder` methods, isn't stopped
+/// by a scoped encoder on the original.
+///
+/// ```no_run
+/// # use metal::*;
+/// # fn encode(queue: &CommandQueueRef, output: &BufferRef) {
+/// let mut command_buffer = queue.new_command_buffer().to_owned();
+/// {
+///     let blit = command_buffer.scoped_blit_command_encoder();
+///     blit.fill_buffer(output, NSRange::new(0, output.length()), 0);
+/// }
+/// command_buffer.commit();
+/// # }
+/// ```
+pub struct ScopedEncoder<'a, E: Deref<Target = CommandEncoderRef>> {
+    encoder: &'a E,
+}
+
+impl<'a, E: Deref<Target = CommandEncoderRef>> ScopedEncoder<'a, E> {
+    /// End encoding now rather than at the end of the scope.
+    pub fn end(self) {}
+}
+
+impl<E: Deref<Target = CommandEncoderRef>> Deref for ScopedEncoder<'_, E> {
+    type Target = E;
+
+    fn deref(&self) -> &E {
+        self.encoder
+    }
+}
+
+impl<E: Deref<Target = CommandEncoderRef>> Drop for ScopedEncoder<'_, E> {
+    fn drop(&mut self) {
+        self.encoder.end_encoding();
+    }
+}
+
 /// See <https://developer.apple.com/documentation/metal/mtlcommandbuffer>.
 pub enum MTLCommandBuffer {}
 
@@ -191,6 +284,42 @@ impl CommandBufferRef {
         unsafe { msg_send![self, accelerationStructureCommandEncoderWithDescriptor: descriptor] }
     }
 
+    /// Create a blit command encoder that ends encoding when dropped.
+    pub fn scoped_blit_command_encoder(&mut self) -> ScopedEncoder<'_, BlitCommandEncoderRef> {
+        ScopedEncoder {
+            encoder: self.new_blit_command_encoder(),
+        }
+    }
+
+    /// Create a compute command encoder that ends encoding when dropped.
+    pub fn scoped_compute_command_encoder(
+        &mut self,
+    ) -> ScopedEncoder<'_, ComputeCommandEncoderRef> {
+        ScopedEncoder {
+            encoder: self.new_compute_command_encoder(),
+        }
+    }
+
+    /// Create a render command encoder that ends encoding when dropped.
+    pub fn scoped_render_command_encoder(
+        &mut self,
+        descriptor: &RenderPassDescriptorRef,
+    ) -> ScopedEncoder<'_, RenderCommandEncoderRef> {
+        ScopedEncoder {
+            encoder: self.new_render_command_encoder(descriptor),
+        }
+    }
+
+    /// Create an acceleration structure command encoder that ends encoding
+    /// when dropped.
+    pub fn scoped_acceleration_structure_command_encoder(
+        &mut self,
+    ) -> ScopedEncoder<'_, AccelerationStructureCommandEncoderRef> {
+        ScopedEncoder {
+            encoder: self.new_acceleration_structure_command_encoder(),
+        }
+    }
+
     pub fn encode_signal_event(&self, event: &EventRef, new_value: u64) {
         unsafe {
             msg_send![self,
@@ -0,0 +1,2296 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Recorded command lists that are replayed onto real encoders later.
+//!
+//! Commands refer to buffers, textures and other objects through handles, so
+//! a list can be built on any thread, inspected, and written to bytes before
+//! a command buffer or even a device exists. At replay time a
+//! [`CommandResources`] table resolves the handles.
+
+use super::*;
+
+use std::ops::Range;
+
+const STREAM_MAGIC: &[u8; 7] = b"MTLCMDS";
+const STREAM_VERSION: u8 = 1;
+
+/// Why a command stream couldn't be read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CommandStreamError {
+    /// The bytes don't start with the command stream magic.
+    NotACommandStream,
+    UnsupportedVersion(u8),
+    /// The stream was recorded for another kind of encoder: `'R'`ender,
+    /// `'C'`ompute or `'B'`lit.
+    WrongEncoder {
+        expected: char,
+        found: char,
+    },
+    /// The stream ends in the middle of the value starting at `offset`.
+    Truncated {
+        offset: usize,
+    },
+    /// The value at `offset` isn't a valid `type_name`, e.g. an unknown
+    /// command tag.
+    InvalidValue {
+        offset: usize,
+        type_name: &'static str,
+    },
+    /// Bytes follow the last command.
+    TrailingBytes(usize),
+}
+
+impl std::fmt::Display for CommandStreamError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            CommandStreamError::NotACommandStream => f.write_str("not a command stream"),
+            CommandStreamError::UnsupportedVersion(version) => {
+                write!(f, "unsupported command stream version {}", version)
+            }
+            CommandStreamError::WrongEncoder { expected, found } => write!(
+                f,
+                "command stream is for a '{}' encoder, expected '{}'",
+                found, expected
+            ),
+            CommandStreamError::Truncated { offset } => {
+                write!(f, "command stream truncated at byte {}", offset)
+            }
+            CommandStreamError::InvalidValue { offset, type_name } => {
+                write!(f, "invalid {} at byte {}", type_name, offset)
+            }
+            CommandStreamError::TrailingBytes(count) => {
+                write!(f, "{} trailing bytes after command stream", count)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CommandStreamError {}
+
+/// A handle passed to one of the `CommandResources::set_*` methods whose
+/// index is above [`CommandResources::MAX_HANDLE_INDEX`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct HandleOutOfRange(pub CommandHandle);
+
+impl std::fmt::Display for HandleOutOfRange {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} is above the maximum handle index {}",
+            self.0,
+            CommandResources::MAX_HANDLE_INDEX
+        )
+    }
+}
+
+impl std::error::Error for HandleOutOfRange {}
+
+macro_rules! command_handle {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(u32);
+
+        impl $name {
+            pub const fn new(index: u32) -> Self {
+                $name(index)
+            }
+
+            pub fn index(self) -> u32 {
+                self.0
+            }
+        }
+
+        impl Wire for $name {
+            fn write(&self, out: &mut Vec<u8>) {
+                self.0.write(out)
+            }
+
+            fn read(r: &mut WireReader) -> Result<Self, CommandStreamError> {
+                Ok($name(u32::read(r)?))
+            }
+        }
+    };
+}
+
+command_handle!(BufferHandle);
+command_handle!(TextureHandle);
+command_handle!(SamplerHandle);
+command_handle!(RenderPipelineHandle);
+command_handle!(ComputePipelineHandle);
+command_handle!(DepthStencilHandle);
+command_handle!(FenceHandle);
+
+/// Any handle a command can refer to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum CommandHandle {
+    Buffer(BufferHandle),
+    Texture(TextureHandle),
+    Sampler(SamplerHandle),
+    RenderPipeline(RenderPipelineHandle),
+    ComputePipeline(ComputePipelineHandle),
+    DepthStencil(DepthStencilHandle),
+    Fence(FenceHandle),
+}
+
+/// A buffer or texture, for commands that take an `MTLResource`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ResourceHandle {
+    Buffer(BufferHandle),
+    Texture(TextureHandle),
+}
+
+impl From<BufferHandle> for ResourceHandle {
+    fn from(buffer: BufferHandle) -> Self {
+        ResourceHandle::Buffer(buffer)
+    }
+}
+
+impl From<TextureHandle> for ResourceHandle {
+    fn from(texture: TextureHandle) -> Self {
+        ResourceHandle::Texture(texture)
+    }
+}
+
+impl From<ResourceHandle> for CommandHandle {
+    fn from(resource: ResourceHandle) -> Self {
+        match resource {
+            ResourceHandle::Buffer(buffer) => CommandHandle::Buffer(buffer),
+            ResourceHandle::Texture(texture) => CommandHandle::Texture(texture),
+        }
+    }
+}
+
+/// The render encoder stage a binding applies to.
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RenderStage {
+    Vertex = 0,
+    Fragment = 1,
+    Object = 2,
+    Mesh = 3,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum RenderCommand {
+    SetRenderPipelineState {
+        pipeline: RenderPipelineHandle,
+    },
+    SetDepthStencilState {
+        state: DepthStencilHandle,
+    },
+    SetViewport {
+        viewport: MTLViewport,
+    },
+    SetScissorRect {
+        rect: MTLScissorRect,
+    },
+    SetFrontFacingWinding {
+        winding: MTLWinding,
+    },
+    SetCullMode {
+        mode: MTLCullMode,
+    },
+    SetDepthClipMode {
+        mode: MTLDepthClipMode,
+    },
+    SetTriangleFillMode {
+        mode: MTLTriangleFillMode,
+    },
+    SetDepthBias {
+        bias: f32,
+        scale: f32,
+        clamp: f32,
+    },
+    SetBlendColor {
+        red: f32,
+        green: f32,
+        blue: f32,
+        alpha: f32,
+    },
+    SetStencilReferenceValues {
+        front: u32,
+        back: u32,
+    },
+    SetBuffer {
+        stage: RenderStage,
+        index: NSUInteger,
+        buffer: Option<BufferHandle>,
+        offset: NSUInteger,
+    },
+    SetBufferOffset {
+        stage: RenderStage,
+        index: NSUInteger,
+        offset: NSUInteger,
+    },
+    SetBytes {
+        stage: RenderStage,
+        index: NSUInteger,
+        bytes: Vec<u8>,
+    },
+    SetTexture {
+        stage: RenderStage,
+        index: NSUInteger,
+        texture: Option<TextureHandle>,
+    },
+    SetSamplerState {
+        stage: RenderStage,
+        index: NSUInteger,
+        sampler: Option<SamplerHandle>,
+        lod_clamp: Option<Range<f32>>,
+    },
+    Draw {
+        primitive_type: MTLPrimitiveType,
+        vertex_start: NSUInteger,
+        vertex_count: NSUInteger,
+        instance_count: NSUInteger,
+        base_instance: NSUInteger,
+    },
+    DrawIndexed {
+        primitive_type: MTLPrimitiveType,
+        index_count: NSUInteger,
+        index_type: MTLIndexType,
+        index_buffer: BufferHandle,
+        index_buffer_offset: NSUInteger,
+        instance_count: NSUInteger,
+        base_vertex: NSInteger,
+        base_instance: NSUInteger,
+    },
+    DrawIndirect {
+        primitive_type: MTLPrimitiveType,
+        indirect_buffer: BufferHandle,
+        indirect_buffer_offset: NSUInteger,
+    },
+    DrawIndexedIndirect {
+        primitive_type: MTLPrimitiveType,
+        index_type: MTLIndexType,
+        index_buffer: BufferHandle,
+        index_buffer_offset: NSUInteger,
+        indirect_buffer: BufferHandle,
+        indirect_buffer_offset: NSUInteger,
+    },
+    DrawMeshThreadgroups {
+        threadgroups_per_grid: MTLSize,
+        threads_per_object_threadgroup: MTLSize,
+        threads_per_mesh_threadgroup: MTLSize,
+    },
+    DrawMeshThreads {
+        threads_per_grid: MTLSize,
+        threads_per_object_threadgroup: MTLSize,
+        threads_per_mesh_threadgroup: MTLSize,
+    },
+    UseResource {
+        resource: ResourceHandle,
+        usage: MTLResourceUsage,
+        stages: MTLRenderStages,
+    },
+    UpdateFence {
+        fence: FenceHandle,
+        stages: MTLRenderStages,
+    },
+    WaitForFence {
+        fence: FenceHandle,
+        stages: MTLRenderStages,
+    },
+    PushDebugGroup {
+        name: String,
+    },
+    PopDebugGroup,
+    InsertDebugSignpost {
+        name: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ComputeCommand {
+    SetComputePipelineState {
+        pipeline: ComputePipelineHandle,
+    },
+    SetBuffer {
+        index: NSUInteger,
+        buffer: Option<BufferHandle>,
+        offset: NSUInteger,
+    },
+    SetBytes {
+        index: NSUInteger,
+        bytes: Vec<u8>,
+    },
+    SetTexture {
+        index: NSUInteger,
+        texture: Option<TextureHandle>,
+    },
+    SetSamplerState {
+        index: NSUInteger,
+        sampler: Option<SamplerHandle>,
+        lod_clamp: Option<Range<f32>>,
+    },
+    SetThreadgroupMemoryLength {
+        index: NSUInteger,
+        length: NSUInteger,
+    },
+    DispatchThreadgroups {
+        threadgroups_per_grid: MTLSize,
+        threads_per_threadgroup: MTLSize,
+    },
+    DispatchThreads {
+        threads_per_grid: MTLSize,
+        threads_per_threadgroup: MTLSize,
+    },
+    DispatchThreadgroupsIndirect {
+        indirect_buffer: BufferHandle,
+        indirect_buffer_offset: NSUInteger,
+        threads_per_threadgroup: MTLSize,
+    },
+    MemoryBarrierWithResources {
+        resources: Vec<ResourceHandle>,
+    },
+    UseResource {
+        resource: ResourceHandle,
+        usage: MTLResourceUsage,
+    },
+    UpdateFence {
+        fence: FenceHandle,
+    },
+    WaitForFence {
+        fence: FenceHandle,
+    },
+    PushDebugGroup {
+        name: String,
+    },
+    PopDebugGroup,
+    InsertDebugSignpost {
+        name: String,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BlitCommand {
+    CopyBufferToBuffer {
+        source: BufferHandle,
+        source_offset: NSUInteger,
+        destination: BufferHandle,
+        destination_offset: NSUInteger,
+        size: NSUInteger,
+    },
+    CopyTextureToTexture {
+        source: TextureHandle,
+        source_slice: NSUInteger,
+        source_level: NSUInteger,
+        source_origin: MTLOrigin,
+        source_size: MTLSize,
+        destination: TextureHandle,
+        destination_slice: NSUInteger,
+        destination_level: NSUInteger,
+        destination_origin: MTLOrigin,
+    },
+    CopyBufferToTexture {
+        source: BufferHandle,
+        source_offset: NSUInteger,
+        source_bytes_per_row: NSUInteger,
+        source_bytes_per_image: NSUInteger,
+        source_size: MTLSize,
+        destination: TextureHandle,
+        destination_slice: NSUInteger,
+        destination_level: NSUInteger,
+        destination_origin: MTLOrigin,
+        options: MTLBlitOption,
+    },
+    CopyTextureToBuffer {
+        source: TextureHandle,
+        source_slice: NSUInteger,
+        source_level: NSUInteger,
+        source_origin: MTLOrigin,
+        source_size: MTLSize,
+        destination: BufferHandle,
+        destination_offset: NSUInteger,
+        destination_bytes_per_row: NSUInteger,
+        destination_bytes_per_image: NSUInteger,
+        options: MTLBlitOption,
+    },
+    FillBuffer {
+        buffer: BufferHandle,
+        offset: NSUInteger,
+        length: NSUInteger,
+        value: u8,
+    },
+    GenerateMipmaps {
+        texture: TextureHandle,
+    },
+    SynchronizeResource {
+        resource: ResourceHandle,
+    },
+    UpdateFence {
+        fence: FenceHandle,
+    },
+    WaitForFence {
+        fence: FenceHandle,
+    },
+    PushDebugGroup {
+        name: String,
+    },
+    PopDebugGroup,
+    InsertDebugSignpost {
+        name: String,
+    },
+}
+
+impl RenderCommand {
+    /// Every handle this command refers to.
+    pub fn handles(&self) -> Vec<CommandHandle> {
+        use RenderCommand::*;
+        match *self {
+            SetRenderPipelineState { pipeline } => vec![CommandHandle::RenderPipeline(pipeline)],
+            SetDepthStencilState { state } => vec![CommandHandle::DepthStencil(state)],
+            SetBuffer {
+                buffer: Some(buffer),
+                ..
+            } => vec![CommandHandle::Buffer(buffer)],
+            SetTexture {
+                texture: Some(texture),
+                ..
+            } => vec![CommandHandle::Texture(texture)],
+            SetSamplerState {
+                sampler: Some(sampler),
+                ..
+            } => vec![CommandHandle::Sampler(sampler)],
+            DrawIndexed { index_buffer, .. } => vec![CommandHandle::Buffer(index_buffer)],
+            DrawIndirect {
+                indirect_buffer, ..
+            } => vec![CommandHandle::Buffer(indirect_buffer)],
+            DrawIndexedIndirect {
+                index_buffer,
+                indirect_buffer,
+                ..
+            } => vec![
+                CommandHandle::Buffer(index_buffer),
+                CommandHandle::Buffer(indirect_buffer),
+            ],
+            UseResource { resource, .. } => vec![resource.into()],
+            UpdateFence { fence, .. } | WaitForFence { fence, .. } => {
+                vec![CommandHandle::Fence(fence)]
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl ComputeCommand {
+    /// Every handle this command refers to.
+    pub fn handles(&self) -> Vec<CommandHandle> {
+        use ComputeCommand::*;
+        match *self {
+            SetComputePipelineState { pipeline } => vec![CommandHandle::ComputePipeline(pipeline)],
+            SetBuffer {
+                buffer: Some(buffer),
+                ..
+            } => vec![CommandHandle::Buffer(buffer)],
+            SetTexture {
+                texture: Some(texture),
+                ..
+            } => vec![CommandHandle::Texture(texture)],
+            SetSamplerState {
+                sampler: Some(sampler),
+                ..
+            } => vec![CommandHandle::Sampler(sampler)],
+            DispatchThreadgroupsIndirect {
+                indirect_buffer, ..
+            } => vec![CommandHandle::Buffer(indirect_buffer)],
+            MemoryBarrierWithResources { ref resources } => {
+                resources.iter().map(|&resource| resource.into()).collect()
+            }
+            UseResource { resource, .. } => vec![resource.into()],
+            UpdateFence { fence } | WaitForFence { fence } => vec![CommandHandle::Fence(fence)],
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl BlitCommand {
+    /// Every handle this command refers to.
+    pub fn handles(&self) -> Vec<CommandHandle> {
+        use BlitCommand::*;
+        match *self {
+            CopyBufferToBuffer {
+                source,
+                destination,
+                ..
+            } => vec![
+                CommandHandle::Buffer(source),
+                CommandHandle::Buffer(destination),
+            ],
+            CopyTextureToTexture {
+                source,
+                destination,
+                ..
+            } => vec![
+                CommandHandle::Texture(source),
+                CommandHandle::Texture(destination),
+            ],
+            CopyBufferToTexture {
+                source,
+                destination,
+                ..
+            } => vec![
+                CommandHandle::Buffer(source),
+                CommandHandle::Texture(destination),
+            ],
+            CopyTextureToBuffer {
+                source,
+                destination,
+                ..
+            } => vec![
+                CommandHandle::Texture(source),
+                CommandHandle::Buffer(destination),
+            ],
+            FillBuffer { buffer, .. } => vec![CommandHandle::Buffer(buffer)],
+            GenerateMipmaps { texture } => vec![CommandHandle::Texture(texture)],
+            SynchronizeResource { resource } => vec![resource.into()],
+            UpdateFence { fence } | WaitForFence { fence } => vec![CommandHandle::Fence(fence)],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// A recorded sequence of commands for one encoder.
+///
+/// ```ignore
+/// let mut list = RenderCommandList::new();
+/// list.set_render_pipeline_state(pipeline)
+///     .set_buffer(RenderStage::Vertex, 0, Some(vertices), 0)
+///     .draw_primitives(MTLPrimitiveType::Triangle, 0, 3);
+/// list.replay(&encoder, &resources)?;
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommandList<C> {
+    label: Option<String>,
+    commands: Vec<C>,
+}
+
+pub type RenderCommandList = CommandList<RenderCommand>;
+pub type ComputeCommandList = CommandList<ComputeCommand>;
+pub type BlitCommandList = CommandList<BlitCommand>;
+
+impl<C> Default for CommandList<C> {
+    fn default() -> Self {
+        CommandList {
+            label: None,
+            commands: Vec::new(),
+        }
+    }
+}
+
+impl<C> CommandList<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Set on the encoder when the list is replayed.
+    pub fn set_label(&mut self, label: &str) -> &mut Self {
+        self.label = Some(label.to_string());
+        self
+    }
+
+    pub fn commands(&self) -> &[C] {
+        &self.commands
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    pub fn push(&mut self, command: C) -> &mut Self {
+        self.commands.push(command);
+        self
+    }
+
+    pub fn extend_from(&mut self, other: &Self) -> &mut Self
+    where
+        C: Clone,
+    {
+        self.commands.extend_from_slice(&other.commands);
+        self
+    }
+
+    pub fn clear(&mut self) {
+        self.commands.clear();
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, C> {
+        self.commands.iter()
+    }
+}
+
+impl<'a, C> IntoIterator for &'a CommandList<C> {
+    type Item = &'a C;
+    type IntoIter = std::slice::Iter<'a, C>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.commands.iter()
+    }
+}
+
+macro_rules! command_list_common {
+    ($command:ident, $kind:literal, $encoder:literal) => {
+        impl CommandList<$command> {
+            pub fn push_debug_group(&mut self, name: &str) -> &mut Self {
+                self.push($command::PushDebugGroup {
+                    name: name.to_string(),
+                })
+            }
+
+            pub fn pop_debug_group(&mut self) -> &mut Self {
+                self.push($command::PopDebugGroup)
+            }
+
+            pub fn insert_debug_signpost(&mut self, name: &str) -> &mut Self {
+                self.push($command::InsertDebugSignpost {
+                    name: name.to_string(),
+                })
+            }
+
+            /// Every distinct handle the list refers to, in order of first use.
+            pub fn handles(&self) -> Vec<CommandHandle> {
+                let mut handles = Vec::new();
+                for handle in self.commands.iter().flat_map(|command| command.handles()) {
+                    if !handles.contains(&handle) {
+                        handles.push(handle);
+                    }
+                }
+                handles
+            }
+
+            /// Check that every handle resolves in `resources`.
+            pub fn validate(&self, resources: &CommandResources) -> Result<(), String> {
+                for (i, command) in self.commands.iter().enumerate() {
+                    for handle in command.handles() {
+                        if !resources.contains(handle) {
+                            return Err(format!(
+                                "{} command {} refers to unknown {:?}",
+                                $encoder, i, handle
+                            ));
+                        }
+                    }
+                }
+                Ok(())
+            }
+
+            pub fn to_bytes(&self) -> Vec<u8> {
+                write_stream($kind, &self.label, &self.commands)
+            }
+
+            pub fn from_bytes(bytes: &[u8]) -> Result<Self, CommandStreamError> {
+                let (label, commands) = read_stream($kind, bytes)?;
+                Ok(CommandList { label, commands })
+            }
+        }
+    };
+}
+
+command_list_common!(RenderCommand, b'R', "render");
+command_list_common!(ComputeCommand, b'C', "compute");
+command_list_common!(BlitCommand, b'B', "blit");
+
+impl RenderCommandList {
+    pub fn set_render_pipeline_state(&mut self, pipeline: RenderPipelineHandle) -> &mut Self {
+        self.push(RenderCommand::SetRenderPipelineState { pipeline })
+    }
+
+    pub fn set_depth_stencil_state(&mut self, state: DepthStencilHandle) -> &mut Self {
+        self.push(RenderCommand::SetDepthStencilState { state })
+    }
+
+    pub fn set_viewport(&mut self, viewport: MTLViewport) -> &mut Self {
+        self.push(RenderCommand::SetViewport { viewport })
+    }
+
+    pub fn set_scissor_rect(&mut self, rect: MTLScissorRect) -> &mut Self {
+        self.push(RenderCommand::SetScissorRect { rect })
+    }
+
+    pub fn set_front_facing_winding(&mut self, winding: MTLWinding) -> &mut Self {
+        self.push(RenderCommand::SetFrontFacingWinding { winding })
+    }
+
+    pub fn set_cull_mode(&mut self, mode: MTLCullMode) -> &mut Self {
+        self.push(RenderCommand::SetCullMode { mode })
+    }
+
+    pub fn set_buffer(
+        &mut self,
+        stage: RenderStage,
+        index: NSUInteger,
+        buffer: Option<BufferHandle>,
+        offset: NSUInteger,
+    ) -> &mut Self {
+        self.push(RenderCommand::SetBuffer {
+            stage,
+            index,
+            buffer,
+            offset,
+        })
+    }
+
+    pub fn set_buffer_offset(
+        &mut self,
+        stage: RenderStage,
+        index: NSUInteger,
+        offset: NSUInteger,
+    ) -> &mut Self {
+        self.push(RenderCommand::SetBufferOffset {
+            stage,
+            index,
+            offset,
+        })
+    }
+
+    pub fn set_bytes(&mut self, stage: RenderStage, index: NSUInteger, bytes: &[u8]) -> &mut Self {
+        self.push(RenderCommand::SetBytes {
+            stage,
+            index,
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    pub fn set_texture(
+        &mut self,
+        stage: RenderStage,
+        index: NSUInteger,
+        texture: Option<TextureHandle>,
+    ) -> &mut Self {
+        self.push(RenderCommand::SetTexture {
+            stage,
+            index,
+            texture,
+        })
+    }
+
+    pub fn set_sampler_state(
+        &mut self,
+        stage: RenderStage,
+        index: NSUInteger,
+        sampler: Option<SamplerHandle>,
+    ) -> &mut Self {
+        self.push(RenderCommand::SetSamplerState {
+            stage,
+            index,
+            sampler,
+            lod_clamp: None,
+        })
+    }
+
+    pub fn draw_primitives(
+        &mut self,
+        primitive_type: MTLPrimitiveType,
+        vertex_start: NSUInteger,
+        vertex_count: NSUInteger,
+    ) -> &mut Self {
+        self.draw_primitives_instanced(primitive_type, vertex_start, vertex_count, 1, 0)
+    }
+
+    pub fn draw_primitives_instanced(
+        &mut self,
+        primitive_type: MTLPrimitiveType,
+        vertex_start: NSUInteger,
+        vertex_count: NSUInteger,
+        instance_count: NSUInteger,
+        base_instance: NSUInteger,
+    ) -> &mut Self {
+        self.push(RenderCommand::Draw {
+            primitive_type,
+            vertex_start,
+            vertex_count,
+            instance_count,
+            base_instance,
+        })
+    }
+
+    pub fn draw_indexed_primitives(
+        &mut self,
+        primitive_type: MTLPrimitiveType,
+        index_count: NSUInteger,
+        index_type: MTLIndexType,
+        index_buffer: BufferHandle,
+        index_buffer_offset: NSUInteger,
+    ) -> &mut Self {
+        self.push(RenderCommand::DrawIndexed {
+            primitive_type,
+            index_count,
+            index_type,
+            index_buffer,
+            index_buffer_offset,
+            instance_count: 1,
+            base_vertex: 0,
+            base_instance: 0,
+        })
+    }
+
+    pub fn update_fence(&mut self, fence: FenceHandle, stages: MTLRenderStages) -> &mut Self {
+        self.push(RenderCommand::UpdateFence { fence, stages })
+    }
+
+    pub fn wait_for_fence(&mut self, fence: FenceHandle, stages: MTLRenderStages) -> &mut Self {
+        self.push(RenderCommand::WaitForFence { fence, stages })
+    }
+
+    /// Encode the list onto `encoder`. Nothing is encoded if a handle fails
+    /// to resolve.
+    pub fn replay(
+        &self,
+        encoder: &RenderCommandEncoderRef,
+        resources: &CommandResources,
+    ) -> Result<(), String> {
+        self.validate(resources)?;
+        if let Some(label) = &self.label {
+            encoder.set_label(label);
+        }
+        for command in &self.commands {
+            replay_render_command(command, encoder, resources)?;
+        }
+        Ok(())
+    }
+}
+
+impl ComputeCommandList {
+    pub fn set_compute_pipeline_state(&mut self, pipeline: ComputePipelineHandle) -> &mut Self {
+        self.push(ComputeCommand::SetComputePipelineState { pipeline })
+    }
+
+    pub fn set_buffer(
+        &mut self,
+        index: NSUInteger,
+        buffer: Option<BufferHandle>,
+        offset: NSUInteger,
+    ) -> &mut Self {
+        self.push(ComputeCommand::SetBuffer {
+            index,
+            buffer,
+            offset,
+        })
+    }
+
+    pub fn set_bytes(&mut self, index: NSUInteger, bytes: &[u8]) -> &mut Self {
+        self.push(ComputeCommand::SetBytes {
+            index,
+            bytes: bytes.to_vec(),
+        })
+    }
+
+    pub fn set_texture(&mut self, index: NSUInteger, texture: Option<TextureHandle>) -> &mut Self {
+        self.push(ComputeCommand::SetTexture { index, texture })
+    }
+
+    pub fn set_sampler_state(
+        &mut self,
+        index: NSUInteger,
+        sampler: Option<SamplerHandle>,
+    ) -> &mut Self {
+        self.push(ComputeCommand::SetSamplerState {
+            index,
+            sampler,
+            lod_clamp: None,
+        })
+    }
+
+    pub fn set_threadgroup_memory_length(
+        &mut self,
+        index: NSUInteger,
+        length: NSUInteger,
+    ) -> &mut Self {
+        self.push(ComputeCommand::SetThreadgroupMemoryLength { index, length })
+    }
+
+    pub fn dispatch_thread_groups(
+        &mut self,
+        threadgroups_per_grid: MTLSize,
+        threads_per_threadgroup: MTLSize,
+    ) -> &mut Self {
+        self.push(ComputeCommand::DispatchThreadgroups {
+            threadgroups_per_grid,
+            threads_per_threadgroup,
+        })
+    }
+
+    pub fn dispatch_threads(
+        &mut self,
+        threads_per_grid: MTLSize,
+        threads_per_threadgroup: MTLSize,
+    ) -> &mut Self {
+        self.push(ComputeCommand::DispatchThreads {
+            threads_per_grid,
+            threads_per_threadgroup,
+        })
+    }
+
+    pub fn memory_barrier_with_resources(&mut self, resources: &[ResourceHandle]) -> &mut Self {
+        self.push(ComputeCommand::MemoryBarrierWithResources {
+            resources: resources.to_vec(),
+        })
+    }
+
+    pub fn update_fence(&mut self, fence: FenceHandle) -> &mut Self {
+        self.push(ComputeCommand::UpdateFence { fence })
+    }
+
+    pub fn wait_for_fence(&mut self, fence: FenceHandle) -> &mut Self {
+        self.push(ComputeCommand::WaitForFence { fence })
+    }
+
+    /// Encode the list onto `encoder`. Nothing is encoded if a handle fails
+    /// to resolve.
+    pub fn replay(
+        &self,
+        encoder: &ComputeCommandEncoderRef,
+        resources: &CommandResources,
+    ) -> Result<(), String> {
+        self.validate(resources)?;
+        if let Some(label) = &self.label {
+            encoder.set_label(label);
+        }
+        for command in &self.commands {
+            replay_compute_command(command, encoder, resources)?;
+        }
+        Ok(())
+    }
+}
+
+impl BlitCommandList {
+    pub fn copy_from_buffer(
+        &mut self,
+        source: BufferHandle,
+        source_offset: NSUInteger,
+        destination: BufferHandle,
+        destination_offset: NSUInteger,
+        size: NSUInteger,
+    ) -> &mut Self {
+        self.push(BlitCommand::CopyBufferToBuffer {
+            source,
+            source_offset,
+            destination,
+            destination_offset,
+            size,
+        })
+    }
+
+    pub fn fill_buffer(
+        &mut self,
+        buffer: BufferHandle,
+        offset: NSUInteger,
+        length: NSUInteger,
+        value: u8,
+    ) -> &mut Self {
+        self.push(BlitCommand::FillBuffer {
+            buffer,
+            offset,
+            length,
+            value,
+        })
+    }
+
+    pub fn generate_mipmaps(&mut self, texture: TextureHandle) -> &mut Self {
+        self.push(BlitCommand::GenerateMipmaps { texture })
+    }
+
+    pub fn synchronize_resource(&mut self, resource: ResourceHandle) -> &mut Self {
+        self.push(BlitCommand::SynchronizeResource { resource })
+    }
+
+    pub fn update_fence(&mut self, fence: FenceHandle) -> &mut Self {
+        self.push(BlitCommand::UpdateFence { fence })
+    }
+
+    pub fn wait_for_fence(&mut self, fence: FenceHandle) -> &mut Self {
+        self.push(BlitCommand::WaitForFence { fence })
+    }
+
+    /// Encode the list onto `encoder`. Nothing is encoded if a handle fails
+    /// to resolve.
+    pub fn replay(
+        &self,
+        encoder: &BlitCommandEncoderRef,
+        resources: &CommandResources,
+    ) -> Result<(), String> {
+        self.validate(resources)?;
+        if let Some(label) = &self.label {
+            encoder.set_label(label);
+        }
+        for command in &self.commands {
+            replay_blit_command(command, encoder, resources)?;
+        }
+        Ok(())
+    }
+}
+
+macro_rules! resource_table {
+    ($($field:ident: $owned:ident, $reference:ident, $handle:ident, $variant:ident, $add:ident, $set:ident, $get:ident;)*) => {
+        /// The objects handles resolve to when a list is replayed.
+        #[derive(Default)]
+        pub struct CommandResources {
+            $($field: Vec<Option<$owned>>,)*
+        }
+
+        impl CommandResources {
+            /// The largest handle index the `set_*` methods accept.
+            pub const MAX_HANDLE_INDEX: u32 = (1 << 20) - 1;
+
+            pub fn new() -> Self {
+                Self::default()
+            }
+
+            pub fn contains(&self, handle: CommandHandle) -> bool {
+                match handle {
+                    $(CommandHandle::$variant(h) => self.$get(h).is_some(),)*
+                }
+            }
+
+            $(
+                pub fn $add(&mut self, object: &$reference) -> $handle {
+                    let handle = $handle::new(self.$field.len() as u32);
+                    self.$field.push(Some(object.to_owned()));
+                    handle
+                }
+
+                /// Bind `handle`, e.g. one recorded before the object existed.
+                /// Unbound handles below it take up a slot each, so its index
+                /// can't be above [`Self::MAX_HANDLE_INDEX`].
+                pub fn $set(
+                    &mut self,
+                    handle: $handle,
+                    object: Option<&$reference>,
+                ) -> Result<(), HandleOutOfRange> {
+                    if handle.index() > Self::MAX_HANDLE_INDEX {
+                        return Err(HandleOutOfRange(CommandHandle::$variant(handle)));
+                    }
+                    let index = handle.index() as usize;
+                    if self.$field.len() <= index {
+                        self.$field.resize(index + 1, None);
+                    }
+                    self.$field[index] = object.map(|o| o.to_owned());
+                    Ok(())
+                }
+
+                pub fn $get(&self, handle: $handle) -> Option<&$reference> {
+                    self.$field
+                        .get(handle.index() as usize)?
+                        .as_ref()
+                        .map(|o| o.as_ref())
+                }
+            )*
+        }
+    };
+}
+
+resource_table! {
+    buffers: Buffer, BufferRef, BufferHandle, Buffer, add_buffer, set_buffer, buffer;
+    textures: Texture, TextureRef, TextureHandle, Texture, add_texture, set_texture, texture;
+    samplers: SamplerState, SamplerStateRef, SamplerHandle, Sampler, add_sampler, set_sampler, sampler;
+    render_pipelines: RenderPipelineState, RenderPipelineStateRef, RenderPipelineHandle, RenderPipeline,
+        add_render_pipeline, set_render_pipeline, render_pipeline;
+    compute_pipelines: ComputePipelineState, ComputePipelineStateRef, ComputePipelineHandle, ComputePipeline,
+        add_compute_pipeline, set_compute_pipeline, compute_pipeline;
+    depth_stencil_states: DepthStencilState, DepthStencilStateRef, DepthStencilHandle, DepthStencil,
+        add_depth_stencil_state, set_depth_stencil_state, depth_stencil_state;
+    fences: Fence, FenceRef, FenceHandle, Fence, add_fence, set_fence, fence;
+}
+
+impl CommandResources {
+    pub fn resource(&self, handle: ResourceHandle) -> Option<&ResourceRef> {
+        match handle {
+            ResourceHandle::Buffer(buffer) => self.buffer(buffer).map(|b| b as &ResourceRef),
+            ResourceHandle::Texture(texture) => self.texture(texture).map(|t| t as &ResourceRef),
+        }
+    }
+}
+
+fn unresolved(handle: CommandHandle) -> String {
+    format!("unknown {:?}", handle)
+}
+
+fn resolve_buffer(
+    resources: &CommandResources,
+    handle: BufferHandle,
+) -> Result<&BufferRef, String> {
+    resources
+        .buffer(handle)
+        .ok_or_else(|| unresolved(CommandHandle::Buffer(handle)))
+}
+
+fn resolve_texture(
+    resources: &CommandResources,
+    handle: TextureHandle,
+) -> Result<&TextureRef, String> {
+    resources
+        .texture(handle)
+        .ok_or_else(|| unresolved(CommandHandle::Texture(handle)))
+}
+
+fn resolve_sampler(
+    resources: &CommandResources,
+    handle: SamplerHandle,
+) -> Result<&SamplerStateRef, String> {
+    resources
+        .sampler(handle)
+        .ok_or_else(|| unresolved(CommandHandle::Sampler(handle)))
+}
+
+fn resolve_fence(resources: &CommandResources, handle: FenceHandle) -> Result<&FenceRef, String> {
+    resources
+        .fence(handle)
+        .ok_or_else(|| unresolved(CommandHandle::Fence(handle)))
+}
+
+fn resolve_resource(
+    resources: &CommandResources,
+    handle: ResourceHandle,
+) -> Result<&ResourceRef, String> {
+    resources
+        .resource(handle)
+        .ok_or_else(|| unresolved(handle.into()))
+}
+
+fn replay_render_command(
+    command: &RenderCommand,
+    encoder: &RenderCommandEncoderRef,
+    resources: &CommandResources,
+) -> Result<(), String> {
+    use RenderCommand::*;
+    match *command {
+        SetRenderPipelineState { pipeline } => {
+            let state = resources
+                .render_pipeline(pipeline)
+                .ok_or_else(|| unresolved(CommandHandle::RenderPipeline(pipeline)))?;
+            encoder.set_render_pipeline_state(state);
+        }
+        SetDepthStencilState { state } => {
+            let state = resources
+                .depth_stencil_state(state)
+                .ok_or_else(|| unresolved(CommandHandle::DepthStencil(state)))?;
+            encoder.set_depth_stencil_state(state);
+        }
+        SetViewport { viewport } => encoder.set_viewport(viewport),
+        SetScissorRect { rect } => encoder.set_scissor_rect(rect),
+        SetFrontFacingWinding { winding } => encoder.set_front_facing_winding(winding),
+        SetCullMode { mode } => encoder.set_cull_mode(mode),
+        SetDepthClipMode { mode } => encoder.set_depth_clip_mode(mode),
+        SetTriangleFillMode { mode } => encoder.set_triangle_fill_mode(mode),
+        SetDepthBias { bias, scale, clamp } => encoder.set_depth_bias(bias, scale, clamp),
+        SetBlendColor {
+            red,
+            green,
+            blue,
+            alpha,
+        } => encoder.set_blend_color(red, green, blue, alpha),
+        SetStencilReferenceValues { front, back } => {
+            encoder.set_stencil_front_back_reference_value(front, back)
+        }
+        SetBuffer {
+            stage,
+            index,
+            buffer,
+            offset,
+        } => {
+            let buffer = buffer.map(|b| resolve_buffer(resources, b)).transpose()?;
+            match stage {
+                RenderStage::Vertex => encoder.set_vertex_buffer(index, buffer, offset),
+                RenderStage::Fragment => encoder.set_fragment_buffer(index, buffer, offset),
+                RenderStage::Object => encoder.set_object_buffer(index, buffer, offset),
+                RenderStage::Mesh => encoder.set_mesh_buffer(index, buffer, offset),
+            }
+        }
+        SetBufferOffset {
+            stage,
+            index,
+            offset,
+        } => match stage {
+            RenderStage::Vertex => encoder.set_vertex_buffer_offset(index, offset),
+            RenderStage::Fragment => encoder.set_fragment_buffer_offset(index, offset),
+            RenderStage::Object => encoder.set_object_buffer_offset(index, offset),
+            RenderStage::Mesh => encoder.set_mesh_buffer_offset(index, offset),
+        },
+        SetBytes {
+            stage,
+            index,
+            ref bytes,
+        } => {
+            let length = bytes.len() as NSUInteger;
+            let bytes = bytes.as_ptr() as *const std::ffi::c_void;
+            match stage {
+                RenderStage::Vertex => encoder.set_vertex_bytes(index, length, bytes),
+                RenderStage::Fragment => encoder.set_fragment_bytes(index, length, bytes),
+                RenderStage::Object => encoder.set_object_bytes(index, length, bytes),
+                RenderStage::Mesh => encoder.set_mesh_bytes(index, length, bytes),
+            }
+        }
+        SetTexture {
+            stage,
+            index,
+            texture,
+        } => {
+            let texture = texture.map(|t| resolve_texture(resources, t)).transpose()?;
+            match stage {
+                RenderStage::Vertex => encoder.set_vertex_texture(index, texture),
+                RenderStage::Fragment => encoder.set_fragment_texture(index, texture),
+                RenderStage::Object => encoder.set_object_texture(index, texture),
+                RenderStage::Mesh => encoder.set_mesh_texture(index, texture),
+            }
+        }
+        SetSamplerState {
+            stage,
+            index,
+            sampler,
+            ref lod_clamp,
+        } => {
+            let sampler = sampler.map(|s| resolve_sampler(resources, s)).transpose()?;
+            match (stage, lod_clamp.clone()) {
+                (RenderStage::Vertex, None) => encoder.set_vertex_sampler_state(index, sampler),
+                (RenderStage::Fragment, None) => encoder.set_fragment_sampler_state(index, sampler),
+                (RenderStage::Object, None) => encoder.set_object_sampler_state(index, sampler),
+                (RenderStage::Mesh, None) => encoder.set_mesh_sampler_state(index, sampler),
+                (RenderStage::Vertex, Some(lod)) => {
+                    encoder.set_vertex_sampler_state_with_lod(index, sampler, lod)
+                }
+                (RenderStage::Fragment, Some(lod)) => {
+                    encoder.set_fragment_sampler_state_with_lod(index, sampler, lod)
+                }
+                (RenderStage::Object, Some(lod)) => {
+                    encoder.set_object_sampler_state_with_lod(index, sampler, lod)
+                }
+                (RenderStage::Mesh, Some(lod)) => {
+                    encoder.set_mesh_sampler_state_with_lod(index, sampler, lod)
+                }
+            }
+        }
+        Draw {
+            primitive_type,
+            vertex_start,
+            vertex_count,
+            instance_count,
+            base_instance,
+        } => {
+            if base_instance != 0 {
+                encoder.draw_primitives_instanced_base_instance(
+                    primitive_type,
+                    vertex_start,
+                    vertex_count,
+                    instance_count,
+                    base_instance,
+                )
+            } else if instance_count != 1 {
+                encoder.draw_primitives_instanced(
+                    primitive_type,
+                    vertex_start,
+                    vertex_count,
+                    instance_count,
+                )
+            } else {
+                encoder.draw_primitives(primitive_type, vertex_start, vertex_count)
+            }
+        }
+        DrawIndexed {
+            primitive_type,
+            index_count,
+            index_type,
+            index_buffer,
+            index_buffer_offset,
+            instance_count,
+            base_vertex,
+            base_instance,
+        } => {
+            let index_buffer = resolve_buffer(resources, index_buffer)?;
+            if base_vertex != 0 || base_instance != 0 {
+                encoder.draw_indexed_primitives_instanced_base_instance(
+                    primitive_type,
+                    index_count,
+                    index_type,
+                    index_buffer,
+                    index_buffer_offset,
+                    instance_count,
+                    base_vertex,
+                    base_instance,
+                )
+            } else if instance_count != 1 {
+                encoder.draw_indexed_primitives_instanced(
+                    primitive_type,
+                    index_count,
+                    index_type,
+                    index_buffer,
+                    index_buffer_offset,
+                    instance_count,
+                )
+            } else {
+                encoder.draw_indexed_primitives(
+                    primitive_type,
+                    index_count,
+                    index_type,
+                    index_buffer,
+                    index_buffer_offset,
+                )
+            }
+        }
+        DrawIndirect {
+            primitive_type,
+            indirect_buffer,
+            indirect_buffer_offset,
+        } => encoder.draw_primitives_indirect(
+            primitive_type,
+            resolve_buffer(resources, indirect_buffer)?,
+            indirect_buffer_offset,
+        ),
+        DrawIndexedIndirect {
+            primitive_type,
+            index_type,
+            index_buffer,
+            index_buffer_offset,
+            indirect_buffer,
+            indirect_buffer_offset,
+        } => encoder.draw_indexed_primitives_indirect(
+            primitive_type,
+            index_type,
+            resolve_buffer(resources, index_buffer)?,
+            index_buffer_offset,
+            resolve_buffer(resources, indirect_buffer)?,
+            indirect_buffer_offset,
+        ),
+        DrawMeshThreadgroups {
+            threadgroups_per_grid,
+            threads_per_object_threadgroup,
+            threads_per_mesh_threadgroup,
+        } => encoder.draw_mesh_threadgroups(
+            threadgroups_per_grid,
+            threads_per_object_threadgroup,
+            threads_per_mesh_threadgroup,
+        ),
+        DrawMeshThreads {
+            threads_per_grid,
+            threads_per_object_threadgroup,
+            threads_per_mesh_threadgroup,
+        } => encoder.draw_mesh_threads(
+            threads_per_grid,
+            threads_per_object_threadgroup,
+            threads_per_mesh_threadgroup,
+        ),
+        UseResource {
+            resource,
+            usage,
+            stages,
+        } => encoder.use_resource_at(resolve_resource(resources, resource)?, usage, stages),
+        UpdateFence { fence, stages } => {
+            encoder.update_fence(resolve_fence(resources, fence)?, stages)
+        }
+        WaitForFence { fence, stages } => {
+            encoder.wait_for_fence(resolve_fence(resources, fence)?, stages)
+        }
+        PushDebugGroup { ref name } => encoder.push_debug_group(name),
+        PopDebugGroup => encoder.pop_debug_group(),
+        InsertDebugSignpost { ref name } => encoder.insert_debug_signpost(name),
+    }
+    Ok(())
+}
+
+fn replay_compute_command(
+    command: &ComputeCommand,
+    encoder: &ComputeCommandEncoderRef,
+    resources: &CommandResources,
+) -> Result<(), String> {
+    use ComputeCommand::*;
+    match *command {
+        SetComputePipelineState { pipeline } => {
+            let state = resources
+                .compute_pipeline(pipeline)
+                .ok_or_else(|| unresolved(CommandHandle::ComputePipeline(pipeline)))?;
+            encoder.set_compute_pipeline_state(state);
+        }
+        SetBuffer {
+            index,
+            buffer,
+            offset,
+        } => {
+            let buffer = buffer.map(|b| resolve_buffer(resources, b)).transpose()?;
+            encoder.set_buffer(index, buffer, offset);
+        }
+        SetBytes { index, ref bytes } => encoder.set_bytes(
+            index,
+            bytes.len() as NSUInteger,
+            bytes.as_ptr() as *const std::ffi::c_void,
+        ),
+        SetTexture { index, texture } => {
+            let texture = texture.map(|t| resolve_texture(resources, t)).transpose()?;
+            encoder.set_texture(index, texture);
+        }
+        SetSamplerState {
+            index,
+            sampler,
+            ref lod_clamp,
+        } => {
+            let sampler = sampler.map(|s| resolve_sampler(resources, s)).transpose()?;
+            match lod_clamp.clone() {
+                Some(lod) => encoder.set_sampler_state_with_lod(index, sampler, lod),
+                None => encoder.set_sampler_state(index, sampler),
+            }
+        }
+        SetThreadgroupMemoryLength { index, length } => {
+            encoder.set_threadgroup_memory_length(index, length)
+        }
+        DispatchThreadgroups {
+            threadgroups_per_grid,
+            threads_per_threadgroup,
+        } => encoder.dispatch_thread_groups(threadgroups_per_grid, threads_per_threadgroup),
+        DispatchThreads {
+            threads_per_grid,
+            threads_per_threadgroup,
+        } => encoder.dispatch_threads(threads_per_grid, threads_per_threadgroup),
+        DispatchThreadgroupsIndirect {
+            indirect_buffer,
+            indirect_buffer_offset,
+            threads_per_threadgroup,
+        } => encoder.dispatch_thread_groups_indirect(
+            resolve_buffer(resources, indirect_buffer)?,
+            indirect_buffer_offset,
+            threads_per_threadgroup,
+        ),
+        MemoryBarrierWithResources {
+            resources: ref handles,
+        } => {
+            let handles = handles
+                .iter()
+                .map(|&h| resolve_resource(resources, h))
+                .collect::<Result<Vec<_>, _>>()?;
+            encoder.memory_barrier_with_resources(&handles);
+        }
+        UseResource { resource, usage } => {
+            encoder.use_resource(resolve_resource(resources, resource)?, usage)
+        }
+        UpdateFence { fence } => encoder.update_fence(resolve_fence(resources, fence)?),
+        WaitForFence { fence } => encoder.wait_for_fence(resolve_fence(resources, fence)?),
+        PushDebugGroup { ref name } => encoder.push_debug_group(name),
+        PopDebugGroup => encoder.pop_debug_group(),
+        InsertDebugSignpost { ref name } => encoder.insert_debug_signpost(name),
+    }
+    Ok(())
+}
+
+fn replay_blit_command(
+    command: &BlitCommand,
+    encoder: &BlitCommandEncoderRef,
+    resources: &CommandResources,
+) -> Result<(), String> {
+    use BlitCommand::*;
+    match *command {
+        CopyBufferToBuffer {
+            source,
+            source_offset,
+            destination,
+            destination_offset,
+            size,
+        } => encoder.copy_from_buffer(
+            resolve_buffer(resources, source)?,
+            source_offset,
+            resolve_buffer(resources, destination)?,
+            destination_offset,
+            size,
+        ),
+        CopyTextureToTexture {
+            source,
+            source_slice,
+            source_level,
+            source_origin,
+            source_size,
+            destination,
+            destination_slice,
+            destination_level,
+            destination_origin,
+        } => encoder.copy_from_texture(
+            resolve_texture(resources, source)?,
+            source_slice,
+            source_level,
+            source_origin,
+            source_size,
+            resolve_texture(resources, destination)?,
+            destination_slice,
+            destination_level,
+            destination_origin,
+        ),
+        CopyBufferToTexture {
+            source,
+            source_offset,
+            source_bytes_per_row,
+            source_bytes_per_image,
+            source_size,
+            destination,
+            destination_slice,
+            destination_level,
+            destination_origin,
+            options,
+        } => encoder.copy_from_buffer_to_texture(
+            resolve_buffer(resources, source)?,
+            source_offset,
+            source_bytes_per_row,
+            source_bytes_per_image,
+            source_size,
+            resolve_texture(resources, destination)?,
+            destination_slice,
+            destination_level,
+            destination_origin,
+            options,
+        ),
+        CopyTextureToBuffer {
+            source,
+            source_slice,
+            source_level,
+            source_origin,
+            source_size,
+            destination,
+            destination_offset,
+            destination_bytes_per_row,
+            destination_bytes_per_image,
+            options,
+        } => encoder.copy_from_texture_to_buffer(
+            resolve_texture(resources, source)?,
+            source_slice,
+            source_level,
+            source_origin,
+            source_size,
+            resolve_buffer(resources, destination)?,
+            destination_offset,
+            destination_bytes_per_row,
+            destination_bytes_per_image,
+            options,
+        ),
+        FillBuffer {
+            buffer,
+            offset,
+            length,
+            value,
+        } => encoder.fill_buffer(
+            resolve_buffer(resources, buffer)?,
+            crate::NSRange::new(offset, length),
+            value,
+        ),
+        GenerateMipmaps { texture } => {
+            encoder.generate_mipmaps(resolve_texture(resources, texture)?)
+        }
+        SynchronizeResource { resource } => {
+            encoder.synchronize_resource(resolve_resource(resources, resource)?)
+        }
+        UpdateFence { fence } => encoder.update_fence(resolve_fence(resources, fence)?),
+        WaitForFence { fence } => encoder.wait_for_fence(resolve_fence(resources, fence)?),
+        PushDebugGroup { ref name } => encoder.push_debug_group(name),
+        PopDebugGroup => encoder.pop_debug_group(),
+        InsertDebugSignpost { ref name } => encoder.insert_debug_signpost(name),
+    }
+    Ok(())
+}
+
+// Serialization: a small little-endian binary format, versioned by
+// `STREAM_VERSION`.
+
+fn write_stream<C: Wire>(kind: u8, label: &Option<String>, commands: &[C]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(STREAM_MAGIC);
+    out.push(STREAM_VERSION);
+    out.push(kind);
+    label.write(&mut out);
+    (commands.len() as u64).write(&mut out);
+    for command in commands {
+        command.write(&mut out);
+    }
+    out
+}
+
+fn read_stream<C: Wire>(
+    kind: u8,
+    bytes: &[u8],
+) -> Result<(Option<String>, Vec<C>), CommandStreamError> {
+    let mut r = WireReader { bytes, pos: 0 };
+    if !bytes.starts_with(STREAM_MAGIC) {
+        return Err(CommandStreamError::NotACommandStream);
+    }
+    r.take(STREAM_MAGIC.len())?;
+    let version = u8::read(&mut r)?;
+    if version != STREAM_VERSION {
+        return Err(CommandStreamError::UnsupportedVersion(version));
+    }
+    let found = u8::read(&mut r)?;
+    if found != kind {
+        return Err(CommandStreamError::WrongEncoder {
+            expected: kind as char,
+            found: found as char,
+        });
+    }
+    let label = Option::<String>::read(&mut r)?;
+    let count = u64::read(&mut r)?;
+    let mut commands = Vec::new();
+    for _ in 0..count {
+        commands.push(C::read(&mut r)?);
+    }
+    if r.pos != bytes.len() {
+        return Err(CommandStreamError::TrailingBytes(bytes.len() - r.pos));
+    }
+    Ok((label, commands))
+}
+
+struct WireReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> WireReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], CommandStreamError> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|&end| end <= self.bytes.len())
+            .ok_or(CommandStreamError::Truncated { offset: self.pos })?;
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// Read a `T` and convert it, reporting a failed conversion as an
+    /// invalid `type_name` at the value's offset.
+    fn read_as<T: Wire, U>(
+        &mut self,
+        type_name: &'static str,
+        convert: impl FnOnce(T) -> Option<U>,
+    ) -> Result<U, CommandStreamError> {
+        let offset = self.pos;
+        convert(T::read(self)?).ok_or(CommandStreamError::InvalidValue { offset, type_name })
+    }
+}
+
+trait Wire: Sized {
+    fn write(&self, out: &mut Vec<u8>);
+    fn read(r: &mut WireReader) -> Result<Self, CommandStreamError>;
+}
+
+macro_rules! wire_int {
+    ($($ty:ty => $wide:ty),*) => {
+        $(impl Wire for $ty {
+            fn write(&self, out: &mut Vec<u8>) {
+                out.extend_from_slice(&(*self as $wide).to_le_bytes());
+            }
+
+            fn read(r: &mut WireReader) -> Result<Self, CommandStreamError> {
+                let offset = r.pos;
+                let mut bytes = [0; std::mem::size_of::<$wide>()];
+                bytes.copy_from_slice(r.take(std::mem::size_of::<$wide>())?);
+                <$ty>::try_from(<$wide>::from_le_bytes(bytes)).map_err(|_| {
+                    CommandStreamError::InvalidValue {
+                        offset,
+                        type_name: stringify!($ty),
+                    }
+                })
+            }
+        })*
+    };
+}
+
+wire_int!(u8 => u8, u32 => u32, u64 => u64, i32 => i64, i64 => i64);
+
+impl Wire for f32 {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.to_bits().write(out)
+    }
+
+    fn read(r: &mut WireReader) -> Result<Self, CommandStreamError> {
+        Ok(f32::from_bits(u32::read(r)?))
+    }
+}
+
+impl Wire for f64 {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.to_bits().write(out)
+    }
+
+    fn read(r: &mut WireReader) -> Result<Self, CommandStreamError> {
+        Ok(f64::from_bits(u64::read(r)?))
+    }
+}
+
+impl Wire for String {
+    fn write(&self, out: &mut Vec<u8>) {
+        (self.len() as u64).write(out);
+        out.extend_from_slice(self.as_bytes());
+    }
+
+    fn read(r: &mut WireReader) -> Result<Self, CommandStreamError> {
+        let offset = r.pos;
+        let len = u64::read(r)?;
+        let len = usize::try_from(len).map_err(|_| CommandStreamError::Truncated { offset })?;
+        String::from_utf8(r.take(len)?.to_vec()).map_err(|_| CommandStreamError::InvalidValue {
+            offset,
+            type_name: "String",
+        })
+    }
+}
+
+impl<T: Wire> Wire for Vec<T> {
+    fn write(&self, out: &mut Vec<u8>) {
+        (self.len() as u64).write(out);
+        for item in self {
+            item.write(out);
+        }
+    }
+
+    fn read(r: &mut WireReader) -> Result<Self, CommandStreamError> {
+        let len = u64::read(r)?;
+        let mut items = Vec::new();
+        for _ in 0..len {
+            items.push(T::read(r)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<T: Wire> Wire for Option<T> {
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            Some(value) => {
+                1u8.write(out);
+                value.write(out);
+            }
+            None => 0u8.write(out),
+        }
+    }
+
+    fn read(r: &mut WireReader) -> Result<Self, CommandStreamError> {
+        match r.read_as("Option", |tag: u8| (tag <= 1).then_some(tag))? {
+            0 => Ok(None),
+            _ => Ok(Some(T::read(r)?)),
+        }
+    }
+}
+
+impl Wire for Range<f32> {
+    fn write(&self, out: &mut Vec<u8>) {
+        self.start.write(out);
+        self.end.write(out);
+    }
+
+    fn read(r: &mut WireReader) -> Result<Self, CommandStreamError> {
+        Ok(f32::read(r)?..f32::read(r)?)
+    }
+}
+
+macro_rules! wire_struct {
+    ($($ty:ident { $($field:ident),* })*) => {
+        $(impl Wire for $ty {
+            fn write(&self, out: &mut Vec<u8>) {
+                $(self.$field.write(out);)*
+            }
+
+            fn read(r: &mut WireReader) -> Result<Self, CommandStreamError> {
+                Ok($ty { $($field: Wire::read(r)?),* })
+            }
+        })*
+    };
+}
+
+wire_struct! {
+    MTLSize { width, height, depth }
+    MTLOrigin { x, y, z }
+    MTLScissorRect { x, y, width, height }
+    MTLViewport { originX, originY, width, height, znear, zfar }
+}
+
+macro_rules! wire_enum {
+    ($($ty:ident { $($variant:ident),* })*) => {
+        $(impl Wire for $ty {
+            fn write(&self, out: &mut Vec<u8>) {
+                (*self as u64).write(out)
+            }
+
+            fn read(r: &mut WireReader) -> Result<Self, CommandStreamError> {
+                r.read_as(stringify!($ty), |value: u64| match value {
+                    $(value if value == $ty::$variant as u64 => Some($ty::$variant),)*
+                    _ => None,
+                })
+            }
+        })*
+    };
+}
+
+wire_enum! {
+    RenderStage { Vertex, Fragment, Object, Mesh }
+    MTLPrimitiveType { Point, Line, LineStrip, Triangle, TriangleStrip }
+    MTLIndexType { UInt16, UInt32 }
+    MTLCullMode { None, Front, Back }
+    MTLWinding { Clockwise, CounterClockwise }
+    MTLDepthClipMode { Clip, Clamp }
+    MTLTriangleFillMode { Fill, Lines }
+}
+
+macro_rules! wire_flags {
+    ($($ty:ident),*) => {
+        $(impl Wire for $ty {
+            fn write(&self, out: &mut Vec<u8>) {
+                (self.bits() as u64).write(out)
+            }
+
+            fn read(r: &mut WireReader) -> Result<Self, CommandStreamError> {
+                r.read_as(stringify!($ty), |bits: u64| $ty::from_bits(bits as NSUInteger))
+            }
+        })*
+    };
+}
+
+wire_flags!(MTLRenderStages, MTLResourceUsage, MTLBlitOption);
+
+impl Wire for ResourceHandle {
+    fn write(&self, out: &mut Vec<u8>) {
+        match self {
+            ResourceHandle::Buffer(buffer) => {
+                0u8.write(out);
+                buffer.write(out);
+            }
+            ResourceHandle::Texture(texture) => {
+                1u8.write(out);
+                texture.write(out);
+            }
+        }
+    }
+
+    fn read(r: &mut WireReader) -> Result<Self, CommandStreamError> {
+        match r.read_as("ResourceHandle", |tag: u8| (tag <= 1).then_some(tag))? {
+            0 => Ok(ResourceHandle::Buffer(Wire::read(r)?)),
+            _ => Ok(ResourceHandle::Texture(Wire::read(r)?)),
+        }
+    }
+}
+
+/// Tags are part of the stream format; append new commands, never renumber.
+macro_rules! wire_commands {
+    ($ty:ident { $($tag:literal => $variant:ident $({ $($field:ident),* })?,)* }) => {
+        impl Wire for $ty {
+            fn write(&self, out: &mut Vec<u8>) {
+                match self {
+                    $($ty::$variant $({ $($field),* })? => {
+                        ($tag as u8).write(out);
+                        $($($field.write(out);)*)?
+                    })*
+                }
+            }
+
+            fn read(r: &mut WireReader) -> Result<Self, CommandStreamError> {
+                let offset = r.pos;
+                match u8::read(r)? {
+                    $($tag => Ok($ty::$variant $({ $($field: Wire::read(r)?),* })?),)*
+                    _ => Err(CommandStreamError::InvalidValue {
+                        offset,
+                        type_name: stringify!($ty),
+                    }),
+                }
+            }
+        }
+    };
+}
+
+wire_commands!(RenderCommand {
+    0 => SetRenderPipelineState { pipeline },
+    1 => SetDepthStencilState { state },
+    2 => SetViewport { viewport },
+    3 => SetScissorRect { rect },
+    4 => SetFrontFacingWinding { winding },
+    5 => SetCullMode { mode },
+    6 => SetDepthClipMode { mode },
+    7 => SetTriangleFillMode { mode },
+    8 => SetDepthBias { bias, scale, clamp },
+    9 => SetBlendColor { red, green, blue, alpha },
+    10 => SetStencilReferenceValues { front, back },
+    11 => SetBuffer { stage, index, buffer, offset },
+    12 => SetBufferOffset { stage, index, offset },
+    13 => SetBytes { stage, index, bytes },
+    14 => SetTexture { stage, index, texture },
+    15 => SetSamplerState { stage, index, sampler, lod_clamp },
+    16 => Draw { primitive_type, vertex_start, vertex_count, instance_count, base_instance },
+    17 => DrawIndexed {
+        primitive_type, index_count, index_type, index_buffer, index_buffer_offset,
+        instance_count, base_vertex, base_instance
+    },
+    18 => DrawIndirect { primitive_type, indirect_buffer, indirect_buffer_offset },
+    19 => DrawIndexedIndirect {
+        primitive_type, index_type, index_buffer, index_buffer_offset,
+        indirect_buffer, indirect_buffer_offset
+    },
+    20 => DrawMeshThreadgroups {
+        threadgroups_per_grid, threads_per_object_threadgroup, threads_per_mesh_threadgroup
+    },
+    21 => DrawMeshThreads {
+        threads_per_grid, threads_per_object_threadgroup, threads_per_mesh_threadgroup
+    },
+    22 => UseResource { resource, usage, stages },
+    23 => UpdateFence { fence, stages },
+    24 => WaitForFence { fence, stages },
+    25 => PushDebugGroup { name },
+    26 => PopDebugGroup,
+    27 => InsertDebugSignpost { name },
+});
+
+wire_commands!(ComputeCommand {
+    0 => SetComputePipelineState { pipeline },
+    1 => SetBuffer { index, buffer, offset },
+    2 => SetBytes { index, bytes },
+    3 => SetTexture { index, texture },
+    4 => SetSamplerState { index, sampler, lod_clamp },
+    5 => SetThreadgroupMemoryLength { index, length },
+    6 => DispatchThreadgroups { threadgroups_per_grid, threads_per_threadgroup },
+    7 => DispatchThreads { threads_per_grid, threads_per_threadgroup },
+    8 => DispatchThreadgroupsIndirect {
+        indirect_buffer, indirect_buffer_offset, threads_per_threadgroup
+    },
+    9 => MemoryBarrierWithResources { resources },
+    10 => UseResource { resource, usage },
+    11 => UpdateFence { fence },
+    12 => WaitForFence { fence },
+    13 => PushDebugGroup { name },
+    14 => PopDebugGroup,
+    15 => InsertDebugSignpost { name },
+});
+
+wire_commands!(BlitCommand {
+    0 => CopyBufferToBuffer { source, source_offset, destination, destination_offset, size },
+    1 => CopyTextureToTexture {
+        source, source_slice, source_level, source_origin, source_size,
+        destination, destination_slice, destination_level, destination_origin
+    },
+    2 => CopyBufferToTexture {
+        source, source_offset, source_bytes_per_row, source_bytes_per_image, source_size,
+        destination, destination_slice, destination_level, destination_origin, options
+    },
+    3 => CopyTextureToBuffer {
+        source, source_slice, source_level, source_origin, source_size,
+        destination, destination_offset, destination_bytes_per_row,
+        destination_bytes_per_image, options
+    },
+    4 => FillBuffer { buffer, offset, length, value },
+    5 => GenerateMipmaps { texture },
+    6 => SynchronizeResource { resource },
+    7 => UpdateFence { fence },
+    8 => WaitForFence { fence },
+    9 => PushDebugGroup { name },
+    10 => PopDebugGroup,
+    11 => InsertDebugSignpost { name },
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render_list() -> RenderCommandList {
+        let viewport = MTLViewport {
+            originX: 0.0,
+            originY: 0.0,
+            width: 640.0,
+            height: 480.0,
+            znear: 0.0,
+            zfar: 1.0,
+        };
+        let mut list = RenderCommandList::new();
+        list.set_label("main pass");
+        list.set_render_pipeline_state(RenderPipelineHandle::new(0))
+            .set_viewport(viewport)
+            .set_cull_mode(MTLCullMode::Back)
+            .set_buffer(RenderStage::Vertex, 0, Some(BufferHandle::new(3)), 16)
+            .set_bytes(RenderStage::Fragment, 1, &[1, 2, 3, 4])
+            .push(RenderCommand::SetSamplerState {
+                stage: RenderStage::Mesh,
+                index: 2,
+                sampler: Some(SamplerHandle::new(1)),
+                lod_clamp: Some(0.0..4.5),
+            })
+            .push(RenderCommand::SetDepthBias {
+                bias: -1.5,
+                scale: 0.25,
+                clamp: f32::INFINITY,
+            })
+            .push_debug_group("opaque")
+            .draw_indexed_primitives(
+                MTLPrimitiveType::Triangle,
+                6,
+                MTLIndexType::UInt16,
+                BufferHandle::new(3),
+                0,
+            )
+            .push(RenderCommand::UseResource {
+                resource: TextureHandle::new(2).into(),
+                usage: MTLResourceUsage::Read | MTLResourceUsage::Sample,
+                stages: MTLRenderStages::Fragment,
+            })
+            .push(RenderCommand::DrawIndexed {
+                primitive_type: MTLPrimitiveType::TriangleStrip,
+                index_count: 4,
+                index_type: MTLIndexType::UInt32,
+                index_buffer: BufferHandle::new(4),
+                index_buffer_offset: 8,
+                instance_count: 2,
+                base_vertex: -3,
+                base_instance: 1,
+            })
+            .update_fence(FenceHandle::new(0), MTLRenderStages::Fragment)
+            .pop_debug_group();
+        list
+    }
+
+    fn compute_list() -> ComputeCommandList {
+        let mut list = ComputeCommandList::new();
+        list.set_compute_pipeline_state(ComputePipelineHandle::new(0))
+            .set_bytes(0, &[9; 12])
+            .set_texture(1, None)
+            .dispatch_threads(MTLSize::new(64, 1, 1), MTLSize::new(32, 1, 1))
+            .memory_barrier_with_resources(&[
+                BufferHandle::new(1).into(),
+                TextureHandle::new(1).into(),
+            ])
+            .wait_for_fence(FenceHandle::new(2));
+        list
+    }
+
+    fn blit_list() -> BlitCommandList {
+        let mut list = BlitCommandList::new();
+        list.copy_from_buffer(BufferHandle::new(0), 0, BufferHandle::new(1), 4, 8)
+            .fill_buffer(BufferHandle::new(1), 0, 4, 7)
+            .push(BlitCommand::CopyBufferToTexture {
+                source: BufferHandle::new(0),
+                source_offset: 0,
+                source_bytes_per_row: 16,
+                source_bytes_per_image: 64,
+                source_size: MTLSize::new(4, 4, 1),
+                destination: TextureHandle::new(0),
+                destination_slice: 0,
+                destination_level: 0,
+                destination_origin: MTLOrigin { x: 0, y: 0, z: 0 },
+                options: MTLBlitOption::None,
+            })
+            .generate_mipmaps(TextureHandle::new(0));
+        list
+    }
+
+    #[test]
+    fn record() {
+        let list = render_list();
+        assert_eq!(list.len(), 13);
+        assert_eq!(list.label(), Some("main pass"));
+        assert_eq!(
+            list.commands()[3],
+            RenderCommand::SetBuffer {
+                stage: RenderStage::Vertex,
+                index: 0,
+                buffer: Some(BufferHandle::new(3)),
+                offset: 16,
+            }
+        );
+        assert_eq!(
+            list.commands()[8],
+            RenderCommand::DrawIndexed {
+                primitive_type: MTLPrimitiveType::Triangle,
+                index_count: 6,
+                index_type: MTLIndexType::UInt16,
+                index_buffer: BufferHandle::new(3),
+                index_buffer_offset: 0,
+                instance_count: 1,
+                base_vertex: 0,
+                base_instance: 0,
+            }
+        );
+
+        let mut combined = blit_list();
+        combined.extend_from(&blit_list());
+        assert_eq!(combined.len(), 8);
+        assert_eq!(combined.commands()[4..], blit_list().commands()[..]);
+        combined.clear();
+        assert!(combined.is_empty());
+    }
+
+    #[test]
+    fn inspect() {
+        assert_eq!(
+            render_list().handles(),
+            [
+                CommandHandle::RenderPipeline(RenderPipelineHandle::new(0)),
+                CommandHandle::Buffer(BufferHandle::new(3)),
+                CommandHandle::Sampler(SamplerHandle::new(1)),
+                CommandHandle::Texture(TextureHandle::new(2)),
+                CommandHandle::Buffer(BufferHandle::new(4)),
+                CommandHandle::Fence(FenceHandle::new(0)),
+            ]
+        );
+        assert_eq!(
+            compute_list().handles(),
+            [
+                CommandHandle::ComputePipeline(ComputePipelineHandle::new(0)),
+                CommandHandle::Buffer(BufferHandle::new(1)),
+                CommandHandle::Texture(TextureHandle::new(1)),
+                CommandHandle::Fence(FenceHandle::new(2)),
+            ]
+        );
+        let draws = render_list()
+            .iter()
+            .filter(|command| matches!(command, RenderCommand::DrawIndexed { .. }))
+            .count();
+        assert_eq!(draws, 2);
+
+        let resources = CommandResources::new();
+        assert_eq!(
+            render_list().validate(&resources).unwrap_err(),
+            "render command 0 refers to unknown RenderPipeline(RenderPipelineHandle(0))"
+        );
+        assert!(ComputeCommandList::new().validate(&resources).is_ok());
+    }
+
+    #[test]
+    fn serialize() {
+        let render = render_list();
+        assert_eq!(
+            RenderCommandList::from_bytes(&render.to_bytes()).unwrap(),
+            render
+        );
+        let compute = compute_list();
+        assert_eq!(
+            ComputeCommandList::from_bytes(&compute.to_bytes()).unwrap(),
+            compute
+        );
+        let blit = blit_list();
+        assert_eq!(BlitCommandList::from_bytes(&blit.to_bytes()).unwrap(), blit);
+        let empty = BlitCommandList::new();
+        assert_eq!(
+            BlitCommandList::from_bytes(&empty.to_bytes()).unwrap(),
+            empty
+        );
+
+        // The format is fixed: magic, version, kind, label, count, commands.
+        let mut list = ComputeCommandList::new();
+        list.pop_debug_group();
+        assert_eq!(
+            list.to_bytes(),
+            [
+                b"MTLCMDS".as_slice(),
+                &[1, b'C', 0],
+                &1u64.to_le_bytes(),
+                &[14]
+            ]
+            .concat()
+        );
+    }
+
+    #[test]
+    fn stream_errors() {
+        let bytes = render_list().to_bytes();
+        assert_eq!(
+            RenderCommandList::from_bytes(b"MTLCMD"),
+            Err(CommandStreamError::NotACommandStream)
+        );
+        assert_eq!(
+            ComputeCommandList::from_bytes(&bytes),
+            Err(CommandStreamError::WrongEncoder {
+                expected: 'C',
+                found: 'R',
+            })
+        );
+
+        let mut newer = bytes.clone();
+        newer[7] = 2;
+        assert_eq!(
+            RenderCommandList::from_bytes(&newer),
+            Err(CommandStreamError::UnsupportedVersion(2))
+        );
+
+        let mut trailing = bytes.clone();
+        trailing.extend_from_slice(&[0, 0]);
+        assert_eq!(
+            RenderCommandList::from_bytes(&trailing),
+            Err(CommandStreamError::TrailingBytes(2))
+        );
+
+        for len in STREAM_MAGIC.len()..bytes.len() {
+            match RenderCommandList::from_bytes(&bytes[..len]) {
+                Err(CommandStreamError::Truncated { offset }) => assert!(offset <= len),
+                result => panic!("prefix of {} bytes: {:?}", len, result),
+            }
+        }
+
+        // A command tag past the last one.
+        let mut list = ComputeCommandList::new();
+        list.pop_debug_group();
+        let mut bytes = list.to_bytes();
+        *bytes.last_mut().unwrap() = 200;
+        let error = ComputeCommandList::from_bytes(&bytes).unwrap_err();
+        assert_eq!(
+            error,
+            CommandStreamError::InvalidValue {
+                offset: 18,
+                type_name: "ComputeCommand",
+            }
+        );
+        assert_eq!(error.to_string(), "invalid ComputeCommand at byte 18");
+
+        // An out of range enum value inside a command.
+        let mut list = RenderCommandList::new();
+        list.set_cull_mode(MTLCullMode::Back);
+        let mut bytes = list.to_bytes();
+        let last = bytes.len() - 8;
+        bytes[last] = 9;
+        assert_eq!(
+            RenderCommandList::from_bytes(&bytes),
+            Err(CommandStreamError::InvalidValue {
+                offset: 19,
+                type_name: "MTLCullMode",
+            })
+        );
+
+        // An invalid `Option` tag for the label.
+        let mut bytes = BlitCommandList::new().to_bytes();
+        bytes[9] = 2;
+        assert_eq!(
+            BlitCommandList::from_bytes(&bytes),
+            Err(CommandStreamError::InvalidValue {
+                offset: 9,
+                type_name: "Option",
+            })
+        );
+
+        // A string length far past the end.
+        let mut bytes = BlitCommandList::new().to_bytes();
+        bytes.truncate(9);
+        bytes.push(1);
+        bytes.extend_from_slice(&u64::MAX.to_le_bytes());
+        assert!(matches!(
+            BlitCommandList::from_bytes(&bytes),
+            Err(CommandStreamError::Truncated { .. })
+        ));
+    }
+
+    #[test]
+    fn handle_bounds() {
+        let mut resources = CommandResources::new();
+        let max = CommandResources::MAX_HANDLE_INDEX;
+        assert_eq!(resources.set_buffer(BufferHandle::new(3), None), Ok(()));
+        assert_eq!(resources.buffers.len(), 4);
+        assert!(!resources.contains(CommandHandle::Buffer(BufferHandle::new(3))));
+
+        let handle = FenceHandle::new(max + 1);
+        assert_eq!(
+            resources.set_fence(handle, None),
+            Err(HandleOutOfRange(CommandHandle::Fence(handle)))
+        );
+        assert_eq!(
+            resources.set_texture(TextureHandle::new(u32::MAX), None),
+            Err(HandleOutOfRange(CommandHandle::Texture(
+                TextureHandle::new(u32::MAX)
+            )))
+        );
+        assert!(resources.fences.is_empty());
+        assert!(resources.textures.is_empty());
+    }
+}
@@ -150,3 +150,112 @@ pub enum MTLPixelFormat {
     BGR10_XR = 554,
     BGR10_XR_SRGB = 555,
 }
+
+/// The unit a pixel format is stored and copied in: one pixel for
+/// uncompressed formats, a block of pixels for compressed ones.
+#[derive(Debug, Copy, Clone, Hash, PartialEq, Eq)]
+pub struct PixelFormatBlock {
+    pub width: u32,
+    pub height: u32,
+    pub bytes: u32,
+}
+
+impl PixelFormatBlock {
+    const fn new(width: u32, height: u32, bytes: u32) -> Self {
+        PixelFormatBlock {
+            width,
+            height,
+            bytes,
+        }
+    }
+
+    const fn pixel(bytes: u32) -> Self {
+        Self::new(1, 1, bytes)
+    }
+}
+
+impl MTLPixelFormat {
+    /// The block layout of the format, or `None` for `Invalid` and the
+    /// formats that combine depth and stencil, which are copied one aspect
+    /// at a time.
+    pub fn block(self) -> Option<PixelFormatBlock> {
+        use MTLPixelFormat::*;
+        Some(match self {
+            Invalid
+            | Depth24Unorm_Stencil8
+            | Depth32Float_Stencil8
+            | X32_Stencil8
+            | X24_Stencil8 => return None,
+            A8Unorm | R8Unorm | R8Unorm_sRGB | R8Snorm | R8Uint | R8Sint | Stencil8 => {
+                PixelFormatBlock::pixel(1)
+            }
+            R16Unorm | R16Snorm | R16Uint | R16Sint | R16Float | RG8Unorm | RG8Unorm_sRGB
+            | RG8Snorm | RG8Uint | RG8Sint | B5G6R5Unorm | A1BGR5Unorm | ABGR4Unorm
+            | BGR5A1Unorm | Depth16Unorm => PixelFormatBlock::pixel(2),
+            R32Uint | R32Sint | R32Float | RG16Unorm | RG16Snorm | RG16Uint | RG16Sint
+            | RG16Float | RGBA8Unorm | RGBA8Unorm_sRGB | RGBA8Snorm | RGBA8Uint | RGBA8Sint
+            | BGRA8Unorm | BGRA8Unorm_sRGB | RGB10A2Unorm | RGB10A2Uint | RG11B10Float
+            | RGB9E5Float | BGR10A2Unorm | Depth32Float | BGR10_XR | BGR10_XR_SRGB => {
+                PixelFormatBlock::pixel(4)
+            }
+            RG32Uint | RG32Sint | RG32Float | RGBA16Unorm | RGBA16Snorm | RGBA16Uint
+            | RGBA16Sint | RGBA16Float | BGRA10_XR | BGRA10_XR_SRGB => PixelFormatBlock::pixel(8),
+            RGBA32Uint | RGBA32Sint | RGBA32Float => PixelFormatBlock::pixel(16),
+            BC1_RGBA | BC1_RGBA_sRGB | BC4_RUnorm | BC4_RSnorm | EAC_R11Unorm | EAC_R11Snorm
+            | ETC2_RGB8 | ETC2_RGB8_sRGB | ETC2_RGB8A1 | ETC2_RGB8A1_sRGB => {
+                PixelFormatBlock::new(4, 4, 8)
+            }
+            BC2_RGBA | BC2_RGBA_sRGB | BC3_RGBA | BC3_RGBA_sRGB | BC5_RGUnorm | BC5_RGSnorm
+            | BC6H_RGBFloat | BC6H_RGBUfloat | BC7_RGBAUnorm | BC7_RGBAUnorm_sRGB
+            | EAC_RG11Unorm | EAC_RG11Snorm | EAC_RGBA8 | EAC_RGBA8_sRGB => {
+                PixelFormatBlock::new(4, 4, 16)
+            }
+            PVRTC_RGB_2BPP | PVRTC_RGB_2BPP_sRGB | PVRTC_RGBA_2BPP | PVRTC_RGBA_2BPP_sRGB => {
+                PixelFormatBlock::new(8, 4, 8)
+            }
+            PVRTC_RGB_4BPP | PVRTC_RGB_4BPP_sRGB | PVRTC_RGBA_4BPP | PVRTC_RGBA_4BPP_sRGB => {
+                PixelFormatBlock::new(4, 4, 8)
+            }
+            ASTC_4x4_sRGB | ASTC_4x4_LDR | ASTC_4x4_HDR => PixelFormatBlock::new(4, 4, 16),
+            ASTC_5x4_sRGB | ASTC_5x4_LDR | ASTC_5x4_HDR => PixelFormatBlock::new(5, 4, 16),
+            ASTC_5x5_sRGB | ASTC_5x5_LDR | ASTC_5x5_HDR => PixelFormatBlock::new(5, 5, 16),
+            ASTC_6x5_sRGB | ASTC_6x5_LDR | ASTC_6x5_HDR => PixelFormatBlock::new(6, 5, 16),
+            ASTC_6x6_sRGB | ASTC_6x6_LDR | ASTC_6x6_HDR => PixelFormatBlock::new(6, 6, 16),
+            ASTC_8x5_sRGB | ASTC_8x5_LDR | ASTC_8x5_HDR => PixelFormatBlock::new(8, 5, 16),
+            ASTC_8x6_sRGB | ASTC_8x6_LDR | ASTC_8x6_HDR => PixelFormatBlock::new(8, 6, 16),
+            ASTC_8x8_sRGB | ASTC_8x8_LDR | ASTC_8x8_HDR => PixelFormatBlock::new(8, 8, 16),
+            ASTC_10x5_sRGB | ASTC_10x5_LDR | ASTC_10x5_HDR => PixelFormatBlock::new(10, 5, 16),
+            ASTC_10x6_sRGB | ASTC_10x6_LDR | ASTC_10x6_HDR => PixelFormatBlock::new(10, 6, 16),
+            ASTC_10x8_sRGB | ASTC_10x8_LDR | ASTC_10x8_HDR => PixelFormatBlock::new(10, 8, 16),
+            ASTC_10x10_sRGB | ASTC_10x10_LDR | ASTC_10x10_HDR => PixelFormatBlock::new(10, 10, 16),
+            ASTC_12x10_sRGB | ASTC_12x10_LDR | ASTC_12x10_HDR => PixelFormatBlock::new(12, 10, 16),
+            ASTC_12x12_sRGB | ASTC_12x12_LDR | ASTC_12x12_HDR => PixelFormatBlock::new(12, 12, 16),
+            GBGR422 | BGRG422 => PixelFormatBlock::new(2, 1, 4),
+        })
+    }
+
+    /// Whether the format is block-compressed. The 4:2:2 formats have 2x1
+    /// blocks but are not compressed.
+    pub fn is_compressed(self) -> bool {
+        use MTLPixelFormat::*;
+        !matches!(self, GBGR422 | BGRG422)
+            && self
+                .block()
+                .is_some_and(|block| block.width > 1 || block.height > 1)
+    }
+
+    pub fn is_pvrtc(self) -> bool {
+        use MTLPixelFormat::*;
+        matches!(
+            self,
+            PVRTC_RGB_2BPP
+                | PVRTC_RGB_2BPP_sRGB
+                | PVRTC_RGB_4BPP
+                | PVRTC_RGB_4BPP_sRGB
+                | PVRTC_RGBA_2BPP
+                | PVRTC_RGBA_2BPP_sRGB
+                | PVRTC_RGBA_4BPP
+                | PVRTC_RGBA_4BPP_sRGB
+        )
+    }
+}
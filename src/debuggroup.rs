@@ -0,0 +1,336 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::*;
+
+use log::warn;
+use std::cell::RefCell;
+
+/// Something that takes debug groups: command encoders and command buffers.
+pub trait DebugGroupTarget {
+    fn push_debug_group(&self, name: &str);
+    fn pop_debug_group(&self);
+    fn insert_debug_signpost(&self, name: &str);
+
+    /// Push a debug group that is popped when the returned guard drops.
+    fn debug_group(&self, name: &str) -> DebugGroup<'_, Self> {
+        DebugGroup::new(self, name)
+    }
+}
+
+macro_rules! impl_debug_group_target {
+    ($($ty:ty),*) => {
+        $(impl DebugGroupTarget for $ty {
+            fn push_debug_group(&self, name: &str) {
+                CommandEncoderRef::push_debug_group(self, name)
+            }
+
+            fn pop_debug_group(&self) {
+                CommandEncoderRef::pop_debug_group(self)
+            }
+
+            fn insert_debug_signpost(&self, name: &str) {
+                CommandEncoderRef::insert_debug_signpost(self, name)
+            }
+        })*
+    };
+}
+
+impl_debug_group_target!(
+    CommandEncoderRef,
+    RenderCommandEncoderRef,
+    ParallelRenderCommandEncoderRef,
+    ComputeCommandEncoderRef,
+    BlitCommandEncoderRef,
+    AccelerationStructureCommandEncoderRef
+);
+
+impl DebugGroupTarget for CommandBufferRef {
+    fn push_debug_group(&self, name: &str) {
+        CommandBufferRef::push_debug_group(self, name)
+    }
+
+    fn pop_debug_group(&self) {
+        CommandBufferRef::pop_debug_group(self)
+    }
+
+    /// Command buffers have no signposts; this inserts an empty group named
+    /// `name` instead.
+    fn insert_debug_signpost(&self, name: &str) {
+        CommandBufferRef::push_debug_group(self, name);
+        CommandBufferRef::pop_debug_group(self);
+    }
+}
+
+/// A debug group that is popped when dropped.
+///
+/// ```ignore
+/// let _shadows = encoder.debug_group("Shadows");
+/// for light in &lights {
+///     let _light = encoder.debug_group(&light.name);
+///     encoder.draw_primitives(MTLPrimitiveType::Triangle, 0, light.vertex_count);
+/// }
+/// ```
+pub struct DebugGroup<'a, T: DebugGroupTarget + ?Sized> {
+    target: &'a T,
+}
+
+impl<'a, T: DebugGroupTarget + ?Sized> DebugGroup<'a, T> {
+    pub fn new(target: &'a T, name: &str) -> Self {
+        target.push_debug_group(name);
+        DebugGroup { target }
+    }
+
+    pub fn target(&self) -> &'a T {
+        self.target
+    }
+
+    /// Insert a signpost inside this group.
+    pub fn signpost(&self, name: &str) {
+        self.target.insert_debug_signpost(name);
+    }
+
+    /// Pop the group now rather than at the end of the scope.
+    pub fn end(self) {}
+}
+
+impl<T: DebugGroupTarget + ?Sized> Drop for DebugGroup<'_, T> {
+    fn drop(&mut self) {
+        self.target.pop_debug_group();
+    }
+}
+
+/// Forwards debug groups to `target` while keeping a copy of the open groups,
+/// so they can be reported without a GPU capture.
+///
+/// Use one per encoder or command buffer, in place of the target:
+///
+/// ```ignore
+/// let groups = DebugGroupStack::new(encoder);
+/// let _frame = groups.debug_group("Frame");
+/// let _pass = groups.debug_group("Lighting");
+/// if let Err(err) = encode_lights(encoder) {
+///     return Err(format!("{} (in {})", err, groups.path()));
+/// }
+/// ```
+pub struct DebugGroupStack<'a, T: DebugGroupTarget + ?Sized> {
+    target: &'a T,
+    groups: RefCell<Vec<String>>,
+    last_signpost: RefCell<Option<String>>,
+}
+
+impl<'a, T: DebugGroupTarget + ?Sized> DebugGroupStack<'a, T> {
+    pub fn new(target: &'a T) -> Self {
+        DebugGroupStack {
+            target,
+            groups: RefCell::new(Vec::new()),
+            last_signpost: RefCell::new(None),
+        }
+    }
+
+    pub fn target(&self) -> &'a T {
+        self.target
+    }
+
+    /// The open groups, outermost first.
+    pub fn groups(&self) -> Vec<String> {
+        self.groups.borrow().clone()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.groups.borrow().len()
+    }
+
+    /// The open groups joined with `" / "`.
+    pub fn path(&self) -> String {
+        self.groups.borrow().join(" / ")
+    }
+
+    /// The most recent signpost, if any.
+    pub fn last_signpost(&self) -> Option<String> {
+        self.last_signpost.borrow().clone()
+    }
+}
+
+impl<T: DebugGroupTarget + ?Sized> DebugGroupTarget for DebugGroupStack<'_, T> {
+    fn push_debug_group(&self, name: &str) {
+        self.target.push_debug_group(name);
+        self.groups.borrow_mut().push(name.to_owned());
+    }
+
+    /// An unbalanced pop is logged and not forwarded, since Metal would
+    /// reject it.
+    fn pop_debug_group(&self) {
+        if self.groups.borrow_mut().pop().is_some() {
+            self.target.pop_debug_group();
+        } else {
+            warn!("pop_debug_group without a matching push_debug_group");
+        }
+    }
+
+    fn insert_debug_signpost(&self, name: &str) {
+        self.target.insert_debug_signpost(name);
+        *self.last_signpost.borrow_mut() = Some(name.to_owned());
+    }
+}
+
+/// Open a debug group on an encoder or command buffer until the end of the
+/// enclosing scope. The name is formatted like [`format!`].
+///
+/// In release builds (without `debug_assertions`) nothing is pushed and
+/// neither the target nor the format arguments are evaluated, so they
+/// shouldn't have side effects the code relies on.
+///
+/// ```ignore
+/// fn encode_shadows(encoder: &RenderCommandEncoderRef, cascade: usize) {
+///     metal::debug_group!(encoder, "Shadow cascade {}", cascade);
+///     // ...
+/// }
+/// ```
+#[macro_export]
+macro_rules! debug_group {
+    ($target:expr, $name:literal $(, $arg:expr)* $(,)?) => {
+        #[cfg(debug_assertions)]
+        let _debug_group = {
+            use $crate::DebugGroupTarget as _;
+            ($target).debug_group(&::std::format!($name $(, $arg)*))
+        };
+        // Borrowed in a closure that never runs, so they count as used
+        // without being evaluated.
+        #[cfg(not(debug_assertions))]
+        let _ = || {
+            let _ = (&$target $(, &$arg)*);
+        };
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records the calls it receives.
+    #[derive(Default)]
+    struct MockTarget {
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl MockTarget {
+        fn take(&self) -> Vec<String> {
+            self.calls.take()
+        }
+    }
+
+    impl DebugGroupTarget for MockTarget {
+        fn push_debug_group(&self, name: &str) {
+            self.calls.borrow_mut().push(format!("push {}", name));
+        }
+
+        fn pop_debug_group(&self) {
+            self.calls.borrow_mut().push("pop".to_owned());
+        }
+
+        fn insert_debug_signpost(&self, name: &str) {
+            self.calls.borrow_mut().push(format!("signpost {}", name));
+        }
+    }
+
+    #[test]
+    fn guards_pop_on_drop() {
+        let target = MockTarget::default();
+        {
+            let frame = target.debug_group("Frame");
+            {
+                let _pass = DebugGroup::new(&target, "Pass");
+                frame.signpost("begin");
+            }
+            assert!(std::ptr::eq(frame.target(), &target));
+            frame.end();
+            target.insert_debug_signpost("after");
+        }
+        assert_eq!(
+            target.take(),
+            [
+                "push Frame",
+                "push Pass",
+                "signpost begin",
+                "pop",
+                "pop",
+                "signpost after"
+            ]
+        );
+    }
+
+    #[test]
+    fn stack_tracks_open_groups() {
+        let target = MockTarget::default();
+        let stack = DebugGroupStack::new(&target);
+        assert_eq!((stack.depth(), stack.path()), (0, String::new()));
+        let frame = stack.debug_group("Frame");
+        {
+            let pass = stack.debug_group("Pass");
+            pass.signpost("draw");
+            assert_eq!(stack.path(), "Frame / Pass");
+            assert_eq!(stack.depth(), 2);
+        }
+        assert_eq!(stack.groups(), ["Frame"]);
+        assert_eq!(stack.last_signpost().as_deref(), Some("draw"));
+        drop(frame);
+        assert_eq!(stack.depth(), 0);
+        assert_eq!(
+            target.take(),
+            ["push Frame", "push Pass", "signpost draw", "pop", "pop"]
+        );
+    }
+
+    #[test]
+    fn stack_drops_unbalanced_pops() {
+        let target = MockTarget::default();
+        let stack = DebugGroupStack::new(&target);
+        stack.push_debug_group("Frame");
+        stack.pop_debug_group();
+        stack.pop_debug_group();
+        assert_eq!(stack.depth(), 0);
+        assert_eq!(target.take(), ["push Frame", "pop"]);
+    }
+
+    #[test]
+    fn stacks_nest() {
+        let target = MockTarget::default();
+        let outer = DebugGroupStack::new(&target);
+        let inner = DebugGroupStack::new(&outer);
+        let _group = inner.debug_group("Shadows");
+        assert_eq!(outer.path(), "Shadows");
+        assert_eq!(inner.path(), "Shadows");
+        assert!(std::ptr::eq(inner.target(), &outer));
+        assert_eq!(target.take(), ["push Shadows"]);
+    }
+
+    #[test]
+    fn macro_scopes_the_group() {
+        let target = MockTarget::default();
+        let mut evaluated = 0;
+        let mut cascade = || {
+            evaluated += 1;
+            3
+        };
+        {
+            crate::debug_group!(target, "Shadow cascade {}", cascade());
+            target.insert_debug_signpost("inside");
+        }
+        if cfg!(debug_assertions) {
+            assert_eq!(evaluated, 1);
+            assert_eq!(
+                target.take(),
+                ["push Shadow cascade 3", "signpost inside", "pop"]
+            );
+        } else {
+            // Neither the target nor the arguments are evaluated.
+            assert_eq!(evaluated, 0);
+            assert_eq!(target.take(), ["signpost inside"]);
+        }
+    }
+}
@@ -11,11 +11,7 @@ use block::Block;
 use log::warn;
 use objc::runtime::{NO, YES};
 
-use std::{
-    ffi::{c_char, CStr},
-    path::Path,
-    ptr,
-};
+use std::{path::Path, ptr};
 
 /// Available on macOS 10.11+, iOS 8.0+, tvOS 9.0+
 ///
@@ -1683,7 +1679,7 @@ impl DeviceRef {
         &self,
         src: &str,
         options: &CompileOptionsRef,
-    ) -> Result<Library, String> {
+    ) -> Result<Library, MetalError> {
         let source = nsstring_from_str(src);
         unsafe {
             let mut err: *mut Object = ptr::null_mut();
@@ -1691,13 +1687,11 @@ impl DeviceRef {
                                                                         options:options
                                                                           error:&mut err];
             if !err.is_null() {
-                let desc: *mut Object = msg_send![err, localizedDescription];
-                let compile_error: *const c_char = msg_send![desc, UTF8String];
-                let message = CStr::from_ptr(compile_error).to_string_lossy().into_owned();
+                let error = MetalError::from_nserror(err);
                 if library.is_null() {
-                    return Err(message);
+                    return Err(error);
                 } else {
-                    warn!("Shader warnings: {}", message);
+                    warn!("Shader warnings: {}", error);
                 }
             }
 
@@ -1706,7 +1700,7 @@ impl DeviceRef {
         }
     }
 
-    pub fn new_library_with_file<P: AsRef<Path>>(&self, file: P) -> Result<Library, String> {
+    pub fn new_library_with_file<P: AsRef<Path>>(&self, file: P) -> Result<Library, MetalError> {
         let filename = nsstring_from_str(file.as_ref().to_string_lossy().as_ref());
         unsafe {
             let library: *mut MTLLibrary = try_objc! { err =>
@@ -1717,7 +1711,7 @@ impl DeviceRef {
         }
     }
 
-    pub fn new_library_with_data(&self, library_data: &[u8]) -> Result<Library, String> {
+    pub fn new_library_with_data(&self, library_data: &[u8]) -> Result<Library, MetalError> {
         unsafe {
             // SAFETY:
             // `library_data` does not necessarily outlive the dispatch data
@@ -1745,17 +1739,13 @@ impl DeviceRef {
     }
 
     /// Only available on (macos(11.0), ios(14.0))
-    pub fn new_dynamic_library(&self, library: &LibraryRef) -> Result<DynamicLibrary, String> {
+    pub fn new_dynamic_library(&self, library: &LibraryRef) -> Result<DynamicLibrary, MetalError> {
         unsafe {
             let mut err: *mut Object = ptr::null_mut();
             let dynamic_library: *mut MTLDynamicLibrary = msg_send![self, newDynamicLibrary:library
                                                                                       error:&mut err];
             if !err.is_null() {
-                // FIXME: copy pasta
-                let desc: *mut Object = msg_send![err, localizedDescription];
-                let compile_error: *const c_char = msg_send![desc, UTF8String];
-                let message = CStr::from_ptr(compile_error).to_string_lossy().into_owned();
-                Err(message)
+                Err(MetalError::from_nserror(err))
             } else {
                 Ok(DynamicLibrary::from_ptr(dynamic_library))
             }
@@ -1763,17 +1753,13 @@ impl DeviceRef {
     }
 
     /// Only available on (macos(11.0), ios(14.0))
-    pub fn new_dynamic_library_with_url(&self, url: &URLRef) -> Result<DynamicLibrary, String> {
+    pub fn new_dynamic_library_with_url(&self, url: &URLRef) -> Result<DynamicLibrary, MetalError> {
         unsafe {
             let mut err: *mut Object = ptr::null_mut();
             let dynamic_library: *mut MTLDynamicLibrary = msg_send![self, newDynamicLibraryWithURL:url
                                                                                              error:&mut err];
             if !err.is_null() {
-                // FIXME: copy pasta
-                let desc: *mut Object = msg_send![err, localizedDescription];
-                let compile_error: *const c_char = msg_send![desc, UTF8String];
-                let message = CStr::from_ptr(compile_error).to_string_lossy().into_owned();
-                Err(message)
+                Err(MetalError::from_nserror(err))
             } else {
                 Ok(DynamicLibrary::from_ptr(dynamic_library))
             }
@@ -1784,17 +1770,13 @@ impl DeviceRef {
     pub fn new_binary_archive_with_descriptor(
         &self,
         descriptor: &BinaryArchiveDescriptorRef,
-    ) -> Result<BinaryArchive, String> {
+    ) -> Result<BinaryArchive, MetalError> {
         unsafe {
             let mut err: *mut Object = ptr::null_mut();
             let binary_archive: *mut MTLBinaryArchive = msg_send![self, newBinaryArchiveWithDescriptor:descriptor
                                                      error:&mut err];
             if !err.is_null() {
-                // TODO: copy pasta
-                let desc: *mut Object = msg_send![err, localizedDescription];
-                let c_msg: *const c_char = msg_send![desc, UTF8String];
-                let message = CStr::from_ptr(c_msg).to_string_lossy().into_owned();
-                Err(message)
+                Err(MetalError::from_nserror(err))
             } else {
                 Ok(BinaryArchive::from_ptr(binary_archive))
             }
@@ -1806,7 +1788,7 @@ impl DeviceRef {
         &self,
         descriptor: &RenderPipelineDescriptorRef,
         reflection_options: MTLPipelineOption,
-    ) -> Result<(RenderPipelineState, RenderPipelineReflection), String> {
+    ) -> Result<(RenderPipelineState, RenderPipelineReflection), MetalError> {
         unsafe {
             let mut reflection: *mut Object = ptr::null_mut();
             let pipeline_state: *mut MTLRenderPipelineState = try_objc! { err =>
@@ -1828,7 +1810,7 @@ impl DeviceRef {
     pub fn new_render_pipeline_state(
         &self,
         descriptor: &RenderPipelineDescriptorRef,
-    ) -> Result<RenderPipelineState, String> {
+    ) -> Result<RenderPipelineState, MetalError> {
         unsafe {
             let pipeline_state: *mut MTLRenderPipelineState = try_objc! { err =>
                 msg_send![self, newRenderPipelineStateWithDescriptor:descriptor
@@ -1844,7 +1826,7 @@ impl DeviceRef {
         &self,
         descriptor: &MeshRenderPipelineDescriptorRef,
         reflection_options: MTLPipelineOption,
-    ) -> Result<(RenderPipelineState, RenderPipelineReflection), String> {
+    ) -> Result<(RenderPipelineState, RenderPipelineReflection), MetalError> {
         unsafe {
             let mut reflection: *mut Object = ptr::null_mut();
             let pipeline_state: *mut MTLRenderPipelineState = try_objc! { err =>
@@ -1867,7 +1849,7 @@ impl DeviceRef {
     pub fn new_mesh_render_pipeline_state(
         &self,
         descriptor: &MeshRenderPipelineDescriptorRef,
-    ) -> Result<RenderPipelineState, String> {
+    ) -> Result<RenderPipelineState, MetalError> {
         unsafe {
             let pipeline_state: *mut MTLRenderPipelineState = try_objc! { err =>
                 msg_send![self, newRenderPipelineStateWithMeshDescriptor:descriptor
@@ -1878,10 +1860,28 @@ impl DeviceRef {
         }
     }
 
+    /// Only available on (macos(11.0), ios(11.0))
+    pub fn new_tile_render_pipeline_state(
+        &self,
+        descriptor: &TileRenderPipelineDescriptorRef,
+    ) -> Result<RenderPipelineState, MetalError> {
+        unsafe {
+            let reflection: *mut *mut Object = ptr::null_mut();
+            let pipeline_state: *mut MTLRenderPipelineState = try_objc! { err =>
+                msg_send![self, newRenderPipelineStateWithTileDescriptor:descriptor
+                                                                 options:MTLPipelineOption::None
+                                                              reflection:reflection
+                                                                   error:&mut err]
+            };
+
+            Ok(RenderPipelineState::from_ptr(pipeline_state))
+        }
+    }
+
     pub fn new_compute_pipeline_state_with_function(
         &self,
         function: &FunctionRef,
-    ) -> Result<ComputePipelineState, String> {
+    ) -> Result<ComputePipelineState, MetalError> {
         unsafe {
             let pipeline_state: *mut MTLComputePipelineState = try_objc! { err =>
                 msg_send![self, newComputePipelineStateWithFunction:function
@@ -1895,7 +1895,7 @@ impl DeviceRef {
     pub fn new_compute_pipeline_state(
         &self,
         descriptor: &ComputePipelineDescriptorRef,
-    ) -> Result<ComputePipelineState, String> {
+    ) -> Result<ComputePipelineState, MetalError> {
         unsafe {
             let pipeline_state: *mut MTLComputePipelineState = try_objc! { err =>
                 msg_send![self, newComputePipelineStateWithDescriptor:descriptor
@@ -1912,7 +1912,7 @@ impl DeviceRef {
         &self,
         descriptor: &ComputePipelineDescriptorRef,
         reflection_options: MTLPipelineOption,
-    ) -> Result<(ComputePipelineState, ComputePipelineReflection), String> {
+    ) -> Result<(ComputePipelineState, ComputePipelineReflection), MetalError> {
         unsafe {
             let mut reflection: *mut Object = ptr::null_mut();
             let pipeline_state: *mut MTLComputePipelineState = try_objc! { err =>
@@ -1966,10 +1966,34 @@ impl DeviceRef {
         }
     }
 
+    /// A buffer initialized with a copy of `value`.
+    pub fn new_buffer_with_value<T: Pod>(&self, value: &T, options: MTLResourceOptions) -> Buffer {
+        self.new_buffer_with_slice(std::slice::from_ref(value), options)
+    }
+
+    /// A buffer initialized with a copy of `values`.
+    ///
+    /// # Panics
+    ///
+    /// If `values` is empty; Metal cannot create zero-length buffers.
+    pub fn new_buffer_with_slice<T: Pod>(
+        &self,
+        values: &[T],
+        options: MTLResourceOptions,
+    ) -> Buffer {
+        let bytes = crate::slice_bytes(values);
+        assert!(!bytes.is_empty(), "cannot create an empty buffer");
+        self.new_buffer_with_data(
+            bytes.as_ptr() as *const std::ffi::c_void,
+            bytes.len() as NSUInteger,
+            options,
+        )
+    }
+
     pub fn new_counter_sample_buffer_with_descriptor(
         &self,
         descriptor: &CounterSampleBufferDescriptorRef,
-    ) -> Result<CounterSampleBuffer, String> {
+    ) -> Result<CounterSampleBuffer, MetalError> {
         unsafe {
             let counter_sample_buffer: *mut MTLCounterSampleBuffer = try_objc! { err =>
                 msg_send![self, newCounterSampleBufferWithDescriptor: descriptor error:&mut err]
@@ -0,0 +1,448 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Parsing of the clang-style compiler log that Metal reports from
+//! [`DeviceRef::new_library_with_source`](crate::DeviceRef::new_library_with_source).
+
+use std::fmt;
+
+/// File name the Metal compiler uses for the source string it was given.
+pub const PROGRAM_SOURCE: &str = "program_source";
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum CompilerDiagnosticSeverity {
+    Note,
+    Remark,
+    Warning,
+    Error,
+    Fatal,
+}
+
+impl CompilerDiagnosticSeverity {
+    fn parse(s: &str) -> Option<Self> {
+        Some(match s {
+            "note" => Self::Note,
+            "remark" => Self::Remark,
+            "warning" => Self::Warning,
+            "error" => Self::Error,
+            "fatal error" => Self::Fatal,
+            _ => return None,
+        })
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Note => "note",
+            Self::Remark => "remark",
+            Self::Warning => "warning",
+            Self::Error => "error",
+            Self::Fatal => "fatal error",
+        }
+    }
+}
+
+/// A suggested replacement, printed by clang below the caret line.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct CompilerFixIt {
+    /// 1-based column the text should be inserted at.
+    pub column: u32,
+    pub text: String,
+}
+
+/// A location in a source file; `line` and `column` are 1-based.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct CompilerSourceLocation {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// One diagnostic from a Metal shader compiler log.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct CompilerDiagnostic {
+    pub severity: CompilerDiagnosticSeverity,
+    pub location: Option<CompilerSourceLocation>,
+    pub message: String,
+    /// The warning group, e.g. `-Wunused-variable`.
+    pub flag: Option<String>,
+    /// The offending source line, as echoed by the compiler.
+    pub snippet: Option<String>,
+    /// 1-based column range highlighted with `~` (and the `^`) under the snippet.
+    pub highlight: Option<(u32, u32)>,
+    pub fix_its: Vec<CompilerFixIt>,
+    /// Notes attached to this diagnostic, in log order.
+    pub notes: Vec<CompilerDiagnostic>,
+    /// `In file included from` locations, outermost first.
+    pub include_stack: Vec<CompilerSourceLocation>,
+}
+
+impl CompilerDiagnostic {
+    pub fn is_error(&self) -> bool {
+        self.severity >= CompilerDiagnosticSeverity::Error
+    }
+
+    /// Rewrite locations in [`PROGRAM_SOURCE`] with `map`, which receives a
+    /// 1-based line of the compiled source and returns the original file and
+    /// line, e.g. from a preprocessor line map. Notes and include locations
+    /// are rewritten too.
+    pub fn remap_lines<F>(&mut self, map: &mut F)
+    where
+        F: FnMut(u32) -> Option<(String, u32)>,
+    {
+        let remap = |location: &mut CompilerSourceLocation, map: &mut F| {
+            if location.file == PROGRAM_SOURCE {
+                if let Some((file, line)) = map(location.line) {
+                    location.file = file;
+                    location.line = line;
+                }
+            }
+        };
+        if let Some(location) = &mut self.location {
+            remap(location, map);
+        }
+        for location in &mut self.include_stack {
+            remap(location, map);
+        }
+        for note in &mut self.notes {
+            note.remap_lines(map);
+        }
+    }
+}
+
+impl fmt::Display for CompilerDiagnostic {
+    /// Render the diagnostic the way clang does, with its source snippet,
+    /// caret and fix-its, followed by its notes.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for include in &self.include_stack {
+            writeln!(
+                f,
+                "In file included from {}:{}:",
+                include.file, include.line
+            )?;
+        }
+        if let Some(location) = &self.location {
+            write!(
+                f,
+                "{}:{}:{}: ",
+                location.file, location.line, location.column
+            )?;
+        }
+        write!(f, "{}: {}", self.severity.as_str(), self.message)?;
+        if let Some(flag) = &self.flag {
+            write!(f, " [{}]", flag)?;
+        }
+        writeln!(f)?;
+
+        if let Some(snippet) = &self.snippet {
+            writeln!(f, "{}", snippet)?;
+            let caret = self.location.as_ref().map_or(0, |l| l.column);
+            let (start, end) = self.highlight.unwrap_or((caret, caret));
+            let mut marker = String::new();
+            for column in 1..=end.max(caret) {
+                marker.push(if column == caret {
+                    '^'
+                } else if column >= start && column <= end {
+                    '~'
+                } else {
+                    ' '
+                });
+            }
+            writeln!(f, "{}", marker.trim_end())?;
+            for fix_it in &self.fix_its {
+                writeln!(
+                    f,
+                    "{}{}",
+                    " ".repeat(fix_it.column.saturating_sub(1) as usize),
+                    fix_it.text
+                )?;
+            }
+        }
+
+        for note in &self.notes {
+            write!(f, "{}", note)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a clang-style compiler log into diagnostics.
+///
+/// Notes are attached to the diagnostic they follow. Lines that are not part
+/// of a diagnostic, such as `Compilation failed:` or `2 errors generated.`,
+/// are skipped.
+pub fn parse_compiler_diagnostics(log: &str) -> Vec<CompilerDiagnostic> {
+    let mut diagnostics: Vec<CompilerDiagnostic> = Vec::new();
+    let mut include_stack = Vec::new();
+    let mut lines = log.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        if let Some(location) = parse_include_line(line) {
+            include_stack.push(location);
+            continue;
+        }
+        let mut diagnostic = match parse_header(line) {
+            Some(diagnostic) => diagnostic,
+            None => continue,
+        };
+        diagnostic.include_stack = std::mem::take(&mut include_stack);
+
+        // Snippet, caret line and fix-its follow the header.
+        if let Some(snippet) = lines.next_if(|l| !is_structural(l)) {
+            match lines.next_if(|l| is_caret_line(l)) {
+                Some(caret) => {
+                    diagnostic.snippet = Some(snippet.to_string());
+                    diagnostic.highlight = caret_range(caret);
+                    while let Some(fix_it) = lines.next_if(|l| !is_structural(l) && is_fix_it(l)) {
+                        let text = fix_it.trim_start();
+                        diagnostic.fix_its.push(CompilerFixIt {
+                            column: (fix_it.len() - text.len()) as u32 + 1,
+                            text: text.to_string(),
+                        });
+                    }
+                }
+                // Not a snippet after all; treat it as a continuation of the message.
+                None => {
+                    diagnostic.message.push('\n');
+                    diagnostic.message.push_str(snippet);
+                }
+            }
+        }
+
+        let attach = diagnostic.severity == CompilerDiagnosticSeverity::Note;
+        match diagnostics.last_mut() {
+            Some(parent) if attach => parent.notes.push(diagnostic),
+            _ => diagnostics.push(diagnostic),
+        }
+    }
+
+    diagnostics
+}
+
+/// Whether a line starts a new diagnostic or is compiler chatter.
+fn is_structural(line: &str) -> bool {
+    parse_header(line).is_some()
+        || parse_include_line(line).is_some()
+        || line.ends_with(" generated.")
+        || line.starts_with("Compilation failed")
+}
+
+fn is_caret_line(line: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.contains('^') && trimmed.chars().all(|c| c == '^' || c == '~' || c == ' ')
+}
+
+fn is_fix_it(line: &str) -> bool {
+    line.starts_with(' ') && !line.trim().is_empty()
+}
+
+fn caret_range(line: &str) -> Option<(u32, u32)> {
+    let start = line.find(['^', '~'])?;
+    let end = line.rfind(['^', '~'])?;
+    Some((start as u32 + 1, end as u32 + 1))
+}
+
+/// Parse `file:line:column: severity: message [-Wflag]`.
+fn parse_header(line: &str) -> Option<CompilerDiagnostic> {
+    // File names may contain ':', so find the severity first and split the
+    // location from the right.
+    let (location, rest) = ["fatal error", "error", "warning", "note", "remark"]
+        .iter()
+        .filter_map(|severity| {
+            let marker = format!(": {}: ", severity);
+            line.find(&marker).map(|at| (&line[..at], &line[at + 2..]))
+        })
+        .min_by_key(|(location, _)| location.len())?;
+
+    let (severity, message) = rest.split_once(": ")?;
+    let severity = CompilerDiagnosticSeverity::parse(severity)?;
+    let location = parse_location(location)?;
+
+    let (message, flag) = match message.strip_suffix(']').and_then(|m| m.rsplit_once(" [")) {
+        Some((message, flag)) if flag.starts_with("-W") => (message, Some(flag.to_string())),
+        _ => (message, None),
+    };
+
+    Some(CompilerDiagnostic {
+        severity,
+        location: Some(location),
+        message: message.to_string(),
+        flag,
+        snippet: None,
+        highlight: None,
+        fix_its: Vec::new(),
+        notes: Vec::new(),
+        include_stack: Vec::new(),
+    })
+}
+
+fn parse_location(location: &str) -> Option<CompilerSourceLocation> {
+    let (rest, column) = location.rsplit_once(':')?;
+    let (file, line) = rest.rsplit_once(':')?;
+    Some(CompilerSourceLocation {
+        file: file.to_string(),
+        line: line.parse().ok()?,
+        column: column.parse().ok()?,
+    })
+}
+
+/// Parse `In file included from file:line:` (also the `from file:line:`
+/// continuation lines of a nested include).
+fn parse_include_line(line: &str) -> Option<CompilerSourceLocation> {
+    let rest = line
+        .strip_prefix("In file included from ")
+        .or_else(|| line.trim_start().strip_prefix("from "))?;
+    let rest = rest.strip_suffix(':').or_else(|| rest.strip_suffix(','))?;
+    let (file, line) = rest.rsplit_once(':')?;
+    Some(CompilerSourceLocation {
+        file: file.to_string(),
+        line: line.parse().ok()?,
+        column: 0,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UNDECLARED_IDENTIFIER: &str =
+        include_str!("../tests/fixtures/diagnostics/undeclared_identifier.log");
+    const WARNINGS: &str = include_str!("../tests/fixtures/diagnostics/warnings.log");
+    const INCLUDE_ERRORS: &str = include_str!("../tests/fixtures/diagnostics/include_errors.log");
+
+    fn location(file: &str, line: u32, column: u32) -> Option<CompilerSourceLocation> {
+        Some(CompilerSourceLocation {
+            file: file.to_string(),
+            line,
+            column,
+        })
+    }
+
+    #[test]
+    fn undeclared_identifier() {
+        let diagnostics = parse_compiler_diagnostics(UNDECLARED_IDENTIFIER);
+        assert_eq!(diagnostics.len(), 2);
+
+        let typo = &diagnostics[0];
+        assert_eq!(typo.severity, CompilerDiagnosticSeverity::Error);
+        assert_eq!(typo.location, location(PROGRAM_SOURCE, 12, 5));
+        assert_eq!(
+            typo.message,
+            "use of undeclared identifier 'colr'; did you mean 'color'?"
+        );
+        assert_eq!(typo.snippet.as_deref(), Some("    colr = in.color * 2.0;"));
+        assert_eq!(typo.highlight, Some((5, 8)));
+        assert_eq!(
+            typo.fix_its,
+            [CompilerFixIt {
+                column: 5,
+                text: "color".to_string()
+            }]
+        );
+        assert_eq!(typo.notes.len(), 1);
+        assert_eq!(typo.notes[0].message, "'color' declared here");
+        assert_eq!(typo.notes[0].location, location(PROGRAM_SOURCE, 10, 12));
+
+        let semicolon = &diagnostics[1];
+        assert!(semicolon.is_error());
+        assert_eq!(
+            semicolon.fix_its,
+            [CompilerFixIt {
+                column: 26,
+                text: ";".to_string()
+            }]
+        );
+        assert!(semicolon.notes.is_empty());
+    }
+
+    #[test]
+    fn warnings() {
+        let diagnostics = parse_compiler_diagnostics(WARNINGS);
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics.iter().all(|d| !d.is_error()));
+        assert_eq!(diagnostics[0].flag.as_deref(), Some("-Wunused-variable"));
+        assert_eq!(diagnostics[0].message, "unused variable 'scale'");
+        assert_eq!(diagnostics[1].flag.as_deref(), Some("-Wsign-compare"));
+        assert_eq!(diagnostics[1].highlight, Some((21, 29)));
+
+        let rendered = diagnostics[0].to_string();
+        assert_eq!(
+            rendered,
+            WARNINGS.lines().take(3).collect::<Vec<_>>().join("\n") + "\n"
+        );
+    }
+
+    #[test]
+    fn include_errors() {
+        let diagnostics = parse_compiler_diagnostics(INCLUDE_ERRORS);
+        assert_eq!(diagnostics.len(), 2);
+
+        let call = &diagnostics[0];
+        assert_eq!(
+            call.location,
+            location("/Users/ci/shaders/lighting.h", 14, 12)
+        );
+        assert_eq!(
+            call.include_stack,
+            [
+                location(PROGRAM_SOURCE, 3, 0).unwrap(),
+                location("/Users/ci/shaders/common.h", 2, 0).unwrap(),
+            ]
+        );
+        assert_eq!(call.notes.len(), 1);
+        assert_eq!(
+            call.notes[0].location,
+            location("/Users/ci/shaders/lighting.h", 5, 7)
+        );
+
+        let missing = &diagnostics[1];
+        assert_eq!(missing.severity, CompilerDiagnosticSeverity::Fatal);
+        assert!(missing.include_stack.is_empty());
+        assert_eq!(missing.highlight, Some((10, 20)));
+    }
+
+    #[test]
+    fn render_round_trip() {
+        for log in [UNDECLARED_IDENTIFIER, WARNINGS, INCLUDE_ERRORS] {
+            let diagnostics = parse_compiler_diagnostics(log);
+            let rendered = diagnostics
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<String>();
+            assert_eq!(parse_compiler_diagnostics(&rendered), diagnostics);
+        }
+    }
+
+    #[test]
+    fn remap_program_source() {
+        let mut diagnostics = parse_compiler_diagnostics(INCLUDE_ERRORS);
+        for diagnostic in &mut diagnostics {
+            diagnostic.remap_lines(&mut |line| match line {
+                1..=5 => Some(("main.metal".to_string(), line)),
+                _ => Some(("shadows.metal".to_string(), line - 5)),
+            });
+        }
+        assert_eq!(
+            diagnostics[0].include_stack[0],
+            location("main.metal", 3, 0).unwrap()
+        );
+        // Locations outside program_source are left alone.
+        assert_eq!(
+            diagnostics[0].include_stack[1].file,
+            "/Users/ci/shaders/common.h"
+        );
+        assert_eq!(diagnostics[0].notes[0].location.as_ref().unwrap().line, 5);
+        assert_eq!(diagnostics[1].location, location("shadows.metal", 4, 10));
+    }
+
+    #[test]
+    fn skip_chatter() {
+        assert!(parse_compiler_diagnostics("").is_empty());
+        assert!(
+            parse_compiler_diagnostics("Compilation failed: \n\n1 error generated.\n").is_empty()
+        );
+    }
+}
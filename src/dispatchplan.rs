@@ -0,0 +1,332 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::*;
+
+/// What a compute pipeline and device allow for threadgroup sizes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DispatchLimits {
+    pub thread_execution_width: NSUInteger,
+    pub max_total_threads_per_threadgroup: NSUInteger,
+    /// Whether `dispatch_threads` may be used, see
+    /// [`MTLFeatureSet::supports_non_uniform_threadgroup_size`].
+    pub non_uniform_threadgroups: bool,
+}
+
+impl DispatchLimits {
+    pub fn new(
+        thread_execution_width: NSUInteger,
+        max_total_threads_per_threadgroup: NSUInteger,
+        non_uniform_threadgroups: bool,
+    ) -> Self {
+        DispatchLimits {
+            thread_execution_width,
+            max_total_threads_per_threadgroup,
+            non_uniform_threadgroups,
+        }
+    }
+
+    pub fn from_pipeline(
+        pipeline: &ComputePipelineStateRef,
+        non_uniform_threadgroups: bool,
+    ) -> Self {
+        Self::new(
+            pipeline.thread_execution_width(),
+            pipeline.max_total_threads_per_threadgroup(),
+            non_uniform_threadgroups,
+        )
+    }
+
+    /// Pick a threadgroup size for `grid`.
+    ///
+    /// The width is the execution width, or for 1D grids the largest
+    /// multiple of it that fits. Height and depth fill the rest of the
+    /// thread budget. No dimension is larger than the grid needs.
+    pub fn threadgroup_size(&self, grid: MTLSize) -> MTLSize {
+        let simd = self.thread_execution_width.max(1);
+        let max_total = self.max_total_threads_per_threadgroup.max(1);
+        // Prefer whole SIMD groups when the budget allows it.
+        let budget = if max_total >= simd {
+            max_total - max_total % simd
+        } else {
+            max_total
+        };
+        let width = if grid.height <= 1 && grid.depth <= 1 {
+            budget.min(round_up(grid.width.max(1), simd))
+        } else {
+            simd.min(budget).min(grid.width.max(1))
+        };
+        let height = (budget / width).min(grid.height.max(1));
+        let depth = (budget / (width * height)).min(grid.depth.max(1));
+        MTLSize::new(width, height, depth)
+    }
+
+    /// Plan a dispatch covering exactly `grid` threads.
+    pub fn plan(&self, grid: MTLSize) -> DispatchPlan {
+        let threads_per_threadgroup = self.threadgroup_size(grid);
+        if self.non_uniform_threadgroups {
+            DispatchPlan::Threads {
+                threads_per_grid: grid,
+                threads_per_threadgroup,
+            }
+        } else {
+            DispatchPlan::ThreadGroups {
+                threadgroups_per_grid: MTLSize::new(
+                    grid.width.div_ceil(threads_per_threadgroup.width),
+                    grid.height.div_ceil(threads_per_threadgroup.height),
+                    grid.depth.div_ceil(threads_per_threadgroup.depth),
+                ),
+                threads_per_threadgroup,
+                grid_size: grid,
+            }
+        }
+    }
+
+    pub fn plan_1d(&self, width: NSUInteger) -> DispatchPlan {
+        self.plan(MTLSize::new(width, 1, 1))
+    }
+
+    pub fn plan_2d(&self, width: NSUInteger, height: NSUInteger) -> DispatchPlan {
+        self.plan(MTLSize::new(width, height, 1))
+    }
+
+    pub fn plan_3d(
+        &self,
+        width: NSUInteger,
+        height: NSUInteger,
+        depth: NSUInteger,
+    ) -> DispatchPlan {
+        self.plan(MTLSize::new(width, height, depth))
+    }
+}
+
+/// A compute dispatch chosen by [`DispatchLimits::plan`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum DispatchPlan {
+    /// `dispatch_threads`, with Metal trimming the edge threadgroups.
+    Threads {
+        threads_per_grid: MTLSize,
+        threads_per_threadgroup: MTLSize,
+    },
+    /// `dispatch_thread_groups` with whole threadgroups. When the grid isn't
+    /// a multiple of the threadgroup size the kernel must skip threads whose
+    /// position is outside `grid_size`; pass it to the kernel for that check.
+    ThreadGroups {
+        threadgroups_per_grid: MTLSize,
+        threads_per_threadgroup: MTLSize,
+        grid_size: MTLSize,
+    },
+}
+
+impl DispatchPlan {
+    pub fn threads_per_threadgroup(&self) -> MTLSize {
+        match *self {
+            DispatchPlan::Threads {
+                threads_per_threadgroup,
+                ..
+            }
+            | DispatchPlan::ThreadGroups {
+                threads_per_threadgroup,
+                ..
+            } => threads_per_threadgroup,
+        }
+    }
+
+    /// The threads the dispatch is meant to cover.
+    pub fn grid_size(&self) -> MTLSize {
+        match *self {
+            DispatchPlan::Threads {
+                threads_per_grid, ..
+            } => threads_per_grid,
+            DispatchPlan::ThreadGroups { grid_size, .. } => grid_size,
+        }
+    }
+
+    /// Whether the kernel launches threads outside [`grid_size`](Self::grid_size)
+    /// and has to bounds-check its thread position.
+    pub fn needs_bounds_check(&self) -> bool {
+        match *self {
+            DispatchPlan::Threads { .. } => false,
+            DispatchPlan::ThreadGroups {
+                threadgroups_per_grid: groups,
+                threads_per_threadgroup: size,
+                grid_size: grid,
+            } => {
+                groups.width * size.width != grid.width
+                    || groups.height * size.height != grid.height
+                    || groups.depth * size.depth != grid.depth
+            }
+        }
+    }
+
+    /// Whether the grid has no threads, in which case there is nothing to
+    /// dispatch.
+    pub fn is_empty(&self) -> bool {
+        let grid = self.grid_size();
+        grid.width == 0 || grid.height == 0 || grid.depth == 0
+    }
+
+    /// Encode the dispatch. Does nothing for an empty grid.
+    pub fn encode(&self, encoder: &ComputeCommandEncoderRef) {
+        if self.is_empty() {
+            return;
+        }
+        match *self {
+            DispatchPlan::Threads {
+                threads_per_grid,
+                threads_per_threadgroup,
+            } => encoder.dispatch_threads(threads_per_grid, threads_per_threadgroup),
+            DispatchPlan::ThreadGroups {
+                threadgroups_per_grid,
+                threads_per_threadgroup,
+                ..
+            } => encoder.dispatch_thread_groups(threadgroups_per_grid, threads_per_threadgroup),
+        }
+    }
+}
+
+fn round_up(value: NSUInteger, multiple: NSUInteger) -> NSUInteger {
+    value.div_ceil(multiple) * multiple
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_dimensional() {
+        let limits = DispatchLimits::new(32, 1024, false);
+        let plan = limits.plan_1d(1000);
+        assert_eq!(
+            plan,
+            DispatchPlan::ThreadGroups {
+                threadgroups_per_grid: MTLSize::new(1, 1, 1),
+                threads_per_threadgroup: MTLSize::new(1024, 1, 1),
+                grid_size: MTLSize::new(1000, 1, 1),
+            }
+        );
+        assert!(plan.needs_bounds_check());
+        assert_eq!(
+            limits.plan_1d(5000).threads_per_threadgroup(),
+            MTLSize::new(1024, 1, 1)
+        );
+        // Small grids still get a whole SIMD group.
+        assert_eq!(
+            limits.plan_1d(7).threads_per_threadgroup(),
+            MTLSize::new(32, 1, 1)
+        );
+        assert!(!limits.plan_1d(2048).needs_bounds_check());
+    }
+
+    #[test]
+    fn multi_dimensional() {
+        let limits = DispatchLimits::new(32, 1024, false);
+        let plan = limits.plan_2d(1920, 1080);
+        assert_eq!(plan.threads_per_threadgroup(), MTLSize::new(32, 32, 1));
+        assert!(plan.needs_bounds_check());
+        assert!(!limits.plan_2d(64, 64).needs_bounds_check());
+        // Narrow grids don't waste the width.
+        assert_eq!(
+            limits.plan_2d(4, 1000).threads_per_threadgroup(),
+            MTLSize::new(4, 256, 1)
+        );
+        assert_eq!(
+            limits.plan_3d(64, 4, 64).threads_per_threadgroup(),
+            MTLSize::new(32, 4, 8)
+        );
+    }
+
+    #[test]
+    fn non_uniform_threadgroups() {
+        let limits = DispatchLimits::new(32, 1000, true);
+        let plan = limits.plan_2d(100, 100);
+        assert_eq!(
+            plan,
+            DispatchPlan::Threads {
+                threads_per_grid: MTLSize::new(100, 100, 1),
+                // 1000 isn't a multiple of 32; height takes what's left.
+                threads_per_threadgroup: MTLSize::new(32, 31, 1),
+            }
+        );
+        assert!(!plan.needs_bounds_check());
+        assert_eq!(plan.grid_size(), MTLSize::new(100, 100, 1));
+    }
+
+    #[test]
+    fn small_budgets() {
+        // Fewer threads than a SIMD group.
+        let limits = DispatchLimits::new(32, 16, false);
+        assert_eq!(
+            limits.plan_1d(100).threads_per_threadgroup(),
+            MTLSize::new(16, 1, 1)
+        );
+        // Zero limits are treated as one.
+        let limits = DispatchLimits::new(0, 0, false);
+        assert_eq!(
+            limits.plan_3d(5, 5, 5).threads_per_threadgroup(),
+            MTLSize::new(1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn empty_grids() {
+        let limits = DispatchLimits::new(32, 1024, false);
+        for plan in [
+            limits.plan_1d(0),
+            limits.plan_2d(16, 0),
+            limits.plan_3d(16, 16, 0),
+        ] {
+            assert!(plan.is_empty());
+            assert!(plan.threads_per_threadgroup().width >= 1);
+        }
+        assert!(!limits.plan_1d(1).is_empty());
+        assert!(DispatchLimits::new(32, 1024, true).plan_1d(0).is_empty());
+    }
+
+    #[test]
+    fn plans_cover_the_grid() {
+        let sizes = [1, 2, 3, 7, 31, 32, 33, 100, 1000];
+        for limits in [
+            DispatchLimits::new(32, 1024, false),
+            DispatchLimits::new(64, 896, false),
+            DispatchLimits::new(16, 24, false),
+        ] {
+            for &width in &sizes {
+                for &height in &sizes {
+                    for &depth in &[1, 3, 64] {
+                        let grid = MTLSize::new(width, height, depth);
+                        let plan = limits.plan(grid);
+                        let size = plan.threads_per_threadgroup();
+                        let DispatchPlan::ThreadGroups {
+                            threadgroups_per_grid: groups,
+                            ..
+                        } = plan
+                        else {
+                            panic!("uniform limits planned {:?}", plan);
+                        };
+                        assert!(
+                            size.width * size.height * size.depth
+                                <= limits.max_total_threads_per_threadgroup,
+                            "{:?} for {:?}",
+                            size,
+                            grid
+                        );
+                        assert!(size.width <= round_up(width, limits.thread_execution_width));
+                        assert!(size.height <= height && size.depth <= depth);
+                        assert!(groups.width * size.width >= width);
+                        assert!(groups.height * size.height >= height);
+                        assert!(groups.depth * size.depth >= depth);
+                        // Never a whole threadgroup past the edge.
+                        assert!((groups.width - 1) * size.width < width);
+                        assert!((groups.height - 1) * size.height < height);
+                        assert!((groups.depth - 1) * size.depth < depth);
+                    }
+                }
+            }
+        }
+    }
+}
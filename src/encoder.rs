@@ -92,7 +92,7 @@ bitflags::bitflags! {
 
 /// See <https://developer.apple.com/documentation/metal/mtlscissorrect>
 #[repr(C)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct MTLScissorRect {
     pub x: NSUInteger,
     pub y: NSUInteger,
@@ -102,7 +102,7 @@ pub struct MTLScissorRect {
 
 /// See <https://developer.apple.com/documentation/metal/mtlviewport>
 #[repr(C)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct MTLViewport {
     pub originX: f64,
     pub originY: f64,
@@ -133,6 +133,68 @@ pub struct MTLDrawIndexedPrimitivesIndirectArguments {
     pub baseInstance: u32,
 }
 
+/// Also used for indexed patch draws; the patch index buffer supplies the
+/// indices.
+///
+/// See <https://developer.apple.com/documentation/metal/mtldrawpatchindirectarguments>
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct MTLDrawPatchIndirectArguments {
+    pub patchCount: u32,
+    pub instanceCount: u32,
+    pub patchStart: u32,
+    pub baseInstance: u32,
+}
+
+/// Used by `dispatch_thread_groups_indirect` and
+/// `draw_mesh_threadgroups_with_indirect_buffer`.
+///
+/// See <https://developer.apple.com/documentation/metal/mtldispatchthreadgroupsindirectarguments>
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct MTLDispatchThreadgroupsIndirectArguments {
+    pub threadgroupsPerGrid: [u32; 3],
+}
+
+/// See <https://developer.apple.com/documentation/metal/mtlstageinregionindirectarguments>
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct MTLStageInRegionIndirectArguments {
+    pub stageInOrigin: [u32; 3],
+    pub stageInSize: [u32; 3],
+}
+
+/// See <https://developer.apple.com/documentation/metal/mtlmapindirectarguments>
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct MTLMapIndirectArguments {
+    pub regionOriginX: u32,
+    pub regionOriginY: u32,
+    pub regionOriginZ: u32,
+    pub regionSizeWidth: u32,
+    pub regionSizeHeight: u32,
+    pub regionSizeDepth: u32,
+    pub mipMapLevel: u32,
+    pub sliceId: u32,
+}
+
+// The GPU reads these layouts; keep them in sync with MTLTypes.h.
+const _: () = {
+    use std::mem::{align_of, size_of};
+    assert!(size_of::<MTLDrawPrimitivesIndirectArguments>() == 16);
+    assert!(size_of::<MTLDrawIndexedPrimitivesIndirectArguments>() == 20);
+    assert!(size_of::<MTLDrawPatchIndirectArguments>() == 16);
+    assert!(size_of::<MTLDispatchThreadgroupsIndirectArguments>() == 12);
+    assert!(size_of::<MTLStageInRegionIndirectArguments>() == 24);
+    assert!(size_of::<MTLMapIndirectArguments>() == 32);
+    assert!(align_of::<MTLDrawPrimitivesIndirectArguments>() == 4);
+    assert!(align_of::<MTLDrawIndexedPrimitivesIndirectArguments>() == 4);
+    assert!(align_of::<MTLDrawPatchIndirectArguments>() == 4);
+    assert!(align_of::<MTLDispatchThreadgroupsIndirectArguments>() == 4);
+    assert!(align_of::<MTLStageInRegionIndirectArguments>() == 4);
+    assert!(align_of::<MTLMapIndirectArguments>() == 4);
+};
+
 /// See <https://developer.apple.com/documentation/metal/mtlvertexamplificationviewmapping>
 #[repr(C)]
 #[derive(Copy, Clone, Debug)]
@@ -317,6 +379,23 @@ impl RenderCommandEncoderRef {
         }
     }
 
+    /// Safe [`set_vertex_bytes`](Self::set_vertex_bytes) for one value. Values over
+    /// [`MAX_INLINE_BYTES`] fail to compile.
+    pub fn set_vertex_value<T: Pod>(&self, index: NSUInteger, value: &T) {
+        let (length, bytes) = crate::inline_value_bytes(value);
+        self.set_vertex_bytes(index, length, bytes)
+    }
+
+    /// Safe [`set_vertex_bytes`](Self::set_vertex_bytes) for a slice.
+    ///
+    /// # Panics
+    ///
+    /// If `values` is empty or larger than [`MAX_INLINE_BYTES`].
+    pub fn set_vertex_slice<T: Pod>(&self, index: NSUInteger, values: &[T]) {
+        let (length, bytes) = crate::inline_slice_bytes(values);
+        self.set_vertex_bytes(index, length, bytes)
+    }
+
     pub fn set_vertex_buffer(
         &self,
         index: NSUInteger,
@@ -524,6 +603,23 @@ impl RenderCommandEncoderRef {
         }
     }
 
+    /// Safe [`set_object_bytes`](Self::set_object_bytes) for one value. Values over
+    /// [`MAX_INLINE_BYTES`] fail to compile.
+    pub fn set_object_value<T: Pod>(&self, index: NSUInteger, value: &T) {
+        let (length, bytes) = crate::inline_value_bytes(value);
+        self.set_object_bytes(index, length, bytes)
+    }
+
+    /// Safe [`set_object_bytes`](Self::set_object_bytes) for a slice.
+    ///
+    /// # Panics
+    ///
+    /// If `values` is empty or larger than [`MAX_INLINE_BYTES`].
+    pub fn set_object_slice<T: Pod>(&self, index: NSUInteger, values: &[T]) {
+        let (length, bytes) = crate::inline_slice_bytes(values);
+        self.set_object_bytes(index, length, bytes)
+    }
+
     /// Only available in (macos(13.0), ios(16.0))
     pub fn set_object_sampler_state(&self, index: NSUInteger, sampler: Option<&SamplerStateRef>) {
         unsafe {
@@ -665,6 +761,23 @@ impl RenderCommandEncoderRef {
         }
     }
 
+    /// Safe [`set_mesh_bytes`](Self::set_mesh_bytes) for one value. Values over
+    /// [`MAX_INLINE_BYTES`] fail to compile.
+    pub fn set_mesh_value<T: Pod>(&self, index: NSUInteger, value: &T) {
+        let (length, bytes) = crate::inline_value_bytes(value);
+        self.set_mesh_bytes(index, length, bytes)
+    }
+
+    /// Safe [`set_mesh_bytes`](Self::set_mesh_bytes) for a slice.
+    ///
+    /// # Panics
+    ///
+    /// If `values` is empty or larger than [`MAX_INLINE_BYTES`].
+    pub fn set_mesh_slice<T: Pod>(&self, index: NSUInteger, values: &[T]) {
+        let (length, bytes) = crate::inline_slice_bytes(values);
+        self.set_mesh_bytes(index, length, bytes)
+    }
+
     /// Only available in (macos(13.0), ios(16.0))
     pub fn set_mesh_sampler_state(&self, index: NSUInteger, sampler: Option<&SamplerStateRef>) {
         unsafe {
@@ -769,6 +882,23 @@ impl RenderCommandEncoderRef {
         }
     }
 
+    /// Safe [`set_fragment_bytes`](Self::set_fragment_bytes) for one value. Values over
+    /// [`MAX_INLINE_BYTES`] fail to compile.
+    pub fn set_fragment_value<T: Pod>(&self, index: NSUInteger, value: &T) {
+        let (length, bytes) = crate::inline_value_bytes(value);
+        self.set_fragment_bytes(index, length, bytes)
+    }
+
+    /// Safe [`set_fragment_bytes`](Self::set_fragment_bytes) for a slice.
+    ///
+    /// # Panics
+    ///
+    /// If `values` is empty or larger than [`MAX_INLINE_BYTES`].
+    pub fn set_fragment_slice<T: Pod>(&self, index: NSUInteger, values: &[T]) {
+        let (length, bytes) = crate::inline_slice_bytes(values);
+        self.set_fragment_bytes(index, length, bytes)
+    }
+
     pub fn set_fragment_buffer(
         &self,
         index: NSUInteger,
@@ -1575,6 +1705,10 @@ impl ComputeCommandEncoderRef {
         unsafe { msg_send![self, setBuffer:buffer offset:offset atIndex:index] }
     }
 
+    pub fn set_buffer_offset(&self, index: NSUInteger, offset: NSUInteger) {
+        unsafe { msg_send![self, setBufferOffset:offset atIndex:index] }
+    }
+
     pub fn set_buffers(
         &self,
         start_index: NSUInteger,
@@ -1662,6 +1796,23 @@ impl ComputeCommandEncoderRef {
         }
     }
 
+    /// Safe [`set_bytes`](Self::set_bytes) for one value. Values over
+    /// [`MAX_INLINE_BYTES`] fail to compile.
+    pub fn set_value<T: Pod>(&self, index: NSUInteger, value: &T) {
+        let (length, bytes) = crate::inline_value_bytes(value);
+        self.set_bytes(index, length, bytes)
+    }
+
+    /// Safe [`set_bytes`](Self::set_bytes) for a slice.
+    ///
+    /// # Panics
+    ///
+    /// If `values` is empty or larger than [`MAX_INLINE_BYTES`].
+    pub fn set_slice<T: Pod>(&self, index: NSUInteger, values: &[T]) {
+        let (length, bytes) = crate::inline_slice_bytes(values);
+        self.set_bytes(index, length, bytes)
+    }
+
     pub fn set_visible_function_table(
         &self,
         buffer_index: NSUInteger,
@@ -0,0 +1,386 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::*;
+
+/// See <https://developer.apple.com/documentation/metal/mtllibraryerrordomain>
+pub const MTLLibraryErrorDomain: &str = "MTLLibraryErrorDomain";
+/// See <https://developer.apple.com/documentation/metal/mtldynamiclibrarydomain>
+pub const MTLDynamicLibraryDomain: &str = "MTLDynamicLibraryDomain";
+/// See <https://developer.apple.com/documentation/metal/mtlbinaryarchivedomain>
+pub const MTLBinaryArchiveDomain: &str = "MTLBinaryArchiveDomain";
+/// See <https://developer.apple.com/documentation/metal/mtlcaptureerrordomain>
+pub const MTLCaptureErrorDomain: &str = "MTLCaptureErrorDomain";
+/// The domain of errors these bindings report themselves, before calling
+/// into Metal, such as a descriptor value that can't be converted.
+pub const MetalRsErrorDomain: &str = "MetalRsErrorDomain";
+
+/// The error code of a [`MetalError`], mapped to the Metal enum of its domain.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum MetalErrorKind {
+    Library(MTLLibraryError),
+    DynamicLibrary(MTLDynamicLibraryError),
+    BinaryArchive(MTLBinaryArchiveError),
+    Capture(MTLCaptureError),
+    /// An unknown domain, or a code the bindings don't know about.
+    Other,
+}
+
+/// An error reported by a Metal API, usually converted from the `NSError`
+/// it returned.
+///
+/// See <https://developer.apple.com/documentation/foundation/nserror>
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MetalError {
+    domain: String,
+    code: NSInteger,
+    localized_description: String,
+    user_info: Vec<(String, String)>,
+}
+
+impl MetalError {
+    pub fn new(domain: &str, code: NSInteger, localized_description: &str) -> Self {
+        MetalError {
+            domain: domain.to_string(),
+            code,
+            localized_description: localized_description.to_string(),
+            user_info: Vec::new(),
+        }
+    }
+
+    /// Copy the contents of an `NSError`.
+    ///
+    /// # Safety
+    ///
+    /// `error` must point to a valid `NSError`.
+    pub unsafe fn from_nserror(error: *mut Object) -> Self {
+        let domain: *mut Object = msg_send![error, domain];
+        let code: NSInteger = msg_send![error, code];
+        let description: *mut Object = msg_send![error, localizedDescription];
+
+        let user_info: *mut Object = msg_send![error, userInfo];
+        let user_info = if user_info.is_null() {
+            Vec::new()
+        } else {
+            let keys: *mut Object = msg_send![user_info, allKeys];
+            let count: NSUInteger = msg_send![keys, count];
+            (0..count)
+                .map(|i| {
+                    let key: *mut Object = msg_send![keys, objectAtIndex: i];
+                    let value: *mut Object = msg_send![user_info, objectForKey: key];
+                    let value: *mut Object = if value.is_null() {
+                        value
+                    } else {
+                        msg_send![value, description]
+                    };
+                    (nsstring_to_string(key), nsstring_to_string(value))
+                })
+                .collect()
+        };
+
+        MetalError {
+            domain: nsstring_to_string(domain),
+            code,
+            localized_description: nsstring_to_string(description),
+            user_info,
+        }
+    }
+
+    /// The error `newFunctionWithName` failures are reported as, since that
+    /// method returns `nil` without an `NSError`.
+    pub(crate) fn function_not_found(name: &str) -> Self {
+        MetalError::new(
+            MTLLibraryErrorDomain,
+            MTLLibraryError::FunctionNotFound as NSInteger,
+            &format!("Function '{}' does not exist", name),
+        )
+    }
+
+    /// An error in [`MetalRsErrorDomain`] with code 0.
+    pub(crate) fn invalid_argument(description: &str) -> Self {
+        MetalError::new(MetalRsErrorDomain, 0, description)
+    }
+
+    pub fn domain(&self) -> &str {
+        &self.domain
+    }
+
+    pub fn code(&self) -> NSInteger {
+        self.code
+    }
+
+    pub fn localized_description(&self) -> &str {
+        &self.localized_description
+    }
+
+    /// The entries of the `userInfo` dictionary, with values converted
+    /// through their `description`.
+    pub fn user_info(&self) -> &[(String, String)] {
+        &self.user_info
+    }
+
+    pub fn user_info_value(&self, key: &str) -> Option<&str> {
+        self.user_info
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn kind(&self) -> MetalErrorKind {
+        let code = self.code as u64;
+        let kind = match self.domain.as_str() {
+            MTLLibraryErrorDomain => MTLLibraryError::from_code(code).map(MetalErrorKind::Library),
+            MTLDynamicLibraryDomain => {
+                MTLDynamicLibraryError::from_code(code).map(MetalErrorKind::DynamicLibrary)
+            }
+            MTLBinaryArchiveDomain => {
+                MTLBinaryArchiveError::from_code(code).map(MetalErrorKind::BinaryArchive)
+            }
+            MTLCaptureErrorDomain => MTLCaptureError::from_code(code).map(MetalErrorKind::Capture),
+            _ => None,
+        };
+        kind.unwrap_or(MetalErrorKind::Other)
+    }
+
+    pub fn library_error(&self) -> Option<MTLLibraryError> {
+        match self.kind() {
+            MetalErrorKind::Library(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    pub fn dynamic_library_error(&self) -> Option<MTLDynamicLibraryError> {
+        match self.kind() {
+            MetalErrorKind::DynamicLibrary(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for MetalError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.localized_description)
+    }
+}
+
+impl std::error::Error for MetalError {}
+
+/// Eases migration from the `Result<_, String>` signatures these APIs used to
+/// have.
+impl From<MetalError> for String {
+    fn from(error: MetalError) -> String {
+        error.localized_description
+    }
+}
+
+/// Copy an `NSString` that may be `nil`, which becomes an empty string.
+unsafe fn nsstring_to_string(string: *mut Object) -> String {
+    if string.is_null() {
+        String::new()
+    } else {
+        crate::nsstring_as_str(&*string).to_string()
+    }
+}
+
+macro_rules! error_from_code {
+    ($ty:ident { $($variant:ident),* $(,)? }) => {
+        impl $ty {
+            fn from_code(code: u64) -> Option<Self> {
+                $(
+                    if code == $ty::$variant as u64 {
+                        return Some($ty::$variant);
+                    }
+                )*
+                None
+            }
+        }
+    };
+}
+
+error_from_code!(MTLLibraryError {
+    Unsupported,
+    Internal,
+    CompileFailure,
+    CompileWarning,
+    FunctionNotFound,
+    FileNotFound,
+});
+
+error_from_code!(MTLDynamicLibraryError {
+    None,
+    InvalidFile,
+    CompilationFailure,
+    UnresolvedInstallName,
+    DependencyLoadFailure,
+    Unsupported,
+});
+
+error_from_code!(MTLBinaryArchiveError {
+    None,
+    InvalidFile,
+    UnexpectedElement,
+    CompilationFailure,
+    InternalError,
+});
+
+error_from_code!(MTLCaptureError {
+    NotSupported,
+    AlreadyCapturing,
+    InvalidDescriptor,
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kinds() {
+        use MetalErrorKind::*;
+        let table: &[(&str, NSInteger, MetalErrorKind)] = &[
+            (
+                MTLLibraryErrorDomain,
+                1,
+                Library(MTLLibraryError::Unsupported),
+            ),
+            (MTLLibraryErrorDomain, 2, Library(MTLLibraryError::Internal)),
+            (
+                MTLLibraryErrorDomain,
+                3,
+                Library(MTLLibraryError::CompileFailure),
+            ),
+            (
+                MTLLibraryErrorDomain,
+                4,
+                Library(MTLLibraryError::CompileWarning),
+            ),
+            (
+                MTLLibraryErrorDomain,
+                5,
+                Library(MTLLibraryError::FunctionNotFound),
+            ),
+            (
+                MTLLibraryErrorDomain,
+                6,
+                Library(MTLLibraryError::FileNotFound),
+            ),
+            (
+                MTLDynamicLibraryDomain,
+                0,
+                DynamicLibrary(MTLDynamicLibraryError::None),
+            ),
+            (
+                MTLDynamicLibraryDomain,
+                1,
+                DynamicLibrary(MTLDynamicLibraryError::InvalidFile),
+            ),
+            (
+                MTLDynamicLibraryDomain,
+                2,
+                DynamicLibrary(MTLDynamicLibraryError::CompilationFailure),
+            ),
+            (
+                MTLDynamicLibraryDomain,
+                3,
+                DynamicLibrary(MTLDynamicLibraryError::UnresolvedInstallName),
+            ),
+            (
+                MTLDynamicLibraryDomain,
+                4,
+                DynamicLibrary(MTLDynamicLibraryError::DependencyLoadFailure),
+            ),
+            (
+                MTLDynamicLibraryDomain,
+                5,
+                DynamicLibrary(MTLDynamicLibraryError::Unsupported),
+            ),
+            (
+                MTLBinaryArchiveDomain,
+                0,
+                BinaryArchive(MTLBinaryArchiveError::None),
+            ),
+            (
+                MTLBinaryArchiveDomain,
+                1,
+                BinaryArchive(MTLBinaryArchiveError::InvalidFile),
+            ),
+            (
+                MTLBinaryArchiveDomain,
+                2,
+                BinaryArchive(MTLBinaryArchiveError::UnexpectedElement),
+            ),
+            (
+                MTLBinaryArchiveDomain,
+                3,
+                BinaryArchive(MTLBinaryArchiveError::CompilationFailure),
+            ),
+            (
+                MTLBinaryArchiveDomain,
+                4,
+                BinaryArchive(MTLBinaryArchiveError::InternalError),
+            ),
+            (
+                MTLCaptureErrorDomain,
+                1,
+                Capture(MTLCaptureError::NotSupported),
+            ),
+            (
+                MTLCaptureErrorDomain,
+                2,
+                Capture(MTLCaptureError::AlreadyCapturing),
+            ),
+            (
+                MTLCaptureErrorDomain,
+                3,
+                Capture(MTLCaptureError::InvalidDescriptor),
+            ),
+            // Codes the bindings don't know about.
+            (MTLLibraryErrorDomain, 0, Other),
+            (MTLLibraryErrorDomain, 7, Other),
+            (MTLLibraryErrorDomain, -5, Other),
+            (MTLDynamicLibraryDomain, 6, Other),
+            (MTLBinaryArchiveDomain, 5, Other),
+            (MTLCaptureErrorDomain, 0, Other),
+            (MTLCaptureErrorDomain, NSInteger::MAX, Other),
+            // Unknown domains, including ones that only differ in case.
+            ("NSCocoaErrorDomain", 5, Other),
+            ("mtllibraryerrordomain", 5, Other),
+            ("", 1, Other),
+            (MetalRsErrorDomain, 0, Other),
+        ];
+        for &(domain, code, kind) in table {
+            let error = MetalError::new(domain, code, "description");
+            assert_eq!(error.kind(), kind, "{} {}", domain, code);
+            let library = match kind {
+                Library(error) => Some(error),
+                _ => None,
+            };
+            assert_eq!(error.library_error(), library);
+            let dynamic_library = match kind {
+                DynamicLibrary(error) => Some(error),
+                _ => None,
+            };
+            assert_eq!(error.dynamic_library_error(), dynamic_library);
+        }
+    }
+
+    #[test]
+    fn bindings_errors() {
+        let error = MetalError::function_not_found("main0");
+        assert_eq!(
+            error.library_error(),
+            Some(MTLLibraryError::FunctionNotFound)
+        );
+        assert_eq!(error.to_string(), "Function 'main0' does not exist");
+
+        let error = MetalError::invalid_argument("bad value");
+        assert_eq!((error.domain(), error.code()), (MetalRsErrorDomain, 0));
+        assert_eq!(error.kind(), MetalErrorKind::Other);
+        assert_eq!(error.user_info(), []);
+        assert_eq!(error.user_info_value("key"), None);
+        assert_eq!(String::from(error), "bad value");
+    }
+}
@@ -270,6 +270,26 @@ impl HeapDescriptorRef {
         unsafe { msg_send![self, resourceOptions] }
     }
 
+    /// Only available on macos(10.15), ios(13.0)
+    pub fn set_resource_options(&self, options: MTLResourceOptions) {
+        unsafe { msg_send![self, setResourceOptions: options] }
+    }
+
+    /// The storage, CPU cache and hazard tracking modes together.
+    pub fn resource_modes(&self) -> ResourceModes {
+        ResourceModes::new(
+            self.storage_mode(),
+            self.cpu_cache_mode(),
+            self.hazard_tracking_mode(),
+        )
+    }
+
+    pub fn set_resource_modes(&self, modes: ResourceModes) {
+        self.set_storage_mode(modes.storage_mode);
+        self.set_cpu_cache_mode(modes.cpu_cache_mode);
+        self.set_hazard_tracking_mode(modes.hazard_tracking_mode);
+    }
+
     /// Only available on macos(10.15), ios(13.0)
     pub fn heap_type(&self) -> MTLHeapType {
         unsafe { msg_send![self, type] }
@@ -0,0 +1,936 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::*;
+
+const SL_BITS: u32 = 4;
+const SL_COUNT: usize = 1 << SL_BITS;
+const FL_COUNT: usize = (NSUInteger::BITS - SL_BITS + 1) as usize;
+const NONE: u32 = u32::MAX;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum BlockState {
+    Free,
+    Used {
+        align: NSUInteger,
+    },
+    /// The arena slot holds no block.
+    Unused,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Block {
+    offset: NSUInteger,
+    size: NSUInteger,
+    state: BlockState,
+    prev_phys: u32,
+    next_phys: u32,
+    prev_free: u32,
+    next_free: u32,
+}
+
+/// Usage of a [`TlsfAllocator`] or of all heaps in a
+/// [`HeapSuballocator`].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct HeapAllocatorStats {
+    pub size: NSUInteger,
+    pub used: NSUInteger,
+    pub allocations: usize,
+    pub free_blocks: usize,
+    pub largest_free_block: NSUInteger,
+}
+
+impl HeapAllocatorStats {
+    pub fn free(&self) -> NSUInteger {
+        self.size - self.used
+    }
+
+    /// How much of the free space is unusable for one large allocation: 0
+    /// when it is all one block, approaching 1 as it splinters.
+    pub fn fragmentation(&self) -> f32 {
+        match self.free() {
+            0 => 0.0,
+            free => 1.0 - self.largest_free_block as f32 / free as f32,
+        }
+    }
+}
+
+/// A range handed out by [`TlsfAllocator::allocate`]. Give it back with
+/// [`TlsfAllocator::free`].
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct TlsfAllocation {
+    offset: NSUInteger,
+    size: NSUInteger,
+    block: u32,
+}
+
+impl TlsfAllocation {
+    pub fn offset(&self) -> NSUInteger {
+        self.offset
+    }
+
+    pub fn size(&self) -> NSUInteger {
+        self.size
+    }
+}
+
+/// Two-level segregated fit allocator for offsets in a range of `size`
+/// bytes. Allocation and freeing are constant time; the memory itself is
+/// never touched, so it can manage a placement heap.
+#[derive(Clone, Debug)]
+pub struct TlsfAllocator {
+    size: NSUInteger,
+    blocks: Vec<Block>,
+    unused: Vec<u32>,
+    fl_bitmap: u64,
+    sl_bitmaps: [u32; FL_COUNT],
+    heads: [[u32; SL_COUNT]; FL_COUNT],
+    used: NSUInteger,
+    allocations: usize,
+    free_blocks: usize,
+}
+
+impl TlsfAllocator {
+    pub fn new(size: NSUInteger) -> Self {
+        let mut allocator = TlsfAllocator {
+            size,
+            blocks: Vec::new(),
+            unused: Vec::new(),
+            fl_bitmap: 0,
+            sl_bitmaps: [0; FL_COUNT],
+            heads: [[NONE; SL_COUNT]; FL_COUNT],
+            used: 0,
+            allocations: 0,
+            free_blocks: 0,
+        };
+        if size > 0 {
+            let block = allocator.new_block(0, size, NONE, NONE);
+            allocator.insert_free(block);
+        }
+        allocator
+    }
+
+    pub fn size(&self) -> NSUInteger {
+        self.size
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.allocations == 0
+    }
+
+    /// Allocate `size` bytes at a multiple of `align`, which must be a power
+    /// of two.
+    pub fn allocate(&mut self, size: NSUInteger, align: NSUInteger) -> Option<TlsfAllocation> {
+        let size = size.max(1);
+        let align = align.max(1);
+        assert!(align.is_power_of_two(), "alignment must be a power of two");
+
+        // Any block in the class of `search` fits regardless of where it
+        // starts; otherwise fall back to checking the smaller classes.
+        let found = size
+            .checked_add(align - 1)
+            .and_then(round_up_to_class)
+            .and_then(|search| self.find_list(mapping(search)))
+            .map(|(fl, sl)| self.heads[fl][sl])
+            .or_else(|| self.scan_fit(size, align))?;
+
+        self.remove_free(found);
+        let block = self.blocks[found as usize];
+        let aligned = block.offset.next_multiple_of(align);
+        let front = aligned - block.offset;
+        if front > 0 {
+            let front_block = self.new_block(block.offset, front, block.prev_phys, found);
+            if block.prev_phys != NONE {
+                self.blocks[block.prev_phys as usize].next_phys = front_block;
+            }
+            let current = &mut self.blocks[found as usize];
+            current.prev_phys = front_block;
+            current.offset = aligned;
+            current.size -= front;
+            self.insert_free(front_block);
+        }
+        let remaining = self.blocks[found as usize].size - size;
+        if remaining > 0 {
+            let next_phys = self.blocks[found as usize].next_phys;
+            let tail = self.new_block(aligned + size, remaining, found, next_phys);
+            if next_phys != NONE {
+                self.blocks[next_phys as usize].prev_phys = tail;
+            }
+            let current = &mut self.blocks[found as usize];
+            current.next_phys = tail;
+            current.size = size;
+            self.insert_free(tail);
+        }
+        self.blocks[found as usize].state = BlockState::Used { align };
+        self.used += size;
+        self.allocations += 1;
+        Some(TlsfAllocation {
+            offset: aligned,
+            size,
+            block: found,
+        })
+    }
+
+    /// # Panics
+    ///
+    /// If `allocation` did not come from this allocator.
+    pub fn free(&mut self, allocation: TlsfAllocation) {
+        let mut index = allocation.block;
+        let block = self.blocks.get(index as usize).copied();
+        assert!(
+            block.is_some_and(|block| matches!(block.state, BlockState::Used { .. })
+                && block.offset == allocation.offset
+                && block.size == allocation.size),
+            "allocation does not belong to this allocator"
+        );
+        self.used -= allocation.size;
+        self.allocations -= 1;
+        self.blocks[index as usize].state = BlockState::Free;
+
+        let next = self.blocks[index as usize].next_phys;
+        if next != NONE && self.blocks[next as usize].state == BlockState::Free {
+            self.remove_free(next);
+            self.merge_into_prev(next);
+        }
+        let prev = self.blocks[index as usize].prev_phys;
+        if prev != NONE && self.blocks[prev as usize].state == BlockState::Free {
+            self.remove_free(prev);
+            self.merge_into_prev(index);
+            index = prev;
+        }
+        self.insert_free(index);
+    }
+
+    pub fn stats(&self) -> HeapAllocatorStats {
+        HeapAllocatorStats {
+            size: self.size,
+            used: self.used,
+            allocations: self.allocations,
+            free_blocks: self.free_blocks,
+            largest_free_block: self.largest_free_block(),
+        }
+    }
+
+    /// The live allocations as `(offset, size, align)`, by offset.
+    pub fn allocations(&self) -> Vec<(NSUInteger, NSUInteger, NSUInteger)> {
+        let mut allocations = self
+            .blocks
+            .iter()
+            .filter_map(|block| match block.state {
+                BlockState::Used { align } => Some((block.offset, block.size, align)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        allocations.sort_unstable();
+        allocations
+    }
+
+    fn largest_free_block(&self) -> NSUInteger {
+        if self.fl_bitmap == 0 {
+            return 0;
+        }
+        let fl = (63 - self.fl_bitmap.leading_zeros()) as usize;
+        let sl = (31 - self.sl_bitmaps[fl].leading_zeros()) as usize;
+        let mut largest = 0;
+        let mut index = self.heads[fl][sl];
+        while index != NONE {
+            largest = largest.max(self.blocks[index as usize].size);
+            index = self.blocks[index as usize].next_free;
+        }
+        largest
+    }
+
+    fn new_block(
+        &mut self,
+        offset: NSUInteger,
+        size: NSUInteger,
+        prev_phys: u32,
+        next_phys: u32,
+    ) -> u32 {
+        let block = Block {
+            offset,
+            size,
+            state: BlockState::Free,
+            prev_phys,
+            next_phys,
+            prev_free: NONE,
+            next_free: NONE,
+        };
+        match self.unused.pop() {
+            Some(index) => {
+                self.blocks[index as usize] = block;
+                index
+            }
+            None => {
+                self.blocks.push(block);
+                (self.blocks.len() - 1) as u32
+            }
+        }
+    }
+
+    /// Fold block `index` into its physical predecessor and release its
+    /// slot. Neither may be in a free list.
+    fn merge_into_prev(&mut self, index: u32) {
+        let block = self.blocks[index as usize];
+        let prev = block.prev_phys;
+        self.blocks[prev as usize].size += block.size;
+        self.blocks[prev as usize].next_phys = block.next_phys;
+        if block.next_phys != NONE {
+            self.blocks[block.next_phys as usize].prev_phys = prev;
+        }
+        self.blocks[index as usize].state = BlockState::Unused;
+        self.unused.push(index);
+    }
+
+    fn insert_free(&mut self, index: u32) {
+        let (fl, sl) = mapping(self.blocks[index as usize].size);
+        let head = self.heads[fl][sl];
+        {
+            let block = &mut self.blocks[index as usize];
+            block.state = BlockState::Free;
+            block.prev_free = NONE;
+            block.next_free = head;
+        }
+        if head != NONE {
+            self.blocks[head as usize].prev_free = index;
+        }
+        self.heads[fl][sl] = index;
+        self.fl_bitmap |= 1 << fl;
+        self.sl_bitmaps[fl] |= 1 << sl;
+        self.free_blocks += 1;
+    }
+
+    fn remove_free(&mut self, index: u32) {
+        let block = self.blocks[index as usize];
+        let (fl, sl) = mapping(block.size);
+        if block.prev_free != NONE {
+            self.blocks[block.prev_free as usize].next_free = block.next_free;
+        } else {
+            self.heads[fl][sl] = block.next_free;
+        }
+        if block.next_free != NONE {
+            self.blocks[block.next_free as usize].prev_free = block.prev_free;
+        }
+        if self.heads[fl][sl] == NONE {
+            self.sl_bitmaps[fl] &= !(1 << sl);
+            if self.sl_bitmaps[fl] == 0 {
+                self.fl_bitmap &= !(1 << fl);
+            }
+        }
+        self.free_blocks -= 1;
+    }
+
+    /// The first non-empty free list at or above `(fl, sl)`.
+    fn find_list(&self, (fl, sl): (usize, usize)) -> Option<(usize, usize)> {
+        let sl_map = self.sl_bitmaps[fl] & (!0u32 << sl);
+        if sl_map != 0 {
+            return Some((fl, sl_map.trailing_zeros() as usize));
+        }
+        let fl_map = self.fl_bitmap & (!0u64).checked_shl(fl as u32 + 1).unwrap_or(0);
+        if fl_map == 0 {
+            return None;
+        }
+        let fl = fl_map.trailing_zeros() as usize;
+        Some((fl, self.sl_bitmaps[fl].trailing_zeros() as usize))
+    }
+
+    /// Check each free block that might fit once aligned.
+    fn scan_fit(&self, size: NSUInteger, align: NSUInteger) -> Option<u32> {
+        let mut list = self.find_list(mapping(size));
+        while let Some((fl, sl)) = list {
+            let mut index = self.heads[fl][sl];
+            while index != NONE {
+                let block = &self.blocks[index as usize];
+                let front = block.offset.next_multiple_of(align) - block.offset;
+                if block.size >= size && block.size - size >= front {
+                    return Some(index);
+                }
+                index = block.next_free;
+            }
+            list = if sl + 1 < SL_COUNT {
+                self.find_list((fl, sl + 1))
+            } else if fl + 1 < FL_COUNT {
+                self.find_list((fl + 1, 0))
+            } else {
+                None
+            };
+        }
+        None
+    }
+}
+
+/// The free list a block of `size` bytes belongs in.
+fn mapping(size: NSUInteger) -> (usize, usize) {
+    if size < SL_COUNT as NSUInteger {
+        (0, size as usize)
+    } else {
+        let fl = NSUInteger::BITS - 1 - size.leading_zeros();
+        (
+            (fl - SL_BITS + 1) as usize,
+            (size >> (fl - SL_BITS)) as usize - SL_COUNT,
+        )
+    }
+}
+
+/// Round `size` up so that every block in its free list is at least `size`.
+fn round_up_to_class(size: NSUInteger) -> Option<NSUInteger> {
+    if size < SL_COUNT as NSUInteger {
+        return Some(size);
+    }
+    let fl = NSUInteger::BITS - 1 - size.leading_zeros();
+    size.checked_add((1 << (fl - SL_BITS)) - 1)
+}
+
+/// A range in one of a [`HeapSuballocator`]'s heaps.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct HeapAllocation {
+    heap: usize,
+    allocation: TlsfAllocation,
+}
+
+impl HeapAllocation {
+    /// Index of the heap, for [`HeapSuballocator::heap`].
+    pub fn heap_index(&self) -> usize {
+        self.heap
+    }
+
+    pub fn offset(&self) -> NSUInteger {
+        self.allocation.offset
+    }
+
+    pub fn size(&self) -> NSUInteger {
+        self.allocation.size
+    }
+}
+
+/// A heap's share of a [`DefragmentationReport`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct HeapReport {
+    pub heap_index: usize,
+    pub stats: HeapAllocatorStats,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct DefragmentationReport {
+    pub heaps: Vec<HeapReport>,
+    /// Heaps whose allocations would all fit in the other heaps' free
+    /// space. Moving those allocations lets the heaps be released. The heaps
+    /// they move into are never listed, so each allocation moves once.
+    pub evacuable_heaps: Vec<usize>,
+    /// Bytes that would have to be copied to empty `evacuable_heaps`.
+    pub bytes_to_move: NSUInteger,
+}
+
+/// Sub-allocates from a growing set of placement heaps of type `H`.
+///
+/// Heaps are created on demand through a callback, which keeps the
+/// bookkeeping independent of a device. [`PlacementHeapAllocator`] pairs it
+/// with one.
+#[derive(Clone, Debug)]
+pub struct HeapSuballocator<H> {
+    heap_size: NSUInteger,
+    heaps: Vec<Option<(H, TlsfAllocator)>>,
+}
+
+impl<H> HeapSuballocator<H> {
+    /// New heaps are `heap_size` bytes, or larger for allocations that don't
+    /// fit.
+    pub fn new(heap_size: NSUInteger) -> Self {
+        HeapSuballocator {
+            heap_size,
+            heaps: Vec::new(),
+        }
+    }
+
+    pub fn heap_size(&self) -> NSUInteger {
+        self.heap_size
+    }
+
+    pub fn heap(&self, index: usize) -> Option<&H> {
+        self.heaps
+            .get(index)
+            .and_then(|heap| heap.as_ref())
+            .map(|(heap, _)| heap)
+    }
+
+    pub fn heaps(&self) -> impl Iterator<Item = (usize, &H)> {
+        self.heaps
+            .iter()
+            .enumerate()
+            .filter_map(|(index, heap)| heap.as_ref().map(|(heap, _)| (index, heap)))
+    }
+
+    /// Allocate from the first heap with room, creating one with
+    /// `new_heap(size)` if none has. Returns `None` if that fails.
+    pub fn allocate(
+        &mut self,
+        size_and_align: MTLSizeAndAlign,
+        new_heap: impl FnOnce(NSUInteger) -> Option<H>,
+    ) -> Option<HeapAllocation> {
+        let MTLSizeAndAlign { size, align } = size_and_align;
+        for (index, entry) in self.heaps.iter_mut().enumerate() {
+            if let Some((_, allocator)) = entry {
+                if let Some(allocation) = allocator.allocate(size, align) {
+                    return Some(HeapAllocation {
+                        heap: index,
+                        allocation,
+                    });
+                }
+            }
+        }
+
+        let heap_size = self.heap_size.max(size.next_multiple_of(align.max(1)));
+        let heap = new_heap(heap_size)?;
+        let mut allocator = TlsfAllocator::new(heap_size);
+        let allocation = allocator.allocate(size, align)?;
+        let entry = Some((heap, allocator));
+        let heap = match self.heaps.iter().position(Option::is_none) {
+            Some(index) => {
+                self.heaps[index] = entry;
+                index
+            }
+            None => {
+                self.heaps.push(entry);
+                self.heaps.len() - 1
+            }
+        };
+        Some(HeapAllocation { heap, allocation })
+    }
+
+    /// # Panics
+    ///
+    /// If `allocation` did not come from this allocator.
+    pub fn free(&mut self, allocation: HeapAllocation) {
+        match self.heaps.get_mut(allocation.heap) {
+            Some(Some((_, allocator))) => allocator.free(allocation.allocation),
+            _ => panic!("allocation does not belong to this allocator"),
+        }
+    }
+
+    /// Remove the heaps with no allocations left and return them.
+    pub fn release_empty_heaps(&mut self) -> Vec<H> {
+        let mut released = Vec::new();
+        for entry in &mut self.heaps {
+            if entry
+                .as_ref()
+                .is_some_and(|(_, allocator)| allocator.is_empty())
+            {
+                released.extend(entry.take().map(|(heap, _)| heap));
+            }
+        }
+        while let Some(None) = self.heaps.last() {
+            self.heaps.pop();
+        }
+        released
+    }
+
+    pub fn heap_stats(&self, index: usize) -> Option<HeapAllocatorStats> {
+        self.heaps
+            .get(index)
+            .and_then(|heap| heap.as_ref())
+            .map(|(_, allocator)| allocator.stats())
+    }
+
+    /// Totals over all heaps. `largest_free_block` is the largest in any
+    /// one heap.
+    pub fn stats(&self) -> HeapAllocatorStats {
+        self.allocators()
+            .map(|(_, allocator)| allocator.stats())
+            .fold(HeapAllocatorStats::default(), |total, stats| {
+                HeapAllocatorStats {
+                    size: total.size + stats.size,
+                    used: total.used + stats.used,
+                    allocations: total.allocations + stats.allocations,
+                    free_blocks: total.free_blocks + stats.free_blocks,
+                    largest_free_block: total.largest_free_block.max(stats.largest_free_block),
+                }
+            })
+    }
+
+    /// Per-heap stats, plus which heaps could be emptied by moving their
+    /// allocations into the others, trying the least used heaps first.
+    pub fn defragmentation_report(&self) -> DefragmentationReport {
+        let heaps = self
+            .allocators()
+            .map(|(heap_index, allocator)| HeapReport {
+                heap_index,
+                stats: allocator.stats(),
+            })
+            .collect::<Vec<_>>();
+
+        let mut candidates = heaps
+            .iter()
+            .filter(|report| report.stats.allocations > 0)
+            .map(|report| (report.stats.used, report.heap_index))
+            .collect::<Vec<_>>();
+        candidates.sort_unstable();
+
+        // Simulate the moves on copies of the allocators.
+        let mut simulated = self
+            .heaps
+            .iter()
+            .map(|heap| heap.as_ref().map(|(_, allocator)| allocator.clone()))
+            .collect::<Vec<_>>();
+        let mut evacuable_heaps = Vec::new();
+        // Heaps that took in moved allocations stay, so nothing moves twice.
+        let mut destinations = vec![false; self.heaps.len()];
+        let mut bytes_to_move = 0;
+        for (used, source) in candidates {
+            if destinations[source] {
+                continue;
+            }
+            let mut trial = simulated.clone();
+            let mut trial_destinations = destinations.clone();
+            let moved = self.heaps[source]
+                .as_ref()
+                .map_or(Vec::new(), |(_, allocator)| allocator.allocations())
+                .into_iter()
+                .all(|(_, size, align)| {
+                    trial.iter_mut().enumerate().any(|(index, allocator)| {
+                        let fits = index != source
+                            && !evacuable_heaps.contains(&index)
+                            && allocator
+                                .as_mut()
+                                .is_some_and(|allocator| allocator.allocate(size, align).is_some());
+                        if fits {
+                            trial_destinations[index] = true;
+                        }
+                        fits
+                    })
+                });
+            if moved {
+                simulated = trial;
+                destinations = trial_destinations;
+                evacuable_heaps.push(source);
+                bytes_to_move += used;
+            }
+        }
+
+        DefragmentationReport {
+            heaps,
+            evacuable_heaps,
+            bytes_to_move,
+        }
+    }
+
+    fn allocators(&self) -> impl Iterator<Item = (usize, &TlsfAllocator)> {
+        self.heaps
+            .iter()
+            .enumerate()
+            .filter_map(|(index, heap)| heap.as_ref().map(|(_, allocator)| (index, allocator)))
+    }
+}
+
+/// Places buffers and textures in placement heaps it creates as needed.
+///
+/// Freeing an allocation only returns its range; drop the resource and make
+/// sure the GPU is done with it first.
+///
+/// ```ignore
+/// let descriptor = HeapDescriptor::new();
+/// descriptor.set_storage_mode(MTLStorageMode::Private);
+/// descriptor.set_size(64 << 20);
+/// let mut heaps = PlacementHeapAllocator::new(&device, &descriptor);
+///
+/// let (buffer, allocation) = heaps
+///     .new_buffer(1 << 20, MTLResourceOptions::StorageModePrivate)
+///     .unwrap();
+/// // ...
+/// drop(buffer);
+/// heaps.free(allocation);
+/// ```
+pub struct PlacementHeapAllocator {
+    device: Device,
+    descriptor: HeapDescriptor,
+    heaps: HeapSuballocator<Heap>,
+}
+
+impl PlacementHeapAllocator {
+    /// Heaps are created with `descriptor`'s size, storage, CPU cache and
+    /// hazard tracking modes, as placement heaps.
+    pub fn new(device: &DeviceRef, descriptor: &HeapDescriptorRef) -> Self {
+        let template = HeapDescriptor::new();
+        template.set_storage_mode(descriptor.storage_mode());
+        template.set_cpu_cache_mode(descriptor.cpu_cache_mode());
+        template.set_hazard_tracking_mode(descriptor.hazard_tracking_mode());
+        template.set_heap_type(MTLHeapType::Placement);
+        PlacementHeapAllocator {
+            device: device.to_owned(),
+            descriptor: template,
+            heaps: HeapSuballocator::new(descriptor.size()),
+        }
+    }
+
+    pub fn suballocator(&self) -> &HeapSuballocator<Heap> {
+        &self.heaps
+    }
+
+    pub fn new_buffer(
+        &mut self,
+        length: NSUInteger,
+        options: MTLResourceOptions,
+    ) -> Option<(Buffer, HeapAllocation)> {
+        let size_and_align = self.device.heap_buffer_size_and_align(length, options);
+        let allocation = self.allocate(size_and_align)?;
+        let heap = self.heaps.heap(allocation.heap_index())?;
+        match heap.new_buffer_with_offset(length, options, allocation.offset()) {
+            Some(buffer) => Some((buffer, allocation)),
+            None => {
+                self.heaps.free(allocation);
+                None
+            }
+        }
+    }
+
+    pub fn new_texture(
+        &mut self,
+        descriptor: &TextureDescriptorRef,
+    ) -> Option<(Texture, HeapAllocation)> {
+        let size_and_align = self.device.heap_texture_size_and_align(descriptor);
+        let allocation = self.allocate(size_and_align)?;
+        let heap = self.heaps.heap(allocation.heap_index())?;
+        match heap.new_texture_with_offset(descriptor, allocation.offset()) {
+            Some(texture) => Some((texture, allocation)),
+            None => {
+                self.heaps.free(allocation);
+                None
+            }
+        }
+    }
+
+    pub fn free(&mut self, allocation: HeapAllocation) {
+        self.heaps.free(allocation);
+    }
+
+    /// Release heaps with no allocations left. Returns how many were
+    /// released.
+    pub fn release_empty_heaps(&mut self) -> usize {
+        self.heaps.release_empty_heaps().len()
+    }
+
+    pub fn stats(&self) -> HeapAllocatorStats {
+        self.heaps.stats()
+    }
+
+    pub fn defragmentation_report(&self) -> DefragmentationReport {
+        self.heaps.defragmentation_report()
+    }
+
+    fn allocate(&mut self, size_and_align: MTLSizeAndAlign) -> Option<HeapAllocation> {
+        let device = &self.device;
+        let descriptor = &self.descriptor;
+        self.heaps.allocate(size_and_align, |size| {
+            descriptor.set_size(size);
+            Some(device.new_heap(descriptor))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// xorshift64, so the stress test is reproducible.
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    fn size_and_align(size: NSUInteger, align: NSUInteger) -> MTLSizeAndAlign {
+        MTLSizeAndAlign { size, align }
+    }
+
+    #[test]
+    fn tlsf_random_workload() {
+        let mut allocator = TlsfAllocator::new(1 << 20);
+        let mut live: Vec<TlsfAllocation> = Vec::new();
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..20000 {
+            if rng.next() % 3 != 0 || live.is_empty() {
+                let size = 1 + rng.next() % 20000;
+                let align = 1 << (rng.next() % 13);
+                if let Some(allocation) = allocator.allocate(size, align) {
+                    assert_eq!(allocation.offset() % align, 0);
+                    assert!(allocation.size() >= size);
+                    assert!(allocation.offset() + allocation.size() <= 1 << 20);
+                    for other in &live {
+                        assert!(
+                            allocation.offset() + allocation.size() <= other.offset()
+                                || other.offset() + other.size() <= allocation.offset(),
+                            "{:?} overlaps {:?}",
+                            allocation,
+                            other
+                        );
+                    }
+                    live.push(allocation);
+                }
+            } else {
+                let index = rng.next() as usize % live.len();
+                allocator.free(live.swap_remove(index));
+            }
+            let stats = allocator.stats();
+            assert_eq!(
+                stats.used,
+                live.iter().map(|a| a.size()).sum::<NSUInteger>()
+            );
+            assert_eq!(stats.allocations, live.len());
+        }
+        for allocation in live.drain(..) {
+            allocator.free(allocation);
+        }
+        let stats = allocator.stats();
+        assert_eq!(
+            (stats.used, stats.free_blocks, stats.largest_free_block),
+            (0, 1, 1 << 20)
+        );
+        assert_eq!(stats.fragmentation(), 0.0);
+        assert!(allocator.is_empty());
+    }
+
+    #[test]
+    fn tlsf_fragmentation() {
+        let mut allocator = TlsfAllocator::new(4096);
+        let allocations = (0..16)
+            .map(|_| allocator.allocate(256, 256).unwrap())
+            .collect::<Vec<_>>();
+        assert!(allocator.allocate(1, 1).is_none());
+        let mut kept = Vec::new();
+        for (index, allocation) in allocations.into_iter().enumerate() {
+            if index % 2 == 0 {
+                allocator.free(allocation);
+            } else {
+                kept.push(allocation);
+            }
+        }
+        let stats = allocator.stats();
+        assert_eq!((stats.free_blocks, stats.largest_free_block), (8, 256));
+        assert_eq!(stats.fragmentation(), 0.875);
+        assert!(allocator.allocate(512, 1).is_none());
+        // An exact fit is found even though its size class is searched past.
+        assert_eq!(allocator.allocate(256, 256).unwrap().offset() % 256, 0);
+        assert_eq!(allocator.allocations().len(), 9);
+    }
+
+    #[test]
+    fn suballocator_grows_and_releases() {
+        let mut heaps: HeapSuballocator<NSUInteger> = HeapSuballocator::new(1024);
+        let mut created = Vec::new();
+        let mut new_heap = |size| {
+            created.push(size);
+            Some(size)
+        };
+        let a = heaps
+            .allocate(size_and_align(600, 256), &mut new_heap)
+            .unwrap();
+        let b = heaps
+            .allocate(size_and_align(600, 256), &mut new_heap)
+            .unwrap();
+        let c = heaps
+            .allocate(size_and_align(4000, 256), &mut new_heap)
+            .unwrap();
+        let d = heaps
+            .allocate(size_and_align(100, 16), &mut new_heap)
+            .unwrap();
+        assert_eq!(
+            [
+                a.heap_index(),
+                b.heap_index(),
+                c.heap_index(),
+                d.heap_index()
+            ],
+            [0, 1, 2, 0]
+        );
+        // Allocations larger than the heap size get a heap of their own.
+        assert_eq!(heaps.heap(2), Some(&4096));
+        assert!(heaps.defragmentation_report().evacuable_heaps.is_empty());
+
+        heaps.free(c);
+        assert_eq!(heaps.release_empty_heaps(), [4096]);
+        assert_eq!(heaps.heaps().count(), 2);
+        assert!(heaps.heap(2).is_none());
+        // No new heap is needed while there is room.
+        let e = heaps.allocate(size_and_align(10, 1), |_| None).unwrap();
+        assert_eq!(e.heap_index(), 0);
+        assert!(heaps.allocate(size_and_align(2048, 1), |_| None).is_none());
+        for allocation in [a, b, d] {
+            heaps.free(allocation);
+        }
+        assert_eq!(heaps.stats().used, 10);
+        assert_eq!(heaps.heap_stats(0).unwrap().allocations, 1);
+        assert_eq!(created, [1024, 1024, 4096]);
+    }
+
+    #[test]
+    fn defragmentation() {
+        let mut heaps: HeapSuballocator<()> = HeapSuballocator::new(1024);
+        let a = heaps
+            .allocate(size_and_align(600, 256), |_| Some(()))
+            .unwrap();
+        let b = heaps
+            .allocate(size_and_align(600, 256), |_| Some(()))
+            .unwrap();
+        let d = heaps
+            .allocate(size_and_align(100, 16), |_| Some(()))
+            .unwrap();
+        heaps.free(a);
+        let a = heaps.allocate(size_and_align(8, 8), |_| Some(())).unwrap();
+        assert_eq!((a.heap_index(), d.heap_index(), b.heap_index()), (0, 0, 1));
+
+        let report = heaps.defragmentation_report();
+        assert_eq!(report.evacuable_heaps, [0]);
+        assert_eq!(report.bytes_to_move, 108);
+        assert_eq!(
+            report
+                .heaps
+                .iter()
+                .map(|heap| (heap.heap_index, heap.stats.used))
+                .collect::<Vec<_>>(),
+            [(0, 108), (1, 600)]
+        );
+    }
+
+    #[test]
+    fn defragmentation_moves_each_allocation_once() {
+        // Heap 0 holds 128 bytes, heap 1 256 and heap 2 768, each 1024 big.
+        let mut heaps: HeapSuballocator<()> = HeapSuballocator::new(1024);
+        let mut fillers = Vec::new();
+        let mut kept = Vec::new();
+        for used in [128, 256, 768] {
+            kept.push(
+                heaps
+                    .allocate(size_and_align(used, 16), |_| Some(()))
+                    .unwrap(),
+            );
+            fillers.push(
+                heaps
+                    .allocate(size_and_align(1024 - used, 16), |_| Some(()))
+                    .unwrap(),
+            );
+        }
+        for filler in fillers {
+            heaps.free(filler);
+        }
+        assert_eq!(
+            kept.iter()
+                .map(HeapAllocation::heap_index)
+                .collect::<Vec<_>>(),
+            [0, 1, 2]
+        );
+
+        // Heap 0 moves into heap 1. Heap 1 then holds 384 bytes, which no
+        // longer fit in heap 2's 256 free bytes, so it has to stay.
+        let report = heaps.defragmentation_report();
+        assert_eq!(report.evacuable_heaps, [0]);
+        assert_eq!(report.bytes_to_move, 128);
+    }
+}
@@ -0,0 +1,572 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Recompiling shader source files as they are edited.
+//!
+//! [`ShaderHotReloader`] polls the files of every watched shader, including
+//! the headers its `#include`s resolved to, and recompiles the shaders whose
+//! files changed. Pipelines registered for a shader are rebuilt from the new
+//! library. If compilation fails the last good library stays in place.
+
+use super::*;
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// The compile step of a [`ShaderHotReloader`].
+pub trait ShaderCompiler {
+    type Library;
+
+    /// Compile preprocessed source. Errors are a compiler log, which is
+    /// remapped to the original files before it is reported.
+    fn compile(&mut self, source: &str) -> Result<Self::Library, String>;
+}
+
+/// Compiles through [`DeviceRef::new_library_with_source`].
+pub struct SourceLibraryCompiler {
+    device: Device,
+    options: CompileOptions,
+}
+
+impl SourceLibraryCompiler {
+    pub fn new(device: &DeviceRef, options: &CompileOptionsRef) -> Self {
+        SourceLibraryCompiler {
+            device: device.to_owned(),
+            options: options.to_owned(),
+        }
+    }
+
+    pub fn options(&self) -> &CompileOptionsRef {
+        &self.options
+    }
+}
+
+impl ShaderCompiler for SourceLibraryCompiler {
+    type Library = Library;
+
+    fn compile(&mut self, source: &str) -> Result<Library, String> {
+        self.device
+            .new_library_with_source(source, &self.options)
+            .map_err(String::from)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ShaderId(usize);
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub struct PipelineListenerId(usize);
+
+/// What happened during a [`ShaderHotReloader::poll`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ShaderReloadEvent {
+    Reloaded {
+        shader: ShaderId,
+        path: PathBuf,
+    },
+    /// The shader failed to compile; the previous library is still in use.
+    CompileFailed {
+        shader: ShaderId,
+        path: PathBuf,
+        log: String,
+    },
+    /// A pipeline failed to rebuild from a new library.
+    PipelineFailed {
+        shader: ShaderId,
+        listener: PipelineListenerId,
+        error: String,
+    },
+}
+
+struct WatchedShader<L> {
+    path: PathBuf,
+    library: Option<L>,
+    last_error: Option<String>,
+    /// Every file the last compile attempt read, with its version at the time.
+    dependencies: Vec<(PathBuf, Option<u64>)>,
+}
+
+type RebuildPipeline<L> = Box<dyn FnMut(&L) -> Result<(), String>>;
+
+struct PipelineListener<L> {
+    id: PipelineListenerId,
+    shader: ShaderId,
+    rebuild: RebuildPipeline<L>,
+}
+
+/// Watches shader source files and recompiles them when they change.
+///
+/// ```ignore
+/// let compiler = SourceLibraryCompiler::new(&device, &CompileOptions::new());
+/// let mut reloader = ShaderHotReloader::new(compiler, ShaderPreprocessor::with_std_file_system());
+/// let shader = reloader.watch("shaders/lighting.metal");
+///
+/// let pipeline = Rc::new(RefCell::new(build_pipeline(reloader.library(shader).unwrap())?));
+/// let target = pipeline.clone();
+/// reloader.register_pipeline(shader, move |library| {
+///     *target.borrow_mut() = build_pipeline(library)?;
+///     Ok(())
+/// });
+///
+/// // Once per frame:
+/// for event in reloader.poll() {
+///     println!("{:?}", event);
+/// }
+/// ```
+pub struct ShaderHotReloader<C: ShaderCompiler, F: ShaderFileSystem = StdShaderFileSystem> {
+    compiler: C,
+    preprocessor: ShaderPreprocessor<F>,
+    shaders: Vec<WatchedShader<C::Library>>,
+    listeners: Vec<PipelineListener<C::Library>>,
+    next_listener: usize,
+    poll_interval: Duration,
+    last_poll: Option<Instant>,
+}
+
+impl<C: ShaderCompiler, F: ShaderFileSystem> ShaderHotReloader<C, F> {
+    /// Shaders are read and their includes resolved through `preprocessor`,
+    /// whose defines are applied to every shader.
+    pub fn new(compiler: C, preprocessor: ShaderPreprocessor<F>) -> Self {
+        ShaderHotReloader {
+            compiler,
+            preprocessor,
+            shaders: Vec::new(),
+            listeners: Vec::new(),
+            next_listener: 0,
+            poll_interval: Duration::from_millis(250),
+            last_poll: None,
+        }
+    }
+
+    pub fn compiler(&self) -> &C {
+        &self.compiler
+    }
+
+    pub fn preprocessor(&self) -> &ShaderPreprocessor<F> {
+        &self.preprocessor
+    }
+
+    /// Changes to the defines or search paths take effect on the next
+    /// recompile; use [`reload_all`](Self::reload_all) to force one.
+    pub fn preprocessor_mut(&mut self) -> &mut ShaderPreprocessor<F> {
+        &mut self.preprocessor
+    }
+
+    /// The minimum time between two checks of [`poll`](Self::poll).
+    pub fn set_poll_interval(&mut self, interval: Duration) {
+        self.poll_interval = interval;
+    }
+
+    /// Start watching the shader at `path` and compile it. The shader stays
+    /// watched even if this first compile fails.
+    pub fn watch<P: AsRef<Path>>(&mut self, path: P) -> ShaderId {
+        let id = ShaderId(self.shaders.len());
+        self.shaders.push(WatchedShader {
+            path: path.as_ref().to_path_buf(),
+            library: None,
+            last_error: None,
+            dependencies: Vec::new(),
+        });
+        self.recompile(id);
+        id
+    }
+
+    pub fn path(&self, shader: ShaderId) -> &Path {
+        &self.shaders[shader.0].path
+    }
+
+    /// The last library that compiled successfully.
+    pub fn library(&self, shader: ShaderId) -> Option<&C::Library> {
+        self.shaders[shader.0].library.as_ref()
+    }
+
+    /// The log of the last compile, if it failed.
+    pub fn last_error(&self, shader: ShaderId) -> Option<&str> {
+        self.shaders[shader.0].last_error.as_deref()
+    }
+
+    /// The files the shader was compiled from, the shader itself first.
+    pub fn dependencies(&self, shader: ShaderId) -> impl Iterator<Item = &Path> {
+        self.shaders[shader.0]
+            .dependencies
+            .iter()
+            .map(|(path, _)| path.as_path())
+    }
+
+    /// Call `rebuild` with every new library of `shader`. It is not called
+    /// for the library that is current at registration.
+    pub fn register_pipeline<R>(&mut self, shader: ShaderId, rebuild: R) -> PipelineListenerId
+    where
+        R: FnMut(&C::Library) -> Result<(), String> + 'static,
+    {
+        let id = PipelineListenerId(self.next_listener);
+        self.next_listener += 1;
+        self.listeners.push(PipelineListener {
+            id,
+            shader,
+            rebuild: Box::new(rebuild),
+        });
+        id
+    }
+
+    pub fn unregister_pipeline(&mut self, listener: PipelineListenerId) {
+        self.listeners.retain(|l| l.id != listener);
+    }
+
+    /// Recompile changed shaders, at most once per poll interval.
+    pub fn poll(&mut self) -> Vec<ShaderReloadEvent> {
+        let now = Instant::now();
+        if let Some(last_poll) = self.last_poll {
+            if now.duration_since(last_poll) < self.poll_interval {
+                return Vec::new();
+            }
+        }
+        self.last_poll = Some(now);
+        self.reload_changed()
+    }
+
+    /// Recompile every shader any of whose files changed since it was last
+    /// compiled, regardless of the poll interval.
+    pub fn reload_changed(&mut self) -> Vec<ShaderReloadEvent> {
+        let file_system = self.preprocessor.file_system();
+        let changed: Vec<ShaderId> = (0..self.shaders.len())
+            .map(ShaderId)
+            .filter(|id| {
+                self.shaders[id.0]
+                    .dependencies
+                    .iter()
+                    .any(|(path, version)| file_system.version(path) != *version)
+            })
+            .collect();
+        changed
+            .into_iter()
+            .flat_map(|id| self.recompile(id))
+            .collect()
+    }
+
+    /// Recompile every shader.
+    pub fn reload_all(&mut self) -> Vec<ShaderReloadEvent> {
+        (0..self.shaders.len())
+            .flat_map(|id| self.recompile(ShaderId(id)))
+            .collect()
+    }
+
+    fn recompile(&mut self, id: ShaderId) -> Vec<ShaderReloadEvent> {
+        let file_system = self.preprocessor.file_system();
+        let shader = &mut self.shaders[id.0];
+
+        // Versions are taken before the files are read, so an edit that lands
+        // while preprocessing is seen by the next poll.
+        let entry_version = file_system.version(&shader.path);
+        let previous: Vec<_> = shader
+            .dependencies
+            .drain(..)
+            .map(|(path, _)| {
+                let version = file_system.version(&path);
+                (path, version)
+            })
+            .collect();
+        let result = self.preprocessor.preprocess_file(&shader.path);
+        // Keep watching the files of the last attempt if preprocessing
+        // failed, so creating a missing include triggers a retry.
+        let dependencies = match &result {
+            Ok(preprocessed) => preprocessed.file_versions.clone(),
+            Err(_) => previous,
+        };
+        let mut seen = HashSet::new();
+        shader.dependencies = std::iter::once((shader.path.clone(), entry_version))
+            .chain(dependencies)
+            .filter(|(path, _)| seen.insert(path.clone()))
+            .collect();
+
+        let compiled = result.and_then(|preprocessed| {
+            self.compiler.compile(&preprocessed.source).map_err(|log| {
+                let mut diagnostics = parse_compiler_diagnostics(&log);
+                if diagnostics.is_empty() {
+                    return log;
+                }
+                diagnostics
+                    .iter_mut()
+                    .fold(String::new(), |mut log, diagnostic| {
+                        preprocessed.line_map.remap(diagnostic);
+                        log.push_str(&diagnostic.to_string());
+                        log
+                    })
+            })
+        });
+
+        let library = match compiled {
+            Ok(library) => library,
+            Err(log) => {
+                shader.last_error = Some(log.clone());
+                return vec![ShaderReloadEvent::CompileFailed {
+                    shader: id,
+                    path: shader.path.clone(),
+                    log,
+                }];
+            }
+        };
+        shader.last_error = None;
+        let library = &*shader.library.insert(library);
+
+        let mut events = vec![ShaderReloadEvent::Reloaded {
+            shader: id,
+            path: shader.path.clone(),
+        }];
+        for listener in self.listeners.iter_mut().filter(|l| l.shader == id) {
+            if let Err(error) = (listener.rebuild)(library) {
+                events.push(ShaderReloadEvent::PipelineFailed {
+                    shader: id,
+                    listener: listener.id,
+                    error,
+                });
+            }
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    /// "Compiles" to the source itself, failing on lines containing `BAD`.
+    #[derive(Default)]
+    struct FakeCompiler {
+        compiles: usize,
+    }
+
+    impl ShaderCompiler for FakeCompiler {
+        type Library = String;
+
+        fn compile(&mut self, source: &str) -> Result<String, String> {
+            self.compiles += 1;
+            match source.lines().position(|line| line.contains("BAD")) {
+                Some(index) => Err(format!(
+                    "Compilation failed: \n\nprogram_source:{}:1: error: unknown type name 'BAD'\nBAD\n^\n1 error generated.\n",
+                    index + 1
+                )),
+                None => Ok(source.to_string()),
+            }
+        }
+    }
+
+    fn reloader(
+        files: &[(&str, &str)],
+    ) -> ShaderHotReloader<FakeCompiler, VirtualShaderFileSystem> {
+        let mut file_system = VirtualShaderFileSystem::new();
+        for (path, contents) in files {
+            file_system.add_file(path, contents);
+        }
+        let mut reloader = ShaderHotReloader::new(
+            FakeCompiler::default(),
+            ShaderPreprocessor::new(file_system),
+        );
+        reloader.set_poll_interval(Duration::ZERO);
+        reloader
+    }
+
+    fn edit(
+        reloader: &mut ShaderHotReloader<FakeCompiler, VirtualShaderFileSystem>,
+        path: &str,
+        contents: &str,
+    ) {
+        reloader
+            .preprocessor_mut()
+            .file_system_mut()
+            .add_file(path, contents);
+    }
+
+    #[test]
+    fn reload_on_include_change() {
+        let mut reloader = reloader(&[
+            ("common.h", "#pragma once\nint common;\n"),
+            ("a.metal", "#include \"common.h\"\nkernel a;\n"),
+            ("b.metal", "kernel b;\n"),
+        ]);
+        let a = reloader.watch("a.metal");
+        let b = reloader.watch("b.metal");
+        assert_eq!(
+            reloader.dependencies(a).collect::<Vec<_>>(),
+            [Path::new("a.metal"), Path::new("common.h")]
+        );
+        assert_eq!(reloader.compiler().compiles, 2);
+
+        let rebuilt = Rc::new(RefCell::new(Vec::new()));
+        let target = rebuilt.clone();
+        reloader.register_pipeline(a, move |library: &String| {
+            target.borrow_mut().push(library.clone());
+            Ok(())
+        });
+        let failing = reloader.register_pipeline(a, |_: &String| Err("no".to_string()));
+
+        assert!(reloader.poll().is_empty());
+        edit(&mut reloader, "common.h", "#pragma once\nint edited;\n");
+        assert_eq!(
+            reloader.poll(),
+            [
+                ShaderReloadEvent::Reloaded {
+                    shader: a,
+                    path: PathBuf::from("a.metal")
+                },
+                ShaderReloadEvent::PipelineFailed {
+                    shader: a,
+                    listener: failing,
+                    error: "no".to_string()
+                },
+            ]
+        );
+        assert_eq!(RefCell::borrow(&rebuilt).len(), 1);
+        assert!(RefCell::borrow(&rebuilt)[0].contains("int edited;"));
+        // Only the shader that includes the header was recompiled.
+        assert_eq!(reloader.compiler().compiles, 3);
+        assert!(reloader.library(b).is_some());
+
+        reloader.unregister_pipeline(failing);
+        edit(
+            &mut reloader,
+            "a.metal",
+            "#include \"common.h\"\nkernel a2;\n",
+        );
+        assert_eq!(reloader.poll().len(), 1);
+        assert_eq!(RefCell::borrow(&rebuilt).len(), 2);
+    }
+
+    #[test]
+    fn keep_last_good_library() {
+        let mut reloader = reloader(&[
+            ("common.h", "int common;\n"),
+            ("main.metal", "#include \"common.h\"\nkernel main;\n"),
+        ]);
+        let shader = reloader.watch("main.metal");
+
+        edit(&mut reloader, "common.h", "BAD\n");
+        let events = reloader.poll();
+        assert!(matches!(
+            &events[..],
+            [ShaderReloadEvent::CompileFailed { .. }]
+        ));
+        assert!(reloader.library(shader).unwrap().contains("int common;"));
+        // The error points at the header, not at program_source.
+        assert!(reloader
+            .last_error(shader)
+            .unwrap()
+            .starts_with("common.h:1:1: error:"));
+
+        // A missing include keeps the previous dependencies watched.
+        reloader
+            .preprocessor_mut()
+            .file_system_mut()
+            .remove_file("common.h");
+        assert!(matches!(
+            &reloader.poll()[..],
+            [ShaderReloadEvent::CompileFailed { .. }]
+        ));
+        assert!(reloader
+            .dependencies(shader)
+            .any(|p| p == Path::new("common.h")));
+        assert!(reloader.poll().is_empty());
+
+        edit(&mut reloader, "common.h", "int fixed;\n");
+        assert!(matches!(
+            &reloader.poll()[..],
+            [ShaderReloadEvent::Reloaded { .. }]
+        ));
+        assert!(reloader.library(shader).unwrap().contains("int fixed;"));
+        assert_eq!(reloader.last_error(shader), None);
+    }
+
+    #[test]
+    fn unique_dependencies() {
+        let mut reloader = reloader(&[
+            (
+                "main.metal",
+                "#include \"a.h\"\n#include \"b.h\"\n#include \"a.h\"\n",
+            ),
+            ("a.h", "#include \"common.h\"\n"),
+            ("b.h", "#include \"common.h\"\n"),
+            ("common.h", "int common;\n"),
+        ]);
+        let shader = reloader.watch("main.metal");
+        assert_eq!(
+            reloader.dependencies(shader).collect::<Vec<_>>(),
+            ["main.metal", "a.h", "common.h", "b.h"].map(Path::new)
+        );
+    }
+
+    /// A file system where reading a file lets a queued edit land right
+    /// after the read.
+    #[derive(Default)]
+    struct RacyFileSystem {
+        files: VirtualShaderFileSystem,
+        pending: RefCell<Option<(PathBuf, String)>>,
+        edited: RefCell<Vec<(PathBuf, String)>>,
+    }
+
+    impl ShaderFileSystem for RacyFileSystem {
+        fn read_file(&self, path: &Path) -> Option<String> {
+            let edited = self
+                .edited
+                .borrow()
+                .iter()
+                .rev()
+                .find(|(p, _)| p == path)
+                .cloned();
+            let contents = match edited {
+                Some((_, contents)) => Some(contents),
+                None => self.files.read_file(path),
+            };
+            let pending = self.pending.borrow_mut().take_if(|(p, _)| p == path);
+            if let Some(edit) = pending {
+                self.edited.borrow_mut().push(edit);
+            }
+            contents
+        }
+
+        fn version(&self, path: &Path) -> Option<u64> {
+            let edits = self
+                .edited
+                .borrow()
+                .iter()
+                .filter(|(p, _)| p == path)
+                .count();
+            self.files
+                .version(path)
+                .map(|version| version + edits as u64 * 1000)
+        }
+    }
+
+    #[test]
+    fn edit_during_preprocessing_is_not_lost() {
+        let mut file_system = RacyFileSystem::default();
+        file_system.files.add_file("main.metal", "kernel old;\n");
+        let mut reloader = ShaderHotReloader::new(
+            FakeCompiler::default(),
+            ShaderPreprocessor::new(file_system),
+        );
+        reloader.set_poll_interval(Duration::ZERO);
+        let shader = reloader.watch("main.metal");
+
+        *reloader.preprocessor().file_system().pending.borrow_mut() =
+            Some((PathBuf::from("main.metal"), "kernel racing;\n".to_string()));
+        // The version changes, but the edit lands after the file was read.
+        let edits = &reloader.preprocessor().file_system().edited;
+        edits
+            .borrow_mut()
+            .push((PathBuf::from("main.metal"), "kernel old2;\n".to_string()));
+        assert_eq!(reloader.poll().len(), 1);
+        assert_eq!(reloader.library(shader).unwrap(), "kernel old2;\n");
+
+        // The racing edit is picked up by the next poll.
+        assert_eq!(reloader.poll().len(), 1);
+        assert_eq!(reloader.library(shader).unwrap(), "kernel racing;\n");
+        assert!(reloader.poll().is_empty());
+    }
+}
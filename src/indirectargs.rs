@@ -0,0 +1,372 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::*;
+
+use std::fmt;
+use std::mem;
+
+/// Offsets into indirect buffers must be multiples of this.
+pub const INDIRECT_ARGUMENT_ALIGNMENT: NSUInteger = 4;
+
+/// The argument layouts indirect draws and dispatches read.
+pub trait IndirectArguments: Pod {}
+
+impl IndirectArguments for MTLDrawPrimitivesIndirectArguments {}
+impl IndirectArguments for MTLDrawIndexedPrimitivesIndirectArguments {}
+impl IndirectArguments for MTLDrawPatchIndirectArguments {}
+impl IndirectArguments for MTLDispatchThreadgroupsIndirectArguments {}
+impl IndirectArguments for MTLStageInRegionIndirectArguments {}
+impl IndirectArguments for MTLMapIndirectArguments {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IndirectArgumentsError {
+    /// The buffer's contents can't be written from the CPU.
+    NotCpuAccessible(MTLStorageMode),
+    /// The offset is not a multiple of [`INDIRECT_ARGUMENT_ALIGNMENT`].
+    Misaligned { offset: NSUInteger },
+    /// The write would run past the end of the buffer.
+    OutOfBounds {
+        offset: NSUInteger,
+        length: NSUInteger,
+        buffer_length: NSUInteger,
+    },
+}
+
+impl fmt::Display for IndirectArgumentsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IndirectArgumentsError::NotCpuAccessible(mode) => {
+                write!(f, "cannot write indirect arguments to {:?} storage", mode)
+            }
+            IndirectArgumentsError::Misaligned { offset } => write!(
+                f,
+                "indirect argument offset {} is not a multiple of {}",
+                offset, INDIRECT_ARGUMENT_ALIGNMENT
+            ),
+            IndirectArgumentsError::OutOfBounds {
+                offset,
+                length,
+                buffer_length,
+            } => write!(
+                f,
+                "{} bytes of indirect arguments at offset {} overrun a {} byte buffer",
+                length, offset, buffer_length
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IndirectArgumentsError {}
+
+/// Writes indirect arguments into a CPU-visible buffer, returning the offset
+/// to pass to the indirect draw or dispatch.
+///
+/// The written range is reported with `did_modify_range` when the writer is
+/// dropped, if the buffer is `Managed`.
+///
+/// ```ignore
+/// let mut writer = IndirectArgumentsWriter::new(&buffer)?;
+/// let draws = writer.push_slice(&draw_args)?;
+/// let dispatch = writer.push(&MTLDispatchThreadgroupsIndirectArguments {
+///     threadgroupsPerGrid: [64, 1, 1],
+/// })?;
+/// drop(writer);
+/// encoder.dispatch_thread_groups_indirect(&buffer, dispatch, threads_per_group);
+/// ```
+pub struct IndirectArgumentsWriter<'a, B: BufferStorage = Buffer> {
+    buffer: &'a B,
+    length: NSUInteger,
+    flush: bool,
+    cursor: NSUInteger,
+    written: Option<(NSUInteger, NSUInteger)>,
+}
+
+impl<'a, B: BufferStorage> IndirectArgumentsWriter<'a, B> {
+    pub fn new(buffer: &'a B) -> Result<Self, IndirectArgumentsError> {
+        let mode = buffer.storage_mode();
+        if !mode.is_cpu_accessible() {
+            return Err(IndirectArgumentsError::NotCpuAccessible(mode));
+        }
+        Ok(IndirectArgumentsWriter {
+            buffer,
+            length: buffer.length(),
+            flush: mode == MTLStorageMode::Managed,
+            cursor: 0,
+            written: None,
+        })
+    }
+
+    /// Where the next [`push`](Self::push) writes.
+    pub fn offset(&self) -> NSUInteger {
+        self.cursor
+    }
+
+    pub fn seek(&mut self, offset: NSUInteger) -> Result<(), IndirectArgumentsError> {
+        self.check(offset, 0)?;
+        self.cursor = offset;
+        Ok(())
+    }
+
+    /// Write `args` at the current offset and return that offset.
+    pub fn push<T: IndirectArguments>(
+        &mut self,
+        args: &T,
+    ) -> Result<NSUInteger, IndirectArgumentsError> {
+        self.push_slice(std::slice::from_ref(args))
+    }
+
+    /// Write `args` back to back at the current offset and return that
+    /// offset. Element `i` is at the returned offset plus
+    /// `i * size_of::<T>()`.
+    pub fn push_slice<T: IndirectArguments>(
+        &mut self,
+        args: &[T],
+    ) -> Result<NSUInteger, IndirectArgumentsError> {
+        let offset = self.cursor;
+        self.write_at(offset, args)?;
+        self.cursor = offset + mem::size_of_val(args) as NSUInteger;
+        Ok(offset)
+    }
+
+    /// Write `args` at `offset` without moving the cursor, e.g. to patch
+    /// arguments written earlier.
+    pub fn write_at<T: IndirectArguments>(
+        &mut self,
+        offset: NSUInteger,
+        args: &[T],
+    ) -> Result<(), IndirectArgumentsError> {
+        let bytes = slice_bytes(args);
+        let length = bytes.len() as NSUInteger;
+        self.check(offset, length)?;
+        if length == 0 {
+            return Ok(());
+        }
+        unsafe {
+            let dst = (self.buffer.contents() as *mut u8).add(offset as usize);
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), dst, bytes.len());
+        }
+        let end = offset + length;
+        self.written = Some(match self.written {
+            Some((start, old_end)) => (start.min(offset), old_end.max(end)),
+            None => (offset, end),
+        });
+        Ok(())
+    }
+
+    /// The bytes written so far, as one range covering all writes.
+    pub fn written_range(&self) -> Option<NSRange> {
+        self.written
+            .map(|(start, end)| NSRange::new(start, end - start))
+    }
+
+    fn check(&self, offset: NSUInteger, length: NSUInteger) -> Result<(), IndirectArgumentsError> {
+        if offset % INDIRECT_ARGUMENT_ALIGNMENT != 0 {
+            return Err(IndirectArgumentsError::Misaligned { offset });
+        }
+        match offset.checked_add(length) {
+            Some(end) if end <= self.length => Ok(()),
+            _ => Err(IndirectArgumentsError::OutOfBounds {
+                offset,
+                length,
+                buffer_length: self.length,
+            }),
+        }
+    }
+}
+
+impl<B: BufferStorage> Drop for IndirectArgumentsWriter<'_, B> {
+    fn drop(&mut self) {
+        if let (true, Some(range)) = (self.flush, self.written_range()) {
+            self.buffer.did_modify_range(range);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typedbuffer::test_util::HostBuffer;
+    use std::mem::offset_of;
+
+    #[test]
+    fn field_offsets() {
+        // Field order and offsets from MTLTypes.h and MTLRenderCommandEncoder.h.
+        assert_eq!(
+            [
+                offset_of!(MTLDrawPrimitivesIndirectArguments, vertexCount),
+                offset_of!(MTLDrawPrimitivesIndirectArguments, instanceCount),
+                offset_of!(MTLDrawPrimitivesIndirectArguments, vertexStart),
+                offset_of!(MTLDrawPrimitivesIndirectArguments, baseInstance),
+            ],
+            [0, 4, 8, 12]
+        );
+        assert_eq!(
+            [
+                offset_of!(MTLDrawIndexedPrimitivesIndirectArguments, indexCount),
+                offset_of!(MTLDrawIndexedPrimitivesIndirectArguments, instanceCount),
+                offset_of!(MTLDrawIndexedPrimitivesIndirectArguments, indexStart),
+                offset_of!(MTLDrawIndexedPrimitivesIndirectArguments, baseVertex),
+                offset_of!(MTLDrawIndexedPrimitivesIndirectArguments, baseInstance),
+            ],
+            [0, 4, 8, 12, 16]
+        );
+        assert_eq!(
+            [
+                offset_of!(MTLDrawPatchIndirectArguments, patchCount),
+                offset_of!(MTLDrawPatchIndirectArguments, instanceCount),
+                offset_of!(MTLDrawPatchIndirectArguments, patchStart),
+                offset_of!(MTLDrawPatchIndirectArguments, baseInstance),
+            ],
+            [0, 4, 8, 12]
+        );
+        assert_eq!(
+            offset_of!(
+                MTLDispatchThreadgroupsIndirectArguments,
+                threadgroupsPerGrid
+            ),
+            0
+        );
+        assert_eq!(
+            [
+                offset_of!(MTLStageInRegionIndirectArguments, stageInOrigin),
+                offset_of!(MTLStageInRegionIndirectArguments, stageInSize),
+            ],
+            [0, 12]
+        );
+        assert_eq!(
+            [
+                offset_of!(MTLMapIndirectArguments, regionOriginX),
+                offset_of!(MTLMapIndirectArguments, regionOriginY),
+                offset_of!(MTLMapIndirectArguments, regionOriginZ),
+                offset_of!(MTLMapIndirectArguments, regionSizeWidth),
+                offset_of!(MTLMapIndirectArguments, regionSizeHeight),
+                offset_of!(MTLMapIndirectArguments, regionSizeDepth),
+                offset_of!(MTLMapIndirectArguments, mipMapLevel),
+                offset_of!(MTLMapIndirectArguments, sliceId),
+            ],
+            [0, 4, 8, 12, 16, 20, 24, 28]
+        );
+    }
+
+    #[test]
+    fn sizes_keep_the_alignment() {
+        for size in [
+            mem::size_of::<MTLDrawPrimitivesIndirectArguments>(),
+            mem::size_of::<MTLDrawIndexedPrimitivesIndirectArguments>(),
+            mem::size_of::<MTLDrawPatchIndirectArguments>(),
+            mem::size_of::<MTLDispatchThreadgroupsIndirectArguments>(),
+            mem::size_of::<MTLStageInRegionIndirectArguments>(),
+            mem::size_of::<MTLMapIndirectArguments>(),
+        ] {
+            // Pushing any of them back to back leaves the cursor aligned.
+            assert_eq!(size as NSUInteger % INDIRECT_ARGUMENT_ALIGNMENT, 0);
+        }
+    }
+
+    #[test]
+    fn push() {
+        let buffer = HostBuffer::new(64, MTLStorageMode::Managed);
+        {
+            let mut writer = IndirectArgumentsWriter::new(&buffer).unwrap();
+            let draw = MTLDrawIndexedPrimitivesIndirectArguments {
+                indexCount: 36,
+                instanceCount: 2,
+                indexStart: 6,
+                baseVertex: -1,
+                baseInstance: 3,
+            };
+            assert_eq!(writer.push_slice(&[draw, draw]).unwrap(), 0);
+            let dispatch = MTLDispatchThreadgroupsIndirectArguments {
+                threadgroupsPerGrid: [8, 4, 1],
+            };
+            assert_eq!(writer.push(&dispatch).unwrap(), 40);
+            assert_eq!(writer.offset(), 52);
+            assert_eq!(
+                writer.push_slice::<MTLDrawPatchIndirectArguments>(&[]),
+                Ok(52)
+            );
+            let range = writer.written_range().unwrap();
+            assert_eq!((range.location, range.length), (0, 52));
+        }
+        assert_eq!(
+            buffer.read::<u32>()[..13],
+            [36, 2, 6, u32::MAX, 3, 36, 2, 6, u32::MAX, 3, 8, 4, 1]
+        );
+        assert_eq!(buffer.modified(), [(0, 52)]);
+    }
+
+    #[test]
+    fn write_at_and_seek() {
+        let buffer = HostBuffer::new(64, MTLStorageMode::Shared);
+        let mut writer = IndirectArgumentsWriter::new(&buffer).unwrap();
+        let dispatch = |x| MTLDispatchThreadgroupsIndirectArguments {
+            threadgroupsPerGrid: [x, 1, 1],
+        };
+        writer.seek(32).unwrap();
+        assert_eq!(writer.push(&dispatch(2)), Ok(32));
+        writer.write_at(4, &[dispatch(7)]).unwrap();
+        assert_eq!(writer.offset(), 44);
+        let range = writer.written_range().unwrap();
+        assert_eq!((range.location, range.length), (4, 40));
+        writer.seek(64).unwrap();
+        drop(writer);
+        assert_eq!(buffer.read::<u32>()[1..4], [7, 1, 1]);
+        assert_eq!(buffer.read::<u32>()[8..11], [2, 1, 1]);
+        // Shared buffers need no flush.
+        assert!(buffer.modified().is_empty());
+    }
+
+    #[test]
+    fn errors() {
+        let buffer = HostBuffer::new(64, MTLStorageMode::Managed);
+        {
+            let mut writer = IndirectArgumentsWriter::new(&buffer).unwrap();
+            assert_eq!(
+                writer.seek(2),
+                Err(IndirectArgumentsError::Misaligned { offset: 2 })
+            );
+            assert_eq!(
+                writer.seek(68),
+                Err(IndirectArgumentsError::OutOfBounds {
+                    offset: 68,
+                    length: 0,
+                    buffer_length: 64
+                })
+            );
+            writer.seek(44).unwrap();
+            let region = MTLStageInRegionIndirectArguments {
+                stageInOrigin: [0; 3],
+                stageInSize: [1; 3],
+            };
+            let err = writer.push(&region).unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                "24 bytes of indirect arguments at offset 44 overrun a 64 byte buffer"
+            );
+            assert_eq!(writer.offset(), 44);
+            assert!(matches!(
+                writer.write_at(NSUInteger::MAX - 3, &[region]),
+                Err(IndirectArgumentsError::OutOfBounds { .. })
+            ));
+            assert_eq!(writer.written_range().map(|r| r.length), None);
+        }
+        assert!(buffer.modified().is_empty());
+        assert!(buffer.read::<u32>().iter().all(|&word| word == 0));
+
+        let private = HostBuffer::new(16, MTLStorageMode::Private);
+        let err = IndirectArgumentsWriter::new(&private).err().unwrap();
+        assert_eq!(
+            err,
+            IndirectArgumentsError::NotCpuAccessible(MTLStorageMode::Private)
+        );
+        assert_eq!(
+            err.to_string(),
+            "cannot write indirect arguments to Private storage"
+        );
+    }
+}
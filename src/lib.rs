@@ -213,10 +213,7 @@ macro_rules! try_objc {
             let mut $err_name: *mut Object = ::std::ptr::null_mut();
             let value = $body;
             if !$err_name.is_null() {
-                let desc: *mut Object = msg_send![$err_name, localizedDescription];
-                let compile_error: *const c_char = msg_send![desc, UTF8String];
-                let message = CStr::from_ptr(compile_error).to_string_lossy().into_owned();
-                return Err(message);
+                return Err(crate::MetalError::from_nserror($err_name));
             }
             value
         }
@@ -248,10 +245,7 @@ macro_rules! msg_send_bool_error_check {
         let result: BOOL = msg_send![$obj, $name:$arg
                                                     error:&mut err];
         if !err.is_null() {
-            let desc: *mut Object = msg_send![err, localizedDescription];
-            let c_msg: *const c_char = msg_send![desc, UTF8String];
-            let message = CStr::from_ptr(c_msg).to_string_lossy().into_owned();
-            Err(message)
+            Err(crate::MetalError::from_nserror(err))
         } else {
             match result {
                 YES => Ok(true),
@@ -557,31 +551,50 @@ impl MetalLayerRef {
 
 mod acceleration_structure;
 mod acceleration_structure_pass;
+mod aliasplan;
 mod argument;
+mod binaryarchive;
+mod bindingvalidation;
 mod blitpass;
+mod blitplan;
 mod buffer;
 mod capturedescriptor;
 mod capturemanager;
 mod commandbuffer;
 mod commandqueue;
+mod commandstream;
 mod computepass;
 mod constants;
 mod counters;
+mod debuggroup;
 mod depthstencil;
 mod device;
+mod diagnostic;
+mod dispatchplan;
 mod drawable;
 mod encoder;
+mod error;
 mod heap;
+mod heapallocator;
+mod hotreload;
 mod indirect_encoder;
+mod indirectargs;
 mod library;
+mod memorybudget;
+mod metallib;
 #[cfg(feature = "mps")]
 pub mod mps;
 mod pipeline;
+mod pod;
+mod preprocessor;
 mod renderpass;
 mod resource;
+mod ringbuffer;
 mod sampler;
+mod statecache;
 mod sync;
 mod texture;
+mod typedbuffer;
 mod types;
 mod vertexdescriptor;
 
@@ -589,8 +602,12 @@ mod vertexdescriptor;
 pub use {
     acceleration_structure::*,
     acceleration_structure_pass::*,
+    aliasplan::*,
     argument::*,
+    binaryarchive::*,
+    bindingvalidation::*,
     blitpass::*,
+    blitplan::*,
     buffer::*,
     counters::*,
     computepass::*,
@@ -598,19 +615,34 @@ pub use {
     capturemanager::*,
     commandbuffer::*,
     commandqueue::*,
+    commandstream::*,
     constants::*,
+    debuggroup::*,
     depthstencil::*,
     device::*,
+    diagnostic::*,
+    dispatchplan::*,
     drawable::*,
     encoder::*,
+    error::*,
     heap::*,
+    heapallocator::*,
+    hotreload::*,
     indirect_encoder::*,
+    indirectargs::*,
     library::*,
+    memorybudget::*,
+    metallib::*,
     pipeline::*,
+    pod::*,
+    preprocessor::*,
     renderpass::*,
     resource::*,
+    ringbuffer::*,
     sampler::*,
+    statecache::*,
     texture::*,
+    typedbuffer::*,
     types::*,
     vertexdescriptor::*,
     sync::*,
@@ -646,6 +678,15 @@ impl URL {
             msg_send![class, URLWithString: ns_str]
         }
     }
+
+    /// A `file://` URL for a path, percent-encoding it as needed.
+    pub fn new_with_file_path(path: &str) -> Self {
+        unsafe {
+            let ns_str = crate::nsstring_from_str(path);
+            let class = class!(NSURL);
+            msg_send![class, fileURLWithPath: ns_str]
+        }
+    }
 }
 
 impl URLRef {
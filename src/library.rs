@@ -9,7 +9,6 @@ use super::*;
 
 use objc::runtime::{BOOL, NO, YES};
 
-use std::ffi::{c_char, CStr};
 use std::ptr;
 
 /// Only available on (macos(10.12), ios(10.0)
@@ -337,6 +336,23 @@ impl FunctionRef {
         unsafe { msg_send![self, functionConstantsDictionary] }
     }
 
+    /// Only available on (macos(10.12), ios(10.0))
+    ///
+    /// Marshal the values of `functionConstantsDictionary` to a Rust Vec
+    pub fn function_constants(&self) -> Vec<FunctionConstant> {
+        unsafe {
+            let dictionary = self.function_constants_dictionary();
+            let constants: *mut Object = msg_send![dictionary, allValues];
+            let count: NSUInteger = msg_send![constants, count];
+            (0..count)
+                .map(|i| {
+                    let constant: *mut MTLFunctionConstant = msg_send![constants, objectAtIndex: i];
+                    FunctionConstant::from_ptr(msg_send![constant, retain])
+                })
+                .collect()
+        }
+    }
+
     /// Only available on (macos(11.0), ios(14.0))
     pub fn options(&self) -> MTLFunctionOptions {
         unsafe { msg_send![self, options] }
@@ -405,8 +421,316 @@ impl FunctionConstantValuesRef {
             msg_send![self, setConstantValue:value type:ty withName:ns_name]
         }
     }
+
+    /// Typed counterpart of [`Self::set_constant_value_at_index`].
+    pub fn set_constant_at_index<T: FunctionConstantValue>(&self, value: T, index: NSUInteger) {
+        let raw = value.to_raw();
+        self.set_constant_value_at_index((&raw as *const T::Raw).cast(), T::DATA_TYPE, index)
+    }
+
+    /// Typed counterpart of [`Self::set_constant_values_with_range`], setting
+    /// `values.len()` constants starting at `start_index`.
+    pub fn set_constants_at_index<T: FunctionConstantValue>(
+        &self,
+        values: &[T],
+        start_index: NSUInteger,
+    ) {
+        let raw: Vec<T::Raw> = values.iter().map(|value| value.to_raw()).collect();
+        self.set_constant_values_with_range(
+            raw.as_ptr().cast(),
+            T::DATA_TYPE,
+            NSRange::new(start_index, raw.len() as NSUInteger),
+        )
+    }
+
+    /// Typed counterpart of [`Self::set_constant_value_with_name`].
+    pub fn set_constant_with_name<T: FunctionConstantValue>(&self, value: T, name: &str) {
+        let raw = value.to_raw();
+        self.set_constant_value_with_name((&raw as *const T::Raw).cast(), T::DATA_TYPE, name)
+    }
+}
+
+/// Reflection data of a single function constant, detached from the
+/// Objective-C object so that it can be inspected and compared freely.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FunctionConstantInfo {
+    pub name: String,
+    pub index: NSUInteger,
+    pub data_type: MTLDataType,
+    pub required: bool,
+}
+
+impl From<&FunctionConstantRef> for FunctionConstantInfo {
+    fn from(constant: &FunctionConstantRef) -> Self {
+        FunctionConstantInfo {
+            name: constant.name().to_string(),
+            index: constant.index(),
+            data_type: constant.data_type(),
+            required: constant.required(),
+        }
+    }
+}
+
+/// Identifies a function constant either by its `[[function_constant(index)]]`
+/// or by its name.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum FunctionConstantKey {
+    Index(NSUInteger),
+    Name(String),
+}
+
+impl std::fmt::Display for FunctionConstantKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            FunctionConstantKey::Index(index) => write!(f, "function constant {}", index),
+            FunctionConstantKey::Name(name) => write!(f, "function constant '{}'", name),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct FunctionConstantEntry {
+    key: FunctionConstantKey,
+    data_type: MTLDataType,
+    bytes: Vec<u8>,
+}
+
+/// Collects typed function constant values and turns them into a
+/// [`FunctionConstantValues`] object.
+///
+/// When reflection data is supplied through [`Self::reflection`], [`Self::validate`]
+/// and [`Self::build`] check every value against the declared constants:
+/// unknown constants, mismatched `MTLDataType`s and missing required constants
+/// are reported as errors.
+///
+/// ```ignore
+/// let constants = FunctionConstantValuesBuilder::new()
+///     .reflection(function.function_constants().iter().map(|c| c.as_ref().into()))
+///     .set("use_shadows", true)
+///     .set_at_index(1, [0.5f32, 0.5, 1.0])
+///     .build()?;
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct FunctionConstantValuesBuilder {
+    /// Sorted by key.
+    entries: Vec<FunctionConstantEntry>,
+    reflection: Option<Vec<FunctionConstantInfo>>,
+}
+
+/// Builders compare and hash by their values, whatever order they were set
+/// in; reflection data only affects validation and is ignored.
+impl PartialEq for FunctionConstantValuesBuilder {
+    fn eq(&self, other: &Self) -> bool {
+        self.entries == other.entries
+    }
+}
+
+impl Eq for FunctionConstantValuesBuilder {}
+
+impl std::hash::Hash for FunctionConstantValuesBuilder {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.entries.hash(state);
+    }
+}
+
+impl FunctionConstantValuesBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Check values against these declared constants.
+    pub fn reflection<I>(&mut self, constants: I) -> &mut Self
+    where
+        I: IntoIterator<Item = FunctionConstantInfo>,
+    {
+        self.reflection = Some(constants.into_iter().collect());
+        self
+    }
+
+    /// Set the constant named `name`, replacing any earlier value.
+    pub fn set<T: FunctionConstantValue>(&mut self, name: &str, value: T) -> &mut Self {
+        self.insert(FunctionConstantKey::Name(name.to_string()), value)
+    }
+
+    /// Set the constant at `index`, replacing any earlier value.
+    pub fn set_at_index<T: FunctionConstantValue>(
+        &mut self,
+        index: NSUInteger,
+        value: T,
+    ) -> &mut Self {
+        self.insert(FunctionConstantKey::Index(index), value)
+    }
+
+    fn insert<T: FunctionConstantValue>(
+        &mut self,
+        key: FunctionConstantKey,
+        value: T,
+    ) -> &mut Self {
+        let raw = value.to_raw();
+        // SAFETY: `raw` is a plain `Copy` value, viewed as bytes for storage only.
+        let bytes = unsafe {
+            std::slice::from_raw_parts(
+                (&raw as *const T::Raw).cast::<u8>(),
+                mem::size_of::<T::Raw>(),
+            )
+        };
+        let entry = FunctionConstantEntry {
+            key,
+            data_type: T::DATA_TYPE,
+            bytes: bytes.to_vec(),
+        };
+        match self.entries.binary_search_by(|e| e.key.cmp(&entry.key)) {
+            Ok(index) => self.entries[index] = entry,
+            Err(index) => self.entries.insert(index, entry),
+        }
+        self
+    }
+
+    /// The data type recorded for `key`, if a value was set.
+    pub fn data_type(&self, key: &FunctionConstantKey) -> Option<MTLDataType> {
+        self.entries
+            .iter()
+            .find(|e| &e.key == key)
+            .map(|e| e.data_type)
+    }
+
+    /// Check the collected values against the reflection data, if any.
+    ///
+    /// Errors are in [`MetalRsErrorDomain`].
+    pub fn validate(&self) -> Result<(), MetalError> {
+        let reflection = match &self.reflection {
+            Some(reflection) => reflection,
+            None => return Ok(()),
+        };
+
+        let mut assigned = Vec::with_capacity(self.entries.len());
+        for entry in &self.entries {
+            let declared = reflection.iter().find(|c| match &entry.key {
+                FunctionConstantKey::Index(index) => c.index == *index,
+                FunctionConstantKey::Name(name) => &c.name == name,
+            });
+            let declared = match declared {
+                Some(declared) => declared,
+                None => {
+                    return Err(MetalError::invalid_argument(&format!(
+                        "{} is not declared by the function",
+                        entry.key
+                    )))
+                }
+            };
+            if declared.data_type != entry.data_type {
+                return Err(MetalError::invalid_argument(&format!(
+                    "{} has type {:?}, but a {:?} value was provided",
+                    entry.key, declared.data_type, entry.data_type
+                )));
+            }
+            if assigned.contains(&declared.index) {
+                return Err(MetalError::invalid_argument(&format!(
+                    "function constant '{}' is set both by name and by index",
+                    declared.name
+                )));
+            }
+            assigned.push(declared.index);
+        }
+
+        match reflection
+            .iter()
+            .find(|c| c.required && !assigned.contains(&c.index))
+        {
+            Some(missing) => Err(MetalError::invalid_argument(&format!(
+                "required function constant '{}' has no value",
+                missing.name
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Validate the values and create a [`FunctionConstantValues`] from them.
+    pub fn build(&self) -> Result<FunctionConstantValues, MetalError> {
+        self.validate()?;
+        let values = FunctionConstantValues::new();
+        for entry in &self.entries {
+            let value = entry.bytes.as_ptr().cast();
+            match &entry.key {
+                FunctionConstantKey::Index(index) => {
+                    values.set_constant_value_at_index(value, entry.data_type, *index)
+                }
+                FunctionConstantKey::Name(name) => {
+                    values.set_constant_value_with_name(value, entry.data_type, name)
+                }
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// A Rust value that can be passed to Metal as a function constant.
+///
+/// # Safety
+///
+/// `Raw` must have the exact in-memory layout Metal expects for `DATA_TYPE`.
+pub unsafe trait FunctionConstantValue: Copy {
+    const DATA_TYPE: MTLDataType;
+    /// The representation handed to Metal. Three-component vectors are
+    /// padded to four components, like their MSL counterparts.
+    type Raw: Copy;
+
+    fn to_raw(self) -> Self::Raw;
+}
+
+macro_rules! function_constant_value {
+    ($ty:ty => $scalar:ident, $vec2:ident, $vec3:ident, $vec4:ident) => {
+        unsafe impl FunctionConstantValue for $ty {
+            const DATA_TYPE: MTLDataType = MTLDataType::$scalar;
+            type Raw = $ty;
+
+            fn to_raw(self) -> $ty {
+                self
+            }
+        }
+
+        unsafe impl FunctionConstantValue for [$ty; 2] {
+            const DATA_TYPE: MTLDataType = MTLDataType::$vec2;
+            type Raw = [$ty; 2];
+
+            fn to_raw(self) -> [$ty; 2] {
+                self
+            }
+        }
+
+        unsafe impl FunctionConstantValue for [$ty; 3] {
+            const DATA_TYPE: MTLDataType = MTLDataType::$vec3;
+            type Raw = [$ty; 4];
+
+            fn to_raw(self) -> [$ty; 4] {
+                [self[0], self[1], self[2], <$ty>::default()]
+            }
+        }
+
+        unsafe impl FunctionConstantValue for [$ty; 4] {
+            const DATA_TYPE: MTLDataType = MTLDataType::$vec4;
+            type Raw = [$ty; 4];
+
+            fn to_raw(self) -> [$ty; 4] {
+                self
+            }
+        }
+    };
 }
 
+function_constant_value!(bool => Bool, Bool2, Bool3, Bool4);
+function_constant_value!(i8 => Char, Char2, Char3, Char4);
+function_constant_value!(u8 => UChar, UChar2, UChar3, UChar4);
+function_constant_value!(i16 => Short, Short2, Short3, Short4);
+function_constant_value!(u16 => UShort, UShort2, UShort3, UShort4);
+function_constant_value!(i32 => Int, Int2, Int3, Int4);
+function_constant_value!(u32 => UInt, UInt2, UInt3, UInt4);
+function_constant_value!(i64 => Long, Long2, Long3, Long4);
+function_constant_value!(u64 => ULong, ULong2, ULong3, ULong4);
+function_constant_value!(f32 => Float, Float2, Float3, Float4);
+#[cfg(feature = "half")]
+function_constant_value!(half::f16 => Half, Half2, Half3, Half4);
+
 /// Only available on (macos(11.0), ios(14.0))
 ///
 /// See <https://developer.apple.com/documentation/metal/mtllibrarytype/>
@@ -588,7 +912,7 @@ impl LibraryRef {
         &self,
         name: &str,
         constants: Option<FunctionConstantValues>,
-    ) -> Result<Function, String> {
+    ) -> Result<Function, MetalError> {
         unsafe {
             let nsname = crate::nsstring_from_str(name);
 
@@ -604,7 +928,7 @@ impl LibraryRef {
             if !function.is_null() {
                 Ok(Function::from_ptr(function))
             } else {
-                Err(format!("Function '{}' does not exist", name))
+                Err(MetalError::function_not_found(name))
             }
         }
     }
@@ -641,7 +965,7 @@ impl LibraryRef {
     pub fn new_function_with_descriptor(
         &self,
         descriptor: &FunctionDescriptorRef,
-    ) -> Result<Function, String> {
+    ) -> Result<Function, MetalError> {
         unsafe {
             let function: *mut MTLFunction = try_objc! {
                 err => msg_send![self,
@@ -653,7 +977,7 @@ impl LibraryRef {
             if !function.is_null() {
                 Ok(Function::from_ptr(function))
             } else {
-                Err(String::from("new_function_with_descriptor() failed"))
+                Err(MetalError::function_not_found(descriptor.name()))
             }
         }
     }
@@ -662,7 +986,7 @@ impl LibraryRef {
     pub fn new_intersection_function_with_descriptor(
         &self,
         descriptor: &IntersectionFunctionDescriptorRef,
-    ) -> Result<Function, String> {
+    ) -> Result<Function, MetalError> {
         unsafe {
             let function: *mut MTLFunction = try_objc! {
                 err => msg_send![self,
@@ -674,9 +998,7 @@ impl LibraryRef {
             if !function.is_null() {
                 Ok(Function::from_ptr(function))
             } else {
-                Err(String::from(
-                    "new_intersection_function_with_descriptor() failed",
-                ))
+                Err(MetalError::function_not_found(descriptor.name()))
             }
         }
     }
@@ -730,7 +1052,7 @@ impl DynamicLibraryRef {
         }
     }
 
-    pub fn serialize_to_url(&self, url: &URLRef) -> Result<bool, String> {
+    pub fn serialize_to_url(&self, url: &URLRef) -> Result<bool, MetalError> {
         unsafe { msg_send_bool_error_check![self, serializeToURL: url] }
     }
 }
@@ -763,6 +1085,19 @@ impl BinaryArchiveDescriptorRef {
     }
 }
 
+/// macOS 11.0+ iOS 14.0+
+///
+/// See <https://developer.apple.com/documentation/metal/mtlbinaryarchiveerror/>
+#[repr(u64)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum MTLBinaryArchiveError {
+    None = 0,
+    InvalidFile = 1,
+    UnexpectedElement = 2,
+    CompilationFailure = 3,
+    InternalError = 4,
+}
+
 /// macOS 11.0+ iOS 14.0+
 ///
 /// See <https://developer.apple.com/documentation/metal/mtlbinaryarchive/>
@@ -795,7 +1130,7 @@ impl BinaryArchiveRef {
     pub fn add_compute_pipeline_functions_with_descriptor(
         &self,
         descriptor: &ComputePipelineDescriptorRef,
-    ) -> Result<bool, String> {
+    ) -> Result<bool, MetalError> {
         unsafe {
             msg_send_bool_error_check![self, addComputePipelineFunctionsWithDescriptor: descriptor]
         }
@@ -804,27 +1139,28 @@ impl BinaryArchiveRef {
     pub fn add_render_pipeline_functions_with_descriptor(
         &self,
         descriptor: &RenderPipelineDescriptorRef,
-    ) -> Result<bool, String> {
+    ) -> Result<bool, MetalError> {
         unsafe {
             msg_send_bool_error_check![self, addRenderPipelineFunctionsWithDescriptor: descriptor]
         }
     }
 
-    // TODO: addTileRenderPipelineFunctionsWithDescriptor
-    // - (BOOL)addTileRenderPipelineFunctionsWithDescriptor:(MTLTileRenderPipelineDescriptor *)descriptor
-    // error:(NSError * _Nullable *)error;
+    pub fn add_tile_render_pipeline_functions_with_descriptor(
+        &self,
+        descriptor: &TileRenderPipelineDescriptorRef,
+    ) -> Result<bool, MetalError> {
+        unsafe {
+            msg_send_bool_error_check![self, addTileRenderPipelineFunctionsWithDescriptor: descriptor]
+        }
+    }
 
-    pub fn serialize_to_url(&self, url: &URLRef) -> Result<bool, String> {
+    pub fn serialize_to_url(&self, url: &URLRef) -> Result<bool, MetalError> {
         unsafe {
             let mut err: *mut Object = ptr::null_mut();
             let result: BOOL = msg_send![self, serializeToURL:url
                                                         error:&mut err];
             if !err.is_null() {
-                // FIXME: copy pasta
-                let desc: *mut Object = msg_send![err, localizedDescription];
-                let c_msg: *const c_char = msg_send![desc, UTF8String];
-                let message = CStr::from_ptr(c_msg).to_string_lossy().into_owned();
-                Err(message)
+                Err(MetalError::from_nserror(err))
             } else {
                 match result {
                     YES => Ok(true),
@@ -901,3 +1237,141 @@ impl LinkedFunctionsRef {
     // TODO: groups
     // @property (readwrite, nonatomic, copy, nullable) NSDictionary<NSString*, NSArray<id<MTLFunction>>*> *groups;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constant(
+        name: &str,
+        index: NSUInteger,
+        data_type: MTLDataType,
+        required: bool,
+    ) -> FunctionConstantInfo {
+        FunctionConstantInfo {
+            name: name.to_string(),
+            index,
+            data_type,
+            required,
+        }
+    }
+
+    /// A builder checking against `use_shadows` (index 0, required) and
+    /// `light_color` (index 1, optional).
+    fn builder() -> FunctionConstantValuesBuilder {
+        let mut builder = FunctionConstantValuesBuilder::new();
+        builder.reflection([
+            constant("use_shadows", 0, MTLDataType::Bool, true),
+            constant("light_color", 1, MTLDataType::Float3, false),
+        ]);
+        builder
+    }
+
+    fn error(builder: &FunctionConstantValuesBuilder) -> String {
+        let error = builder.validate().unwrap_err();
+        assert_eq!(error.domain(), MetalRsErrorDomain);
+        error.to_string()
+    }
+
+    #[test]
+    fn valid_values() {
+        let mut builder = builder();
+        builder.set("use_shadows", true);
+        assert_eq!(builder.validate(), Ok(()));
+        builder.set_at_index(1, [1.0f32, 0.5, 0.25]);
+        assert_eq!(builder.validate(), Ok(()));
+        assert_eq!(
+            builder.data_type(&FunctionConstantKey::Index(1)),
+            Some(MTLDataType::Float3)
+        );
+        assert_eq!(
+            builder.data_type(&FunctionConstantKey::Name("light_color".into())),
+            None
+        );
+
+        // Without reflection data anything goes.
+        let mut unchecked = FunctionConstantValuesBuilder::new();
+        unchecked.set("anything", 1u8).set_at_index(7, 2i16);
+        assert_eq!(unchecked.validate(), Ok(()));
+    }
+
+    #[test]
+    fn undeclared_constant() {
+        let mut builder = builder();
+        builder.set("use_shadows", true).set("use_fog", true);
+        assert_eq!(
+            error(&builder),
+            "function constant 'use_fog' is not declared by the function"
+        );
+
+        let mut builder = self::builder();
+        builder.set("use_shadows", true).set_at_index(2, true);
+        assert_eq!(
+            error(&builder),
+            "function constant 2 is not declared by the function"
+        );
+    }
+
+    #[test]
+    fn type_mismatch() {
+        let mut builder = builder();
+        builder.set("use_shadows", 1u32);
+        assert_eq!(
+            error(&builder),
+            "function constant 'use_shadows' has type Bool, but a UInt value was provided"
+        );
+
+        let mut builder = self::builder();
+        builder
+            .set("use_shadows", true)
+            .set_at_index(1, [1.0f32, 0.5, 0.25, 1.0]);
+        assert_eq!(
+            error(&builder),
+            "function constant 1 has type Float3, but a Float4 value was provided"
+        );
+    }
+
+    #[test]
+    fn set_by_name_and_index() {
+        let mut builder = builder();
+        builder.set("use_shadows", true).set_at_index(0, false);
+        assert_eq!(
+            error(&builder),
+            "function constant 'use_shadows' is set both by name and by index"
+        );
+    }
+
+    #[test]
+    fn missing_required_constant() {
+        let mut builder = builder();
+        assert_eq!(
+            error(&builder),
+            "required function constant 'use_shadows' has no value"
+        );
+        builder.set("light_color", [1.0f32; 3]);
+        assert_eq!(
+            error(&builder),
+            "required function constant 'use_shadows' has no value"
+        );
+        builder.set_at_index(0, true);
+        assert_eq!(builder.validate(), Ok(()));
+    }
+
+    #[test]
+    fn three_component_vectors_are_padded() {
+        assert_eq!([1.0f32, 2.0, 3.0].to_raw(), [1.0, 2.0, 3.0, 0.0]);
+        assert_eq!([true; 3].to_raw(), [true, true, true, false]);
+        assert_eq!([-1i8, 2, -3].to_raw(), [-1, 2, -3, 0]);
+        assert_eq!([1u16, 2].to_raw(), [1, 2]);
+        assert_eq!(<[u32; 3]>::DATA_TYPE, MTLDataType::UInt3);
+
+        let mut builder = FunctionConstantValuesBuilder::new();
+        builder.set_at_index(0, [7u32, 8, 9]);
+        let words: Vec<u32> = builder.entries[0]
+            .bytes
+            .chunks(4)
+            .map(|word| u32::from_ne_bytes(word.try_into().unwrap()))
+            .collect();
+        assert_eq!(words, [7, 8, 9, 0]);
+    }
+}
@@ -0,0 +1,679 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::*;
+
+use log::warn;
+use std::collections::BTreeMap;
+use std::fmt;
+
+const STORAGE_MODES: [MTLStorageMode; 4] = [
+    MTLStorageMode::Shared,
+    MTLStorageMode::Managed,
+    MTLStorageMode::Private,
+    MTLStorageMode::Memoryless,
+];
+
+/// Budget fractions that raise a warning by default.
+pub const DEFAULT_MEMORY_WARNING_THRESHOLDS: [f64; 3] = [0.8, 0.95, 1.0];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MemoryEntryId(u64);
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum MemoryEntryKind {
+    Resource,
+    /// A heap; `used` is how much of it its resources take.
+    Heap {
+        used: NSUInteger,
+    },
+}
+
+/// One tracked allocation.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MemoryEntry {
+    pub category: String,
+    pub label: String,
+    pub storage_mode: MTLStorageMode,
+    pub kind: MemoryEntryKind,
+    pub size: NSUInteger,
+}
+
+impl MemoryEntry {
+    pub fn resource(
+        category: impl Into<String>,
+        label: impl Into<String>,
+        storage_mode: MTLStorageMode,
+        size: NSUInteger,
+    ) -> Self {
+        MemoryEntry {
+            category: category.into(),
+            label: label.into(),
+            storage_mode,
+            kind: MemoryEntryKind::Resource,
+            size,
+        }
+    }
+
+    pub fn heap(
+        category: impl Into<String>,
+        label: impl Into<String>,
+        storage_mode: MTLStorageMode,
+        size: NSUInteger,
+        used: NSUInteger,
+    ) -> Self {
+        MemoryEntry {
+            category: category.into(),
+            label: label.into(),
+            storage_mode,
+            kind: MemoryEntryKind::Heap { used },
+            size,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MemoryCategoryTotal {
+    pub category: String,
+    pub size: NSUInteger,
+    pub entries: usize,
+}
+
+/// Raised when the tracked total first goes over `threshold * budget`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MemoryBudgetWarning {
+    pub threshold: f64,
+    pub total: NSUInteger,
+    pub budget: NSUInteger,
+}
+
+impl fmt::Display for MemoryBudgetWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "GPU memory use {} bytes is over {:.0}% of the {} byte budget",
+            self.total,
+            self.threshold * 100.0,
+            self.budget
+        )
+    }
+}
+
+/// Device-independent memory accounting: allocations registered with a
+/// category and label, summed by category and storage mode and checked
+/// against a budget.
+///
+/// A warning is raised once each time the total rises past a threshold; it
+/// is logged and queued for [`take_warnings`](Self::take_warnings). Dropping
+/// back below a threshold re-arms it.
+///
+/// Resources placed in a tracked heap are already counted by the heap and
+/// should not be registered separately.
+#[derive(Clone, Debug)]
+pub struct MemoryTracker {
+    entries: BTreeMap<MemoryEntryId, MemoryEntry>,
+    next_id: u64,
+    budget: NSUInteger,
+    thresholds: Vec<f64>,
+    crossed: usize,
+    warnings: Vec<MemoryBudgetWarning>,
+}
+
+impl MemoryTracker {
+    /// A `budget` of 0 disables warnings.
+    pub fn new(budget: NSUInteger) -> Self {
+        MemoryTracker {
+            entries: BTreeMap::new(),
+            next_id: 0,
+            budget,
+            thresholds: DEFAULT_MEMORY_WARNING_THRESHOLDS.to_vec(),
+            crossed: 0,
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn budget(&self) -> NSUInteger {
+        self.budget
+    }
+
+    pub fn set_budget(&mut self, budget: NSUInteger) {
+        self.budget = budget;
+        self.update_warnings();
+    }
+
+    pub fn thresholds(&self) -> &[f64] {
+        &self.thresholds
+    }
+
+    /// Fractions of the budget to warn at, e.g. `[0.75, 1.0]`.
+    pub fn set_thresholds(&mut self, thresholds: &[f64]) {
+        self.thresholds = thresholds.to_vec();
+        self.thresholds.sort_by(f64::total_cmp);
+        self.crossed = 0;
+        self.update_warnings();
+    }
+
+    pub fn register(&mut self, entry: MemoryEntry) -> MemoryEntryId {
+        let id = MemoryEntryId(self.next_id);
+        self.next_id += 1;
+        self.entries.insert(id, entry);
+        self.update_warnings();
+        id
+    }
+
+    pub fn unregister(&mut self, id: MemoryEntryId) -> Option<MemoryEntry> {
+        let entry = self.entries.remove(&id);
+        self.update_warnings();
+        entry
+    }
+
+    /// Returns `false` if `id` isn't registered.
+    pub fn resize(&mut self, id: MemoryEntryId, size: NSUInteger) -> bool {
+        match self.entries.get_mut(&id) {
+            Some(entry) => {
+                entry.size = size;
+                self.update_warnings();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns `false` if `id` isn't a registered heap.
+    pub fn set_heap_used(&mut self, id: MemoryEntryId, used: NSUInteger) -> bool {
+        match self.entries.get_mut(&id) {
+            Some(MemoryEntry {
+                kind: MemoryEntryKind::Heap { used: heap_used },
+                ..
+            }) => {
+                *heap_used = used;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn entry(&self, id: MemoryEntryId) -> Option<&MemoryEntry> {
+        self.entries.get(&id)
+    }
+
+    pub fn entries(&self) -> impl Iterator<Item = (MemoryEntryId, &MemoryEntry)> {
+        self.entries.iter().map(|(&id, entry)| (id, entry))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn total(&self) -> NSUInteger {
+        self.entries.values().map(|entry| entry.size).sum()
+    }
+
+    pub fn category_total(&self, category: &str) -> NSUInteger {
+        self.entries
+            .values()
+            .filter(|entry| entry.category == category)
+            .map(|entry| entry.size)
+            .sum()
+    }
+
+    /// Totals per category, largest first.
+    pub fn by_category(&self) -> Vec<MemoryCategoryTotal> {
+        let mut totals = BTreeMap::<&str, (NSUInteger, usize)>::new();
+        for entry in self.entries.values() {
+            let total = totals.entry(&entry.category).or_default();
+            total.0 += entry.size;
+            total.1 += 1;
+        }
+        let mut totals = totals
+            .into_iter()
+            .map(|(category, (size, entries))| MemoryCategoryTotal {
+                category: category.to_owned(),
+                size,
+                entries,
+            })
+            .collect::<Vec<_>>();
+        totals.sort_by_key(|total| std::cmp::Reverse(total.size));
+        totals
+    }
+
+    /// Totals per storage mode, for the modes that have any entries.
+    pub fn by_storage_mode(&self) -> Vec<(MTLStorageMode, NSUInteger)> {
+        STORAGE_MODES
+            .iter()
+            .filter_map(|&mode| {
+                let mut entries = self
+                    .entries
+                    .values()
+                    .filter(|entry| entry.storage_mode == mode)
+                    .peekable();
+                entries.peek()?;
+                Some((mode, entries.map(|entry| entry.size).sum()))
+            })
+            .collect()
+    }
+
+    /// The tracked total as a fraction of the budget, or `None` without one.
+    pub fn budget_usage(&self) -> Option<f64> {
+        if self.budget == 0 {
+            None
+        } else {
+            Some(self.total() as f64 / self.budget as f64)
+        }
+    }
+
+    pub fn remaining_budget(&self) -> NSUInteger {
+        self.budget.saturating_sub(self.total())
+    }
+
+    /// Warnings raised since the last call.
+    pub fn take_warnings(&mut self) -> Vec<MemoryBudgetWarning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// A snapshot of the tracked memory. `device_allocated` is
+    /// `DeviceRef::current_allocated_size`, if known, and is used to show
+    /// how much memory isn't tracked.
+    pub fn report(&self, device_allocated: Option<NSUInteger>) -> MemoryReport {
+        let mut entries = self.entries.values().cloned().collect::<Vec<_>>();
+        entries.sort_by(|a, b| {
+            (&a.category, std::cmp::Reverse(a.size)).cmp(&(&b.category, std::cmp::Reverse(b.size)))
+        });
+        MemoryReport {
+            total: self.total(),
+            budget: self.budget,
+            device_allocated,
+            categories: self.by_category(),
+            storage_modes: self.by_storage_mode(),
+            entries,
+        }
+    }
+
+    fn update_warnings(&mut self) {
+        let usage = self.budget_usage().unwrap_or(0.0);
+        let crossed = self
+            .thresholds
+            .iter()
+            .take_while(|&&threshold| usage >= threshold)
+            .count();
+        let total = self.total();
+        for &threshold in self.thresholds.get(self.crossed..crossed).unwrap_or(&[]) {
+            let warning = MemoryBudgetWarning {
+                threshold,
+                total,
+                budget: self.budget,
+            };
+            warn!("{}", warning);
+            self.warnings.push(warning);
+        }
+        self.crossed = crossed;
+    }
+}
+
+/// A snapshot from [`MemoryTracker::report`], printable with `Display`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct MemoryReport {
+    pub total: NSUInteger,
+    pub budget: NSUInteger,
+    pub device_allocated: Option<NSUInteger>,
+    pub categories: Vec<MemoryCategoryTotal>,
+    pub storage_modes: Vec<(MTLStorageMode, NSUInteger)>,
+    /// Grouped by category, largest first.
+    pub entries: Vec<MemoryEntry>,
+}
+
+impl MemoryReport {
+    /// Memory the device reports that isn't registered with the tracker.
+    pub fn untracked(&self) -> Option<NSUInteger> {
+        self.device_allocated
+            .map(|allocated| allocated.saturating_sub(self.total))
+    }
+}
+
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "GPU memory: {} bytes", self.total)?;
+        if self.budget != 0 {
+            write!(
+                f,
+                " of {} budget ({:.1}%)",
+                self.budget,
+                self.total as f64 * 100.0 / self.budget as f64
+            )?;
+        }
+        writeln!(f)?;
+        if let (Some(allocated), Some(untracked)) = (self.device_allocated, self.untracked()) {
+            writeln!(
+                f,
+                "  device allocated: {} bytes ({} untracked)",
+                allocated, untracked
+            )?;
+        }
+        for (mode, size) in &self.storage_modes {
+            writeln!(f, "  {:?}: {} bytes", mode, size)?;
+        }
+        for category in &self.categories {
+            writeln!(
+                f,
+                "  {}: {} bytes in {} allocations",
+                category.category, category.size, category.entries
+            )?;
+            for entry in self
+                .entries
+                .iter()
+                .filter(|e| e.category == category.category)
+            {
+                write!(
+                    f,
+                    "    {} ({:?}): {} bytes",
+                    entry.label, entry.storage_mode, entry.size
+                )?;
+                if let MemoryEntryKind::Heap { used } = entry.kind {
+                    write!(f, ", heap {} bytes used", used)?;
+                }
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A [`MemoryTracker`] fed from live resources and heaps, with the budget
+/// taken from `recommended_max_working_set_size`.
+///
+/// ```ignore
+/// let mut memory = DeviceMemoryTracker::new(&device);
+/// let shadow_map = device.new_texture(&descriptor);
+/// let id = memory.register_resource(&shadow_map, "shadows");
+/// // ...
+/// println!("{}", memory.report());
+/// for warning in memory.tracker_mut().take_warnings() {
+///     shrink_caches();
+/// }
+/// ```
+pub struct DeviceMemoryTracker {
+    device: Device,
+    tracker: MemoryTracker,
+}
+
+impl DeviceMemoryTracker {
+    pub fn new(device: &DeviceRef) -> Self {
+        DeviceMemoryTracker {
+            device: device.to_owned(),
+            tracker: MemoryTracker::new(device.recommended_max_working_set_size()),
+        }
+    }
+
+    pub fn device(&self) -> &DeviceRef {
+        &self.device
+    }
+
+    pub fn tracker(&self) -> &MemoryTracker {
+        &self.tracker
+    }
+
+    pub fn tracker_mut(&mut self) -> &mut MemoryTracker {
+        &mut self.tracker
+    }
+
+    /// Register a resource by its `allocated_size`, labelled with its label.
+    pub fn register_resource(
+        &mut self,
+        resource: &ResourceRef,
+        category: impl Into<String>,
+    ) -> MemoryEntryId {
+        self.tracker.register(MemoryEntry::resource(
+            category,
+            resource.label(),
+            resource.storage_mode(),
+            resource.allocated_size(),
+        ))
+    }
+
+    pub fn register_heap(&mut self, heap: &HeapRef, category: impl Into<String>) -> MemoryEntryId {
+        self.tracker.register(MemoryEntry::heap(
+            category,
+            heap.label(),
+            heap.storage_mode(),
+            heap.size(),
+            heap.used_size(),
+        ))
+    }
+
+    /// Re-read a heap's `used_size`.
+    pub fn refresh_heap(&mut self, id: MemoryEntryId, heap: &HeapRef) -> bool {
+        self.tracker.set_heap_used(id, heap.used_size())
+    }
+
+    pub fn unregister(&mut self, id: MemoryEntryId) -> Option<MemoryEntry> {
+        self.tracker.unregister(id)
+    }
+
+    /// Re-read the budget from the device; it can change at runtime.
+    pub fn refresh_budget(&mut self) {
+        self.tracker
+            .set_budget(self.device.recommended_max_working_set_size());
+    }
+
+    pub fn report(&self) -> MemoryReport {
+        self.tracker
+            .report(Some(self.device.current_allocated_size()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thresholds(warnings: &[MemoryBudgetWarning]) -> Vec<f64> {
+        warnings.iter().map(|warning| warning.threshold).collect()
+    }
+
+    #[test]
+    fn totals() {
+        let mut tracker = MemoryTracker::new(0);
+        assert!(tracker.is_empty());
+        let albedo = tracker.register(MemoryEntry::resource(
+            "textures",
+            "albedo",
+            MTLStorageMode::Private,
+            500,
+        ));
+        let heap = tracker.register(MemoryEntry::heap(
+            "transient",
+            "frame heap",
+            MTLStorageMode::Private,
+            300,
+            100,
+        ));
+        tracker.register(MemoryEntry::resource(
+            "textures",
+            "normal",
+            MTLStorageMode::Managed,
+            10,
+        ));
+        tracker.register(MemoryEntry::resource(
+            "buffers",
+            "vertices",
+            MTLStorageMode::Shared,
+            250,
+        ));
+        assert_eq!((tracker.len(), tracker.total()), (4, 1060));
+        assert_eq!(tracker.category_total("textures"), 510);
+        assert_eq!(tracker.category_total("missing"), 0);
+        assert_eq!(
+            tracker.by_category(),
+            [
+                MemoryCategoryTotal {
+                    category: "textures".to_owned(),
+                    size: 510,
+                    entries: 2
+                },
+                MemoryCategoryTotal {
+                    category: "transient".to_owned(),
+                    size: 300,
+                    entries: 1
+                },
+                MemoryCategoryTotal {
+                    category: "buffers".to_owned(),
+                    size: 250,
+                    entries: 1
+                },
+            ]
+        );
+        assert_eq!(
+            tracker.by_storage_mode(),
+            [
+                (MTLStorageMode::Shared, 250),
+                (MTLStorageMode::Managed, 10),
+                (MTLStorageMode::Private, 800),
+            ]
+        );
+
+        assert!(tracker.resize(albedo, 700));
+        assert!(tracker.set_heap_used(heap, 200));
+        assert!(!tracker.set_heap_used(albedo, 1));
+        assert_eq!(
+            tracker.entry(heap).unwrap().kind,
+            MemoryEntryKind::Heap { used: 200 }
+        );
+        let removed = tracker.unregister(albedo).unwrap();
+        assert_eq!((removed.label.as_str(), removed.size), ("albedo", 700));
+        assert!(tracker.unregister(albedo).is_none());
+        assert!(!tracker.resize(albedo, 1));
+        assert_eq!(tracker.total(), 560);
+        // Ids aren't reused.
+        let id = tracker.register(MemoryEntry::resource("a", "b", MTLStorageMode::Shared, 1));
+        assert!(tracker.entries().all(|(other, _)| other <= id));
+        assert!(id > albedo);
+    }
+
+    #[test]
+    fn warnings() {
+        let mut tracker = MemoryTracker::new(1000);
+        let texture = tracker.register(MemoryEntry::resource(
+            "textures",
+            "albedo",
+            MTLStorageMode::Private,
+            500,
+        ));
+        assert!(tracker.take_warnings().is_empty());
+        tracker.register(MemoryEntry::heap(
+            "transient",
+            "frame heap",
+            MTLStorageMode::Private,
+            300,
+            100,
+        ));
+        let warnings = tracker.take_warnings();
+        assert_eq!(
+            warnings,
+            [MemoryBudgetWarning {
+                threshold: 0.8,
+                total: 800,
+                budget: 1000
+            }]
+        );
+        assert_eq!(
+            warnings[0].to_string(),
+            "GPU memory use 800 bytes is over 80% of the 1000 byte budget"
+        );
+        // Crossing several thresholds at once raises each of them.
+        let buffer = tracker.register(MemoryEntry::resource(
+            "buffers",
+            "vertices",
+            MTLStorageMode::Shared,
+            250,
+        ));
+        assert_eq!(thresholds(&tracker.take_warnings()), [0.95, 1.0]);
+        assert!(tracker.take_warnings().is_empty());
+        // Staying above a threshold doesn't warn again; dropping below it
+        // re-arms it.
+        tracker.resize(texture, 510);
+        assert!(tracker.take_warnings().is_empty());
+        tracker.unregister(buffer);
+        assert!(tracker.take_warnings().is_empty());
+        tracker.resize(texture, 700);
+        assert_eq!(thresholds(&tracker.take_warnings()), [0.95, 1.0]);
+        assert_eq!(
+            (tracker.remaining_budget(), tracker.budget_usage()),
+            (0, Some(1.0))
+        );
+
+        // A bigger budget re-arms them too.
+        tracker.set_budget(2000);
+        assert!(tracker.take_warnings().is_empty());
+        tracker.set_budget(1000);
+        assert_eq!(thresholds(&tracker.take_warnings()), [0.8, 0.95, 1.0]);
+
+        tracker.set_thresholds(&[1.0, 0.5]);
+        assert_eq!(tracker.thresholds(), [0.5, 1.0]);
+        assert_eq!(thresholds(&tracker.take_warnings()), [0.5, 1.0]);
+
+        tracker.set_budget(0);
+        assert_eq!(tracker.budget_usage(), None);
+        tracker.resize(texture, 1 << 40);
+        assert!(tracker.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn report() {
+        let mut tracker = MemoryTracker::new(2000);
+        tracker.register(MemoryEntry::resource(
+            "textures",
+            "normal",
+            MTLStorageMode::Managed,
+            10,
+        ));
+        tracker.register(MemoryEntry::heap(
+            "transient",
+            "frame heap",
+            MTLStorageMode::Private,
+            300,
+            100,
+        ));
+        tracker.register(MemoryEntry::resource(
+            "textures",
+            "albedo",
+            MTLStorageMode::Private,
+            700,
+        ));
+        let report = tracker.report(Some(1500));
+        assert_eq!(report.untracked(), Some(490));
+        assert_eq!(
+            report
+                .entries
+                .iter()
+                .map(|entry| entry.label.as_str())
+                .collect::<Vec<_>>(),
+            ["albedo", "normal", "frame heap"]
+        );
+        assert_eq!(
+            report.to_string(),
+            "GPU memory: 1010 bytes of 2000 budget (50.5%)\n\
+             \x20 device allocated: 1500 bytes (490 untracked)\n\
+             \x20 Managed: 10 bytes\n\
+             \x20 Private: 1000 bytes\n\
+             \x20 textures: 710 bytes in 2 allocations\n\
+             \x20   albedo (Private): 700 bytes\n\
+             \x20   normal (Managed): 10 bytes\n\
+             \x20 transient: 300 bytes in 1 allocations\n\
+             \x20   frame heap (Private): 300 bytes, heap 100 bytes used\n"
+        );
+
+        tracker.set_budget(0);
+        let report = tracker.report(None);
+        assert_eq!(report.untracked(), None);
+        assert!(report
+            .to_string()
+            .starts_with("GPU memory: 1010 bytes\n  Managed"));
+    }
+}
@@ -0,0 +1,1162 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Reading and writing of `.metallib` containers, the format consumed by
+//! [`DeviceRef::new_library_with_data`](crate::DeviceRef::new_library_with_data).
+//!
+//! The layout is undocumented by Apple. A file starts with a fixed 88 byte
+//! header, followed by the function table, an optional list of header
+//! extension tags, the public and private metadata sections, the bitcode
+//! section and finally any blobs (such as embedded source) that the
+//! extension tags point at. Tables are lists of 4-character tags with a
+//! `u16` length, terminated by an `ENDT` tag.
+
+use std::collections::HashSet;
+
+const MAGIC: &[u8; 4] = b"MTLB";
+const HEADER_SIZE: usize = 88;
+
+const TAG_END: [u8; 4] = *b"ENDT";
+const TAG_NAME: [u8; 4] = *b"NAME";
+const TAG_TYPE: [u8; 4] = *b"TYPE";
+const TAG_HASH: [u8; 4] = *b"HASH";
+const TAG_MDSZ: [u8; 4] = *b"MDSZ";
+const TAG_OFFT: [u8; 4] = *b"OFFT";
+const TAG_SOFF: [u8; 4] = *b"SOFF";
+const TAG_DEBI: [u8; 4] = *b"DEBI";
+const TAG_DEPF: [u8; 4] = *b"DEPF";
+
+/// Extension tags whose payload is an absolute `(offset, size)` pair pointing
+/// past the bitcode section.
+const BLOB_TAGS: [[u8; 4]; 4] = [*b"HSRC", *b"HSRD", *b"VLST", *b"ILST"];
+/// Extension tags that describe embedded shader source.
+const SOURCE_TAGS: [[u8; 4]; 2] = [*b"HSRC", *b"HSRD"];
+
+/// A single `NAME`-`data` entry of a metallib tag list.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MetallibTag {
+    pub name: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+impl MetallibTag {
+    pub fn new(name: [u8; 4], data: Vec<u8>) -> Self {
+        Self { name, data }
+    }
+
+    /// The tag name as a string, e.g. `"NAME"`.
+    pub fn name_str(&self) -> &str {
+        std::str::from_utf8(&self.name).unwrap_or("????")
+    }
+}
+
+/// The fixed fields of a metallib header that are not derived from the
+/// file layout.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MetallibHeader {
+    /// Raw target platform field. The high bit is set for macOS libraries.
+    pub platform: u16,
+    pub version_major: u16,
+    pub version_minor: u16,
+    /// 0 = executable, 1 = Core Image, 2 = dynamic, 3 = symbol companion.
+    pub library_type: u8,
+    /// Raw target OS, e.g. `0x81` for macOS; 0 if unspecified.
+    pub target_os: u8,
+    pub os_version_major: u16,
+    pub os_version_minor: u16,
+}
+
+impl MetallibHeader {
+    pub fn is_macos(&self) -> bool {
+        self.platform & 0x8000 != 0
+    }
+}
+
+/// A function entry together with the metadata and bitcode it owns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MetallibFunction {
+    /// All tags of the function table entry, in file order. The `MDSZ` and
+    /// `OFFT` payloads are rewritten by [`Metallib::to_bytes`].
+    pub tags: Vec<MetallibTag>,
+    /// Tags of this function's public metadata group (e.g. `CNST`).
+    pub public_metadata: Vec<MetallibTag>,
+    /// Tags of this function's private metadata group (e.g. `DEBI`, `DEPF`).
+    pub private_metadata: Vec<MetallibTag>,
+    pub bitcode: Vec<u8>,
+}
+
+impl MetallibFunction {
+    pub fn tag(&self, name: &[u8; 4]) -> Option<&MetallibTag> {
+        self.tags.iter().find(|tag| &tag.name == name)
+    }
+
+    pub fn name(&self) -> &str {
+        self.tag(&TAG_NAME)
+            .map(|tag| {
+                let data = tag.data.strip_suffix(&[0]).unwrap_or(&tag.data);
+                std::str::from_utf8(data).unwrap_or("")
+            })
+            .unwrap_or("")
+    }
+
+    /// Raw `TYPE` byte: 0 = vertex, 1 = fragment, 2 = kernel, 6 = intersection,
+    /// 7 = mesh, 8 = object.
+    pub fn function_type(&self) -> Option<u8> {
+        self.tag(&TAG_TYPE)
+            .and_then(|tag| tag.data.first().copied())
+    }
+
+    /// SHA-256 of the function's bitcode.
+    pub fn hash(&self) -> Option<&[u8]> {
+        self.tag(&TAG_HASH).map(|tag| &tag.data[..])
+    }
+
+    /// Offset of this function's source inside the embedded source blob.
+    pub fn source_offset(&self) -> Option<u64> {
+        self.tag(&TAG_SOFF)
+            .and_then(|tag| read_u64(&tag.data, 0).ok())
+    }
+}
+
+/// An out-of-line blob referenced from a header extension tag.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MetallibBlob {
+    pub tag: [u8; 4],
+    pub data: Vec<u8>,
+}
+
+/// An in-memory `.metallib` container.
+///
+/// ```ignore
+/// let mut lib = Metallib::parse(&std::fs::read("a.metallib")?)?;
+/// lib.merge(&Metallib::parse(&std::fs::read("b.metallib")?)?)?;
+/// lib.retain_functions(|f| f.name() != "unused");
+/// lib.strip_source();
+/// let library = device.new_library_with_data(&lib.to_bytes())?;
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Metallib {
+    pub header: MetallibHeader,
+    pub functions: Vec<MetallibFunction>,
+    /// Whether the file carries a header extension tag list. Only very old
+    /// metallibs omit it.
+    pub has_extension: bool,
+    /// Header extension tags, in file order. The payload of tags referencing
+    /// one of `blobs` is rewritten by [`Metallib::to_bytes`].
+    pub extension: Vec<MetallibTag>,
+    /// Blobs stored after the bitcode section, such as embedded source, one
+    /// per referencing extension tag.
+    pub blobs: Vec<MetallibBlob>,
+}
+
+impl Metallib {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < HEADER_SIZE {
+            return Err(format!(
+                "metallib is {} bytes, smaller than its header",
+                data.len()
+            ));
+        }
+        if &data[0..4] != MAGIC {
+            return Err(String::from("missing MTLB magic"));
+        }
+
+        let header = MetallibHeader {
+            platform: read_u16(data, 4)?,
+            version_major: read_u16(data, 6)?,
+            version_minor: read_u16(data, 8)?,
+            library_type: data[10],
+            target_os: data[11],
+            os_version_major: read_u16(data, 12)?,
+            os_version_minor: read_u16(data, 14)?,
+        };
+        let file_size = read_u64(data, 16)?;
+        if file_size != data.len() as u64 {
+            return Err(format!(
+                "header declares {} bytes but the file has {}",
+                file_size,
+                data.len()
+            ));
+        }
+        let function_list = section(data, 24)?;
+        let public_metadata = section(data, 40)?;
+        let private_metadata = section(data, 56)?;
+        let bitcode = section(data, 72)?;
+
+        let count = read_u32(data, function_list.start)? as usize;
+        let mut offset = add(function_list.start, 4)?;
+        let mut functions = Vec::new();
+        for _ in 0..count {
+            let entry_size = read_u32(data, offset)? as usize;
+            let (tags, _) = read_tags(data, add(offset, 4)?)?;
+            let mut function = MetallibFunction {
+                tags,
+                public_metadata: Vec::new(),
+                private_metadata: Vec::new(),
+                bitcode: Vec::new(),
+            };
+
+            let offt = function
+                .tag(&TAG_OFFT)
+                .ok_or_else(|| format!("function '{}' has no OFFT tag", function.name()))?;
+            let public_offset = to_usize(read_u64(&offt.data, 0)?)?;
+            let private_offset = to_usize(read_u64(&offt.data, 8)?)?;
+            let bitcode_offset = to_usize(read_u64(&offt.data, 16)?)?;
+            let bitcode_size = function
+                .tag(&TAG_MDSZ)
+                .ok_or_else(|| format!("function '{}' has no MDSZ tag", function.name()))
+                .and_then(|tag| read_u64(&tag.data, 0))
+                .and_then(to_usize)?;
+
+            function.public_metadata = read_group(data, &public_metadata, public_offset)?;
+            function.private_metadata = read_group(data, &private_metadata, private_offset)?;
+            function.bitcode = slice(data, &bitcode, bitcode_offset, bitcode_size)?.to_vec();
+
+            functions.push(function);
+            offset = add(offset, entry_size)?;
+        }
+
+        let has_extension = offset < public_metadata.start;
+        let mut extension = Vec::new();
+        let mut blobs = Vec::new();
+        if has_extension {
+            let (tags, _) = read_tags(data, offset)?;
+            for tag in tags {
+                if BLOB_TAGS.contains(&tag.name) {
+                    let start = to_usize(read_u64(&tag.data, 0)?)?;
+                    let size = to_usize(read_u64(&tag.data, 8)?)?;
+                    let blob = slice(data, &(0..data.len()), start, size)?;
+                    blobs.push(MetallibBlob {
+                        tag: tag.name,
+                        data: blob.to_vec(),
+                    });
+                }
+                extension.push(tag);
+            }
+        }
+
+        Ok(Metallib {
+            header,
+            functions,
+            has_extension,
+            extension,
+            blobs,
+        })
+    }
+
+    /// Serialize the container, recomputing every offset and size.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut function_list = Vec::new();
+        let mut public_metadata = Vec::new();
+        let mut private_metadata = Vec::new();
+        let mut bitcode_size = 0u64;
+
+        function_list.extend_from_slice(&(self.functions.len() as u32).to_le_bytes());
+        for function in &self.functions {
+            let mut offt = Vec::with_capacity(24);
+            offt.extend_from_slice(&(public_metadata.len() as u64).to_le_bytes());
+            offt.extend_from_slice(&(private_metadata.len() as u64).to_le_bytes());
+            offt.extend_from_slice(&bitcode_size.to_le_bytes());
+
+            let mut entry = Vec::new();
+            for tag in &function.tags {
+                let data = match tag.name {
+                    TAG_MDSZ => (function.bitcode.len() as u64).to_le_bytes().to_vec(),
+                    TAG_OFFT => offt.clone(),
+                    _ => tag.data.clone(),
+                };
+                write_tag(&mut entry, &tag.name, &data);
+            }
+            entry.extend_from_slice(&TAG_END);
+            function_list.extend_from_slice(&(entry.len() as u32 + 4).to_le_bytes());
+            function_list.extend_from_slice(&entry);
+
+            write_group(&mut public_metadata, &function.public_metadata);
+            write_group(&mut private_metadata, &function.private_metadata);
+            bitcode_size += function.bitcode.len() as u64;
+        }
+
+        let extension_size = if self.has_extension {
+            self.extension
+                .iter()
+                .map(|tag| 4 + 2 + tag.data.len())
+                .sum::<usize>()
+                + 4
+        } else {
+            0
+        };
+
+        let function_list_offset = HEADER_SIZE as u64;
+        let public_metadata_offset =
+            function_list_offset + function_list.len() as u64 + extension_size as u64;
+        let private_metadata_offset = public_metadata_offset + public_metadata.len() as u64;
+        let bitcode_offset = private_metadata_offset + private_metadata.len() as u64;
+        let blobs_offset = bitcode_offset + bitcode_size;
+        let blobs: Vec<&MetallibBlob> = if self.has_extension {
+            self.extension
+                .iter()
+                .filter(|tag| BLOB_TAGS.contains(&tag.name))
+                .filter_map(|tag| self.blobs.iter().find(|blob| blob.tag == tag.name))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let file_size = blobs_offset + blobs.iter().map(|b| b.data.len() as u64).sum::<u64>();
+
+        let mut out = Vec::with_capacity(file_size as usize);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&self.header.platform.to_le_bytes());
+        out.extend_from_slice(&self.header.version_major.to_le_bytes());
+        out.extend_from_slice(&self.header.version_minor.to_le_bytes());
+        out.push(self.header.library_type);
+        out.push(self.header.target_os);
+        out.extend_from_slice(&self.header.os_version_major.to_le_bytes());
+        out.extend_from_slice(&self.header.os_version_minor.to_le_bytes());
+        for value in [
+            file_size,
+            function_list_offset,
+            function_list.len() as u64 - 4,
+            public_metadata_offset,
+            public_metadata.len() as u64,
+            private_metadata_offset,
+            private_metadata.len() as u64,
+            bitcode_offset,
+            bitcode_size,
+        ] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+
+        out.extend_from_slice(&function_list);
+        if self.has_extension {
+            let mut blob_offset = blobs_offset;
+            for tag in &self.extension {
+                match self.blobs.iter().find(|blob| blob.tag == tag.name) {
+                    Some(blob) if BLOB_TAGS.contains(&tag.name) => {
+                        let mut data = blob_offset.to_le_bytes().to_vec();
+                        data.extend_from_slice(&(blob.data.len() as u64).to_le_bytes());
+                        write_tag(&mut out, &tag.name, &data);
+                        blob_offset += blob.data.len() as u64;
+                    }
+                    _ => write_tag(&mut out, &tag.name, &tag.data),
+                }
+            }
+            out.extend_from_slice(&TAG_END);
+        }
+        out.extend_from_slice(&public_metadata);
+        out.extend_from_slice(&private_metadata);
+        for function in &self.functions {
+            out.extend_from_slice(&function.bitcode);
+        }
+        for blob in blobs {
+            out.extend_from_slice(&blob.data);
+        }
+
+        debug_assert_eq!(out.len() as u64, file_size);
+        out
+    }
+
+    pub fn function_names(&self) -> Vec<&str> {
+        self.functions.iter().map(|f| f.name()).collect()
+    }
+
+    pub fn function(&self, name: &str) -> Option<&MetallibFunction> {
+        self.functions.iter().find(|f| f.name() == name)
+    }
+
+    /// Whether the library carries embedded shader source (`-frecord-sources`).
+    pub fn has_embedded_source(&self) -> bool {
+        self.blobs
+            .iter()
+            .any(|blob| SOURCE_TAGS.contains(&blob.tag))
+    }
+
+    /// Keep only the functions for which `keep` returns `true`.
+    pub fn retain_functions<F>(&mut self, keep: F)
+    where
+        F: FnMut(&MetallibFunction) -> bool,
+    {
+        self.functions.retain(keep);
+    }
+
+    /// Remove the embedded source archive and the per-function `SOFF` tags
+    /// pointing into it.
+    pub fn strip_source(&mut self) {
+        self.extension
+            .retain(|tag| !SOURCE_TAGS.contains(&tag.name));
+        self.blobs.retain(|blob| !SOURCE_TAGS.contains(&blob.tag));
+        for function in &mut self.functions {
+            function.tags.retain(|tag| tag.name != TAG_SOFF);
+        }
+    }
+
+    /// Remove the debug info and dependency file records from the private
+    /// metadata. Debug info compiled into the bitcode itself is left alone.
+    pub fn strip_debug_info(&mut self) {
+        for function in &mut self.functions {
+            function
+                .private_metadata
+                .retain(|tag| tag.name != TAG_DEBI && tag.name != TAG_DEPF);
+        }
+    }
+
+    /// Append the functions of `other` to this library.
+    ///
+    /// Both libraries must target the same platform and library type, and
+    /// function names must be unique. Embedded source archives cannot be
+    /// combined, so at most one of the libraries may carry one. Other header
+    /// extension tags and blobs of `other` are carried over; a tag present in
+    /// both libraries with different contents is an error.
+    pub fn merge(&mut self, other: &Metallib) -> Result<(), String> {
+        let (ours, theirs) = (self.header.target_os, other.header.target_os);
+        if self.header.platform != other.header.platform
+            || (ours != 0 && theirs != 0 && ours != theirs)
+        {
+            return Err(String::from(
+                "cannot merge metallibs built for different platforms",
+            ));
+        }
+        if self.header.library_type != other.header.library_type {
+            return Err(String::from(
+                "cannot merge metallibs of different library types",
+            ));
+        }
+        if self.has_embedded_source() && other.has_embedded_source() {
+            return Err(String::from(
+                "cannot merge two metallibs with embedded source, strip the source first",
+            ));
+        }
+
+        let names: HashSet<&str> = self.function_names().into_iter().collect();
+        if let Some(name) = other
+            .function_names()
+            .into_iter()
+            .find(|n| names.contains(n))
+        {
+            return Err(format!("function '{}' is defined in both metallibs", name));
+        }
+
+        let mut extension = Vec::new();
+        let mut blobs = Vec::new();
+        for tag in &other.extension {
+            if BLOB_TAGS.contains(&tag.name) {
+                let Some(blob) = other.blobs.iter().find(|blob| blob.tag == tag.name) else {
+                    continue;
+                };
+                // Source offsets are relative to the one source archive that
+                // survives, so a source blob is never in both libraries.
+                match self.blobs.iter().find(|ours| ours.tag == tag.name) {
+                    None => {
+                        extension.push(MetallibTag::new(tag.name, vec![0; 16]));
+                        blobs.push(blob.clone());
+                    }
+                    Some(ours) if ours.data == blob.data => {}
+                    Some(_) => {
+                        return Err(format!(
+                            "cannot merge metallibs with different {} records",
+                            tag.name_str()
+                        ))
+                    }
+                }
+            } else {
+                match self.extension.iter().find(|ours| ours.name == tag.name) {
+                    None => extension.push(tag.clone()),
+                    Some(ours) if ours.data == tag.data => {}
+                    Some(_) => {
+                        return Err(format!(
+                            "cannot merge metallibs with different {} tags",
+                            tag.name_str()
+                        ))
+                    }
+                }
+            }
+        }
+
+        self.functions.extend(other.functions.iter().cloned());
+        self.extension.extend(extension);
+        self.blobs.extend(blobs);
+
+        // The merged library needs the newest format and deployment target of
+        // its inputs.
+        let header = &mut self.header;
+        let version = (other.header.version_major, other.header.version_minor);
+        if version > (header.version_major, header.version_minor) {
+            (header.version_major, header.version_minor) = version;
+        }
+        let os_version = (other.header.os_version_major, other.header.os_version_minor);
+        if os_version > (header.os_version_major, header.os_version_minor) {
+            (header.os_version_major, header.os_version_minor) = os_version;
+        }
+        if header.target_os == 0 {
+            header.target_os = other.header.target_os;
+        }
+        self.has_extension |= other.has_extension || !self.blobs.is_empty();
+        Ok(())
+    }
+}
+
+fn add(a: usize, b: usize) -> Result<usize, String> {
+    a.checked_add(b)
+        .ok_or_else(|| format!("metallib offset {}+{} overflows", a, b))
+}
+
+fn to_usize(value: u64) -> Result<usize, String> {
+    usize::try_from(value).map_err(|_| format!("metallib offset {} is too large", value))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, String> {
+    data.get(offset..add(offset, 2)?)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| format!("unexpected end of metallib data at {}", offset))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, String> {
+    data.get(offset..add(offset, 4)?)
+        .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| format!("unexpected end of metallib data at {}", offset))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Result<u64, String> {
+    data.get(offset..add(offset, 8)?)
+        .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        .ok_or_else(|| format!("unexpected end of metallib data at {}", offset))
+}
+
+/// Read an `(offset, size)` pair from the header and check it against the file.
+fn section(data: &[u8], field: usize) -> Result<std::ops::Range<usize>, String> {
+    let offset = to_usize(read_u64(data, field)?)?;
+    let size = to_usize(read_u64(data, field + 8)?)?;
+    match offset.checked_add(size) {
+        Some(end) if end <= data.len() => Ok(offset..end),
+        _ => Err(format!("metallib section at {} is out of bounds", field)),
+    }
+}
+
+fn slice<'a>(
+    data: &'a [u8],
+    section: &std::ops::Range<usize>,
+    offset: usize,
+    size: usize,
+) -> Result<&'a [u8], String> {
+    let start = section.start.checked_add(offset);
+    let end = start.and_then(|start| start.checked_add(size));
+    match (start, end) {
+        (Some(start), Some(end)) if end <= section.end => Ok(&data[start..end]),
+        _ => Err(format!(
+            "range {}+{} exceeds its metallib section",
+            offset, size
+        )),
+    }
+}
+
+/// Read a tag list terminated by `ENDT`, returning the tags and the offset
+/// just past the terminator.
+fn read_tags(data: &[u8], mut offset: usize) -> Result<(Vec<MetallibTag>, usize), String> {
+    let mut tags = Vec::new();
+    loop {
+        let name: [u8; 4] = data
+            .get(offset..add(offset, 4)?)
+            .ok_or_else(|| String::from("unterminated metallib tag list"))?
+            .try_into()
+            .unwrap();
+        offset += 4;
+        if name == TAG_END {
+            return Ok((tags, offset));
+        }
+        let len = read_u16(data, offset)? as usize;
+        offset += 2;
+        let end = add(offset, len)?;
+        let value = data
+            .get(offset..end)
+            .ok_or_else(|| format!("truncated metallib tag at {}", offset))?;
+        tags.push(MetallibTag::new(name, value.to_vec()));
+        offset = end;
+    }
+}
+
+/// Read a size-prefixed metadata group.
+fn read_group(
+    data: &[u8],
+    section: &std::ops::Range<usize>,
+    offset: usize,
+) -> Result<Vec<MetallibTag>, String> {
+    let start = add(section.start, offset)?;
+    let size = read_u32(data, start)? as usize;
+    slice(data, section, offset, add(size, 4)?)?;
+    let (tags, end) = read_tags(data, start + 4)?;
+    if end != start + 4 + size {
+        return Err(format!(
+            "metallib metadata group at {} has a bad size",
+            start
+        ));
+    }
+    Ok(tags)
+}
+
+fn write_tag(out: &mut Vec<u8>, name: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(name);
+    out.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    out.extend_from_slice(data);
+}
+
+fn write_group(out: &mut Vec<u8>, tags: &[MetallibTag]) {
+    let mut group = Vec::new();
+    for tag in tags {
+        write_tag(&mut group, &tag.name, &tag.data);
+    }
+    group.extend_from_slice(&TAG_END);
+    out.extend_from_slice(&(group.len() as u32).to_le_bytes());
+    out.extend_from_slice(&group);
+}
+
+/// A `.metallib` embedded in the binary whose structure is checked in a
+/// `const` context, so that a corrupt or wrong-platform file fails the build.
+///
+/// Usually created through [`include_metallib!`](crate::include_metallib).
+#[derive(Copy, Clone, Debug)]
+pub struct EmbeddedMetallib {
+    bytes: &'static [u8],
+    function_count: usize,
+}
+
+impl EmbeddedMetallib {
+    /// Validate `bytes` as a metallib for the current target.
+    ///
+    /// Panics if the data is not a well-formed metallib, or if it was built
+    /// for macOS and the Apple target is not (or vice versa). Used in a `const`
+    /// item, the panic becomes a compile error.
+    pub const fn new(bytes: &'static [u8]) -> Self {
+        if bytes.len() < HEADER_SIZE {
+            panic!("metallib is smaller than its header");
+        }
+        if !const_eq(bytes, 0, MAGIC) {
+            panic!("missing MTLB magic, not a metallib");
+        }
+        if const_u64(bytes, 16) != bytes.len() as u64 {
+            panic!("metallib file size does not match its header");
+        }
+        let is_macos = const_u16(bytes, 4) & 0x8000 != 0;
+        if cfg!(target_vendor = "apple") && is_macos != cfg!(target_os = "macos") {
+            panic!("metallib was built for a different platform");
+        }
+
+        let function_list = const_u64(bytes, 24) as usize;
+        let function_list_size = const_u64(bytes, 32) as usize;
+        if function_list + 4 + function_list_size > bytes.len() {
+            panic!("metallib function list is out of bounds");
+        }
+        let function_count = const_u32(bytes, function_list) as usize;
+        let mut offset = function_list + 4;
+        let mut i = 0;
+        while i < function_count {
+            if const_function_name(bytes, offset).is_none() {
+                panic!("metallib function entry has no NAME tag");
+            }
+            offset += const_u32(bytes, offset) as usize;
+            i += 1;
+        }
+        if offset != function_list + 4 + function_list_size {
+            panic!("metallib function list size does not match its entries");
+        }
+
+        EmbeddedMetallib {
+            bytes,
+            function_count,
+        }
+    }
+
+    /// The raw library data, as expected by
+    /// [`DeviceRef::new_library_with_data`](crate::DeviceRef::new_library_with_data).
+    pub const fn as_bytes(&self) -> &'static [u8] {
+        self.bytes
+    }
+
+    pub const fn function_count(&self) -> usize {
+        self.function_count
+    }
+
+    /// Name of the function at `index` in the function table.
+    pub const fn function_name(&self, index: usize) -> &'static str {
+        if index >= self.function_count {
+            panic!("metallib function index out of range");
+        }
+        let mut offset = const_u64(self.bytes, 24) as usize + 4;
+        let mut i = 0;
+        while i < index {
+            offset += const_u32(self.bytes, offset) as usize;
+            i += 1;
+        }
+        match const_function_name(self.bytes, offset) {
+            Some(name) => name,
+            None => panic!("metallib function entry has no NAME tag"),
+        }
+    }
+
+    /// All function names, for use as `const NAMES: [&str; N]`.
+    pub const fn function_names<const N: usize>(&self) -> [&'static str; N] {
+        if N != self.function_count {
+            panic!("function name array length does not match the metallib");
+        }
+        let mut names = [""; N];
+        let mut i = 0;
+        while i < N {
+            names[i] = self.function_name(i);
+            i += 1;
+        }
+        names
+    }
+
+    pub const fn has_function(&self, name: &str) -> bool {
+        let mut i = 0;
+        while i < self.function_count {
+            if const_str_eq(self.function_name(i), name) {
+                return true;
+            }
+            i += 1;
+        }
+        false
+    }
+
+    /// Return `name` if the library defines it, and panic otherwise. Used in
+    /// a `const` item, a misspelled function name becomes a compile error.
+    pub const fn function(&self, name: &'static str) -> &'static str {
+        if !self.has_function(name) {
+            panic!("function not found in embedded metallib");
+        }
+        name
+    }
+}
+
+const fn const_eq(data: &[u8], offset: usize, expected: &[u8]) -> bool {
+    if offset + expected.len() > data.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < expected.len() {
+        if data[offset + i] != expected[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn const_str_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && const_eq(a.as_bytes(), 0, b.as_bytes())
+}
+
+const fn const_u16(data: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([data[offset], data[offset + 1]])
+}
+
+const fn const_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        data[offset],
+        data[offset + 1],
+        data[offset + 2],
+        data[offset + 3],
+    ])
+}
+
+const fn const_u64(data: &[u8], offset: usize) -> u64 {
+    const_u32(data, offset) as u64 | (const_u32(data, offset + 4) as u64) << 32
+}
+
+/// Find the `NAME` tag of the function entry at `offset`.
+const fn const_function_name(data: &'static [u8], entry: usize) -> Option<&'static str> {
+    let end = entry + const_u32(data, entry) as usize;
+    let mut offset = entry + 4;
+    while offset + 4 <= end {
+        if const_eq(data, offset, &TAG_END) {
+            return None;
+        }
+        let len = const_u16(data, offset + 4) as usize;
+        if const_eq(data, offset, &TAG_NAME) && len > 0 {
+            // Drop the trailing NUL.
+            let (_, rest) = data.split_at(offset + 6);
+            let (name, _) = rest.split_at(len - 1);
+            return match std::str::from_utf8(name) {
+                Ok(name) => Some(name),
+                Err(_) => None,
+            };
+        }
+        offset += 6 + len;
+    }
+    None
+}
+
+/// Embed a `.metallib` file and validate it at compile time.
+///
+/// The single-argument form evaluates to an [`EmbeddedMetallib`]:
+///
+/// ```ignore
+/// const SHADERS: metal::EmbeddedMetallib = metal::include_metallib!("shaders.metallib");
+/// let library = device.new_library_with_data(SHADERS.as_bytes())?;
+/// ```
+///
+/// The module form additionally declares a constant per function name. Each
+/// name is looked up at compile time, so a typo fails the build rather than
+/// `get_function`:
+///
+/// ```ignore
+/// metal::include_metallib! {
+///     mod shaders = "shaders.metallib" {
+///         SUM = "sum",
+///     }
+/// }
+///
+/// let library = device.new_library_with_data(shaders::LIBRARY.as_bytes())?;
+/// let kernel = library.get_function(shaders::SUM, None)?;
+/// ```
+///
+/// The generated module also contains `FUNCTION_NAMES`, every function name
+/// in table order. Paths are resolved like [`include_bytes!`].
+///
+/// ```
+/// metal::include_metallib! {
+///     mod shaders = "../examples/compute/shaders.metallib" {
+///         SUM = "sum",
+///     }
+/// }
+///
+/// assert_eq!(shaders::SUM, "sum");
+/// assert_eq!(shaders::FUNCTION_NAMES, ["sum"]);
+/// ```
+///
+/// A function the library doesn't define is a compile error:
+///
+/// ```compile_fail,E0080
+/// metal::include_metallib! {
+///     mod shaders = "../examples/compute/shaders.metallib" {
+///         SUM = "summ",
+///     }
+/// }
+/// ```
+///
+/// And so is a file that isn't a metallib:
+///
+/// ```compile_fail,E0080
+/// const SHADERS: metal::EmbeddedMetallib =
+///     metal::include_metallib!("../examples/compute/shaders.metal");
+/// ```
+#[macro_export]
+macro_rules! include_metallib {
+    {
+        $vis:vis mod $module:ident = $path:literal {
+            $($name:ident = $function:literal),* $(,)?
+        }
+    } => {
+        $vis mod $module {
+            pub const LIBRARY: $crate::EmbeddedMetallib =
+                $crate::EmbeddedMetallib::new(include_bytes!($path));
+            pub const FUNCTION_NAMES: [&str; LIBRARY.function_count()] =
+                LIBRARY.function_names();
+            $(pub const $name: &str = LIBRARY.function($function);)*
+        }
+    };
+    ($path:expr) => {{
+        const LIBRARY: $crate::EmbeddedMetallib =
+            $crate::EmbeddedMetallib::new(include_bytes!($path));
+        LIBRARY
+    }};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLES: [(&str, &[u8]); 6] = [
+        (
+            "circle",
+            include_bytes!("../examples/circle/shaders.metallib"),
+        ),
+        (
+            "compute",
+            include_bytes!("../examples/compute/shaders.metallib"),
+        ),
+        (
+            "mesh-shader",
+            include_bytes!("../examples/mesh-shader/shaders.metallib"),
+        ),
+        ("mps", include_bytes!("../examples/mps/shaders.metallib")),
+        (
+            "raytracing",
+            include_bytes!("../examples/raytracing/shaders.metallib"),
+        ),
+        (
+            "window",
+            include_bytes!("../examples/window/shaders.metallib"),
+        ),
+    ];
+
+    /// Compare functions, ignoring the `OFFT` and `MDSZ` payloads that
+    /// `to_bytes` rewrites.
+    fn assert_same_function(a: &MetallibFunction, b: &MetallibFunction) {
+        let layout_free = |f: &MetallibFunction| {
+            f.tags
+                .iter()
+                .filter(|t| t.name != TAG_OFFT && t.name != TAG_MDSZ)
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(layout_free(a), layout_free(b));
+        assert_eq!(a.public_metadata, b.public_metadata);
+        assert_eq!(a.private_metadata, b.private_metadata);
+        assert!(a.bitcode == b.bitcode);
+    }
+
+    fn example(name: &str) -> Metallib {
+        let (_, data) = EXAMPLES.iter().find(|(n, _)| *n == name).unwrap();
+        Metallib::parse(data).unwrap()
+    }
+
+    #[test]
+    fn round_trip_examples() {
+        for (name, data) in EXAMPLES {
+            let lib = Metallib::parse(data).unwrap();
+            assert!(!lib.functions.is_empty(), "{}", name);
+            assert!(lib.to_bytes() == data, "{} does not round-trip", name);
+        }
+    }
+
+    #[test]
+    fn merge_examples() {
+        let mut lib = example("compute");
+        let window = example("window");
+        lib.merge(&window).unwrap();
+        assert_eq!(
+            lib.function_names(),
+            [
+                "sum",
+                "triangle_vertex",
+                "clear_rect_vertex",
+                "triangle_fragment",
+                "clear_rect_fragment"
+            ]
+        );
+        let bytes = lib.to_bytes();
+        let parsed = Metallib::parse(&bytes).unwrap();
+        assert!(parsed.to_bytes() == bytes);
+        for function in example("compute").functions.iter().chain(&window.functions) {
+            assert_same_function(parsed.function(function.name()).unwrap(), function);
+        }
+
+        // Duplicate function names.
+        assert!(lib.merge(&window).is_err());
+    }
+
+    #[test]
+    fn merge_embedded_source() {
+        let mut lib = example("raytracing");
+        let mps = example("mps");
+        assert!(mps.has_embedded_source());
+        lib.merge(&mps).unwrap();
+        assert!(lib.has_embedded_source());
+
+        let bytes = lib.to_bytes();
+        let parsed = Metallib::parse(&bytes).unwrap();
+        assert!(parsed.to_bytes() == bytes);
+        assert_eq!(parsed.blobs, mps.blobs);
+
+        let mut other = example("mps");
+        other.functions.clear();
+        assert!(lib.merge(&other).is_err());
+    }
+
+    #[test]
+    fn merge_keeps_extension_tags() {
+        let mut lib = example("compute");
+        let mut other = example("window");
+        other.has_extension = true;
+        other
+            .extension
+            .push(MetallibTag::new(*b"UUID", vec![7; 16]));
+        other
+            .extension
+            .push(MetallibTag::new(*b"VLST", vec![0; 16]));
+        other.blobs.push(MetallibBlob {
+            tag: *b"VLST",
+            data: vec![1, 2, 3],
+        });
+
+        lib.merge(&other).unwrap();
+        let parsed = Metallib::parse(&lib.to_bytes()).unwrap();
+        assert_eq!(parsed.extension[0], MetallibTag::new(*b"UUID", vec![7; 16]));
+        assert_eq!(parsed.blobs, other.blobs);
+
+        // Identical tags merge, conflicting ones don't.
+        let mut again = example("circle");
+        again.has_extension = true;
+        again.extension = other.extension.clone();
+        again.blobs = other.blobs.clone();
+        lib.merge(&again).unwrap();
+        assert_eq!(lib.extension.len(), 2);
+
+        let mut conflict = example("raytracing");
+        conflict.has_extension = true;
+        conflict
+            .extension
+            .push(MetallibTag::new(*b"UUID", vec![8; 16]));
+        let names = lib.function_names().len();
+        assert!(lib.merge(&conflict).is_err());
+        assert_eq!(lib.function_names().len(), names);
+    }
+
+    #[test]
+    fn strip_and_retain() {
+        let original = EXAMPLES[3].1;
+        let mut lib = example("mps");
+        lib.strip_source();
+        lib.strip_debug_info();
+        assert!(!lib.has_embedded_source());
+        assert!(lib.functions.iter().all(|f| f.source_offset().is_none()
+            && f.private_metadata.iter().all(|t| t.name != TAG_DEBI)));
+        let bytes = lib.to_bytes();
+        assert!(bytes.len() < original.len());
+        let parsed = Metallib::parse(&bytes).unwrap();
+        for (a, b) in parsed.functions.iter().zip(&lib.functions) {
+            assert_same_function(a, b);
+        }
+
+        let mut lib = example("window");
+        let fragment = lib.function("triangle_fragment").unwrap().clone();
+        lib.retain_functions(|f| f.name() == "triangle_fragment");
+        let parsed = Metallib::parse(&lib.to_bytes()).unwrap();
+        assert_eq!(parsed.function_names(), ["triangle_fragment"]);
+        assert_same_function(&parsed.functions[0], &fragment);
+    }
+
+    #[test]
+    fn reject_malformed() {
+        let data = EXAMPLES[1].1;
+        assert!(Metallib::parse(&data[..HEADER_SIZE - 1]).is_err());
+        assert!(Metallib::parse(&data[..data.len() - 1]).is_err());
+
+        let mut bad_magic = data.to_vec();
+        bad_magic[0] = b'X';
+        assert!(Metallib::parse(&bad_magic).is_err());
+
+        // An OFFT entry that overflows when added to the section start.
+        let mut overflow = data.to_vec();
+        let offt = overflow.windows(4).position(|w| w == TAG_OFFT).unwrap() + 6;
+        overflow[offt..offt + 8].copy_from_slice(&(u64::MAX - 1).to_le_bytes());
+        assert!(Metallib::parse(&overflow).is_err());
+
+        // No single corrupted byte may panic.
+        for index in HEADER_SIZE..data.len() {
+            for value in [0x00, 0x7f, 0xff] {
+                let mut corrupt = data.to_vec();
+                corrupt[index] = value;
+                let _ = Metallib::parse(&corrupt);
+            }
+        }
+    }
+
+    const COMPUTE: EmbeddedMetallib =
+        EmbeddedMetallib::new(include_bytes!("../examples/compute/shaders.metallib"));
+    const COMPUTE_NAMES: [&str; 1] = COMPUTE.function_names();
+    const SUM: &str = COMPUTE.function("sum");
+
+    /// The compute example with `corrupt` applied, leaked to satisfy
+    /// `EmbeddedMetallib::new`.
+    fn corrupt_compute(corrupt: impl FnOnce(&mut Vec<u8>)) -> &'static [u8] {
+        let mut data = COMPUTE.as_bytes().to_vec();
+        corrupt(&mut data);
+        Box::leak(data.into_boxed_slice())
+    }
+
+    #[test]
+    fn embedded_examples() {
+        assert_eq!((COMPUTE_NAMES, SUM), (["sum"], "sum"));
+        for (name, data) in EXAMPLES {
+            let embedded = EmbeddedMetallib::new(data);
+            let names = (0..embedded.function_count())
+                .map(|i| embedded.function_name(i))
+                .collect::<Vec<_>>();
+            assert_eq!(names, example(name).function_names(), "{}", name);
+            assert!(embedded.as_bytes() == data);
+            for function in names {
+                assert!(embedded.has_function(function));
+                assert_eq!(embedded.function(function), function);
+            }
+        }
+        let window = EmbeddedMetallib::new(EXAMPLES[5].1);
+        assert_eq!(
+            window.function_names::<4>(),
+            [
+                "triangle_vertex",
+                "clear_rect_vertex",
+                "triangle_fragment",
+                "clear_rect_fragment"
+            ]
+        );
+        assert!(!window.has_function("sum"));
+        assert!(!window.has_function("triangle"));
+        assert!(!window.has_function("triangle_vertex_"));
+        assert!(!window.has_function(""));
+    }
+
+    #[test]
+    #[should_panic(expected = "function not found in embedded metallib")]
+    fn embedded_missing_function() {
+        COMPUTE.function("summ");
+    }
+
+    #[test]
+    #[should_panic(expected = "metallib function index out of range")]
+    fn embedded_function_index_out_of_range() {
+        COMPUTE.function_name(1);
+    }
+
+    #[test]
+    #[should_panic(expected = "function name array length does not match the metallib")]
+    fn embedded_function_names_length() {
+        COMPUTE.function_names::<2>();
+    }
+
+    #[test]
+    #[should_panic(expected = "metallib is smaller than its header")]
+    fn embedded_truncated_header() {
+        EmbeddedMetallib::new(&COMPUTE.as_bytes()[..HEADER_SIZE - 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "missing MTLB magic, not a metallib")]
+    fn embedded_bad_magic() {
+        EmbeddedMetallib::new(corrupt_compute(|data| data[0] = b'X'));
+    }
+
+    #[test]
+    #[should_panic(expected = "metallib file size does not match its header")]
+    fn embedded_truncated_file() {
+        let data = COMPUTE.as_bytes();
+        EmbeddedMetallib::new(&data[..data.len() - 1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "metallib function list is out of bounds")]
+    fn embedded_function_list_out_of_bounds() {
+        EmbeddedMetallib::new(corrupt_compute(|data| {
+            let size = (data.len() as u64).to_le_bytes();
+            data[32..40].copy_from_slice(&size);
+        }));
+    }
+
+    #[test]
+    #[should_panic(expected = "metallib function list size does not match its entries")]
+    fn embedded_function_list_size() {
+        EmbeddedMetallib::new(corrupt_compute(|data| data[32] -= 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "metallib function entry has no NAME tag")]
+    fn embedded_unnamed_function() {
+        EmbeddedMetallib::new(corrupt_compute(|data| {
+            let name = data.windows(4).position(|w| w == TAG_NAME).unwrap();
+            data[name + 3] = b'X';
+        }));
+    }
+}
@@ -0,0 +1,312 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::*;
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// The hash a [`PipelineCache`] files a descriptor value under. Stable within
+/// a process only.
+pub fn pipeline_key<K: Hash>(desc: &K) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    desc.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PipelineCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+/// Pipeline states keyed by the hash of the descriptor value they were
+/// created from, so equal descriptors share one state.
+///
+/// Entries with colliding hashes are told apart by comparing the values.
+///
+/// ```ignore
+/// let mut cache = RenderPipelineCache::new();
+/// let state = cache.get_or_build(&device, &objects, &desc)?;
+/// ```
+pub struct PipelineCache<K, P> {
+    entries: HashMap<u64, Vec<(K, P)>>,
+    len: usize,
+    stats: PipelineCacheStats,
+}
+
+pub type RenderPipelineCache = PipelineCache<RenderPipelineDesc, RenderPipelineState>;
+pub type ComputePipelineCache = PipelineCache<ComputePipelineDesc, ComputePipelineState>;
+pub type MeshRenderPipelineCache = PipelineCache<MeshRenderPipelineDesc, RenderPipelineState>;
+
+impl<K, P> Default for PipelineCache<K, P> {
+    fn default() -> Self {
+        PipelineCache {
+            entries: HashMap::new(),
+            len: 0,
+            stats: PipelineCacheStats::default(),
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, P: Clone> PipelineCache<K, P> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn stats(&self) -> PipelineCacheStats {
+        self.stats
+    }
+
+    pub fn get(&self, desc: &K) -> Option<&P> {
+        self.entries
+            .get(&pipeline_key(desc))?
+            .iter()
+            .find(|(k, _)| k == desc)
+            .map(|(_, p)| p)
+    }
+
+    /// Return the cached state for `desc`, calling `create` only if there is
+    /// none yet. Failures are not cached.
+    pub fn get_or_insert_with<E, F>(&mut self, desc: &K, create: F) -> Result<P, E>
+    where
+        F: FnOnce(&K) -> Result<P, E>,
+    {
+        if let Some(pipeline) = self.get(desc) {
+            let pipeline = pipeline.clone();
+            self.stats.hits += 1;
+            return Ok(pipeline);
+        }
+        self.stats.misses += 1;
+        let pipeline = create(desc)?;
+        self.entries
+            .entry(pipeline_key(desc))
+            .or_default()
+            .push((desc.clone(), pipeline.clone()));
+        self.len += 1;
+        Ok(pipeline)
+    }
+
+    pub fn remove(&mut self, desc: &K) -> Option<P> {
+        let key = pipeline_key(desc);
+        let bucket = self.entries.get_mut(&key)?;
+        let index = bucket.iter().position(|(k, _)| k == desc)?;
+        let (_, pipeline) = bucket.swap_remove(index);
+        if bucket.is_empty() {
+            self.entries.remove(&key);
+        }
+        self.len -= 1;
+        Some(pipeline)
+    }
+
+    /// Drop every entry whose descriptor matches `f`, e.g. those using a
+    /// library that was just reloaded.
+    pub fn remove_where<F: FnMut(&K) -> bool>(&mut self, mut f: F) {
+        let mut removed = 0;
+        self.entries.retain(|_, bucket| {
+            let before = bucket.len();
+            bucket.retain(|(k, _)| !f(k));
+            removed += before - bucket.len();
+            !bucket.is_empty()
+        });
+        self.len -= removed;
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &P)> {
+        self.entries
+            .values()
+            .flat_map(|bucket| bucket.iter().map(|(k, p)| (k, p)))
+    }
+}
+
+impl RenderPipelineCache {
+    pub fn get_or_build(
+        &mut self,
+        device: &DeviceRef,
+        objects: &PipelineObjects,
+        desc: &RenderPipelineDesc,
+    ) -> Result<RenderPipelineState, MetalError> {
+        self.get_or_insert_with(desc, |desc| {
+            let descriptor = desc.to_descriptor(objects)?;
+            device.new_render_pipeline_state(&descriptor)
+        })
+    }
+}
+
+impl ComputePipelineCache {
+    pub fn get_or_build(
+        &mut self,
+        device: &DeviceRef,
+        objects: &PipelineObjects,
+        desc: &ComputePipelineDesc,
+    ) -> Result<ComputePipelineState, MetalError> {
+        self.get_or_insert_with(desc, |desc| {
+            let descriptor = desc.to_descriptor(objects)?;
+            device.new_compute_pipeline_state(&descriptor)
+        })
+    }
+}
+
+impl MeshRenderPipelineCache {
+    pub fn get_or_build(
+        &mut self,
+        device: &DeviceRef,
+        objects: &PipelineObjects,
+        desc: &MeshRenderPipelineDesc,
+    ) -> Result<RenderPipelineState, MetalError> {
+        self.get_or_insert_with(desc, |desc| {
+            let descriptor = desc.to_descriptor(objects)?;
+            device.new_mesh_render_pipeline_state(&descriptor)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A key whose hash ignores `id`, so every key lands in one bucket.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct Colliding {
+        id: u32,
+    }
+
+    impl Hash for Colliding {
+        fn hash<H: Hasher>(&self, state: &mut H) {
+            0u32.hash(state);
+        }
+    }
+
+    fn function(library: LibraryKey, name: &str) -> PipelineFunction {
+        PipelineFunction::new(library, name)
+    }
+
+    fn render_desc() -> RenderPipelineDesc {
+        RenderPipelineDesc {
+            vertex_function: Some(function(LibraryKey::new(1), "vs")),
+            fragment_function: Some(function(LibraryKey::new(1), "fs")),
+            color_attachments: vec![ColorAttachmentDesc::new(MTLPixelFormat::BGRA8Unorm)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn key_derivation() {
+        assert_eq!(pipeline_key(&render_desc()), pipeline_key(&render_desc()));
+
+        let mut other = render_desc();
+        other.color_attachments[0].blending_enabled = true;
+        assert_ne!(render_desc(), other);
+        assert_ne!(pipeline_key(&render_desc()), pipeline_key(&other));
+
+        let mut other = render_desc();
+        other.fragment_function = Some(function(LibraryKey::new(2), "fs"));
+        assert_ne!(pipeline_key(&render_desc()), pipeline_key(&other));
+
+        let compute = ComputePipelineDesc::new(function(LibraryKey::new(1), "main"));
+        let mut other = compute.clone();
+        other.buffers.insert(0, MTLMutability::Immutable);
+        assert_ne!(pipeline_key(&compute), pipeline_key(&other));
+    }
+
+    #[test]
+    fn constants_key_ignores_order() {
+        let mut a = FunctionConstantValuesBuilder::new();
+        a.set("use_shadows", true).set_at_index(1, 0.5f32);
+        let mut b = FunctionConstantValuesBuilder::new();
+        b.set_at_index(1, 0.5f32).set("use_shadows", true);
+        assert_eq!(a, b);
+
+        let with_a = function(LibraryKey::new(1), "fs").with_constants(a.clone());
+        let with_b = function(LibraryKey::new(1), "fs").with_constants(b);
+        assert_eq!(with_a, with_b);
+        assert_eq!(pipeline_key(&with_a), pipeline_key(&with_b));
+
+        // Replacing a value keeps one entry per constant.
+        a.set("use_shadows", false);
+        let mut c = FunctionConstantValuesBuilder::new();
+        c.set_at_index(1, 0.5f32).set("use_shadows", false);
+        assert_eq!(a, c);
+        let with_c = function(LibraryKey::new(1), "fs").with_constants(c);
+        assert_ne!(pipeline_key(&with_c), pipeline_key(&with_a));
+    }
+
+    #[test]
+    fn dedup() {
+        let mut cache = PipelineCache::<RenderPipelineDesc, u32>::new();
+        let mut created = 0;
+        for _ in 0..3 {
+            let state = cache
+                .get_or_insert_with(&render_desc(), |_| -> Result<u32, ()> {
+                    created += 1;
+                    Ok(7)
+                })
+                .unwrap();
+            assert_eq!(state, 7);
+        }
+        assert_eq!(created, 1);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.stats(), PipelineCacheStats { hits: 2, misses: 1 });
+        assert_eq!(cache.get(&render_desc()), Some(&7));
+    }
+
+    #[test]
+    fn failures_are_not_cached() {
+        let mut cache = PipelineCache::<RenderPipelineDesc, u32>::new();
+        let error = cache
+            .get_or_insert_with(&render_desc(), |_| Err("no device"))
+            .unwrap_err();
+        assert_eq!(error, "no device");
+        assert!(cache.is_empty());
+        assert!(cache.entries.is_empty());
+
+        let state = cache
+            .get_or_insert_with(&render_desc(), |_| Ok::<_, ()>(3))
+            .unwrap();
+        assert_eq!(state, 3);
+        assert_eq!(cache.stats(), PipelineCacheStats { hits: 0, misses: 2 });
+    }
+
+    #[test]
+    fn colliding_keys() {
+        let mut cache = PipelineCache::<Colliding, u32>::new();
+        for id in 0..3 {
+            cache
+                .get_or_insert_with(&Colliding { id }, |key| Ok::<_, ()>(key.id * 10))
+                .unwrap();
+        }
+        assert_eq!(cache.entries.len(), 1);
+        assert_eq!(cache.len(), 3);
+        assert_eq!(cache.get(&Colliding { id: 2 }), Some(&20));
+
+        assert_eq!(cache.remove(&Colliding { id: 1 }), Some(10));
+        assert_eq!(cache.remove(&Colliding { id: 1 }), None);
+        assert_eq!(cache.len(), 2);
+
+        cache.remove_where(|key| key.id == 0);
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.iter().map(|(_, &p)| p).collect::<Vec<_>>(), [20]);
+
+        cache.remove_where(|_| true);
+        assert!(cache.is_empty());
+        assert!(cache.entries.is_empty());
+    }
+}
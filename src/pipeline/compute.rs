@@ -188,8 +188,9 @@ impl ComputePipelineDescriptorRef {
             let count: NSUInteger = msg_send![libraries, count];
             (0..count)
                 .map(|i| {
-                    let lib = msg_send![libraries, objectAtIndex: i];
-                    DynamicLibrary::from_ptr(lib)
+                    // `objectAtIndex:` doesn't transfer ownership.
+                    let lib: &DynamicLibraryRef = msg_send![libraries, objectAtIndex: i];
+                    lib.to_owned()
                 })
                 .collect()
         }
@@ -209,8 +210,9 @@ impl ComputePipelineDescriptorRef {
             let count: NSUInteger = msg_send![archives, count];
             (0..count)
                 .map(|i| {
-                    let a = msg_send![archives, objectAtIndex: i];
-                    BinaryArchive::from_ptr(a)
+                    // `objectAtIndex:` doesn't transfer ownership.
+                    let a: &BinaryArchiveRef = msg_send![archives, objectAtIndex: i];
+                    a.to_owned()
                 })
                 .collect()
         }
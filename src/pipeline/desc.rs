@@ -0,0 +1,654 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Plain Rust mirrors of the pipeline descriptors.
+//!
+//! Unlike the Objective-C descriptors these values can be compared, hashed
+//! and used as map keys, e.g. in a [`PipelineCache`]. Functions, dynamic
+//! libraries and binary archives are referred to by keys issued by a
+//! [`PipelineObjects`] registry, which resolves them when the values are
+//! converted into descriptors.
+
+use super::*;
+
+use std::collections::{BTreeMap, HashMap};
+
+macro_rules! pipeline_object_key {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        pub struct $name(u64);
+
+        impl $name {
+            pub const fn new(id: u64) -> Self {
+                $name(id)
+            }
+
+            pub const fn id(&self) -> u64 {
+                self.0
+            }
+        }
+    };
+}
+
+pipeline_object_key!(
+    /// A [`Library`] registered with [`PipelineObjects`].
+    LibraryKey
+);
+pipeline_object_key!(
+    /// A [`DynamicLibrary`] registered with [`PipelineObjects`].
+    DynamicLibraryKey
+);
+pipeline_object_key!(
+    /// A [`BinaryArchive`] registered with [`PipelineObjects`].
+    BinaryArchiveKey
+);
+
+/// The objects pipeline descriptor values refer to by key.
+///
+/// Keys are only meaningful to the registry that issued them. Registered
+/// objects are retained, so a key never refers to a different object.
+#[derive(Default)]
+pub struct PipelineObjects {
+    libraries: HashMap<LibraryKey, Library>,
+    dynamic_libraries: HashMap<DynamicLibraryKey, DynamicLibrary>,
+    binary_archives: HashMap<BinaryArchiveKey, BinaryArchive>,
+    next_id: u64,
+}
+
+impl PipelineObjects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    pub fn add_library(&mut self, library: &LibraryRef) -> LibraryKey {
+        let key = LibraryKey(self.next_id());
+        self.libraries.insert(key, library.to_owned());
+        key
+    }
+
+    pub fn add_dynamic_library(&mut self, library: &DynamicLibraryRef) -> DynamicLibraryKey {
+        let key = DynamicLibraryKey(self.next_id());
+        self.dynamic_libraries.insert(key, library.to_owned());
+        key
+    }
+
+    pub fn add_binary_archive(&mut self, archive: &BinaryArchiveRef) -> BinaryArchiveKey {
+        let key = BinaryArchiveKey(self.next_id());
+        self.binary_archives.insert(key, archive.to_owned());
+        key
+    }
+
+    pub fn library(&self, key: LibraryKey) -> Option<&LibraryRef> {
+        self.libraries.get(&key).map(|l| l.as_ref())
+    }
+
+    pub fn dynamic_library(&self, key: DynamicLibraryKey) -> Option<&DynamicLibraryRef> {
+        self.dynamic_libraries.get(&key).map(|l| l.as_ref())
+    }
+
+    pub fn binary_archive(&self, key: BinaryArchiveKey) -> Option<&BinaryArchiveRef> {
+        self.binary_archives.get(&key).map(|a| a.as_ref())
+    }
+
+    /// Look up, and specialize if needed, the function `function` names.
+    pub fn function(&self, function: &PipelineFunction) -> Result<Function, MetalError> {
+        let library = self
+            .library(function.library)
+            .ok_or_else(|| not_registered(function.library))?;
+        let constants = match &function.constants {
+            Some(constants) => Some(constants.build()?),
+            None => None,
+        };
+        library.get_function(&function.name, constants)
+    }
+
+    fn functions(&self, functions: &[PipelineFunction]) -> Result<Vec<Function>, MetalError> {
+        functions.iter().map(|f| self.function(f)).collect()
+    }
+
+    fn binary_archive_list(
+        &self,
+        keys: &[BinaryArchiveKey],
+    ) -> Result<Vec<&BinaryArchiveRef>, MetalError> {
+        keys.iter()
+            .map(|&key| self.binary_archive(key).ok_or_else(|| not_registered(key)))
+            .collect()
+    }
+}
+
+fn not_registered<K: std::fmt::Debug>(key: K) -> MetalError {
+    MetalError::invalid_argument(&format!("{:?} is not registered", key))
+}
+
+/// A function of a registered library, optionally specialized with function
+/// constants.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineFunction {
+    pub library: LibraryKey,
+    pub name: String,
+    pub constants: Option<FunctionConstantValuesBuilder>,
+}
+
+impl PipelineFunction {
+    pub fn new(library: LibraryKey, name: &str) -> Self {
+        PipelineFunction {
+            library,
+            name: name.to_string(),
+            constants: None,
+        }
+    }
+
+    pub fn with_constants(mut self, constants: FunctionConstantValuesBuilder) -> Self {
+        self.constants = Some(constants);
+        self
+    }
+}
+
+/// See [`RenderPipelineColorAttachmentDescriptorRef`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ColorAttachmentDesc {
+    pub pixel_format: MTLPixelFormat,
+    pub blending_enabled: bool,
+    pub source_rgb_blend_factor: MTLBlendFactor,
+    pub destination_rgb_blend_factor: MTLBlendFactor,
+    pub rgb_blend_operation: MTLBlendOperation,
+    pub source_alpha_blend_factor: MTLBlendFactor,
+    pub destination_alpha_blend_factor: MTLBlendFactor,
+    pub alpha_blend_operation: MTLBlendOperation,
+    pub write_mask: MTLColorWriteMask,
+}
+
+impl Default for ColorAttachmentDesc {
+    fn default() -> Self {
+        ColorAttachmentDesc {
+            pixel_format: MTLPixelFormat::Invalid,
+            blending_enabled: false,
+            source_rgb_blend_factor: MTLBlendFactor::One,
+            destination_rgb_blend_factor: MTLBlendFactor::Zero,
+            rgb_blend_operation: MTLBlendOperation::Add,
+            source_alpha_blend_factor: MTLBlendFactor::One,
+            destination_alpha_blend_factor: MTLBlendFactor::Zero,
+            alpha_blend_operation: MTLBlendOperation::Add,
+            write_mask: MTLColorWriteMask::All,
+        }
+    }
+}
+
+impl ColorAttachmentDesc {
+    pub fn new(pixel_format: MTLPixelFormat) -> Self {
+        ColorAttachmentDesc {
+            pixel_format,
+            ..Default::default()
+        }
+    }
+
+    /// Premultiplied alpha blending: `src + dst * (1 - src.a)`.
+    pub fn premultiplied_alpha(pixel_format: MTLPixelFormat) -> Self {
+        ColorAttachmentDesc {
+            pixel_format,
+            blending_enabled: true,
+            source_rgb_blend_factor: MTLBlendFactor::One,
+            destination_rgb_blend_factor: MTLBlendFactor::OneMinusSourceAlpha,
+            source_alpha_blend_factor: MTLBlendFactor::One,
+            destination_alpha_blend_factor: MTLBlendFactor::OneMinusSourceAlpha,
+            ..Default::default()
+        }
+    }
+
+    fn apply(&self, attachment: &RenderPipelineColorAttachmentDescriptorRef) {
+        attachment.set_pixel_format(self.pixel_format);
+        attachment.set_blending_enabled(self.blending_enabled);
+        attachment.set_source_rgb_blend_factor(self.source_rgb_blend_factor);
+        attachment.set_destination_rgb_blend_factor(self.destination_rgb_blend_factor);
+        attachment.set_rgb_blend_operation(self.rgb_blend_operation);
+        attachment.set_source_alpha_blend_factor(self.source_alpha_blend_factor);
+        attachment.set_destination_alpha_blend_factor(self.destination_alpha_blend_factor);
+        attachment.set_alpha_blend_operation(self.alpha_blend_operation);
+        attachment.set_write_mask(self.write_mask);
+    }
+}
+
+/// See [`VertexAttributeDescriptorRef`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VertexAttributeDesc {
+    pub format: MTLVertexFormat,
+    pub offset: NSUInteger,
+    pub buffer_index: NSUInteger,
+}
+
+/// See [`VertexBufferLayoutDescriptorRef`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VertexBufferLayoutDesc {
+    pub stride: NSUInteger,
+    pub step_function: MTLVertexStepFunction,
+    pub step_rate: NSUInteger,
+}
+
+impl VertexBufferLayoutDesc {
+    pub fn per_vertex(stride: NSUInteger) -> Self {
+        VertexBufferLayoutDesc {
+            stride,
+            step_function: MTLVertexStepFunction::PerVertex,
+            step_rate: 1,
+        }
+    }
+}
+
+/// See [`VertexDescriptorRef`]. Attributes and layouts are keyed by index.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct VertexDesc {
+    pub attributes: BTreeMap<NSUInteger, VertexAttributeDesc>,
+    pub layouts: BTreeMap<NSUInteger, VertexBufferLayoutDesc>,
+}
+
+impl VertexDesc {
+    /// Create a new, autoreleased descriptor with these values.
+    pub fn to_descriptor(&self) -> &VertexDescriptorRef {
+        let descriptor = VertexDescriptor::new();
+        for (&index, attribute) in &self.attributes {
+            let target = descriptor.attributes().object_at(index).unwrap();
+            target.set_format(attribute.format);
+            target.set_offset(attribute.offset);
+            target.set_buffer_index(attribute.buffer_index);
+        }
+        for (&index, layout) in &self.layouts {
+            let target = descriptor.layouts().object_at(index).unwrap();
+            target.set_stride(layout.stride);
+            target.set_step_function(layout.step_function);
+            target.set_step_rate(layout.step_rate);
+        }
+        descriptor
+    }
+}
+
+/// See [`AttributeDescriptorRef`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StageInputAttributeDesc {
+    pub format: MTLAttributeFormat,
+    pub offset: NSUInteger,
+    pub buffer_index: NSUInteger,
+}
+
+/// See [`BufferLayoutDescriptorRef`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StageInputLayoutDesc {
+    pub stride: NSUInteger,
+    pub step_function: MTLStepFunction,
+    pub step_rate: NSUInteger,
+}
+
+/// See [`StageInputOutputDescriptorRef`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StageInputOutputDesc {
+    pub attributes: BTreeMap<NSUInteger, StageInputAttributeDesc>,
+    pub layouts: BTreeMap<NSUInteger, StageInputLayoutDesc>,
+    pub index_buffer_index: NSUInteger,
+    pub index_type: MTLIndexType,
+}
+
+impl Default for StageInputOutputDesc {
+    fn default() -> Self {
+        StageInputOutputDesc {
+            attributes: BTreeMap::new(),
+            layouts: BTreeMap::new(),
+            index_buffer_index: 0,
+            index_type: MTLIndexType::UInt16,
+        }
+    }
+}
+
+impl StageInputOutputDesc {
+    /// Create a new, autoreleased descriptor with these values.
+    pub fn to_descriptor(&self) -> &StageInputOutputDescriptorRef {
+        let descriptor = StageInputOutputDescriptor::new();
+        if let Some(attributes) = descriptor.attributes() {
+            for (&index, attribute) in &self.attributes {
+                let target = attributes.object_at(index).unwrap();
+                target.set_format(attribute.format);
+                target.set_offset(attribute.offset);
+                target.set_buffer_index(attribute.buffer_index);
+            }
+        }
+        if let Some(layouts) = descriptor.layouts() {
+            for (&index, layout) in &self.layouts {
+                let target = layouts.object_at(index).unwrap();
+                target.set_stride(layout.stride);
+                target.set_step_function(layout.step_function);
+                target.set_step_rate(layout.step_rate);
+            }
+        }
+        descriptor.set_index_buffer_index(self.index_buffer_index);
+        descriptor.set_index_type(self.index_type);
+        descriptor
+    }
+}
+
+/// Per-buffer mutability, keyed by buffer index.
+pub type PipelineBufferMutability = BTreeMap<NSUInteger, MTLMutability>;
+
+fn apply_mutability(
+    buffers: Option<&PipelineBufferDescriptorArrayRef>,
+    mutability: &PipelineBufferMutability,
+) {
+    let buffers = match buffers {
+        Some(buffers) => buffers,
+        None => return,
+    };
+    for (&index, &value) in mutability {
+        if let Some(buffer) = buffers.object_at(index) {
+            buffer.set_mutability(value);
+        }
+    }
+}
+
+fn apply_color_attachments(
+    array: &RenderPipelineColorAttachmentDescriptorArrayRef,
+    attachments: &[ColorAttachmentDesc],
+) -> Result<(), MetalError> {
+    for (index, attachment) in attachments.iter().enumerate() {
+        let target = array.object_at(index as NSUInteger).ok_or_else(|| {
+            MetalError::invalid_argument(&format!("color attachment {} is out of range", index))
+        })?;
+        attachment.apply(target);
+    }
+    Ok(())
+}
+
+/// See [`RenderPipelineDescriptorRef`]. Color attachments are indexed by
+/// their position.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RenderPipelineDesc {
+    pub label: Option<String>,
+    pub vertex_function: Option<PipelineFunction>,
+    pub fragment_function: Option<PipelineFunction>,
+    pub vertex_descriptor: Option<VertexDesc>,
+    pub color_attachments: Vec<ColorAttachmentDesc>,
+    pub depth_attachment_pixel_format: MTLPixelFormat,
+    pub stencil_attachment_pixel_format: MTLPixelFormat,
+    pub raster_sample_count: NSUInteger,
+    pub max_vertex_amplification_count: NSUInteger,
+    pub alpha_to_coverage_enabled: bool,
+    pub alpha_to_one_enabled: bool,
+    pub rasterization_enabled: bool,
+    pub input_primitive_topology: MTLPrimitiveTopologyClass,
+    pub support_indirect_command_buffers: bool,
+    pub vertex_buffers: PipelineBufferMutability,
+    pub fragment_buffers: PipelineBufferMutability,
+    pub binary_archives: Vec<BinaryArchiveKey>,
+    pub fragment_linked_functions: Vec<PipelineFunction>,
+}
+
+impl Default for RenderPipelineDesc {
+    fn default() -> Self {
+        RenderPipelineDesc {
+            label: None,
+            vertex_function: None,
+            fragment_function: None,
+            vertex_descriptor: None,
+            color_attachments: Vec::new(),
+            depth_attachment_pixel_format: MTLPixelFormat::Invalid,
+            stencil_attachment_pixel_format: MTLPixelFormat::Invalid,
+            raster_sample_count: 1,
+            max_vertex_amplification_count: 1,
+            alpha_to_coverage_enabled: false,
+            alpha_to_one_enabled: false,
+            rasterization_enabled: true,
+            input_primitive_topology: MTLPrimitiveTopologyClass::Unspecified,
+            support_indirect_command_buffers: false,
+            vertex_buffers: BTreeMap::new(),
+            fragment_buffers: BTreeMap::new(),
+            binary_archives: Vec::new(),
+            fragment_linked_functions: Vec::new(),
+        }
+    }
+}
+
+impl RenderPipelineDesc {
+    pub fn to_descriptor(
+        &self,
+        objects: &PipelineObjects,
+    ) -> Result<RenderPipelineDescriptor, MetalError> {
+        let descriptor = RenderPipelineDescriptor::new();
+        if let Some(label) = &self.label {
+            descriptor.set_label(label);
+        }
+        if let Some(function) = &self.vertex_function {
+            descriptor.set_vertex_function(Some(objects.function(function)?.as_ref()));
+        }
+        if let Some(function) = &self.fragment_function {
+            descriptor.set_fragment_function(Some(objects.function(function)?.as_ref()));
+        }
+        if let Some(vertex_descriptor) = &self.vertex_descriptor {
+            descriptor.set_vertex_descriptor(Some(vertex_descriptor.to_descriptor()));
+        }
+        apply_color_attachments(descriptor.color_attachments(), &self.color_attachments)?;
+        descriptor.set_depth_attachment_pixel_format(self.depth_attachment_pixel_format);
+        descriptor.set_stencil_attachment_pixel_format(self.stencil_attachment_pixel_format);
+        descriptor.set_raster_sample_count(self.raster_sample_count);
+        descriptor.set_max_vertex_amplification_count(self.max_vertex_amplification_count);
+        descriptor.set_alpha_to_coverage_enabled(self.alpha_to_coverage_enabled);
+        descriptor.set_alpha_to_one_enabled(self.alpha_to_one_enabled);
+        descriptor.set_rasterization_enabled(self.rasterization_enabled);
+        descriptor.set_input_primitive_topology(self.input_primitive_topology);
+        descriptor.set_support_indirect_command_buffers(self.support_indirect_command_buffers);
+        apply_mutability(descriptor.vertex_buffers(), &self.vertex_buffers);
+        apply_mutability(descriptor.fragment_buffers(), &self.fragment_buffers);
+        if !self.binary_archives.is_empty() {
+            descriptor.set_binary_archives(&objects.binary_archive_list(&self.binary_archives)?);
+        }
+        if !self.fragment_linked_functions.is_empty() {
+            let functions = objects.functions(&self.fragment_linked_functions)?;
+            let functions: Vec<&FunctionRef> = functions.iter().map(|f| f.as_ref()).collect();
+            let linked = LinkedFunctions::new();
+            linked.set_functions(&functions);
+            descriptor.set_fragment_linked_functions(&linked);
+        }
+        Ok(descriptor)
+    }
+}
+
+/// See [`ComputePipelineDescriptorRef`].
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ComputePipelineDesc {
+    pub label: Option<String>,
+    pub compute_function: Option<PipelineFunction>,
+    pub thread_group_size_is_multiple_of_thread_execution_width: bool,
+    /// Zero means the device maximum.
+    pub max_total_threads_per_threadgroup: NSUInteger,
+    pub support_indirect_command_buffers: bool,
+    pub support_adding_binary_functions: bool,
+    pub max_call_stack_depth: NSUInteger,
+    pub stage_input_descriptor: Option<StageInputOutputDesc>,
+    pub buffers: PipelineBufferMutability,
+    pub insert_libraries: Vec<DynamicLibraryKey>,
+    pub binary_archives: Vec<BinaryArchiveKey>,
+    pub linked_functions: Vec<PipelineFunction>,
+}
+
+impl Default for ComputePipelineDesc {
+    fn default() -> Self {
+        ComputePipelineDesc {
+            label: None,
+            compute_function: None,
+            thread_group_size_is_multiple_of_thread_execution_width: false,
+            max_total_threads_per_threadgroup: 0,
+            support_indirect_command_buffers: false,
+            support_adding_binary_functions: false,
+            max_call_stack_depth: 1,
+            stage_input_descriptor: None,
+            buffers: BTreeMap::new(),
+            insert_libraries: Vec::new(),
+            binary_archives: Vec::new(),
+            linked_functions: Vec::new(),
+        }
+    }
+}
+
+impl ComputePipelineDesc {
+    pub fn new(function: PipelineFunction) -> Self {
+        ComputePipelineDesc {
+            compute_function: Some(function),
+            ..Default::default()
+        }
+    }
+
+    pub fn to_descriptor(
+        &self,
+        objects: &PipelineObjects,
+    ) -> Result<ComputePipelineDescriptor, MetalError> {
+        let descriptor = ComputePipelineDescriptor::new();
+        if let Some(label) = &self.label {
+            descriptor.set_label(label);
+        }
+        if let Some(function) = &self.compute_function {
+            descriptor.set_compute_function(Some(objects.function(function)?.as_ref()));
+        }
+        descriptor.set_thread_group_size_is_multiple_of_thread_execution_width(
+            self.thread_group_size_is_multiple_of_thread_execution_width,
+        );
+        descriptor.set_max_total_threads_per_threadgroup(self.max_total_threads_per_threadgroup);
+        descriptor.set_support_indirect_command_buffers(self.support_indirect_command_buffers);
+        descriptor.set_support_adding_binary_functions(self.support_adding_binary_functions);
+        descriptor.set_max_call_stack_depth(self.max_call_stack_depth);
+        if let Some(stage_input) = &self.stage_input_descriptor {
+            descriptor.set_stage_input_descriptor(Some(stage_input.to_descriptor()));
+        }
+        apply_mutability(descriptor.buffers(), &self.buffers);
+        if !self.insert_libraries.is_empty() {
+            let libraries = self
+                .insert_libraries
+                .iter()
+                .map(|&key| {
+                    objects
+                        .dynamic_library(key)
+                        .ok_or_else(|| not_registered(key))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            descriptor.set_insert_libraries(&libraries);
+        }
+        if !self.binary_archives.is_empty() {
+            descriptor.set_binary_archives(&objects.binary_archive_list(&self.binary_archives)?);
+        }
+        if !self.linked_functions.is_empty() {
+            let functions = objects.functions(&self.linked_functions)?;
+            let functions: Vec<&FunctionRef> = functions.iter().map(|f| f.as_ref()).collect();
+            let linked = LinkedFunctions::new();
+            linked.set_functions(&functions);
+            descriptor.set_linked_functions(&linked);
+        }
+        Ok(descriptor)
+    }
+}
+
+/// See [`MeshRenderPipelineDescriptorRef`]. Color attachments are indexed by
+/// their position.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MeshRenderPipelineDesc {
+    pub label: Option<String>,
+    pub object_function: Option<PipelineFunction>,
+    pub mesh_function: Option<PipelineFunction>,
+    pub fragment_function: Option<PipelineFunction>,
+    pub color_attachments: Vec<ColorAttachmentDesc>,
+    pub depth_attachment_pixel_format: MTLPixelFormat,
+    pub stencil_attachment_pixel_format: MTLPixelFormat,
+    pub raster_sample_count: NSUInteger,
+    pub max_vertex_amplification_count: NSUInteger,
+    pub alpha_to_coverage_enabled: bool,
+    pub alpha_to_one_enabled: bool,
+    pub rasterization_enabled: bool,
+    pub max_total_threadgroups_per_mesh_grid: NSUInteger,
+    pub max_total_threads_per_mesh_threadgroup: NSUInteger,
+    pub max_total_threads_per_object_threadgroup: NSUInteger,
+    pub mesh_threadgroup_size_is_multiple_of_thread_execution_width: bool,
+    pub object_threadgroup_size_is_multiple_of_thread_execution_width: bool,
+    pub payload_memory_length: NSUInteger,
+    pub object_buffers: PipelineBufferMutability,
+    pub mesh_buffers: PipelineBufferMutability,
+    pub fragment_buffers: PipelineBufferMutability,
+}
+
+impl Default for MeshRenderPipelineDesc {
+    fn default() -> Self {
+        MeshRenderPipelineDesc {
+            label: None,
+            object_function: None,
+            mesh_function: None,
+            fragment_function: None,
+            color_attachments: Vec::new(),
+            depth_attachment_pixel_format: MTLPixelFormat::Invalid,
+            stencil_attachment_pixel_format: MTLPixelFormat::Invalid,
+            raster_sample_count: 1,
+            max_vertex_amplification_count: 1,
+            alpha_to_coverage_enabled: false,
+            alpha_to_one_enabled: false,
+            rasterization_enabled: true,
+            max_total_threadgroups_per_mesh_grid: 0,
+            max_total_threads_per_mesh_threadgroup: 0,
+            max_total_threads_per_object_threadgroup: 0,
+            mesh_threadgroup_size_is_multiple_of_thread_execution_width: false,
+            object_threadgroup_size_is_multiple_of_thread_execution_width: false,
+            payload_memory_length: 0,
+            object_buffers: BTreeMap::new(),
+            mesh_buffers: BTreeMap::new(),
+            fragment_buffers: BTreeMap::new(),
+        }
+    }
+}
+
+impl MeshRenderPipelineDesc {
+    pub fn to_descriptor(
+        &self,
+        objects: &PipelineObjects,
+    ) -> Result<MeshRenderPipelineDescriptor, MetalError> {
+        let descriptor = MeshRenderPipelineDescriptor::new();
+        if let Some(label) = &self.label {
+            descriptor.set_label(label);
+        }
+        if let Some(function) = &self.object_function {
+            descriptor.set_object_function(Some(objects.function(function)?.as_ref()));
+        }
+        if let Some(function) = &self.mesh_function {
+            descriptor.set_mesh_function(Some(objects.function(function)?.as_ref()));
+        }
+        if let Some(function) = &self.fragment_function {
+            descriptor.set_fragment_function(Some(objects.function(function)?.as_ref()));
+        }
+        apply_color_attachments(descriptor.color_attachments(), &self.color_attachments)?;
+        descriptor.set_depth_attachment_pixel_format(self.depth_attachment_pixel_format);
+        descriptor.set_stencil_attachment_pixel_format(self.stencil_attachment_pixel_format);
+        descriptor.set_raster_sample_count(self.raster_sample_count);
+        descriptor.set_max_vertex_amplification_count(self.max_vertex_amplification_count);
+        descriptor.set_alpha_to_coverage_enabled(self.alpha_to_coverage_enabled);
+        descriptor.set_alpha_to_one_enabled(self.alpha_to_one_enabled);
+        descriptor.set_rasterization_enabled(self.rasterization_enabled);
+        descriptor
+            .set_max_total_threadgroups_per_mesh_grid(self.max_total_threadgroups_per_mesh_grid);
+        descriptor.set_max_total_threads_per_mesh_threadgroup(
+            self.max_total_threads_per_mesh_threadgroup,
+        );
+        descriptor.set_max_total_threads_per_object_threadgroup(
+            self.max_total_threads_per_object_threadgroup,
+        );
+        descriptor.set_mesh_threadgroup_size_is_multiple_of_thread_execution_width(
+            self.mesh_threadgroup_size_is_multiple_of_thread_execution_width,
+        );
+        descriptor.set_object_threadgroup_size_is_multiple_of_thread_execution_width(
+            self.object_threadgroup_size_is_multiple_of_thread_execution_width,
+        );
+        descriptor.set_payload_memory_length(self.payload_memory_length);
+        apply_mutability(descriptor.object_buffers(), &self.object_buffers);
+        apply_mutability(descriptor.mesh_buffers(), &self.mesh_buffers);
+        apply_mutability(descriptor.fragment_buffers(), &self.fragment_buffers);
+        Ok(descriptor)
+    }
+}
@@ -7,10 +7,14 @@
 
 use super::*;
 
+mod cache;
 mod compute;
+mod desc;
 mod render;
 
+pub use self::cache::*;
 pub use self::compute::*;
+pub use self::desc::*;
 pub use self::render::*;
 
 /// See <https://developer.apple.com/documentation/metal/mtlmutability>
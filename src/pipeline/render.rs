@@ -475,6 +475,154 @@ impl MeshRenderPipelineDescriptorRef {
     }
 }
 
+/// See <https://developer.apple.com/documentation/metal/mtltilerenderpipelinecolorattachmentdescriptor>
+pub enum MTLTileRenderPipelineColorAttachmentDescriptor {}
+
+foreign_obj_type! {
+    type CType = MTLTileRenderPipelineColorAttachmentDescriptor;
+    pub struct TileRenderPipelineColorAttachmentDescriptor;
+}
+
+impl TileRenderPipelineColorAttachmentDescriptorRef {
+    pub fn pixel_format(&self) -> MTLPixelFormat {
+        unsafe { msg_send![self, pixelFormat] }
+    }
+
+    pub fn set_pixel_format(&self, pixel_format: MTLPixelFormat) {
+        unsafe { msg_send![self, setPixelFormat: pixel_format] }
+    }
+}
+
+/// See <https://developer.apple.com/documentation/metal/mtltilerenderpipelinecolorattachmentdescriptorarray>
+pub enum MTLTileRenderPipelineColorAttachmentDescriptorArray {}
+
+foreign_obj_type! {
+    type CType = MTLTileRenderPipelineColorAttachmentDescriptorArray;
+    pub struct TileRenderPipelineColorAttachmentDescriptorArray;
+}
+
+impl TileRenderPipelineColorAttachmentDescriptorArrayRef {
+    pub fn object_at(
+        &self,
+        index: NSUInteger,
+    ) -> Option<&TileRenderPipelineColorAttachmentDescriptorRef> {
+        unsafe { msg_send![self, objectAtIndexedSubscript: index] }
+    }
+
+    pub fn set_object_at(
+        &self,
+        index: NSUInteger,
+        attachment: Option<&TileRenderPipelineColorAttachmentDescriptorRef>,
+    ) {
+        unsafe {
+            msg_send![self, setObject:attachment
+                   atIndexedSubscript:index]
+        }
+    }
+}
+
+/// Only available on (macos(11.0), ios(11.0))
+///
+/// See <https://developer.apple.com/documentation/metal/mtltilerenderpipelinedescriptor>
+pub enum MTLTileRenderPipelineDescriptor {}
+
+foreign_obj_type! {
+    type CType = MTLTileRenderPipelineDescriptor;
+    pub struct TileRenderPipelineDescriptor;
+}
+
+impl TileRenderPipelineDescriptor {
+    pub fn new() -> Self {
+        unsafe {
+            let class = class!(MTLTileRenderPipelineDescriptor);
+            msg_send![class, new]
+        }
+    }
+}
+
+impl TileRenderPipelineDescriptorRef {
+    pub fn label(&self) -> &str {
+        unsafe {
+            let label = msg_send![self, label];
+            crate::nsstring_as_str(label)
+        }
+    }
+
+    pub fn set_label(&self, label: &str) {
+        unsafe {
+            let nslabel = crate::nsstring_from_str(label);
+            let () = msg_send![self, setLabel: nslabel];
+        }
+    }
+
+    pub fn tile_function(&self) -> Option<&FunctionRef> {
+        unsafe { msg_send![self, tileFunction] }
+    }
+
+    pub fn set_tile_function(&self, function: Option<&FunctionRef>) {
+        unsafe { msg_send![self, setTileFunction: function] }
+    }
+
+    pub fn raster_sample_count(&self) -> NSUInteger {
+        unsafe { msg_send![self, rasterSampleCount] }
+    }
+
+    pub fn set_raster_sample_count(&self, count: NSUInteger) {
+        unsafe { msg_send![self, setRasterSampleCount: count] }
+    }
+
+    pub fn color_attachments(&self) -> &TileRenderPipelineColorAttachmentDescriptorArrayRef {
+        unsafe { msg_send![self, colorAttachments] }
+    }
+
+    pub fn threadgroup_size_matches_tile_size(&self) -> bool {
+        unsafe { msg_send_bool![self, threadgroupSizeMatchesTileSize] }
+    }
+
+    pub fn set_threadgroup_size_matches_tile_size(&self, matches: bool) {
+        unsafe { msg_send![self, setThreadgroupSizeMatchesTileSize: matches] }
+    }
+
+    pub fn max_total_threads_per_threadgroup(&self) -> NSUInteger {
+        unsafe { msg_send![self, maxTotalThreadsPerThreadgroup] }
+    }
+
+    pub fn set_max_total_threads_per_threadgroup(&self, max_total_threads: NSUInteger) {
+        unsafe { msg_send![self, setMaxTotalThreadsPerThreadgroup: max_total_threads] }
+    }
+
+    pub fn tile_buffers(&self) -> Option<&PipelineBufferDescriptorArrayRef> {
+        unsafe { msg_send![self, tileBuffers] }
+    }
+
+    /// API_AVAILABLE(macos(11.0), ios(14.0));
+    /// Marshal to Rust Vec
+    pub fn binary_archives(&self) -> Vec<BinaryArchive> {
+        unsafe {
+            let archives: *mut Object = msg_send![self, binaryArchives];
+            let count: NSUInteger = msg_send![archives, count];
+            (0..count)
+                .map(|i| {
+                    // `objectAtIndex:` doesn't transfer ownership.
+                    let a: &BinaryArchiveRef = msg_send![archives, objectAtIndex: i];
+                    a.to_owned()
+                })
+                .collect()
+        }
+    }
+
+    /// API_AVAILABLE(macos(11.0), ios(14.0));
+    /// Marshal from Rust slice
+    pub fn set_binary_archives(&self, archives: &[&BinaryArchiveRef]) {
+        let ns_array = Array::<BinaryArchive>::from_slice(archives);
+        unsafe { msg_send![self, setBinaryArchives: ns_array] }
+    }
+
+    pub fn reset(&self) {
+        unsafe { msg_send![self, reset] }
+    }
+}
+
 /// See <https://developer.apple.com/documentation/metal/mtlrenderpipelinedescriptor>
 pub enum MTLRenderPipelineDescriptor {}
 
@@ -649,8 +797,9 @@ impl RenderPipelineDescriptorRef {
             let count: NSUInteger = msg_send![archives, count];
             (0..count)
                 .map(|i| {
-                    let a = msg_send![archives, objectAtIndex: i];
-                    BinaryArchive::from_ptr(a)
+                    // `objectAtIndex:` doesn't transfer ownership.
+                    let a: &BinaryArchiveRef = msg_send![archives, objectAtIndex: i];
+                    a.to_owned()
                 })
                 .collect()
         }
@@ -0,0 +1,187 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::*;
+
+use std::mem;
+
+#[cfg(feature = "derive")]
+pub use metal_derive::Pod;
+
+/// The most `set_*_bytes` accepts. Larger data belongs in a buffer.
+pub const MAX_INLINE_BYTES: usize = 4096;
+
+/// Plain-old-data: types that can be handed to the GPU, or read back from
+/// it, as raw bytes.
+///
+/// Implemented for the primitive numeric types, arrays of `Pod` types and
+/// Metal's `repr(C)` value types. With the `derive` feature,
+/// `#[derive(Pod)]` implements it for `#[repr(C)]` structs and checks the
+/// requirements at compile time.
+///
+/// # Safety
+///
+/// The type must have no padding bytes, no pointers or references, and
+/// every bit pattern must be a valid value.
+///
+/// # Alignment
+///
+/// [`bytes_of`], [`slice_bytes`] and the `set_*_value`/`set_*_slice` and
+/// `new_buffer_with_*` methods built on them need no alignment check: they
+/// only view a `&T` or `&[T]`, which Rust already aligns, as bytes, and
+/// Metal copies those bytes without caring about their alignment. Only
+/// going the other way, viewing buffer contents as `T`, needs one, and
+/// [`TypedBuffer::from_buffer`] checks it.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($ty:ty),*) => {
+        $(unsafe impl Pod for $ty {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+impl_pod!(
+    MTLSize,
+    MTLOrigin,
+    MTLRegion,
+    MTLScissorRect,
+    MTLViewport,
+    MTLDrawPrimitivesIndirectArguments,
+    MTLDrawIndexedPrimitivesIndirectArguments,
+    MTLDrawPatchIndirectArguments,
+    MTLDispatchThreadgroupsIndirectArguments,
+    MTLStageInRegionIndirectArguments,
+    MTLMapIndirectArguments
+);
+
+#[cfg(feature = "half")]
+impl_pod!(half::f16);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// The bytes of `value`.
+pub fn bytes_of<T: Pod>(value: &T) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+}
+
+/// The bytes of `values`, back to back.
+pub fn slice_bytes<T: Pod>(values: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(values.as_ptr() as *const u8, mem::size_of_val(values)) }
+}
+
+/// Fails to compile, rather than at runtime, when `T` is too large for
+/// `set_*_bytes`.
+pub(crate) fn inline_value_bytes<T: Pod>(value: &T) -> (NSUInteger, *const std::ffi::c_void) {
+    const {
+        assert!(
+            mem::size_of::<T>() <= MAX_INLINE_BYTES,
+            "value is larger than the 4 KB set_*_bytes limit, bind a buffer instead"
+        )
+    };
+    inline_bytes(bytes_of(value))
+}
+
+pub(crate) fn inline_slice_bytes<T: Pod>(values: &[T]) -> (NSUInteger, *const std::ffi::c_void) {
+    let bytes = slice_bytes(values);
+    assert!(
+        bytes.len() <= MAX_INLINE_BYTES,
+        "{} bytes is larger than the 4 KB set_*_bytes limit, bind a buffer instead",
+        bytes.len()
+    );
+    inline_bytes(bytes)
+}
+
+fn inline_bytes(bytes: &[u8]) -> (NSUInteger, *const std::ffi::c_void) {
+    assert!(!bytes.is_empty(), "set_*_bytes needs at least one byte");
+    (
+        bytes.len() as NSUInteger,
+        bytes.as_ptr() as *const std::ffi::c_void,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes() {
+        assert_eq!(bytes_of(&0x0403_0201u32), [1, 2, 3, 4]);
+        assert_eq!(slice_bytes(&[1u16, 2]), [1, 0, 2, 0]);
+        assert_eq!(slice_bytes::<u32>(&[]), [] as [u8; 0]);
+        assert_eq!(bytes_of(&[[1u8; 3]; 2]).len(), 6);
+        assert_eq!(bytes_of(&MTLSize::new(1, 2, 3)).len(), 24);
+        assert_eq!(
+            &bytes_of(&MTLSize::new(1, 2, 3))[8..16],
+            bytes_of::<NSUInteger>(&2)
+        );
+    }
+
+    #[test]
+    fn inline_bytes() {
+        let values = [0.5f32; 4];
+        let (length, ptr) = inline_slice_bytes(&values);
+        assert_eq!(length, 16);
+        assert_eq!(ptr, values.as_ptr() as *const std::ffi::c_void);
+
+        let (length, _) = inline_value_bytes(&[0u8; MAX_INLINE_BYTES]);
+        assert_eq!(length, MAX_INLINE_BYTES as NSUInteger);
+        let (length, _) = inline_slice_bytes(&[0u32; MAX_INLINE_BYTES / 4]);
+        assert_eq!(length, MAX_INLINE_BYTES as NSUInteger);
+    }
+
+    #[test]
+    #[should_panic(expected = "4100 bytes is larger than the 4 KB set_*_bytes limit")]
+    fn inline_slice_too_large() {
+        inline_slice_bytes(&[0u8; MAX_INLINE_BYTES + 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "set_*_bytes needs at least one byte")]
+    fn inline_slice_empty() {
+        inline_slice_bytes::<f32>(&[]);
+    }
+
+    #[cfg(feature = "derive")]
+    mod derive {
+        use super::*;
+
+        #[derive(Clone, Copy, Pod)]
+        #[pod(crate = "crate")]
+        #[repr(C)]
+        struct Uniforms {
+            model: [[f32; 4]; 4],
+            tint: [f32; 4],
+            count: u32,
+            flags: [u16; 2],
+        }
+
+        #[derive(Clone, Copy, Pod)]
+        #[pod(crate = "crate")]
+        #[repr(transparent)]
+        struct Index(u32);
+
+        #[derive(Clone, Copy, Pod)]
+        #[pod(crate = "crate")]
+        #[repr(C, align(4))]
+        struct Color([u8; 4]);
+
+        #[test]
+        fn derived() {
+            let uniforms = Uniforms {
+                model: [[1.0; 4]; 4],
+                tint: [0.5; 4],
+                count: 7,
+                flags: [1, 2],
+            };
+            assert_eq!(bytes_of(&uniforms).len(), 88);
+            assert_eq!(&bytes_of(&uniforms)[80..], [7, 0, 0, 0, 1, 0, 2, 0]);
+            assert_eq!(slice_bytes(&[Index(1), Index(2)]), [1, 0, 0, 0, 2, 0, 0, 0]);
+            assert_eq!(bytes_of(&Color([1, 2, 3, 4])), [1, 2, 3, 4]);
+        }
+    }
+}
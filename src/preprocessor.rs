@@ -0,0 +1,612 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! A minimal Metal Shading Language preprocessor that resolves
+//! `#include "..."` ahead of [`DeviceRef::new_library_with_source`], which
+//! compiles a single string without an include path.
+//!
+//! Only quoted includes are expanded; `#include <...>` and every other
+//! directive are left for the Metal compiler. Conditional blocks are not
+//! evaluated, so an include inside `#if 0` is still expanded.
+//!
+//! [`DeviceRef::new_library_with_source`]: crate::DeviceRef::new_library_with_source
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Source of the files the preprocessor reads.
+pub trait ShaderFileSystem {
+    /// Return the contents of `path`, or `None` if it does not exist.
+    fn read_file(&self, path: &Path) -> Option<String>;
+
+    /// An opaque value that changes whenever the file at `path` does, used to
+    /// poll for edits. Hashes the contents by default.
+    fn version(&self, path: &Path) -> Option<u64> {
+        let mut hasher = DefaultHasher::new();
+        self.read_file(path)?.hash(&mut hasher);
+        Some(hasher.finish())
+    }
+}
+
+/// Reads shader files from disk.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct StdShaderFileSystem;
+
+impl ShaderFileSystem for StdShaderFileSystem {
+    fn read_file(&self, path: &Path) -> Option<String> {
+        std::fs::read_to_string(path).ok()
+    }
+
+    fn version(&self, path: &Path) -> Option<u64> {
+        let modified = std::fs::metadata(path).ok()?.modified().ok()?;
+        let since_epoch = modified.duration_since(UNIX_EPOCH).ok()?;
+        Some(since_epoch.as_nanos() as u64)
+    }
+}
+
+/// An in-memory file system, e.g. for shaders embedded with `include_str!`.
+#[derive(Clone, Debug, Default)]
+pub struct VirtualShaderFileSystem {
+    files: HashMap<PathBuf, (String, u64)>,
+    generation: u64,
+}
+
+impl VirtualShaderFileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add or replace a file.
+    pub fn add_file<P: AsRef<Path>>(&mut self, path: P, contents: &str) -> &mut Self {
+        self.generation += 1;
+        self.files.insert(
+            normalize_path(path.as_ref()),
+            (contents.to_string(), self.generation),
+        );
+        self
+    }
+
+    pub fn remove_file<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        self.files.remove(&normalize_path(path.as_ref()));
+        self
+    }
+}
+
+impl ShaderFileSystem for VirtualShaderFileSystem {
+    fn read_file(&self, path: &Path) -> Option<String> {
+        self.files
+            .get(&normalize_path(path))
+            .map(|(contents, _)| contents.clone())
+    }
+
+    fn version(&self, path: &Path) -> Option<u64> {
+        self.files
+            .get(&normalize_path(path))
+            .map(|&(_, generation)| generation)
+    }
+}
+
+/// Maps lines of preprocessed source back to the files they came from.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ShaderLineMap {
+    files: Vec<String>,
+    /// `(first output line, file index, first source line)`, sorted by output
+    /// line. Lines are 1-based; a file index of `None` marks generated lines.
+    segments: Vec<(u32, Option<usize>, u32)>,
+}
+
+impl ShaderLineMap {
+    /// The original file and 1-based line of a 1-based output line, or `None`
+    /// for lines the preprocessor generated itself (injected defines).
+    pub fn lookup(&self, line: u32) -> Option<(&str, u32)> {
+        let index = match self
+            .segments
+            .binary_search_by_key(&line, |&(start, _, _)| start)
+        {
+            Ok(index) => index,
+            Err(0) => return None,
+            Err(index) => index - 1,
+        };
+        let (start, file, source_line) = self.segments[index];
+        file.map(|file| (self.files[file].as_str(), source_line + (line - start)))
+    }
+
+    /// Rewrite the `program_source` locations of a compiler diagnostic.
+    pub fn remap(&self, diagnostic: &mut crate::CompilerDiagnostic) {
+        diagnostic.remap_lines(&mut |line| {
+            self.lookup(line)
+                .map(|(file, line)| (file.to_string(), line))
+        });
+    }
+
+    /// Every file that contributed to the output, in inclusion order.
+    pub fn files(&self) -> &[String] {
+        &self.files
+    }
+
+    fn push_line(&mut self, output_line: u32, file: Option<usize>, source_line: u32) {
+        if let Some(&(start, last_file, last_line)) = self.segments.last() {
+            if last_file == file && last_line + (output_line - start) == source_line {
+                return;
+            }
+        }
+        self.segments.push((output_line, file, source_line));
+    }
+}
+
+/// The result of [`ShaderPreprocessor::preprocess_file`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PreprocessedShader {
+    pub source: String,
+    pub line_map: ShaderLineMap,
+    /// Every file that was read, with its [`ShaderFileSystem::version`] taken
+    /// just before the read. An edit racing with preprocessing therefore
+    /// shows up as a newer version rather than being missed.
+    pub file_versions: Vec<(PathBuf, Option<u64>)>,
+}
+
+/// Expands `#include "..."` directives against search paths, injects
+/// `#define`s and records where every output line came from.
+///
+/// ```ignore
+/// let mut fs = VirtualShaderFileSystem::new();
+/// fs.add_file("common.h", "#pragma once\nstruct Light { float3 dir; };\n");
+/// fs.add_file("main.metal", "#include \"common.h\"\nkernel void k() {}\n");
+///
+/// let mut preprocessor = ShaderPreprocessor::new(fs);
+/// preprocessor.define("USE_SHADOWS", "1");
+/// let shader = preprocessor.preprocess_file("main.metal")?;
+/// let library = device.new_library_with_source(&shader.source, &options);
+/// ```
+#[derive(Clone, Debug)]
+pub struct ShaderPreprocessor<F: ShaderFileSystem = StdShaderFileSystem> {
+    file_system: F,
+    search_paths: Vec<PathBuf>,
+    defines: BTreeMap<String, String>,
+}
+
+impl<F: ShaderFileSystem> ShaderPreprocessor<F> {
+    pub fn new(file_system: F) -> Self {
+        ShaderPreprocessor {
+            file_system,
+            search_paths: Vec::new(),
+            defines: BTreeMap::new(),
+        }
+    }
+
+    pub fn file_system(&self) -> &F {
+        &self.file_system
+    }
+
+    pub fn file_system_mut(&mut self) -> &mut F {
+        &mut self.file_system
+    }
+
+    /// Directories searched, in order, after the including file's own
+    /// directory.
+    pub fn add_search_path<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        self.search_paths.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Emit `#define name value` at the top of the output.
+    pub fn define(&mut self, name: &str, value: &str) -> &mut Self {
+        self.defines.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    pub fn defines(&self) -> &BTreeMap<String, String> {
+        &self.defines
+    }
+
+    /// Preprocess the file at `path`, resolved against the search paths if it
+    /// is relative and not found as is.
+    pub fn preprocess_file<P: AsRef<Path>>(&self, path: P) -> Result<PreprocessedShader, String> {
+        let path = path.as_ref();
+        let (path, source, version) = std::iter::once(path.to_path_buf())
+            .chain(self.search_paths.iter().map(|dir| dir.join(path)))
+            .find_map(|candidate| self.read(candidate))
+            .ok_or_else(|| format!("'{}' file not found", path.display()))?;
+        let mut state = State::default();
+        state.record_version(&path, version);
+        self.run(state, &path, &source)
+    }
+
+    /// Preprocess `source` as if it were the contents of the file `path`.
+    pub fn preprocess_source<P: AsRef<Path>>(
+        &self,
+        path: P,
+        source: &str,
+    ) -> Result<PreprocessedShader, String> {
+        self.run(State::default(), &normalize_path(path.as_ref()), source)
+    }
+
+    fn run(
+        &self,
+        mut state: State,
+        path: &Path,
+        source: &str,
+    ) -> Result<PreprocessedShader, String> {
+        for (name, value) in &self.defines {
+            state.emit(&format!("#define {} {}", name, value), None, 0);
+        }
+        self.expand(&mut state, path, source)?;
+        Ok(PreprocessedShader {
+            source: state.output,
+            line_map: state.line_map,
+            file_versions: state.versions,
+        })
+    }
+
+    /// Read a file, taking its version first.
+    fn read(&self, path: PathBuf) -> Option<(PathBuf, String, Option<u64>)> {
+        let path = normalize_path(&path);
+        let version = self.file_system.version(&path);
+        let contents = self.file_system.read_file(&path)?;
+        Some((path, contents, version))
+    }
+
+    fn expand(&self, state: &mut State, path: &Path, source: &str) -> Result<(), String> {
+        let name = path.display().to_string();
+        if state.stack.contains(&name) {
+            return Err(format!(
+                "{}: recursive #include without an include guard",
+                name
+            ));
+        }
+        let file = match state.line_map.files.iter().position(|f| *f == name) {
+            Some(file) => file,
+            None => {
+                state.line_map.files.push(name.clone());
+                state.line_map.files.len() - 1
+            }
+        };
+        if let Some(guard) = include_guard(source) {
+            state.guards.insert(guard.to_string());
+        }
+        state.stack.push(name.clone());
+
+        let mut in_comment = false;
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index as u32 + 1;
+            let directive = if in_comment { None } else { directive(line) };
+            in_comment = ends_in_block_comment(line, in_comment);
+
+            match directive {
+                Some(("pragma", "once")) => {
+                    state.once.insert(name.clone());
+                    // Keep the line so the map stays one-to-one.
+                    state.emit("", Some(file), line_number);
+                }
+                Some(("include", argument)) if argument.starts_with('"') => {
+                    let target = argument[1..]
+                        .split('"')
+                        .next()
+                        .filter(|target| argument[1..].len() > target.len())
+                        .ok_or_else(|| format!("{}:{}: malformed #include", name, line_number))?;
+                    let (resolved, contents, version) =
+                        self.resolve(path, target).ok_or_else(|| {
+                            format!("{}:{}: '{}' file not found", name, line_number, target)
+                        })?;
+                    state.record_version(&resolved, version);
+                    let resolved_name = resolved.display().to_string();
+                    let guarded =
+                        include_guard(&contents).is_some_and(|guard| state.guards.contains(guard));
+                    if !state.once.contains(&resolved_name) && !guarded {
+                        self.expand(state, &resolved, &contents)?;
+                    }
+                }
+                _ => state.emit(line, Some(file), line_number),
+            }
+        }
+
+        state.stack.pop();
+        Ok(())
+    }
+
+    fn resolve(&self, including: &Path, target: &str) -> Option<(PathBuf, String, Option<u64>)> {
+        let local = including.parent().map(|dir| dir.join(target));
+        local
+            .into_iter()
+            .chain(self.search_paths.iter().map(|dir| dir.join(target)))
+            .find_map(|candidate| self.read(candidate))
+    }
+}
+
+impl ShaderPreprocessor<StdShaderFileSystem> {
+    /// A preprocessor reading from disk.
+    pub fn with_std_file_system() -> Self {
+        Self::new(StdShaderFileSystem)
+    }
+}
+
+#[derive(Default)]
+struct State {
+    output: String,
+    output_line: u32,
+    line_map: ShaderLineMap,
+    /// Files currently being expanded, for cycle detection.
+    stack: Vec<String>,
+    /// Files that contained `#pragma once`.
+    once: HashSet<String>,
+    /// Include guard macros of files expanded so far.
+    guards: HashSet<String>,
+    /// Version of every file read, from its first read.
+    versions: Vec<(PathBuf, Option<u64>)>,
+}
+
+impl State {
+    fn emit(&mut self, line: &str, file: Option<usize>, source_line: u32) {
+        self.output_line += 1;
+        self.output.push_str(line);
+        self.output.push('\n');
+        self.line_map.push_line(self.output_line, file, source_line);
+    }
+
+    fn record_version(&mut self, path: &Path, version: Option<u64>) {
+        if !self.versions.iter().any(|(seen, _)| seen == path) {
+            self.versions.push((path.to_path_buf(), version));
+        }
+    }
+}
+
+/// Split a preprocessor directive line into its name and argument.
+fn directive(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start();
+    let end = rest
+        .find(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+        .unwrap_or(rest.len());
+    let (name, argument) = rest.split_at(end);
+    Some((name, argument.trim()))
+}
+
+/// The macro of a classic `#ifndef X` / `#define X` ... `#endif` guard.
+fn include_guard(source: &str) -> Option<&str> {
+    let mut directives = source
+        .lines()
+        .filter(|line| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with("//")
+        })
+        .map(directive);
+    let guard = match directives.next()?? {
+        ("ifndef", guard) => guard,
+        _ => return None,
+    };
+    match directives.next()?? {
+        ("define", define) if define == guard => {}
+        _ => return None,
+    }
+    match source
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty() && !line.trim().starts_with("//"))
+        .and_then(directive)?
+    {
+        ("endif", _) => Some(guard),
+        _ => None,
+    }
+}
+
+/// Whether a block comment is still open at the end of `line`.
+fn ends_in_block_comment(line: &str, mut in_comment: bool) -> bool {
+    let bytes = line.as_bytes();
+    let mut i = 0;
+    while i + 1 < bytes.len() {
+        match (in_comment, bytes[i], bytes[i + 1]) {
+            (true, b'*', b'/') => {
+                in_comment = false;
+                i += 1;
+            }
+            (false, b'/', b'*') => {
+                in_comment = true;
+                i += 1;
+            }
+            (false, b'/', b'/') => return false,
+            _ => {}
+        }
+        i += 1;
+    }
+    in_comment
+}
+
+/// Resolve `.` and `..` components lexically, without touching the disk.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push("..");
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preprocessor(files: &[(&str, &str)]) -> ShaderPreprocessor<VirtualShaderFileSystem> {
+        let mut file_system = VirtualShaderFileSystem::new();
+        for (path, contents) in files {
+            file_system.add_file(path, contents);
+        }
+        ShaderPreprocessor::new(file_system)
+    }
+
+    /// Check that every output line tagged `// @file:line` maps back there.
+    fn assert_line_map(shader: &PreprocessedShader) {
+        for (index, line) in shader.source.lines().enumerate() {
+            if let Some((_, tag)) = line.split_once("// @") {
+                let (file, source_line) = tag.rsplit_once(':').unwrap();
+                assert_eq!(
+                    shader.line_map.lookup(index as u32 + 1),
+                    Some((file, source_line.parse().unwrap())),
+                    "output line {}",
+                    index + 1
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_includes() {
+        let mut preprocessor = preprocessor(&[
+            ("shaders/main.metal", "#include \"local.h\"\n#include \"shared.h\"\nkernel void k() {} // @shaders/main.metal:3\n"),
+            ("shaders/local.h", "int local; // @shaders/local.h:1\n#include \"../common/up.h\"\n"),
+            ("common/up.h", "int up; // @common/up.h:1\n"),
+            ("include/shared.h", "\nint shared; // @include/shared.h:2\n"),
+        ]);
+        preprocessor.add_search_path("include");
+        let shader = preprocessor.preprocess_file("shaders/main.metal").unwrap();
+        assert_eq!(
+            shader.source,
+            "int local; // @shaders/local.h:1\nint up; // @common/up.h:1\n\nint shared; // @include/shared.h:2\nkernel void k() {} // @shaders/main.metal:3\n"
+        );
+        assert_eq!(
+            shader.line_map.files(),
+            [
+                "shaders/main.metal",
+                "shaders/local.h",
+                "common/up.h",
+                "include/shared.h"
+            ]
+        );
+        assert_line_map(&shader);
+
+        // The entry file itself is found through the search paths too.
+        let shader = preprocessor.preprocess_file("shared.h").unwrap();
+        assert_eq!(shader.line_map.files(), ["include/shared.h"]);
+    }
+
+    #[test]
+    fn include_once() {
+        let preprocessor = preprocessor(&[
+            (
+                "main.metal",
+                "#include \"once.h\"\n#include \"guarded.h\"\n#include \"once.h\"\n#include \"guarded.h\"\nint main; // @main.metal:5\n",
+            ),
+            ("once.h", "#pragma once\nint once; // @once.h:2\n"),
+            (
+                "guarded.h",
+                "// comment\n#ifndef GUARDED_H\n#define GUARDED_H\nint guarded; // @guarded.h:4\n#endif\n",
+            ),
+        ]);
+        let shader = preprocessor.preprocess_file("main.metal").unwrap();
+        assert_eq!(shader.source.matches("int once;").count(), 1);
+        assert_eq!(shader.source.matches("int guarded;").count(), 1);
+        assert_line_map(&shader);
+    }
+
+    #[test]
+    fn defines_and_untouched_directives() {
+        let mut preprocessor = preprocessor(&[(
+            "main.metal",
+            "#include <metal_stdlib>\n/*\n#include \"missing.h\"\n*/\n#if USE_SHADOWS\nint shadows; // @main.metal:6\n#endif\n",
+        )]);
+        preprocessor
+            .define("USE_SHADOWS", "1")
+            .define("LIGHT_COUNT", "4");
+        let shader = preprocessor.preprocess_file("main.metal").unwrap();
+        let lines = shader.source.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[..3],
+            [
+                "#define LIGHT_COUNT 4",
+                "#define USE_SHADOWS 1",
+                "#include <metal_stdlib>"
+            ]
+        );
+        assert!(shader.source.contains("#include \"missing.h\""));
+        assert_eq!(shader.line_map.lookup(1), None);
+        assert_eq!(shader.line_map.lookup(2), None);
+        assert_eq!(shader.line_map.lookup(3), Some(("main.metal", 1)));
+        assert_line_map(&shader);
+    }
+
+    #[test]
+    fn errors() {
+        let preprocessor = preprocessor(&[
+            ("missing.metal", "\n#include \"nope.h\"\n"),
+            ("malformed.metal", "#include \"nope.h\n"),
+            ("a.h", "#include \"b.h\"\n"),
+            ("b.h", "#include \"a.h\"\n"),
+        ]);
+        assert_eq!(
+            preprocessor.preprocess_file("missing.metal").unwrap_err(),
+            "missing.metal:2: 'nope.h' file not found"
+        );
+        assert_eq!(
+            preprocessor.preprocess_file("malformed.metal").unwrap_err(),
+            "malformed.metal:1: malformed #include"
+        );
+        assert!(preprocessor
+            .preprocess_file("a.h")
+            .unwrap_err()
+            .contains("recursive #include"));
+        assert!(preprocessor.preprocess_file("absent.metal").is_err());
+    }
+
+    #[test]
+    fn remap_diagnostics() {
+        let mut preprocessor = preprocessor(&[
+            (
+                "main.metal",
+                "#include \"common.h\"\nkernel void k() { x; }\n",
+            ),
+            ("common.h", "#pragma once\nstruct Light {};\n"),
+        ]);
+        preprocessor.define("DEBUG", "1");
+        let shader = preprocessor.preprocess_file("main.metal").unwrap();
+        let log = "program_source:4:19: error: use of undeclared identifier 'x'\n\
+                   program_source:3:1: note: previous definition is here\n";
+        let mut diagnostics = crate::parse_compiler_diagnostics(log);
+        shader.line_map.remap(&mut diagnostics[0]);
+        let location = diagnostics[0].location.as_ref().unwrap();
+        assert_eq!((location.file.as_str(), location.line), ("main.metal", 2));
+        let note = diagnostics[0].notes[0].location.as_ref().unwrap();
+        assert_eq!((note.file.as_str(), note.line), ("common.h", 2));
+    }
+
+    #[test]
+    fn virtual_file_versions() {
+        let mut file_system = VirtualShaderFileSystem::new();
+        file_system.add_file("./dir/../a.h", "1");
+        let version = file_system.version(Path::new("a.h")).unwrap();
+        file_system.add_file("a.h", "2");
+        assert!(file_system.version(Path::new("a.h")).unwrap() > version);
+        file_system.remove_file("a.h");
+        assert_eq!(file_system.version(Path::new("a.h")), None);
+        assert_eq!(
+            normalize_path(Path::new("../x/./y/../z")),
+            Path::new("../x/z")
+        );
+    }
+
+    #[test]
+    fn std_file_system() {
+        let dir =
+            std::env::temp_dir().join(format!("metal-rs-preprocessor-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("include")).unwrap();
+        std::fs::write(dir.join("main.metal"), "#include \"common.h\"\nint main;\n").unwrap();
+        std::fs::write(dir.join("include/common.h"), "int common;\n").unwrap();
+
+        let mut preprocessor = ShaderPreprocessor::with_std_file_system();
+        preprocessor.add_search_path(dir.join("include"));
+        let shader = preprocessor.preprocess_file(dir.join("main.metal"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(shader.unwrap().source, "int common;\nint main;\n");
+    }
+}
@@ -37,6 +37,13 @@ pub enum MTLStorageMode {
     Memoryless = 3,
 }
 
+impl MTLStorageMode {
+    /// Whether `contents()` gives CPU access to the resource's memory.
+    pub fn is_cpu_accessible(self) -> bool {
+        matches!(self, MTLStorageMode::Shared | MTLStorageMode::Managed)
+    }
+}
+
 /// Only available on macos(10.15), ios(13.0)
 ///
 /// See <https://developer.apple.com/documentation/metal/mtlhazardtrackingmode>
@@ -77,6 +84,229 @@ bitflags::bitflags! {
     }
 }
 
+impl MTLResourceOptions {
+    /// The storage mode bits, or `None` if they don't name a mode.
+    pub fn storage_mode(self) -> Option<MTLStorageMode> {
+        match (self.bits() & MTLResourceStorageModeMask) >> MTLResourceStorageModeShift {
+            0 => Some(MTLStorageMode::Shared),
+            1 => Some(MTLStorageMode::Managed),
+            2 => Some(MTLStorageMode::Private),
+            3 => Some(MTLStorageMode::Memoryless),
+            _ => None,
+        }
+    }
+
+    /// The CPU cache mode bits, or `None` if they don't name a mode.
+    pub fn cpu_cache_mode(self) -> Option<MTLCPUCacheMode> {
+        match (self.bits() & MTLResourceCPUCacheModeMask) >> MTLResourceCPUCacheModeShift {
+            0 => Some(MTLCPUCacheMode::DefaultCache),
+            1 => Some(MTLCPUCacheMode::WriteCombined),
+            _ => None,
+        }
+    }
+
+    /// The hazard tracking mode bits, or `None` if they don't name a mode.
+    pub fn hazard_tracking_mode(self) -> Option<MTLHazardTrackingMode> {
+        match (self.bits() & MTLResourceHazardTrackingModeMask)
+            >> MTLResourceHazardTrackingModeShift
+        {
+            0 => Some(MTLHazardTrackingMode::Default),
+            1 => Some(MTLHazardTrackingMode::Untracked),
+            2 => Some(MTLHazardTrackingMode::Tracked),
+            _ => None,
+        }
+    }
+
+    /// Replace the storage mode bits.
+    pub fn with_storage_mode(self, mode: MTLStorageMode) -> Self {
+        Self::from_bits_retain(
+            (self.bits() & !MTLResourceStorageModeMask)
+                | (mode as NSUInteger) << MTLResourceStorageModeShift,
+        )
+    }
+
+    /// Replace the CPU cache mode bits.
+    pub fn with_cpu_cache_mode(self, mode: MTLCPUCacheMode) -> Self {
+        Self::from_bits_retain(
+            (self.bits() & !MTLResourceCPUCacheModeMask)
+                | (mode as NSUInteger) << MTLResourceCPUCacheModeShift,
+        )
+    }
+
+    /// Replace the hazard tracking mode bits.
+    pub fn with_hazard_tracking_mode(self, mode: MTLHazardTrackingMode) -> Self {
+        Self::from_bits_retain(
+            (self.bits() & !MTLResourceHazardTrackingModeMask)
+                | (mode as NSUInteger) << MTLResourceHazardTrackingModeShift,
+        )
+    }
+}
+
+/// The kind of resource a set of [`ResourceModes`] is for.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ResourceKind {
+    Buffer,
+    Texture,
+    Heap,
+}
+
+/// Whether the target OS has `MTLStorageMode::Managed`: macOS and Mac
+/// Catalyst do, iOS, tvOS and visionOS don't.
+pub const MANAGED_STORAGE_AVAILABLE: bool = cfg!(any(target_os = "macos", target_abi = "macabi"));
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum ResourceOptionsError {
+    /// Bits outside the storage, CPU cache and hazard tracking fields.
+    UnknownBits(NSUInteger),
+    UnknownStorageMode(NSUInteger),
+    UnknownCPUCacheMode(NSUInteger),
+    UnknownHazardTrackingMode(NSUInteger),
+    /// Managed storage on a target without it.
+    ManagedUnavailable,
+    /// Only textures can be memoryless.
+    Memoryless(ResourceKind),
+    /// Heaps can only be shared or private.
+    ManagedHeap,
+}
+
+impl std::fmt::Display for ResourceOptionsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match *self {
+            ResourceOptionsError::UnknownBits(bits) => {
+                write!(f, "unknown resource option bits {:#x}", bits)
+            }
+            ResourceOptionsError::UnknownStorageMode(value) => {
+                write!(f, "unknown storage mode {}", value)
+            }
+            ResourceOptionsError::UnknownCPUCacheMode(value) => {
+                write!(f, "unknown CPU cache mode {}", value)
+            }
+            ResourceOptionsError::UnknownHazardTrackingMode(value) => {
+                write!(f, "unknown hazard tracking mode {}", value)
+            }
+            ResourceOptionsError::ManagedUnavailable => {
+                write!(f, "managed storage is only available on macOS")
+            }
+            ResourceOptionsError::Memoryless(kind) => {
+                write!(f, "{:?} resources can't be memoryless", kind)
+            }
+            ResourceOptionsError::ManagedHeap => write!(f, "heaps can't use managed storage"),
+        }
+    }
+}
+
+impl std::error::Error for ResourceOptionsError {}
+
+/// The fields packed into [`MTLResourceOptions`], converting to and from it
+/// without loss.
+///
+/// ```ignore
+/// let modes = ResourceModes::try_from(buffer.resource_options())?;
+/// if modes.storage_mode == MTLStorageMode::Managed {
+///     buffer.did_modify_range(range);
+/// }
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct ResourceModes {
+    pub storage_mode: MTLStorageMode,
+    pub cpu_cache_mode: MTLCPUCacheMode,
+    pub hazard_tracking_mode: MTLHazardTrackingMode,
+}
+
+impl Default for ResourceModes {
+    fn default() -> Self {
+        ResourceModes {
+            storage_mode: MTLStorageMode::Shared,
+            cpu_cache_mode: MTLCPUCacheMode::DefaultCache,
+            hazard_tracking_mode: MTLHazardTrackingMode::Default,
+        }
+    }
+}
+
+impl ResourceModes {
+    pub fn new(
+        storage_mode: MTLStorageMode,
+        cpu_cache_mode: MTLCPUCacheMode,
+        hazard_tracking_mode: MTLHazardTrackingMode,
+    ) -> Self {
+        ResourceModes {
+            storage_mode,
+            cpu_cache_mode,
+            hazard_tracking_mode,
+        }
+    }
+
+    pub fn options(self) -> MTLResourceOptions {
+        self.into()
+    }
+
+    /// Check the combination is legal for `kind`, given whether the target
+    /// has managed storage (see [`MANAGED_STORAGE_AVAILABLE`]).
+    pub fn validate(
+        self,
+        kind: ResourceKind,
+        managed_available: bool,
+    ) -> Result<(), ResourceOptionsError> {
+        match (self.storage_mode, kind) {
+            (MTLStorageMode::Managed, _) if !managed_available => {
+                Err(ResourceOptionsError::ManagedUnavailable)
+            }
+            (MTLStorageMode::Managed, ResourceKind::Heap) => Err(ResourceOptionsError::ManagedHeap),
+            (MTLStorageMode::Memoryless, ResourceKind::Buffer | ResourceKind::Heap) => {
+                Err(ResourceOptionsError::Memoryless(kind))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// [`validate`](Self::validate) for the OS being compiled for.
+    pub fn validate_for_target(self, kind: ResourceKind) -> Result<(), ResourceOptionsError> {
+        self.validate(kind, MANAGED_STORAGE_AVAILABLE)
+    }
+}
+
+impl From<ResourceModes> for MTLResourceOptions {
+    fn from(modes: ResourceModes) -> Self {
+        MTLResourceOptions::empty()
+            .with_storage_mode(modes.storage_mode)
+            .with_cpu_cache_mode(modes.cpu_cache_mode)
+            .with_hazard_tracking_mode(modes.hazard_tracking_mode)
+    }
+}
+
+impl TryFrom<MTLResourceOptions> for ResourceModes {
+    type Error = ResourceOptionsError;
+
+    fn try_from(options: MTLResourceOptions) -> Result<Self, Self::Error> {
+        let bits = options.bits();
+        let unknown = bits
+            & !(MTLResourceStorageModeMask
+                | MTLResourceCPUCacheModeMask
+                | MTLResourceHazardTrackingModeMask);
+        if unknown != 0 {
+            return Err(ResourceOptionsError::UnknownBits(unknown));
+        }
+        Ok(ResourceModes {
+            storage_mode: options.storage_mode().ok_or(
+                ResourceOptionsError::UnknownStorageMode(
+                    (bits & MTLResourceStorageModeMask) >> MTLResourceStorageModeShift,
+                ),
+            )?,
+            cpu_cache_mode: options.cpu_cache_mode().ok_or(
+                ResourceOptionsError::UnknownCPUCacheMode(
+                    (bits & MTLResourceCPUCacheModeMask) >> MTLResourceCPUCacheModeShift,
+                ),
+            )?,
+            hazard_tracking_mode: options.hazard_tracking_mode().ok_or(
+                ResourceOptionsError::UnknownHazardTrackingMode(
+                    (bits & MTLResourceHazardTrackingModeMask)
+                        >> MTLResourceHazardTrackingModeShift,
+                ),
+            )?,
+        })
+    }
+}
+
 bitflags::bitflags! {
     /// Options that describe how a graphics or compute function uses an argument buffer’s resource.
     ///
@@ -180,3 +410,215 @@ impl ResourceRef {
         unsafe { msg_send_bool![self, isAliasable] }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STORAGE_MODES: [MTLStorageMode; 4] = [
+        MTLStorageMode::Shared,
+        MTLStorageMode::Managed,
+        MTLStorageMode::Private,
+        MTLStorageMode::Memoryless,
+    ];
+    const CPU_CACHE_MODES: [MTLCPUCacheMode; 2] = [
+        MTLCPUCacheMode::DefaultCache,
+        MTLCPUCacheMode::WriteCombined,
+    ];
+    const HAZARD_TRACKING_MODES: [MTLHazardTrackingMode; 3] = [
+        MTLHazardTrackingMode::Default,
+        MTLHazardTrackingMode::Untracked,
+        MTLHazardTrackingMode::Tracked,
+    ];
+    const KINDS: [ResourceKind; 3] = [
+        ResourceKind::Buffer,
+        ResourceKind::Texture,
+        ResourceKind::Heap,
+    ];
+
+    fn all_modes() -> impl Iterator<Item = ResourceModes> {
+        STORAGE_MODES.into_iter().flat_map(|storage_mode| {
+            CPU_CACHE_MODES.into_iter().flat_map(move |cpu_cache_mode| {
+                HAZARD_TRACKING_MODES
+                    .into_iter()
+                    .map(move |hazard_tracking_mode| {
+                        ResourceModes::new(storage_mode, cpu_cache_mode, hazard_tracking_mode)
+                    })
+            })
+        })
+    }
+
+    #[test]
+    fn named_flags() {
+        let flags = |storage, cache, hazard| ResourceModes::new(storage, cache, hazard).options();
+        assert_eq!(
+            flags(
+                MTLStorageMode::Private,
+                MTLCPUCacheMode::DefaultCache,
+                MTLHazardTrackingMode::Default
+            ),
+            MTLResourceOptions::StorageModePrivate
+        );
+        assert_eq!(
+            flags(
+                MTLStorageMode::Shared,
+                MTLCPUCacheMode::WriteCombined,
+                MTLHazardTrackingMode::Untracked
+            ),
+            MTLResourceOptions::CPUCacheModeWriteCombined
+                | MTLResourceOptions::HazardTrackingModeUntracked
+        );
+        assert_eq!(
+            flags(
+                MTLStorageMode::Memoryless,
+                MTLCPUCacheMode::DefaultCache,
+                MTLHazardTrackingMode::Tracked
+            ),
+            MTLResourceOptions::StorageModeMemoryless
+                | MTLResourceOptions::HazardTrackingModeTracked
+        );
+        assert_eq!(
+            ResourceModes::default().options(),
+            MTLResourceOptions::empty()
+        );
+    }
+
+    #[test]
+    fn every_combination_round_trips() {
+        let mut combinations = 0;
+        for modes in all_modes() {
+            let options = modes.options();
+            let bits = (modes.storage_mode as NSUInteger) << 4
+                | modes.cpu_cache_mode as NSUInteger
+                | (modes.hazard_tracking_mode as NSUInteger) << 8;
+            assert_eq!(options.bits(), bits, "{:?}", modes);
+            assert_eq!(ResourceModes::try_from(options), Ok(modes));
+            assert_eq!(options.storage_mode(), Some(modes.storage_mode));
+            assert_eq!(options.cpu_cache_mode(), Some(modes.cpu_cache_mode));
+            assert_eq!(
+                options.hazard_tracking_mode(),
+                Some(modes.hazard_tracking_mode)
+            );
+            combinations += 1;
+        }
+        assert_eq!(combinations, 24);
+    }
+
+    #[test]
+    fn replacing_one_field_keeps_the_others() {
+        for modes in all_modes() {
+            for other in all_modes() {
+                let options = modes
+                    .options()
+                    .with_storage_mode(other.storage_mode)
+                    .with_hazard_tracking_mode(other.hazard_tracking_mode);
+                assert_eq!(
+                    ResourceModes::try_from(options),
+                    Ok(ResourceModes {
+                        cpu_cache_mode: modes.cpu_cache_mode,
+                        ..other
+                    })
+                );
+                assert_eq!(
+                    options.with_cpu_cache_mode(other.cpu_cache_mode),
+                    other.options()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn every_bit_pattern_is_decoded_or_rejected() {
+        let mut valid = 0;
+        for bits in 0..0x1000 {
+            let options = MTLResourceOptions::from_bits_retain(bits);
+            match ResourceModes::try_from(options) {
+                Ok(modes) => {
+                    assert_eq!(modes.options(), options);
+                    valid += 1;
+                }
+                Err(ResourceOptionsError::UnknownBits(unknown)) => {
+                    assert_eq!(unknown, bits & !0x3ff);
+                }
+                Err(ResourceOptionsError::UnknownStorageMode(value)) => {
+                    assert!(value >= 4);
+                    assert_eq!(options.storage_mode(), None);
+                }
+                Err(ResourceOptionsError::UnknownCPUCacheMode(value)) => {
+                    assert!(value >= 2);
+                    assert_eq!(options.cpu_cache_mode(), None);
+                }
+                Err(ResourceOptionsError::UnknownHazardTrackingMode(value)) => {
+                    assert_eq!(value, 3);
+                    assert_eq!(options.hazard_tracking_mode(), None);
+                }
+                Err(err) => panic!("{:#x}: unexpected {:?}", bits, err),
+            }
+        }
+        assert_eq!(valid, 24);
+        assert_eq!(
+            ResourceModes::try_from(MTLResourceOptions::from_bits_retain(0x50)),
+            Err(ResourceOptionsError::UnknownStorageMode(5))
+        );
+        assert_eq!(
+            ResourceModes::try_from(MTLResourceOptions::from_bits_retain(0x1000)),
+            Err(ResourceOptionsError::UnknownBits(0x1000))
+        );
+    }
+
+    #[test]
+    fn every_combination_is_validated() {
+        use MTLStorageMode::*;
+        use ResourceKind::*;
+        use ResourceOptionsError as E;
+        // Storage mode, kind, result without and with managed storage.
+        #[rustfmt::skip]
+        let table = [
+            (Shared,     Buffer,  Ok(()),                      Ok(())),
+            (Shared,     Texture, Ok(()),                      Ok(())),
+            (Shared,     Heap,    Ok(()),                      Ok(())),
+            (Managed,    Buffer,  Err(E::ManagedUnavailable),  Ok(())),
+            (Managed,    Texture, Err(E::ManagedUnavailable),  Ok(())),
+            (Managed,    Heap,    Err(E::ManagedUnavailable),  Err(E::ManagedHeap)),
+            (Private,    Buffer,  Ok(()),                      Ok(())),
+            (Private,    Texture, Ok(()),                      Ok(())),
+            (Private,    Heap,    Ok(()),                      Ok(())),
+            (Memoryless, Buffer,  Err(E::Memoryless(Buffer)),  Err(E::Memoryless(Buffer))),
+            (Memoryless, Texture, Ok(()),                      Ok(())),
+            (Memoryless, Heap,    Err(E::Memoryless(Heap)),    Err(E::Memoryless(Heap))),
+        ];
+        let mut checked = 0;
+        for modes in all_modes() {
+            for kind in KINDS {
+                let &(_, _, without_managed, with_managed) = table
+                    .iter()
+                    .find(|&&(storage_mode, k, _, _)| {
+                        storage_mode == modes.storage_mode && k == kind
+                    })
+                    .unwrap();
+                for (managed_available, expected) in
+                    [(false, without_managed), (true, with_managed)]
+                {
+                    assert_eq!(
+                        modes.validate(kind, managed_available),
+                        expected,
+                        "{:?} for {:?}, managed storage available: {}",
+                        modes,
+                        kind,
+                        managed_available
+                    );
+                    checked += 1;
+                }
+                assert_eq!(
+                    modes.validate_for_target(kind),
+                    modes.validate(kind, MANAGED_STORAGE_AVAILABLE)
+                );
+            }
+        }
+        assert_eq!(checked, 24 * 3 * 2);
+        assert_eq!(
+            ResourceOptionsError::Memoryless(ResourceKind::Buffer).to_string(),
+            "Buffer resources can't be memoryless"
+        );
+    }
+}
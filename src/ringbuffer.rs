@@ -0,0 +1,677 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::*;
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::mem;
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// Offsets in a ring of `capacity` bytes, handed out per frame and
+/// reclaimed once a frame is known to be complete.
+///
+/// Frames are numbered from 1. Everything allocated between two calls to
+/// [`finish_frame`](Self::finish_frame) belongs to the frame it returns.
+#[derive(Clone, Debug)]
+pub struct RingAllocator {
+    capacity: NSUInteger,
+    head: NSUInteger,
+    tail: NSUInteger,
+    used: NSUInteger,
+    current_frame: u64,
+    current_bytes: NSUInteger,
+    /// Finished frames still in flight: id, head when finished, bytes.
+    in_flight: VecDeque<(u64, NSUInteger, NSUInteger)>,
+}
+
+impl RingAllocator {
+    pub fn new(capacity: NSUInteger) -> Self {
+        Self::starting_at_frame(capacity, 1)
+    }
+
+    /// An empty ring whose first frame is `frame`, e.g. to replace a ring
+    /// that ran out of space without reusing frame numbers.
+    pub fn starting_at_frame(capacity: NSUInteger, frame: u64) -> Self {
+        RingAllocator {
+            capacity,
+            head: 0,
+            tail: 0,
+            used: 0,
+            current_frame: frame,
+            current_bytes: 0,
+            in_flight: VecDeque::new(),
+        }
+    }
+
+    pub fn capacity(&self) -> NSUInteger {
+        self.capacity
+    }
+
+    /// Bytes allocated and not yet reclaimed, including padding.
+    pub fn used(&self) -> NSUInteger {
+        self.used
+    }
+
+    /// The frame new allocations belong to.
+    pub fn current_frame(&self) -> u64 {
+        self.current_frame
+    }
+
+    /// The oldest finished frame that hasn't been reclaimed.
+    pub fn oldest_in_flight(&self) -> Option<u64> {
+        self.in_flight.front().map(|&(frame, _, _)| frame)
+    }
+
+    pub fn frames_in_flight(&self) -> usize {
+        self.in_flight.len()
+    }
+
+    /// Allocate `size` bytes at a multiple of `align`. Returns `None` when
+    /// the free part of the ring has no room for it.
+    pub fn allocate(&mut self, size: NSUInteger, align: NSUInteger) -> Option<NSUInteger> {
+        let align = align.max(1);
+        if self.used == 0 {
+            self.head = 0;
+            self.tail = 0;
+        } else if self.head == self.tail {
+            return None;
+        }
+        let start = self.head.checked_next_multiple_of(align)?;
+        let end = start.checked_add(size)?;
+        let (start, end) = if self.head < self.tail {
+            if end > self.tail {
+                return None;
+            }
+            (start, end)
+        } else if end <= self.capacity {
+            (start, end)
+        } else if size <= self.tail {
+            // Skip the rest of the ring and start again at 0.
+            (0, size)
+        } else {
+            return None;
+        };
+        let consumed = if start >= self.head {
+            end - self.head
+        } else {
+            self.capacity - self.head + end
+        };
+        self.head = end;
+        self.used += consumed;
+        self.current_bytes += consumed;
+        Some(start)
+    }
+
+    /// End the current frame and return its number.
+    pub fn finish_frame(&mut self) -> u64 {
+        let frame = self.current_frame;
+        self.in_flight
+            .push_back((frame, self.head, mem::take(&mut self.current_bytes)));
+        self.current_frame += 1;
+        frame
+    }
+
+    /// Reclaim the space of every finished frame up to and including
+    /// `completed_frame`.
+    pub fn reclaim(&mut self, completed_frame: u64) {
+        while let Some(&(frame, end, bytes)) = self.in_flight.front() {
+            if frame > completed_frame {
+                break;
+            }
+            self.in_flight.pop_front();
+            // An empty frame's recorded head may predate a reset of the
+            // ring in `allocate`, so only frames that own space move the tail.
+            if bytes > 0 {
+                self.tail = end;
+            }
+            self.used -= bytes;
+        }
+    }
+}
+
+/// How far the GPU has got through a sequence of frames.
+pub trait FrameCompletion {
+    /// The highest frame known to be complete, 0 if none is.
+    fn completed_frame(&self) -> u64;
+
+    /// Wait until `frame` is complete. Returns `false` on timeout.
+    fn wait_for_frame(&self, frame: u64, timeout: Duration) -> bool;
+}
+
+/// Frame completion signalled from command buffer completion handlers.
+#[derive(Clone, Debug, Default)]
+pub struct FrameFence {
+    inner: Arc<(Mutex<u64>, Condvar)>,
+}
+
+impl FrameFence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mark every frame up to `frame` complete.
+    pub fn signal(&self, frame: u64) {
+        let (completed, condvar) = &*self.inner;
+        let mut completed = completed.lock().unwrap();
+        if frame > *completed {
+            *completed = frame;
+            condvar.notify_all();
+        }
+    }
+
+    /// Signal `frame` when `command_buffer` completes.
+    pub fn signal_on_completion(&self, command_buffer: &CommandBufferRef, frame: u64) {
+        let fence = self.clone();
+        let block =
+            block::ConcreteBlock::new(move |_: &CommandBufferRef| fence.signal(frame)).copy();
+        command_buffer.add_completed_handler(&block);
+    }
+}
+
+impl FrameCompletion for FrameFence {
+    fn completed_frame(&self) -> u64 {
+        *self.inner.0.lock().unwrap()
+    }
+
+    fn wait_for_frame(&self, frame: u64, timeout: Duration) -> bool {
+        let (completed, condvar) = &*self.inner;
+        let completed = completed.lock().unwrap();
+        let (completed, _) = condvar
+            .wait_timeout_while(completed, timeout, |completed| *completed < frame)
+            .unwrap();
+        *completed >= frame
+    }
+}
+
+/// Frame completion read from a shared event's signaled value, set with
+/// `encode_signal_event(event, frame)` at the end of each frame. Use an
+/// event dedicated to one ring buffer.
+///
+/// Waiting polls the event.
+impl FrameCompletion for SharedEvent {
+    fn completed_frame(&self) -> u64 {
+        self.signaled_value()
+    }
+
+    fn wait_for_frame(&self, frame: u64, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if self.signaled_value() >= frame {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                return false;
+            }
+            std::thread::sleep(Duration::from_micros(100));
+        }
+    }
+}
+
+/// What [`FrameRingBuffer`] does when an allocation doesn't fit.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RingBufferFullPolicy {
+    /// Fail with [`RingBufferError::OutOfSpace`].
+    Fail,
+    /// Wait for in-flight frames to complete, up to `timeout` per frame.
+    Block { timeout: Duration },
+    /// Switch to a buffer twice the size, or `max_capacity` if that is
+    /// smaller, and at least large enough for the allocation. Fails once the
+    /// buffer is `max_capacity` bytes. The old buffer is kept until its
+    /// frames complete.
+    Grow { max_capacity: NSUInteger },
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RingBufferError {
+    OutOfSpace {
+        size: NSUInteger,
+        capacity: NSUInteger,
+    },
+    /// Blocking for `frame` to complete timed out.
+    Timeout { frame: u64 },
+}
+
+impl fmt::Display for RingBufferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RingBufferError::OutOfSpace { size, capacity } => write!(
+                f,
+                "no room for {} bytes in a {} byte ring buffer",
+                size, capacity
+            ),
+            RingBufferError::Timeout { frame } => {
+                write!(f, "timed out waiting for frame {} to complete", frame)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RingBufferError {}
+
+/// Part of a [`FrameRingBuffer`], valid until the frame it was allocated in
+/// completes on the GPU.
+#[derive(Clone, Debug)]
+pub struct RingSlice<B = Buffer> {
+    pub buffer: B,
+    pub offset: NSUInteger,
+    pub size: NSUInteger,
+}
+
+/// A ring of transient per-frame data, such as uniforms and dynamic vertex
+/// data, in a CPU-visible buffer.
+///
+/// Call [`finish_frame`](Self::finish_frame) once per frame and arrange for
+/// the frame's completion to be reported to the [`FrameCompletion`] source;
+/// space is reclaimed as frames complete.
+///
+/// ```ignore
+/// let fence = FrameFence::new();
+/// let mut ring = FrameRingBuffer::new(
+///     &device,
+///     MTLFeatureSet::macOS_GPUFamily2_v1,
+///     4 << 20,
+///     MTLResourceOptions::StorageModeShared,
+///     RingBufferFullPolicy::Block { timeout: Duration::from_secs(1) },
+///     fence.clone(),
+/// );
+///
+/// let uniforms = ring.upload(&[frame_uniforms])?;
+/// encoder.set_vertex_buffer(1, Some(&uniforms.buffer), uniforms.offset);
+/// // ...
+/// fence.signal_on_completion(command_buffer, ring.finish_frame());
+/// command_buffer.commit();
+/// ```
+pub struct FrameRingBuffer<B: BufferStorage + Clone = Buffer, C: FrameCompletion = FrameFence> {
+    buffer: B,
+    ring: RingAllocator,
+    alignment: NSUInteger,
+    policy: RingBufferFullPolicy,
+    completion: C,
+    new_buffer: Box<dyn FnMut(NSUInteger) -> Option<B>>,
+    /// Replaced buffers and the last frame that used them.
+    retired: Vec<(B, u64)>,
+}
+
+impl<C: FrameCompletion> FrameRingBuffer<Buffer, C> {
+    /// Allocations are aligned to at least
+    /// [`MTLFeatureSet::min_buffer_offset_alignment`] of `feature_set`, which
+    /// should be one `device` supports.
+    ///
+    /// # Panics
+    ///
+    /// If `options` don't give CPU-accessible storage.
+    pub fn new(
+        device: &DeviceRef,
+        feature_set: MTLFeatureSet,
+        capacity: NSUInteger,
+        options: MTLResourceOptions,
+        policy: RingBufferFullPolicy,
+        completion: C,
+    ) -> Self {
+        let alignment = feature_set.min_buffer_offset_alignment() as NSUInteger;
+        let device = device.to_owned();
+        let buffer = device.new_buffer(capacity, options);
+        assert!(
+            buffer.storage_mode().is_cpu_accessible(),
+            "ring buffers need CPU-accessible storage"
+        );
+        Self::with_buffer(buffer, alignment, policy, completion, move |capacity| {
+            Some(device.new_buffer(capacity, options))
+        })
+    }
+}
+
+impl<B: BufferStorage + Clone, C: FrameCompletion> FrameRingBuffer<B, C> {
+    /// A ring over `buffer`, creating larger buffers with `new_buffer` when
+    /// the policy is [`RingBufferFullPolicy::Grow`].
+    pub fn with_buffer(
+        buffer: B,
+        alignment: NSUInteger,
+        policy: RingBufferFullPolicy,
+        completion: C,
+        new_buffer: impl FnMut(NSUInteger) -> Option<B> + 'static,
+    ) -> Self {
+        FrameRingBuffer {
+            ring: RingAllocator::new(buffer.length()),
+            buffer,
+            alignment: alignment.max(1),
+            policy,
+            completion,
+            new_buffer: Box::new(new_buffer),
+            retired: Vec::new(),
+        }
+    }
+
+    pub fn buffer(&self) -> &B {
+        &self.buffer
+    }
+
+    pub fn capacity(&self) -> NSUInteger {
+        self.ring.capacity()
+    }
+
+    pub fn completion(&self) -> &C {
+        &self.completion
+    }
+
+    pub fn ring(&self) -> &RingAllocator {
+        &self.ring
+    }
+
+    /// Buffers replaced by growing that frames in flight still use.
+    pub fn retired_buffers(&self) -> usize {
+        self.retired.len()
+    }
+
+    /// End the current frame. Report its completion under the returned
+    /// number.
+    pub fn finish_frame(&mut self) -> u64 {
+        self.ring.finish_frame()
+    }
+
+    /// Reclaim the space of completed frames. Allocating does this too.
+    pub fn reclaim(&mut self) {
+        let completed = self.completion.completed_frame();
+        self.ring.reclaim(completed);
+        self.retired
+            .retain(|&(_, last_frame)| last_frame > completed);
+    }
+
+    /// Allocate `size` bytes aligned to `align` and to the buffer offset
+    /// alignment.
+    pub fn allocate(
+        &mut self,
+        size: NSUInteger,
+        align: NSUInteger,
+    ) -> Result<RingSlice<B>, RingBufferError> {
+        let align = align.max(self.alignment).next_power_of_two();
+        self.reclaim();
+        loop {
+            if let Some(offset) = self.ring.allocate(size, align) {
+                return Ok(RingSlice {
+                    buffer: self.buffer.clone(),
+                    offset,
+                    size,
+                });
+            }
+            let out_of_space = RingBufferError::OutOfSpace {
+                size,
+                capacity: self.ring.capacity(),
+            };
+            match self.policy {
+                RingBufferFullPolicy::Fail => return Err(out_of_space),
+                RingBufferFullPolicy::Block { timeout } => {
+                    let frame = self.ring.oldest_in_flight().ok_or(out_of_space)?;
+                    if !self.completion.wait_for_frame(frame, timeout) {
+                        return Err(RingBufferError::Timeout { frame });
+                    }
+                    self.reclaim();
+                }
+                RingBufferFullPolicy::Grow { max_capacity } => {
+                    let needed = size.checked_add(align - 1).ok_or(out_of_space)?;
+                    let capacity = self
+                        .ring
+                        .capacity()
+                        .saturating_mul(2)
+                        .min(max_capacity)
+                        .max(needed);
+                    if capacity > max_capacity || capacity <= self.ring.capacity() {
+                        return Err(out_of_space);
+                    }
+                    let buffer = (self.new_buffer)(capacity).ok_or(out_of_space)?;
+                    let frame = self.ring.current_frame();
+                    let old = mem::replace(&mut self.buffer, buffer);
+                    self.retired.push((old, frame));
+                    self.ring = RingAllocator::starting_at_frame(capacity, frame);
+                }
+            }
+        }
+    }
+
+    /// Copy `values` into the ring.
+    pub fn upload<T: Pod>(&mut self, values: &[T]) -> Result<RingSlice<B>, RingBufferError> {
+        let bytes = slice_bytes(values);
+        let slice = self.allocate(
+            bytes.len() as NSUInteger,
+            mem::align_of::<T>() as NSUInteger,
+        )?;
+        if !bytes.is_empty() {
+            unsafe {
+                let dst = (slice.buffer.contents() as *mut u8).add(slice.offset as usize);
+                std::ptr::copy_nonoverlapping(bytes.as_ptr(), dst, bytes.len());
+            }
+            if slice.buffer.storage_mode() == MTLStorageMode::Managed {
+                slice
+                    .buffer
+                    .did_modify_range(NSRange::new(slice.offset, slice.size));
+            }
+        }
+        Ok(slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typedbuffer::test_util::HostBuffer;
+
+    fn ring_buffer(
+        capacity: NSUInteger,
+        alignment: NSUInteger,
+        policy: RingBufferFullPolicy,
+        fence: &FrameFence,
+    ) -> FrameRingBuffer<HostBuffer> {
+        FrameRingBuffer::with_buffer(
+            HostBuffer::new(capacity, MTLStorageMode::Shared),
+            alignment,
+            policy,
+            fence.clone(),
+            |capacity| Some(HostBuffer::new(capacity, MTLStorageMode::Shared)),
+        )
+    }
+
+    #[test]
+    fn allocator_wraps_and_reclaims() {
+        let mut ring = RingAllocator::new(1024);
+        assert_eq!(ring.allocate(300, 256), Some(0));
+        assert_eq!(ring.allocate(300, 256), Some(512));
+        assert_eq!(ring.finish_frame(), 1);
+        // 812..1024 is too small and the start of the ring is in use.
+        assert_eq!(ring.allocate(300, 256), None);
+        ring.reclaim(0);
+        assert_eq!(ring.oldest_in_flight(), Some(1));
+        assert_eq!(ring.allocate(100, 4), Some(812));
+        assert_eq!(ring.finish_frame(), 2);
+        ring.reclaim(1);
+        assert_eq!((ring.used(), ring.frames_in_flight()), (100, 1));
+        // Wraps, skipping 912..1024.
+        assert_eq!(ring.allocate(700, 256), Some(0));
+        assert_eq!(ring.used(), 100 + 112 + 700);
+        assert_eq!(ring.allocate(200, 1), None);
+        assert_eq!(ring.allocate(112, 1), Some(700));
+        // Completely full.
+        assert_eq!(ring.allocate(1, 1), None);
+        ring.finish_frame();
+        ring.reclaim(3);
+        assert_eq!((ring.used(), ring.oldest_in_flight()), (0, None));
+        assert_eq!(ring.allocate(1024, 1024), Some(0));
+    }
+
+    #[test]
+    fn empty_frames_dont_move_the_tail() {
+        let mut ring = RingAllocator::new(1024);
+        assert_eq!(ring.allocate(100, 1), Some(0));
+        assert_eq!(ring.finish_frame(), 1);
+        assert_eq!(ring.finish_frame(), 2);
+        ring.reclaim(1);
+        // The ring is empty and starts again at 0 with frame 2 in flight.
+        assert_eq!(ring.allocate(500, 1), Some(0));
+        assert_eq!(ring.allocate(524, 1), Some(500));
+        ring.reclaim(2);
+        assert_eq!((ring.used(), ring.frames_in_flight()), (1024, 0));
+        // Frame 3 still owns the whole ring.
+        assert_eq!(ring.allocate(100, 1), None);
+        assert_eq!(ring.finish_frame(), 3);
+        ring.reclaim(3);
+        assert_eq!(ring.allocate(100, 1), Some(0));
+    }
+
+    #[test]
+    fn allocator_rejects_overflow() {
+        let mut ring = RingAllocator::starting_at_frame(1024, 7);
+        assert_eq!(ring.current_frame(), 7);
+        assert_eq!(ring.allocate(NSUInteger::MAX, 1), None);
+        assert_eq!(ring.allocate(2048, 1), None);
+        assert_eq!(ring.used(), 0);
+    }
+
+    #[test]
+    fn fence() {
+        let fence = FrameFence::new();
+        assert_eq!(fence.completed_frame(), 0);
+        fence.signal(3);
+        fence.signal(2);
+        assert_eq!(fence.completed_frame(), 3);
+        assert!(fence.wait_for_frame(3, Duration::ZERO));
+        assert!(!fence.wait_for_frame(4, Duration::from_millis(1)));
+
+        let signaller = fence.clone();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(5));
+            signaller.signal(5);
+        });
+        assert!(fence.wait_for_frame(5, Duration::from_secs(10)));
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn fail_policy() {
+        let fence = FrameFence::new();
+        let mut ring = ring_buffer(1024, 256, RingBufferFullPolicy::Fail, &fence);
+        ring.allocate(300, 1).unwrap();
+        let slice = ring.allocate(500, 1).unwrap();
+        assert_eq!(slice.offset, 512);
+        let frame = ring.finish_frame();
+        assert_eq!(
+            ring.allocate(600, 1).err(),
+            Some(RingBufferError::OutOfSpace {
+                size: 600,
+                capacity: 1024
+            })
+        );
+        fence.signal(frame);
+        assert_eq!(ring.allocate(600, 1).unwrap().offset, 0);
+    }
+
+    #[test]
+    fn block_policy() {
+        let fence = FrameFence::new();
+        let policy = RingBufferFullPolicy::Block {
+            timeout: Duration::from_millis(5),
+        };
+        let mut ring = ring_buffer(1024, 4, policy, &fence);
+        ring.allocate(1000, 1).unwrap();
+        // Nothing in flight to wait for.
+        assert!(matches!(
+            ring.allocate(100, 1),
+            Err(RingBufferError::OutOfSpace { .. })
+        ));
+        let frame = ring.finish_frame();
+        assert_eq!(
+            ring.allocate(100, 1).err(),
+            Some(RingBufferError::Timeout { frame })
+        );
+        assert_eq!(
+            RingBufferError::Timeout { frame }.to_string(),
+            "timed out waiting for frame 1 to complete"
+        );
+
+        let signaller = fence.clone();
+        let thread = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(5));
+            signaller.signal(frame);
+        });
+        let policy = RingBufferFullPolicy::Block {
+            timeout: Duration::from_secs(10),
+        };
+        let mut ring = ring_buffer(1024, 4, policy, &fence);
+        ring.allocate(1000, 1).unwrap();
+        ring.finish_frame();
+        assert_eq!(ring.allocate(100, 1).unwrap().offset, 0);
+        thread.join().unwrap();
+    }
+
+    #[test]
+    fn grow_policy() {
+        let fence = FrameFence::new();
+        let policy = RingBufferFullPolicy::Grow { max_capacity: 3000 };
+        let mut ring = ring_buffer(1024, 256, policy, &fence);
+        let first = ring.allocate(800, 1).unwrap();
+        let second = ring.allocate(800, 1).unwrap();
+        assert_eq!((ring.capacity(), second.offset), (2048, 0));
+        assert!(!first.buffer.shares_memory_with(&second.buffer));
+        assert_eq!(ring.retired_buffers(), 1);
+
+        // Doubling would overshoot the maximum, so the ring grows to it.
+        ring.allocate(1200, 1).unwrap();
+        ring.allocate(1200, 1).unwrap();
+        assert_eq!(ring.capacity(), 3000);
+        assert_eq!(ring.retired_buffers(), 2);
+
+        // At the maximum there's nowhere left to grow.
+        assert_eq!(
+            ring.allocate(1500, 1).err(),
+            Some(RingBufferError::OutOfSpace {
+                size: 1500,
+                capacity: 3000
+            })
+        );
+        assert!(ring.allocate(5000, 1).is_err());
+        assert_eq!(ring.capacity(), 3000);
+
+        let frame = ring.finish_frame();
+        fence.signal(frame);
+        ring.reclaim();
+        assert_eq!(ring.retired_buffers(), 0);
+        assert_eq!(ring.ring().current_frame(), 2);
+        assert_eq!(ring.allocate(1500, 1).unwrap().offset, 0);
+    }
+
+    #[test]
+    fn grow_to_fit_a_large_allocation() {
+        let fence = FrameFence::new();
+        let policy = RingBufferFullPolicy::Grow {
+            max_capacity: 1 << 20,
+        };
+        let mut ring = ring_buffer(1024, 256, policy, &fence);
+        ring.allocate(10000, 1).unwrap();
+        assert_eq!(ring.capacity(), 10000 + 255);
+    }
+
+    #[test]
+    fn upload() {
+        let fence = FrameFence::new();
+        let mut ring = FrameRingBuffer::with_buffer(
+            HostBuffer::new(1024, MTLStorageMode::Managed),
+            256,
+            RingBufferFullPolicy::Fail,
+            fence,
+            |_| None,
+        );
+        let slice = ring.upload(&[1u32, 2, 3]).unwrap();
+        assert_eq!((slice.offset, slice.size), (0, 12));
+        let slice = ring.upload(&[4u64]).unwrap();
+        assert_eq!((slice.offset, slice.size), (256, 8));
+        let words = ring.buffer().read::<u64>();
+        assert_eq!(words[0], 1 | (2 << 32));
+        assert_eq!(words[1] as u32, 3);
+        assert_eq!(words[32], 4);
+        assert_eq!(ring.buffer().modified(), [(0, 12), (256, 8)]);
+    }
+}
@@ -0,0 +1,1026 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Redundant state elimination for render and compute encoders.
+//!
+//! Binds are held back until the next draw or dispatch. At that point slots
+//! that already hold the requested object are skipped, rebinding the same
+//! buffer at a new offset becomes a `set_*_buffer_offset` call, and runs of
+//! contiguous slots go out as one `set_*_buffers` / `set_*_textures` call.
+
+use super::*;
+
+use std::ptr;
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct EncoderStateStats {
+    /// Bind calls made on the wrapper.
+    pub binds: u64,
+    /// Binds dropped because the slot already held that object.
+    pub redundant_binds: u64,
+    /// Binds dropped because a later bind to the same slot replaced them
+    /// before the next draw or dispatch.
+    pub coalesced_binds: u64,
+    /// Rebinds of the same buffer issued as `set_*_buffer_offset`.
+    pub offset_updates: u64,
+    /// `set_*_buffers`, `set_*_textures` and `set_*_sampler_states` calls
+    /// issued, including single-slot ones.
+    pub range_calls: u64,
+    /// Slots covered by those calls.
+    pub range_slots: u64,
+}
+
+impl std::ops::AddAssign for EncoderStateStats {
+    fn add_assign(&mut self, other: Self) {
+        self.binds += other.binds;
+        self.redundant_binds += other.redundant_binds;
+        self.coalesced_binds += other.coalesced_binds;
+        self.offset_updates += other.offset_updates;
+        self.range_calls += other.range_calls;
+        self.range_slots += other.range_slots;
+    }
+}
+
+/// Where a [`BindingStateTracker`] sends the binds that survive, for one
+/// stage of an encoder.
+pub trait BindingSink {
+    type Buffer: ?Sized;
+    type Texture: ?Sized;
+    type Sampler: ?Sized;
+
+    fn set_buffers(
+        &mut self,
+        start_index: NSUInteger,
+        buffers: &[Option<&Self::Buffer>],
+        offsets: &[NSUInteger],
+    );
+    fn set_buffer_offset(&mut self, index: NSUInteger, offset: NSUInteger);
+    fn set_textures(&mut self, start_index: NSUInteger, textures: &[Option<&Self::Texture>]);
+    fn set_sampler_states(&mut self, start_index: NSUInteger, samplers: &[Option<&Self::Sampler>]);
+}
+
+fn same<T: ?Sized>(a: Option<&T>, b: Option<&T>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => ptr::eq(a, b),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+/// What the encoder holds in each slot (`None` if unknown) and what has been
+/// requested since the last flush.
+struct Slots<V> {
+    bound: Vec<Option<V>>,
+    pending: Vec<Option<V>>,
+}
+
+impl<V: Copy> Slots<V> {
+    fn new() -> Self {
+        Slots {
+            bound: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    fn set(
+        &mut self,
+        index: usize,
+        value: V,
+        eq: impl Fn(&V, &V) -> bool,
+        stats: &mut EncoderStateStats,
+    ) {
+        if self.bound.len() <= index {
+            self.bound.resize(index + 1, None);
+            self.pending.resize(index + 1, None);
+        }
+        stats.binds += 1;
+        if self.pending[index].take().is_some() {
+            stats.coalesced_binds += 1;
+        }
+        match self.bound[index] {
+            Some(ref bound) if eq(bound, &value) => stats.redundant_binds += 1,
+            _ => self.pending[index] = Some(value),
+        }
+    }
+
+    fn forget(&mut self, index: usize) {
+        if let Some(bound) = self.bound.get_mut(index) {
+            *bound = None;
+            self.pending[index] = None;
+        }
+    }
+
+    fn forget_all(&mut self) {
+        self.bound.iter_mut().for_each(|bound| *bound = None);
+    }
+
+    /// Pending binds in slot order, with what each slot held before.
+    fn take_pending(&mut self) -> Vec<(usize, Option<V>, V)> {
+        let mut taken = Vec::new();
+        for (index, pending) in self.pending.iter_mut().enumerate() {
+            if let Some(value) = pending.take() {
+                taken.push((index, self.bound[index].replace(value), value));
+            }
+        }
+        taken
+    }
+}
+
+/// Split slot-ordered binds into runs of contiguous slots.
+fn runs<V>(binds: &[(usize, V)]) -> impl Iterator<Item = &[(usize, V)]> {
+    let mut rest = binds;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let len = 1 + rest
+            .windows(2)
+            .take_while(|pair| pair[1].0 == pair[0].0 + 1)
+            .count();
+        let (run, tail) = rest.split_at(len);
+        rest = tail;
+        Some(run)
+    })
+}
+
+type BufferBinding<'a, B> = (Option<&'a B>, NSUInteger);
+
+/// Buffer, texture and sampler bindings of one encoder stage.
+///
+/// Generic over the bound object types so it can drive any
+/// [`BindingSink`], not only a Metal encoder.
+pub struct BindingStateTracker<'a, B: ?Sized, T: ?Sized, S: ?Sized> {
+    buffers: Slots<BufferBinding<'a, B>>,
+    textures: Slots<Option<&'a T>>,
+    samplers: Slots<Option<&'a S>>,
+    stats: EncoderStateStats,
+}
+
+impl<'a, B: ?Sized, T: ?Sized, S: ?Sized> Default for BindingStateTracker<'a, B, T, S> {
+    fn default() -> Self {
+        BindingStateTracker {
+            buffers: Slots::new(),
+            textures: Slots::new(),
+            samplers: Slots::new(),
+            stats: EncoderStateStats::default(),
+        }
+    }
+}
+
+impl<'a, B: ?Sized, T: ?Sized, S: ?Sized> BindingStateTracker<'a, B, T, S> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn stats(&self) -> EncoderStateStats {
+        self.stats
+    }
+
+    pub fn set_buffer(&mut self, index: NSUInteger, buffer: Option<&'a B>, offset: NSUInteger) {
+        self.buffers.set(
+            index as usize,
+            (buffer, offset),
+            |a, b| same(a.0, b.0) && a.1 == b.1,
+            &mut self.stats,
+        );
+    }
+
+    pub fn set_texture(&mut self, index: NSUInteger, texture: Option<&'a T>) {
+        self.textures.set(
+            index as usize,
+            texture,
+            |a, b| same(*a, *b),
+            &mut self.stats,
+        );
+    }
+
+    pub fn set_sampler_state(&mut self, index: NSUInteger, sampler: Option<&'a S>) {
+        self.samplers.set(
+            index as usize,
+            sampler,
+            |a, b| same(*a, *b),
+            &mut self.stats,
+        );
+    }
+
+    /// Record that a buffer slot was changed behind the tracker's back, e.g.
+    /// by `set_*_bytes`.
+    pub fn forget_buffer(&mut self, index: NSUInteger) {
+        self.buffers.forget(index as usize);
+    }
+
+    /// Forget everything the encoder is known to hold. Pending binds are
+    /// kept.
+    pub fn invalidate(&mut self) {
+        self.buffers.forget_all();
+        self.textures.forget_all();
+        self.samplers.forget_all();
+    }
+
+    /// Send pending binds to `sink`.
+    pub fn flush<K>(&mut self, sink: &mut K)
+    where
+        K: BindingSink<Buffer = B, Texture = T, Sampler = S>,
+    {
+        let mut full = Vec::new();
+        for (index, old, (buffer, offset)) in self.buffers.take_pending() {
+            match (old, buffer) {
+                (Some((Some(old), _)), Some(new)) if ptr::eq(old, new) => {
+                    sink.set_buffer_offset(index as NSUInteger, offset);
+                    self.stats.offset_updates += 1;
+                }
+                _ => full.push((index, (buffer, offset))),
+            }
+        }
+        for run in runs(&full) {
+            let buffers: Vec<_> = run.iter().map(|&(_, (buffer, _))| buffer).collect();
+            let offsets: Vec<_> = run.iter().map(|&(_, (_, offset))| offset).collect();
+            sink.set_buffers(run[0].0 as NSUInteger, &buffers, &offsets);
+            self.count_range(run.len());
+        }
+
+        let textures: Vec<_> = self
+            .textures
+            .take_pending()
+            .into_iter()
+            .map(|(index, _, texture)| (index, texture))
+            .collect();
+        for run in runs(&textures) {
+            let textures: Vec<_> = run.iter().map(|&(_, texture)| texture).collect();
+            sink.set_textures(run[0].0 as NSUInteger, &textures);
+            self.count_range(run.len());
+        }
+
+        let samplers: Vec<_> = self
+            .samplers
+            .take_pending()
+            .into_iter()
+            .map(|(index, _, sampler)| (index, sampler))
+            .collect();
+        for run in runs(&samplers) {
+            let samplers: Vec<_> = run.iter().map(|&(_, sampler)| sampler).collect();
+            sink.set_sampler_states(run[0].0 as NSUInteger, &samplers);
+            self.count_range(run.len());
+        }
+    }
+
+    fn count_range(&mut self, slots: usize) {
+        self.stats.range_calls += 1;
+        self.stats.range_slots += slots as u64;
+    }
+}
+
+type EncoderBindings<'a> = BindingStateTracker<'a, BufferRef, TextureRef, SamplerStateRef>;
+
+struct RenderStageSink<'e> {
+    encoder: &'e RenderCommandEncoderRef,
+    stage: RenderStage,
+}
+
+impl BindingSink for RenderStageSink<'_> {
+    type Buffer = BufferRef;
+    type Texture = TextureRef;
+    type Sampler = SamplerStateRef;
+
+    fn set_buffers(
+        &mut self,
+        start_index: NSUInteger,
+        buffers: &[Option<&BufferRef>],
+        offsets: &[NSUInteger],
+    ) {
+        let encoder = self.encoder;
+        match (self.stage, buffers) {
+            (RenderStage::Vertex, &[buffer]) => {
+                encoder.set_vertex_buffer(start_index, buffer, offsets[0])
+            }
+            (RenderStage::Fragment, &[buffer]) => {
+                encoder.set_fragment_buffer(start_index, buffer, offsets[0])
+            }
+            (RenderStage::Object, &[buffer]) => {
+                encoder.set_object_buffer(start_index, buffer, offsets[0])
+            }
+            (RenderStage::Mesh, &[buffer]) => {
+                encoder.set_mesh_buffer(start_index, buffer, offsets[0])
+            }
+            (RenderStage::Vertex, _) => encoder.set_vertex_buffers(start_index, buffers, offsets),
+            (RenderStage::Fragment, _) => {
+                encoder.set_fragment_buffers(start_index, buffers, offsets)
+            }
+            (RenderStage::Object, _) => encoder.set_object_buffers(start_index, buffers, offsets),
+            (RenderStage::Mesh, _) => encoder.set_mesh_buffers(start_index, buffers, offsets),
+        }
+    }
+
+    fn set_buffer_offset(&mut self, index: NSUInteger, offset: NSUInteger) {
+        match self.stage {
+            RenderStage::Vertex => self.encoder.set_vertex_buffer_offset(index, offset),
+            RenderStage::Fragment => self.encoder.set_fragment_buffer_offset(index, offset),
+            RenderStage::Object => self.encoder.set_object_buffer_offset(index, offset),
+            RenderStage::Mesh => self.encoder.set_mesh_buffer_offset(index, offset),
+        }
+    }
+
+    fn set_textures(&mut self, start_index: NSUInteger, textures: &[Option<&TextureRef>]) {
+        let encoder = self.encoder;
+        match (self.stage, textures) {
+            (RenderStage::Vertex, &[texture]) => encoder.set_vertex_texture(start_index, texture),
+            (RenderStage::Fragment, &[texture]) => {
+                encoder.set_fragment_texture(start_index, texture)
+            }
+            (RenderStage::Object, &[texture]) => encoder.set_object_texture(start_index, texture),
+            (RenderStage::Mesh, &[texture]) => encoder.set_mesh_texture(start_index, texture),
+            (RenderStage::Vertex, _) => encoder.set_vertex_textures(start_index, textures),
+            (RenderStage::Fragment, _) => encoder.set_fragment_textures(start_index, textures),
+            (RenderStage::Object, _) => encoder.set_object_textures(start_index, textures),
+            (RenderStage::Mesh, _) => encoder.set_mesh_textures(start_index, textures),
+        }
+    }
+
+    fn set_sampler_states(
+        &mut self,
+        start_index: NSUInteger,
+        samplers: &[Option<&SamplerStateRef>],
+    ) {
+        let encoder = self.encoder;
+        match (self.stage, samplers) {
+            (RenderStage::Vertex, &[sampler]) => {
+                encoder.set_vertex_sampler_state(start_index, sampler)
+            }
+            (RenderStage::Fragment, &[sampler]) => {
+                encoder.set_fragment_sampler_state(start_index, sampler)
+            }
+            (RenderStage::Object, &[sampler]) => {
+                encoder.set_object_sampler_state(start_index, sampler)
+            }
+            (RenderStage::Mesh, &[sampler]) => encoder.set_mesh_sampler_state(start_index, sampler),
+            (RenderStage::Vertex, _) => encoder.set_vertex_sampler_states(start_index, samplers),
+            (RenderStage::Fragment, _) => {
+                encoder.set_fragment_sampler_states(start_index, samplers)
+            }
+            (RenderStage::Object, _) => encoder.set_object_sampler_states(start_index, samplers),
+            (RenderStage::Mesh, _) => encoder.set_mesh_sampler_states(start_index, samplers),
+        }
+    }
+}
+
+struct ComputeSink<'e> {
+    encoder: &'e ComputeCommandEncoderRef,
+}
+
+impl BindingSink for ComputeSink<'_> {
+    type Buffer = BufferRef;
+    type Texture = TextureRef;
+    type Sampler = SamplerStateRef;
+
+    fn set_buffers(
+        &mut self,
+        start_index: NSUInteger,
+        buffers: &[Option<&BufferRef>],
+        offsets: &[NSUInteger],
+    ) {
+        match buffers {
+            &[buffer] => self.encoder.set_buffer(start_index, buffer, offsets[0]),
+            _ => self.encoder.set_buffers(start_index, buffers, offsets),
+        }
+    }
+
+    fn set_buffer_offset(&mut self, index: NSUInteger, offset: NSUInteger) {
+        self.encoder.set_buffer_offset(index, offset);
+    }
+
+    fn set_textures(&mut self, start_index: NSUInteger, textures: &[Option<&TextureRef>]) {
+        match textures {
+            &[texture] => self.encoder.set_texture(start_index, texture),
+            _ => self.encoder.set_textures(start_index, textures),
+        }
+    }
+
+    fn set_sampler_states(
+        &mut self,
+        start_index: NSUInteger,
+        samplers: &[Option<&SamplerStateRef>],
+    ) {
+        match samplers {
+            &[sampler] => self.encoder.set_sampler_state(start_index, sampler),
+            _ => self.encoder.set_sampler_states(start_index, samplers),
+        }
+    }
+}
+
+const RENDER_STAGES: [RenderStage; 4] = [
+    RenderStage::Vertex,
+    RenderStage::Fragment,
+    RenderStage::Object,
+    RenderStage::Mesh,
+];
+
+/// A [`RenderCommandEncoderRef`] that drops redundant state changes.
+///
+/// Bindings made directly on [`encoder`](Self::encoder) are invisible to the
+/// cache; call [`invalidate`](Self::invalidate) afterwards.
+pub struct CachedRenderCommandEncoder<'a> {
+    encoder: &'a RenderCommandEncoderRef,
+    stages: [EncoderBindings<'a>; 4],
+    pipeline: Option<&'a RenderPipelineStateRef>,
+    depth_stencil: Option<&'a DepthStencilStateRef>,
+    stats: EncoderStateStats,
+}
+
+impl<'a> CachedRenderCommandEncoder<'a> {
+    pub fn new(encoder: &'a RenderCommandEncoderRef) -> Self {
+        CachedRenderCommandEncoder {
+            encoder,
+            stages: Default::default(),
+            pipeline: None,
+            depth_stencil: None,
+            stats: EncoderStateStats::default(),
+        }
+    }
+
+    pub fn encoder(&self) -> &'a RenderCommandEncoderRef {
+        self.encoder
+    }
+
+    pub fn stats(&self) -> EncoderStateStats {
+        let mut stats = self.stats;
+        for stage in &self.stages {
+            stats += stage.stats();
+        }
+        stats
+    }
+
+    pub fn invalidate(&mut self) {
+        for stage in &mut self.stages {
+            stage.invalidate();
+        }
+        self.pipeline = None;
+        self.depth_stencil = None;
+    }
+
+    pub fn set_render_pipeline_state(&mut self, pipeline_state: &'a RenderPipelineStateRef) {
+        self.stats.binds += 1;
+        if same(self.pipeline, Some(pipeline_state)) {
+            self.stats.redundant_binds += 1;
+            return;
+        }
+        self.pipeline = Some(pipeline_state);
+        self.encoder.set_render_pipeline_state(pipeline_state);
+    }
+
+    pub fn set_depth_stencil_state(&mut self, depth_stencil_state: &'a DepthStencilStateRef) {
+        self.stats.binds += 1;
+        if same(self.depth_stencil, Some(depth_stencil_state)) {
+            self.stats.redundant_binds += 1;
+            return;
+        }
+        self.depth_stencil = Some(depth_stencil_state);
+        self.encoder.set_depth_stencil_state(depth_stencil_state);
+    }
+
+    pub fn set_buffer(
+        &mut self,
+        stage: RenderStage,
+        index: NSUInteger,
+        buffer: Option<&'a BufferRef>,
+        offset: NSUInteger,
+    ) {
+        self.stages[stage as usize].set_buffer(index, buffer, offset);
+    }
+
+    pub fn set_texture(
+        &mut self,
+        stage: RenderStage,
+        index: NSUInteger,
+        texture: Option<&'a TextureRef>,
+    ) {
+        self.stages[stage as usize].set_texture(index, texture);
+    }
+
+    pub fn set_sampler_state(
+        &mut self,
+        stage: RenderStage,
+        index: NSUInteger,
+        sampler: Option<&'a SamplerStateRef>,
+    ) {
+        self.stages[stage as usize].set_sampler_state(index, sampler);
+    }
+
+    /// Issued in order with the pending binds, since it replaces whatever
+    /// buffer was bound at `index`.
+    pub fn set_bytes(&mut self, stage: RenderStage, index: NSUInteger, bytes: &[u8]) {
+        self.flush();
+        self.stages[stage as usize].forget_buffer(index);
+        let length = bytes.len() as NSUInteger;
+        let bytes = bytes.as_ptr() as *const std::ffi::c_void;
+        match stage {
+            RenderStage::Vertex => self.encoder.set_vertex_bytes(index, length, bytes),
+            RenderStage::Fragment => self.encoder.set_fragment_bytes(index, length, bytes),
+            RenderStage::Object => self.encoder.set_object_bytes(index, length, bytes),
+            RenderStage::Mesh => self.encoder.set_mesh_bytes(index, length, bytes),
+        }
+    }
+
+    pub fn set_vertex_buffer(
+        &mut self,
+        index: NSUInteger,
+        buffer: Option<&'a BufferRef>,
+        offset: NSUInteger,
+    ) {
+        self.set_buffer(RenderStage::Vertex, index, buffer, offset);
+    }
+
+    pub fn set_fragment_buffer(
+        &mut self,
+        index: NSUInteger,
+        buffer: Option<&'a BufferRef>,
+        offset: NSUInteger,
+    ) {
+        self.set_buffer(RenderStage::Fragment, index, buffer, offset);
+    }
+
+    pub fn set_vertex_texture(&mut self, index: NSUInteger, texture: Option<&'a TextureRef>) {
+        self.set_texture(RenderStage::Vertex, index, texture);
+    }
+
+    pub fn set_fragment_texture(&mut self, index: NSUInteger, texture: Option<&'a TextureRef>) {
+        self.set_texture(RenderStage::Fragment, index, texture);
+    }
+
+    pub fn set_vertex_sampler_state(
+        &mut self,
+        index: NSUInteger,
+        sampler: Option<&'a SamplerStateRef>,
+    ) {
+        self.set_sampler_state(RenderStage::Vertex, index, sampler);
+    }
+
+    pub fn set_fragment_sampler_state(
+        &mut self,
+        index: NSUInteger,
+        sampler: Option<&'a SamplerStateRef>,
+    ) {
+        self.set_sampler_state(RenderStage::Fragment, index, sampler);
+    }
+
+    /// Send pending binds to the encoder. Draws do this implicitly.
+    pub fn flush(&mut self) {
+        for (stage, bindings) in RENDER_STAGES.iter().zip(self.stages.iter_mut()) {
+            bindings.flush(&mut RenderStageSink {
+                encoder: self.encoder,
+                stage: *stage,
+            });
+        }
+    }
+
+    pub fn draw_primitives(
+        &mut self,
+        primitive_type: MTLPrimitiveType,
+        vertex_start: NSUInteger,
+        vertex_count: NSUInteger,
+    ) {
+        self.flush();
+        self.encoder
+            .draw_primitives(primitive_type, vertex_start, vertex_count);
+    }
+
+    pub fn draw_primitives_instanced(
+        &mut self,
+        primitive_type: MTLPrimitiveType,
+        vertex_start: NSUInteger,
+        vertex_count: NSUInteger,
+        instance_count: NSUInteger,
+    ) {
+        self.flush();
+        self.encoder.draw_primitives_instanced(
+            primitive_type,
+            vertex_start,
+            vertex_count,
+            instance_count,
+        );
+    }
+
+    pub fn draw_primitives_indirect(
+        &mut self,
+        primitive_type: MTLPrimitiveType,
+        indirect_buffer: &BufferRef,
+        indirect_buffer_offset: NSUInteger,
+    ) {
+        self.flush();
+        self.encoder.draw_primitives_indirect(
+            primitive_type,
+            indirect_buffer,
+            indirect_buffer_offset,
+        );
+    }
+
+    pub fn draw_indexed_primitives(
+        &mut self,
+        primitive_type: MTLPrimitiveType,
+        index_count: NSUInteger,
+        index_type: MTLIndexType,
+        index_buffer: &BufferRef,
+        index_buffer_offset: NSUInteger,
+    ) {
+        self.flush();
+        self.encoder.draw_indexed_primitives(
+            primitive_type,
+            index_count,
+            index_type,
+            index_buffer,
+            index_buffer_offset,
+        );
+    }
+
+    pub fn draw_indexed_primitives_instanced(
+        &mut self,
+        primitive_type: MTLPrimitiveType,
+        index_count: NSUInteger,
+        index_type: MTLIndexType,
+        index_buffer: &BufferRef,
+        index_buffer_offset: NSUInteger,
+        instance_count: NSUInteger,
+    ) {
+        self.flush();
+        self.encoder.draw_indexed_primitives_instanced(
+            primitive_type,
+            index_count,
+            index_type,
+            index_buffer,
+            index_buffer_offset,
+            instance_count,
+        );
+    }
+
+    pub fn draw_indexed_primitives_indirect(
+        &mut self,
+        primitive_type: MTLPrimitiveType,
+        index_type: MTLIndexType,
+        index_buffer: &BufferRef,
+        index_buffer_offset: NSUInteger,
+        indirect_buffer: &BufferRef,
+        indirect_buffer_offset: NSUInteger,
+    ) {
+        self.flush();
+        self.encoder.draw_indexed_primitives_indirect(
+            primitive_type,
+            index_type,
+            index_buffer,
+            index_buffer_offset,
+            indirect_buffer,
+            indirect_buffer_offset,
+        );
+    }
+
+    pub fn draw_mesh_threadgroups(
+        &mut self,
+        threadgroups_per_grid: MTLSize,
+        threads_per_object_threadgroup: MTLSize,
+        threads_per_mesh_threadgroup: MTLSize,
+    ) {
+        self.flush();
+        self.encoder.draw_mesh_threadgroups(
+            threadgroups_per_grid,
+            threads_per_object_threadgroup,
+            threads_per_mesh_threadgroup,
+        );
+    }
+
+    pub fn draw_mesh_threads(
+        &mut self,
+        threads_per_grid: MTLSize,
+        threads_per_object_threadgroup: MTLSize,
+        threads_per_mesh_threadgroup: MTLSize,
+    ) {
+        self.flush();
+        self.encoder.draw_mesh_threads(
+            threads_per_grid,
+            threads_per_object_threadgroup,
+            threads_per_mesh_threadgroup,
+        );
+    }
+}
+
+/// A [`ComputeCommandEncoderRef`] that drops redundant state changes.
+///
+/// Bindings made directly on [`encoder`](Self::encoder) are invisible to the
+/// cache; call [`invalidate`](Self::invalidate) afterwards.
+pub struct CachedComputeCommandEncoder<'a> {
+    encoder: &'a ComputeCommandEncoderRef,
+    bindings: EncoderBindings<'a>,
+    pipeline: Option<&'a ComputePipelineStateRef>,
+    stats: EncoderStateStats,
+}
+
+impl<'a> CachedComputeCommandEncoder<'a> {
+    pub fn new(encoder: &'a ComputeCommandEncoderRef) -> Self {
+        CachedComputeCommandEncoder {
+            encoder,
+            bindings: BindingStateTracker::new(),
+            pipeline: None,
+            stats: EncoderStateStats::default(),
+        }
+    }
+
+    pub fn encoder(&self) -> &'a ComputeCommandEncoderRef {
+        self.encoder
+    }
+
+    pub fn stats(&self) -> EncoderStateStats {
+        let mut stats = self.stats;
+        stats += self.bindings.stats();
+        stats
+    }
+
+    pub fn invalidate(&mut self) {
+        self.bindings.invalidate();
+        self.pipeline = None;
+    }
+
+    pub fn set_compute_pipeline_state(&mut self, state: &'a ComputePipelineStateRef) {
+        self.stats.binds += 1;
+        if same(self.pipeline, Some(state)) {
+            self.stats.redundant_binds += 1;
+            return;
+        }
+        self.pipeline = Some(state);
+        self.encoder.set_compute_pipeline_state(state);
+    }
+
+    pub fn set_buffer(
+        &mut self,
+        index: NSUInteger,
+        buffer: Option<&'a BufferRef>,
+        offset: NSUInteger,
+    ) {
+        self.bindings.set_buffer(index, buffer, offset);
+    }
+
+    pub fn set_texture(&mut self, index: NSUInteger, texture: Option<&'a TextureRef>) {
+        self.bindings.set_texture(index, texture);
+    }
+
+    pub fn set_sampler_state(&mut self, index: NSUInteger, sampler: Option<&'a SamplerStateRef>) {
+        self.bindings.set_sampler_state(index, sampler);
+    }
+
+    /// Issued in order with the pending binds, since it replaces whatever
+    /// buffer was bound at `index`.
+    pub fn set_bytes(&mut self, index: NSUInteger, bytes: &[u8]) {
+        self.flush();
+        self.bindings.forget_buffer(index);
+        self.encoder.set_bytes(
+            index,
+            bytes.len() as NSUInteger,
+            bytes.as_ptr() as *const std::ffi::c_void,
+        );
+    }
+
+    /// Send pending binds to the encoder. Dispatches do this implicitly.
+    pub fn flush(&mut self) {
+        self.bindings.flush(&mut ComputeSink {
+            encoder: self.encoder,
+        });
+    }
+
+    pub fn dispatch_thread_groups(
+        &mut self,
+        thread_groups_count: MTLSize,
+        threads_per_threadgroup: MTLSize,
+    ) {
+        self.flush();
+        self.encoder
+            .dispatch_thread_groups(thread_groups_count, threads_per_threadgroup);
+    }
+
+    pub fn dispatch_threads(
+        &mut self,
+        threads_per_grid: MTLSize,
+        threads_per_thread_group: MTLSize,
+    ) {
+        self.flush();
+        self.encoder
+            .dispatch_threads(threads_per_grid, threads_per_thread_group);
+    }
+
+    pub fn dispatch_thread_groups_indirect(
+        &mut self,
+        buffer: &BufferRef,
+        offset: NSUInteger,
+        threads_per_threadgroup: MTLSize,
+    ) {
+        self.flush();
+        self.encoder
+            .dispatch_thread_groups_indirect(buffer, offset, threads_per_threadgroup);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bound object, told apart from others by address like a Metal one.
+    #[derive(Debug)]
+    struct Object(&'static str);
+
+    #[derive(Debug, PartialEq)]
+    enum Call {
+        Buffers(NSUInteger, Vec<(Option<&'static str>, NSUInteger)>),
+        BufferOffset(NSUInteger, NSUInteger),
+        Textures(NSUInteger, Vec<Option<&'static str>>),
+        Samplers(NSUInteger, Vec<Option<&'static str>>),
+    }
+
+    #[derive(Default)]
+    struct MockSink {
+        calls: Vec<Call>,
+    }
+
+    fn names(objects: &[Option<&Object>]) -> Vec<Option<&'static str>> {
+        objects.iter().map(|o| o.map(|o| o.0)).collect()
+    }
+
+    impl BindingSink for MockSink {
+        type Buffer = Object;
+        type Texture = Object;
+        type Sampler = Object;
+
+        fn set_buffers(
+            &mut self,
+            start_index: NSUInteger,
+            buffers: &[Option<&Object>],
+            offsets: &[NSUInteger],
+        ) {
+            let buffers = names(buffers).into_iter().zip(offsets.iter().copied());
+            self.calls
+                .push(Call::Buffers(start_index, buffers.collect()));
+        }
+
+        fn set_buffer_offset(&mut self, index: NSUInteger, offset: NSUInteger) {
+            self.calls.push(Call::BufferOffset(index, offset));
+        }
+
+        fn set_textures(&mut self, start_index: NSUInteger, textures: &[Option<&Object>]) {
+            self.calls
+                .push(Call::Textures(start_index, names(textures)));
+        }
+
+        fn set_sampler_states(&mut self, start_index: NSUInteger, samplers: &[Option<&Object>]) {
+            self.calls
+                .push(Call::Samplers(start_index, names(samplers)));
+        }
+    }
+
+    type Tracker<'a> = BindingStateTracker<'a, Object, Object, Object>;
+
+    fn flush(tracker: &mut Tracker) -> Vec<Call> {
+        let mut sink = MockSink::default();
+        tracker.flush(&mut sink);
+        sink.calls
+    }
+
+    #[test]
+    fn redundant_binds() {
+        let (a, b) = (Object("a"), Object("b"));
+        let mut tracker = Tracker::new();
+        tracker.set_buffer(0, Some(&a), 0);
+        tracker.set_texture(0, Some(&b));
+        assert_eq!(
+            flush(&mut tracker),
+            [
+                Call::Buffers(0, vec![(Some("a"), 0)]),
+                Call::Textures(0, vec![Some("b")]),
+            ]
+        );
+
+        tracker.set_buffer(0, Some(&a), 0);
+        tracker.set_texture(0, Some(&b));
+        assert_eq!(flush(&mut tracker), []);
+        // An equal object at another address is a different object.
+        let other_a = Object("a");
+        tracker.set_texture(0, Some(&other_a));
+        assert_eq!(flush(&mut tracker), [Call::Textures(0, vec![Some("a")])]);
+
+        let stats = tracker.stats();
+        assert_eq!(stats.binds, 5);
+        assert_eq!(stats.redundant_binds, 2);
+        assert_eq!(stats.range_calls, 3);
+    }
+
+    #[test]
+    fn coalesced_binds() {
+        let (a, b) = (Object("a"), Object("b"));
+        let mut tracker = Tracker::new();
+        tracker.set_sampler_state(1, Some(&a));
+        tracker.set_sampler_state(1, Some(&b));
+        assert_eq!(flush(&mut tracker), [Call::Samplers(1, vec![Some("b")])]);
+
+        // Switching away and back before a draw issues nothing.
+        tracker.set_sampler_state(1, Some(&a));
+        tracker.set_sampler_state(1, Some(&b));
+        assert_eq!(flush(&mut tracker), []);
+
+        let stats = tracker.stats();
+        assert_eq!(stats.binds, 4);
+        assert_eq!(stats.coalesced_binds, 2);
+        assert_eq!(stats.redundant_binds, 1);
+    }
+
+    #[test]
+    fn offset_updates() {
+        let (a, b) = (Object("a"), Object("b"));
+        let mut tracker = Tracker::new();
+        tracker.set_buffer(2, Some(&a), 0);
+        flush(&mut tracker);
+
+        tracker.set_buffer(2, Some(&a), 256);
+        assert_eq!(flush(&mut tracker), [Call::BufferOffset(2, 256)]);
+        tracker.set_buffer(2, Some(&b), 256);
+        assert_eq!(
+            flush(&mut tracker),
+            [Call::Buffers(2, vec![(Some("b"), 256)])]
+        );
+        tracker.set_buffer(2, None, 0);
+        assert_eq!(flush(&mut tracker), [Call::Buffers(2, vec![(None, 0)])]);
+        assert_eq!(tracker.stats().offset_updates, 1);
+    }
+
+    #[test]
+    fn contiguous_runs() {
+        let objects = ["a", "b", "c", "d", "e"].map(Object);
+        let mut tracker = Tracker::new();
+        for (slot, object) in [5, 0, 2, 1, 6].into_iter().zip(&objects) {
+            tracker.set_buffer(slot, Some(object), slot * 16);
+            tracker.set_texture(slot, Some(object));
+        }
+        assert_eq!(
+            flush(&mut tracker),
+            [
+                Call::Buffers(0, vec![(Some("b"), 0), (Some("d"), 16), (Some("c"), 32)]),
+                Call::Buffers(5, vec![(Some("a"), 80), (Some("e"), 96)]),
+                Call::Textures(0, vec![Some("b"), Some("d"), Some("c")]),
+                Call::Textures(5, vec![Some("a"), Some("e")]),
+            ]
+        );
+        let stats = tracker.stats();
+        assert_eq!(stats.range_calls, 4);
+        assert_eq!(stats.range_slots, 10);
+
+        // Only the changed slot goes out, even inside a bound run.
+        tracker.set_texture(1, Some(&objects[4]));
+        tracker.set_texture(0, Some(&objects[1]));
+        assert_eq!(flush(&mut tracker), [Call::Textures(1, vec![Some("e")])]);
+    }
+
+    #[test]
+    fn forget_and_invalidate() {
+        let (a, b) = (Object("a"), Object("b"));
+        let mut tracker = Tracker::new();
+        tracker.set_buffer(0, Some(&a), 64);
+        tracker.set_sampler_state(0, Some(&b));
+        flush(&mut tracker);
+
+        // After `set_bytes` the slot holds something else.
+        tracker.forget_buffer(0);
+        tracker.set_buffer(0, Some(&a), 64);
+        assert_eq!(
+            flush(&mut tracker),
+            [Call::Buffers(0, vec![(Some("a"), 64)])]
+        );
+        // Forgetting a slot that was never bound is harmless.
+        tracker.forget_buffer(9);
+
+        // Pending binds survive an invalidation.
+        tracker.set_buffer(1, Some(&b), 0);
+        tracker.invalidate();
+        tracker.set_sampler_state(0, Some(&b));
+        tracker.set_buffer(0, Some(&a), 64);
+        assert_eq!(
+            flush(&mut tracker),
+            [
+                Call::Buffers(0, vec![(Some("a"), 64), (Some("b"), 0)]),
+                Call::Samplers(0, vec![Some("b")]),
+            ]
+        );
+    }
+
+    #[test]
+    fn stats_add() {
+        let mut total = EncoderStateStats::default();
+        let stats = EncoderStateStats {
+            binds: 3,
+            redundant_binds: 1,
+            coalesced_binds: 1,
+            offset_updates: 1,
+            range_calls: 2,
+            range_slots: 4,
+        };
+        total += stats;
+        total += stats;
+        assert_eq!(total.binds, 6);
+        assert_eq!(total.range_slots, 8);
+    }
+}
@@ -162,6 +162,31 @@ impl TextureDescriptorRef {
         unsafe { msg_send![self, setStorageMode: mode] }
     }
 
+    /// Only available on macos(10.15), ios(13.0)
+    pub fn hazard_tracking_mode(&self) -> MTLHazardTrackingMode {
+        unsafe { msg_send![self, hazardTrackingMode] }
+    }
+
+    /// Only available on macos(10.15), ios(13.0)
+    pub fn set_hazard_tracking_mode(&self, mode: MTLHazardTrackingMode) {
+        unsafe { msg_send![self, setHazardTrackingMode: mode] }
+    }
+
+    /// The storage, CPU cache and hazard tracking modes together.
+    pub fn resource_modes(&self) -> ResourceModes {
+        ResourceModes::new(
+            self.storage_mode(),
+            self.cpu_cache_mode(),
+            self.hazard_tracking_mode(),
+        )
+    }
+
+    pub fn set_resource_modes(&self, modes: ResourceModes) {
+        self.set_storage_mode(modes.storage_mode);
+        self.set_cpu_cache_mode(modes.cpu_cache_mode);
+        self.set_hazard_tracking_mode(modes.hazard_tracking_mode);
+    }
+
     pub fn usage(&self) -> MTLTextureUsage {
         unsafe { msg_send![self, usage] }
     }
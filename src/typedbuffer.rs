@@ -0,0 +1,519 @@
+// Copyright 2026 GFX developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+use super::*;
+
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::{Bound, Deref, DerefMut, Range, RangeBounds};
+
+/// The memory behind a [`TypedBuffer`]. Implemented for [`Buffer`]; other
+/// implementations can stand in for a GPU buffer, e.g. in tests.
+///
+/// # Safety
+///
+/// `contents` must point to at least `length` bytes, aligned for any
+/// [`Pod`] type the buffer is viewed as, that stay valid for as long as the
+/// storage lives.
+pub unsafe trait BufferStorage {
+    fn length(&self) -> NSUInteger;
+    fn contents(&self) -> *mut std::ffi::c_void;
+    fn storage_mode(&self) -> MTLStorageMode;
+    fn did_modify_range(&self, range: NSRange);
+}
+
+unsafe impl BufferStorage for Buffer {
+    fn length(&self) -> NSUInteger {
+        BufferRef::length(self) as NSUInteger
+    }
+
+    fn contents(&self) -> *mut std::ffi::c_void {
+        BufferRef::contents(self)
+    }
+
+    fn storage_mode(&self) -> MTLStorageMode {
+        ResourceRef::storage_mode(self)
+    }
+
+    fn did_modify_range(&self, range: NSRange) {
+        BufferRef::did_modify_range(self, range)
+    }
+}
+
+/// A buffer holding `len` values of `T`.
+///
+/// CPU views are only available for `Shared` and `Managed` storage. Writes
+/// through a [`TypedBufferWriteGuard`] are flushed with `did_modify_range`
+/// when the guard is dropped, which `Managed` buffers need before the GPU
+/// sees them.
+pub struct TypedBuffer<T: Pod, B: BufferStorage = Buffer> {
+    buffer: B,
+    len: usize,
+    storage_mode: MTLStorageMode,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> TypedBuffer<T> {
+    /// A zero-initialized buffer of `len` values.
+    ///
+    /// # Panics
+    ///
+    /// If the buffer would be empty, which Metal does not allow, or its
+    /// size in bytes overflows.
+    pub fn new(device: &DeviceRef, len: usize, options: MTLResourceOptions) -> Self {
+        let length = len
+            .checked_mul(mem::size_of::<T>())
+            .expect("TypedBuffer size overflows");
+        assert!(length != 0, "cannot create an empty TypedBuffer");
+        // The buffer is new, so nothing else can view its contents.
+        unsafe { Self::from_buffer(device.new_buffer(length as NSUInteger, options)) }
+    }
+
+    /// # Panics
+    ///
+    /// If `values` is empty.
+    pub fn from_slice(device: &DeviceRef, values: &[T], options: MTLResourceOptions) -> Self {
+        unsafe { Self::from_buffer(device.new_buffer_with_slice(values, options)) }
+    }
+}
+
+impl<T: Pod, B: BufferStorage> TypedBuffer<T, B> {
+    /// View `buffer` as values of `T`. Trailing bytes that don't make up a
+    /// whole value are not part of the view.
+    ///
+    /// # Panics
+    ///
+    /// If `T` is zero-sized, or the contents aren't aligned for `T`.
+    ///
+    /// # Safety
+    ///
+    /// [`as_slice`](Self::as_slice) and the write methods hand out `&[T]`
+    /// and `&mut [T]` into the contents. `Buffer` is a reference-counted
+    /// handle, so a clone of `buffer` (or of [`buffer`](Self::buffer)) sees
+    /// the same memory: while this `TypedBuffer` is alive, its contents must
+    /// not be read or written through any other handle, including another
+    /// `TypedBuffer` made from a clone.
+    pub unsafe fn from_buffer(buffer: B) -> Self {
+        assert!(mem::size_of::<T>() != 0, "TypedBuffer of a zero-sized type");
+        assert!(
+            (buffer.contents() as *mut T).is_aligned(),
+            "buffer contents are not aligned for the element type"
+        );
+        let len = buffer.length() as usize / mem::size_of::<T>();
+        let storage_mode = buffer.storage_mode();
+        TypedBuffer {
+            buffer,
+            len,
+            storage_mode,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn buffer(&self) -> &B {
+        &self.buffer
+    }
+
+    pub fn into_inner(self) -> B {
+        self.buffer
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn byte_len(&self) -> NSUInteger {
+        (self.len * mem::size_of::<T>()) as NSUInteger
+    }
+
+    pub fn storage_mode(&self) -> MTLStorageMode {
+        self.storage_mode
+    }
+
+    /// The values, or `None` if the storage mode has no CPU access.
+    ///
+    /// The GPU may be writing the buffer concurrently; only read results
+    /// once the command buffers using it have completed.
+    pub fn as_slice(&self) -> Option<&[T]> {
+        if !self.storage_mode.is_cpu_accessible() {
+            return None;
+        }
+        Some(unsafe { std::slice::from_raw_parts(self.contents_ptr(), self.len) })
+    }
+
+    /// Write access to every value, or `None` if the storage mode has no CPU
+    /// access.
+    pub fn as_mut_slice(&mut self) -> Option<TypedBufferWriteGuard<'_, T, B>> {
+        self.write_range(..)
+    }
+
+    /// Write access to `range`. Only that range is reported as modified.
+    ///
+    /// # Panics
+    ///
+    /// If `range` is out of bounds.
+    pub fn write_range<R: RangeBounds<usize>>(
+        &mut self,
+        range: R,
+    ) -> Option<TypedBufferWriteGuard<'_, T, B>> {
+        let range = self.check_range(range);
+        if !self.storage_mode.is_cpu_accessible() {
+            return None;
+        }
+        let values = unsafe {
+            std::slice::from_raw_parts_mut(
+                self.contents_ptr().add(range.start),
+                range.end - range.start,
+            )
+        };
+        Some(TypedBufferWriteGuard {
+            buffer: &self.buffer,
+            flush: self.storage_mode == MTLStorageMode::Managed,
+            byte_offset: range.start * mem::size_of::<T>(),
+            values,
+        })
+    }
+
+    /// Copy `values` into the buffer starting at value `start`.
+    ///
+    /// Returns `false`, writing nothing, if the storage mode has no CPU
+    /// access.
+    pub fn write(&mut self, start: usize, values: &[T]) -> bool {
+        match self.write_range(start..start + values.len()) {
+            Some(mut guard) => {
+                guard.copy_from_slice(values);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The values in `range`, as a buffer plus byte offset for binding.
+    ///
+    /// # Panics
+    ///
+    /// If `range` is out of bounds.
+    pub fn slice<R: RangeBounds<usize>>(&self, range: R) -> TypedBufferSlice<'_, T, B> {
+        let range = self.check_range(range);
+        TypedBufferSlice {
+            buffer: &self.buffer,
+            start: range.start,
+            len: range.end - range.start,
+            _marker: PhantomData,
+        }
+    }
+
+    fn contents_ptr(&self) -> *mut T {
+        self.buffer.contents() as *mut T
+    }
+
+    fn check_range<R: RangeBounds<usize>>(&self, range: R) -> Range<usize> {
+        let start = match range.start_bound() {
+            Bound::Included(&start) => start,
+            Bound::Excluded(&start) => start + 1,
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&end) => end + 1,
+            Bound::Excluded(&end) => end,
+            Bound::Unbounded => self.len,
+        };
+        assert!(
+            start <= end && end <= self.len,
+            "range {}..{} out of bounds for TypedBuffer of length {}",
+            start,
+            end,
+            self.len
+        );
+        start..end
+    }
+}
+
+/// Mutable access to part of a [`TypedBuffer`]. Dropping it reports the
+/// range as modified for `Managed` storage.
+pub struct TypedBufferWriteGuard<'a, T: Pod, B: BufferStorage> {
+    buffer: &'a B,
+    flush: bool,
+    byte_offset: usize,
+    values: &'a mut [T],
+}
+
+impl<T: Pod, B: BufferStorage> Deref for TypedBufferWriteGuard<'_, T, B> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.values
+    }
+}
+
+impl<T: Pod, B: BufferStorage> DerefMut for TypedBufferWriteGuard<'_, T, B> {
+    fn deref_mut(&mut self) -> &mut [T] {
+        self.values
+    }
+}
+
+impl<T: Pod, B: BufferStorage> Drop for TypedBufferWriteGuard<'_, T, B> {
+    fn drop(&mut self) {
+        if self.flush && !self.values.is_empty() {
+            self.buffer.did_modify_range(NSRange::new(
+                self.byte_offset as NSUInteger,
+                mem::size_of_val(self.values) as NSUInteger,
+            ));
+        }
+    }
+}
+
+/// A range of values in a [`TypedBuffer`].
+///
+/// ```ignore
+/// let instances = buffer.slice(first..first + count);
+/// encoder.set_vertex_buffer(1, Some(instances.buffer()), instances.offset());
+/// ```
+pub struct TypedBufferSlice<'a, T: Pod, B: BufferStorage = Buffer> {
+    buffer: &'a B,
+    start: usize,
+    len: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod, B: BufferStorage> Clone for TypedBufferSlice<'_, T, B> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: Pod, B: BufferStorage> Copy for TypedBufferSlice<'_, T, B> {}
+
+impl<'a, T: Pod, B: BufferStorage> TypedBufferSlice<'a, T, B> {
+    pub fn buffer(&self) -> &'a B {
+        self.buffer
+    }
+
+    /// Index of the first value.
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Byte offset of the first value, for `set_*_buffer`.
+    pub fn offset(&self) -> NSUInteger {
+        (self.start * mem::size_of::<T>()) as NSUInteger
+    }
+
+    pub fn byte_len(&self) -> NSUInteger {
+        (self.len * mem::size_of::<T>()) as NSUInteger
+    }
+
+    pub fn byte_range(&self) -> NSRange {
+        NSRange::new(self.offset(), self.byte_len())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test_util {
+    use super::*;
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+
+    /// Host memory standing in for a Metal buffer. Clones share it.
+    #[derive(Clone)]
+    pub(crate) struct HostBuffer(Rc<HostMemory>);
+
+    struct HostMemory {
+        words: Box<[Cell<u64>]>,
+        storage_mode: MTLStorageMode,
+        modified: RefCell<Vec<(NSUInteger, NSUInteger)>>,
+    }
+
+    impl HostBuffer {
+        /// `length` zeroed bytes, rounded down to whole 8-byte words.
+        pub(crate) fn new(length: NSUInteger, storage_mode: MTLStorageMode) -> Self {
+            HostBuffer(Rc::new(HostMemory {
+                words: (0..length / 8).map(|_| Cell::new(0)).collect(),
+                storage_mode,
+                modified: RefCell::new(Vec::new()),
+            }))
+        }
+
+        pub(crate) fn shares_memory_with(&self, other: &HostBuffer) -> bool {
+            Rc::ptr_eq(&self.0, &other.0)
+        }
+
+        /// The contents as `T` values, dropping any bytes left over.
+        pub(crate) fn read<T: Pod>(&self) -> Vec<T> {
+            assert!(mem::align_of::<T>() <= mem::align_of::<u64>());
+            let len = self.length() as usize / mem::size_of::<T>();
+            // SAFETY: the words are aligned for `T` and `T` is plain data.
+            unsafe { std::slice::from_raw_parts(self.contents() as *const T, len) }.to_vec()
+        }
+
+        /// The ranges passed to `did_modify_range`, as `(location, length)`.
+        pub(crate) fn modified(&self) -> Vec<(NSUInteger, NSUInteger)> {
+            self.0.modified.borrow().clone()
+        }
+    }
+
+    unsafe impl BufferStorage for HostBuffer {
+        fn length(&self) -> NSUInteger {
+            mem::size_of_val(&*self.0.words) as NSUInteger
+        }
+
+        fn contents(&self) -> *mut std::ffi::c_void {
+            self.0.words.as_ptr() as *mut std::ffi::c_void
+        }
+
+        fn storage_mode(&self) -> MTLStorageMode {
+            self.0.storage_mode
+        }
+
+        fn did_modify_range(&self, range: NSRange) {
+            self.0
+                .modified
+                .borrow_mut()
+                .push((range.location, range.length));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_util::HostBuffer;
+    use super::*;
+
+    fn typed<T: Pod>(
+        length: NSUInteger,
+        storage_mode: MTLStorageMode,
+    ) -> TypedBuffer<T, HostBuffer> {
+        unsafe { TypedBuffer::from_buffer(HostBuffer::new(length, storage_mode)) }
+    }
+
+    #[test]
+    fn managed_writes_are_flushed() {
+        // 40 bytes hold three 12-byte values.
+        let mut buffer = typed::<[f32; 3]>(40, MTLStorageMode::Managed);
+        assert_eq!((buffer.len(), buffer.byte_len()), (3, 36));
+        {
+            let mut values = buffer.as_mut_slice().unwrap();
+            values[1] = [1.0, 2.0, 3.0];
+        }
+        assert!(buffer.write(2, &[[4.0; 3]]));
+        drop(buffer.write_range(0..0).unwrap());
+        assert_eq!(
+            buffer.as_slice().unwrap(),
+            [[0.0; 3], [1.0, 2.0, 3.0], [4.0; 3]]
+        );
+        assert_eq!(buffer.buffer().modified(), [(0, 36), (24, 12)]);
+        assert_eq!(
+            buffer.buffer().read::<u64>()[1],
+            (1.0f32.to_bits() as u64) << 32
+        );
+    }
+
+    #[test]
+    fn shared_writes_are_not_flushed() {
+        let mut buffer = typed::<u32>(40, MTLStorageMode::Shared);
+        assert_eq!(buffer.len(), 10);
+        buffer.as_mut_slice().unwrap()[9] = 7;
+        assert!(buffer.write(0, &[1, 2]));
+        assert_eq!(buffer.as_slice().unwrap()[..2], [1, 2]);
+        assert_eq!(buffer.as_slice().unwrap()[9], 7);
+        assert!(buffer.buffer().modified().is_empty());
+    }
+
+    #[test]
+    fn private_storage_has_no_cpu_views() {
+        let mut buffer = typed::<u32>(16, MTLStorageMode::Private);
+        assert!(buffer.as_slice().is_none());
+        assert!(buffer.as_mut_slice().is_none());
+        assert!(buffer.write_range(1..2).is_none());
+        assert!(!buffer.write(0, &[1]));
+        assert_eq!(buffer.storage_mode(), MTLStorageMode::Private);
+        assert_eq!(buffer.slice(2..4).offset(), 8);
+    }
+
+    #[test]
+    fn slices() {
+        let buffer = typed::<[f32; 3]>(40, MTLStorageMode::Shared);
+        let slice = buffer.slice(1..);
+        assert_eq!(
+            (slice.start(), slice.len(), slice.offset(), slice.byte_len()),
+            (1, 2, 12, 24)
+        );
+        let range = slice.byte_range();
+        assert_eq!((range.location, range.length), (12, 24));
+        assert!(buffer.slice(..=0).len() == 1 && buffer.slice(3..).is_empty());
+        assert!(std::ptr::eq(slice.buffer(), buffer.buffer()));
+    }
+
+    #[test]
+    #[should_panic(expected = "range 2..4 out of bounds for TypedBuffer of length 3")]
+    fn slice_out_of_bounds() {
+        typed::<[f32; 3]>(40, MTLStorageMode::Shared).slice(2..4);
+    }
+
+    #[test]
+    #[should_panic(expected = "range 3..5 out of bounds for TypedBuffer of length 4")]
+    fn write_out_of_bounds() {
+        typed::<u32>(16, MTLStorageMode::Shared).write(3, &[1, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "TypedBuffer of a zero-sized type")]
+    fn zero_sized_values() {
+        typed::<[u32; 0]>(16, MTLStorageMode::Shared);
+    }
+
+    #[test]
+    #[should_panic(expected = "buffer contents are not aligned for the element type")]
+    fn unaligned_contents() {
+        /// Contents one byte into a host buffer.
+        struct Unaligned(HostBuffer);
+
+        unsafe impl BufferStorage for Unaligned {
+            fn length(&self) -> NSUInteger {
+                self.0.length() - 1
+            }
+
+            fn contents(&self) -> *mut std::ffi::c_void {
+                unsafe { self.0.contents().add(1) }
+            }
+
+            fn storage_mode(&self) -> MTLStorageMode {
+                self.0.storage_mode()
+            }
+
+            fn did_modify_range(&self, range: NSRange) {
+                self.0.did_modify_range(range)
+            }
+        }
+
+        let buffer = HostBuffer::new(16, MTLStorageMode::Shared);
+        // Bytes need no alignment.
+        let bytes = unsafe { TypedBuffer::<u8, _>::from_buffer(Unaligned(buffer.clone())) };
+        assert_eq!(bytes.len(), 15);
+        unsafe { TypedBuffer::<u32, _>::from_buffer(Unaligned(buffer)) };
+    }
+
+    #[test]
+    fn empty_storage() {
+        let mut buffer = typed::<u64>(0, MTLStorageMode::Managed);
+        assert!(buffer.is_empty());
+        assert_eq!(buffer.as_slice().unwrap(), [] as [u64; 0]);
+        drop(buffer.as_mut_slice().unwrap());
+        assert!(buffer.into_inner().modified().is_empty());
+    }
+}